@@ -1,5 +1,82 @@
 extern crate embed_resource;
 
+use std::{env, fs, path::PathBuf};
+
+use serde_json::{Value, json};
+
 fn main() {
     embed_resource::compile("dotnet5-webinst.rc");
-}
\ No newline at end of file
+    generate_catalog();
+}
+
+/// Writes the Windows installers listed in the `releases.json` files found in
+/// `$DOTNET5_REDIST_RELEASES_DIR` to `$OUT_DIR/catalog.json`, and the channels
+/// with their newest releases to `$OUT_DIR/channels.json`, which are embedded
+/// into the binary. Without the variable both are empty and every request is
+/// resolved online.
+fn generate_catalog() {
+    println!("cargo:rerun-if-env-changed=DOTNET5_REDIST_RELEASES_DIR");
+
+    let mut entries = Vec::new();
+    let mut channels = Vec::new();
+    if let Some(dir) = env::var_os("DOTNET5_REDIST_RELEASES_DIR") {
+        let dir = PathBuf::from(dir);
+        println!("cargo:rerun-if-changed={}", dir.display());
+
+        let mut paths = fs::read_dir(&dir)
+            .expect("cannot read DOTNET5_REDIST_RELEASES_DIR")
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        for path in paths {
+            println!("cargo:rerun-if-changed={}", path.display());
+            let contents = fs::read_to_string(&path).expect("cannot read releases.json");
+            let releases: Value = serde_json::from_str(&contents).expect("invalid releases.json");
+
+            if let Some(channel) = releases["channel-version"].as_str() {
+                let newest = |product: &str| {
+                    releases["releases"].as_array().into_iter().flatten().find_map(|release| release[product]["version"].as_str())
+                };
+                channels.push(json!({
+                    "channel": channel,
+                    "release_type": releases["release-type"],
+                    "support_phase": releases["support-phase"],
+                    "runtime": releases["latest-runtime"],
+                    "aspnetcore": newest("aspnetcore-runtime"),
+                    "windowsdesktop": newest("windowsdesktop"),
+                    "sdk": releases["latest-sdk"],
+                }));
+            }
+
+            for release in releases["releases"].as_array().into_iter().flatten() {
+                let products = ["runtime", "aspnetcore-runtime", "windowsdesktop"]
+                    .iter()
+                    .map(|key| &release[*key])
+                    .chain(release["sdks"].as_array().into_iter().flatten());
+                for product in products {
+                    for file in product["files"].as_array().into_iter().flatten() {
+                        let name = file["name"].as_str().unwrap_or_default();
+                        let windows = name.contains("-win-x") || name.contains("-win-arm64");
+                        if !windows || !(name.ends_with(".exe") || name.ends_with(".zip")) {
+                            continue;
+                        }
+                        entries.push(json!({
+                            "name": name,
+                            "version": product["version"],
+                            "url": file["url"],
+                            "hash": file["hash"],
+                        }));
+                    }
+                }
+            }
+        }
+    }
+
+    let out = PathBuf::from(env::var_os("OUT_DIR").unwrap()).join("catalog.json");
+    fs::write(out, serde_json::to_string(&entries).unwrap()).expect("cannot write catalog.json");
+    let out = PathBuf::from(env::var_os("OUT_DIR").unwrap()).join("channels.json");
+    fs::write(out, serde_json::to_string(&channels).unwrap()).expect("cannot write channels.json");
+}
@@ -0,0 +1,38 @@
+/* C declarations of the library's ABI, built with
+ * cargo rustc --lib --release --features ffi --crate-type cdylib */
+#ifndef DOTNET5_WEBINST_H
+#define DOTNET5_WEBINST_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define DOTNET_REDIST_STAGE_RESOLVING 0
+#define DOTNET_REDIST_STAGE_DOWNLOAD_STARTED 1
+#define DOTNET_REDIST_STAGE_DOWNLOADING 2
+#define DOTNET_REDIST_STAGE_INSTALLING 3
+#define DOTNET_REDIST_STAGE_DONE 4
+
+/* Receives the stage, the component or URL it is about, and for downloads
+ * the bytes received so far and the total, 0 if unknown. */
+typedef void (*dotnet_redist_progress_cb)(int stage, const char *subject, uint64_t bytes, uint64_t total);
+
+/* Installs `runtime` of `version` for `arch` unless it is present. Null
+ * arguments mean the machine's architecture, dotnet and the latest LTS
+ * release; `progress` may be null. Returns 0, 3010 if a reboot is needed,
+ * a failing installer's exit code, or 1 for other failures. */
+int dotnet_redist_ensure(const char *arch, const char *runtime, const char *version, dotnet_redist_progress_cb progress);
+
+/* The message of the last failed call on this thread, or null. */
+const char *dotnet_redist_last_error(void);
+
+/* Asks a running dotnet_redist_ensure to stop; later calls are cancelled too. */
+void dotnet_redist_cancel(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
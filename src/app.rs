@@ -0,0 +1,232 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde::Deserialize;
+
+use crate::{
+    Architecture, DotnetVersion, Requirement, Requirements, RollForward, Runtime,
+    conflicts::pe_machine,
+    platform::{IMAGE_FILE_MACHINE_AMD64, IMAGE_FILE_MACHINE_ARM64, IMAGE_FILE_MACHINE_I386},
+};
+
+#[derive(Deserialize)]
+struct RuntimeConfig {
+    #[serde(rename = "runtimeOptions")]
+    runtime_options: RuntimeOptions,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RuntimeOptions {
+    roll_forward: Option<String>,
+    framework: Option<Framework>,
+    #[serde(default)]
+    frameworks: Vec<Framework>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Deps {
+    runtime_target: Option<RuntimeTarget>,
+}
+
+#[derive(Deserialize)]
+struct RuntimeTarget {
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Framework {
+    name: String,
+    version: String,
+    roll_forward: Option<String>,
+}
+
+/// Works out the shared frameworks an app needs from its `runtimeconfig.json`.
+///
+/// `app` is either the config itself or the app's exe or dll, next to which
+/// the config is found. Without `arch` the architecture is read from the exe.
+pub fn requirements(app: &Path, arch: Option<Architecture>) -> Result<Requirements> {
+    let config_path = if app.to_string_lossy().ends_with(".runtimeconfig.json") {
+        app.to_path_buf()
+    } else {
+        app.with_extension("runtimeconfig.json")
+    };
+    let arch = match arch {
+        Some(arch) => arch,
+        None => exe_arch(app)?.ok_or_else(|| anyhow!("cannot tell the architecture of {}; pass --arch", app.display()))?,
+    };
+
+    let components = frameworks(&config_path, arch)?;
+    if components.is_empty() {
+        bail!("{} lists no frameworks; self-contained apps need none installed", config_path.display());
+    }
+    Ok(Requirements { components })
+}
+
+/// Works out the shared frameworks every app in a published folder needs,
+/// from the `runtimeconfig.json` of each app in `dir` or below it, and the
+/// target framework in the `deps.json` of libraries without one.
+///
+/// Requirements that one release can satisfy together are merged. Without
+/// `arch` each app's architecture is read from its exe, and apps without
+/// one are taken to run on the machine's own.
+pub fn scan(dir: &Path, arch: Option<Architecture>) -> Result<Requirements> {
+    let mut files = Vec::new();
+    walk(dir, &mut files).with_context(|| format!("cannot scan {}", dir.display()))?;
+    files.sort();
+
+    let mut components: Vec<Requirement> = Vec::new();
+    for file in &files {
+        let name = file.file_name().unwrap_or_default().to_string_lossy();
+        let found = if let Some(stem) = name.strip_suffix(".runtimeconfig.json") {
+            frameworks(file, app_arch(&file.with_file_name(stem), arch)?)?
+        } else if let Some(stem) = name.strip_suffix(".deps.json") {
+            let config = file.with_file_name(format!("{}.runtimeconfig.json", stem));
+            if config.exists() || file.with_file_name("System.Private.CoreLib.dll").exists() {
+                // Covered by the app's config, or a self-contained app carrying its runtime.
+                continue;
+            }
+            target_framework(file, app_arch(&file.with_file_name(stem), arch)?)?.into_iter().collect()
+        } else {
+            continue;
+        };
+        log::debug!("{} needs {} frameworks", file.display(), found.len());
+        for requirement in found {
+            merge(&mut components, requirement);
+        }
+    }
+
+    if components.is_empty() {
+        bail!("no app in {} needs a shared framework", dir.display());
+    }
+    Ok(Requirements { components })
+}
+
+/// The frameworks `config_path` lists, which is none for a self-contained app.
+fn frameworks(config_path: &Path, arch: Architecture) -> Result<Vec<Requirement>> {
+    let contents = fs::read_to_string(config_path)
+        .with_context(|| format!("cannot read {}", config_path.display()))?;
+    let config: RuntimeConfig =
+        serde_json::from_str(&contents).with_context(|| format!("invalid {}", config_path.display()))?;
+    let options = config.runtime_options;
+
+    let mut components = Vec::new();
+    for framework in options.framework.iter().chain(&options.frameworks) {
+        let runtime = match framework.name.as_str() {
+            "Microsoft.NETCore.App" => Runtime::Dotnet,
+            "Microsoft.AspNetCore.App" => Runtime::AspCore,
+            "Microsoft.WindowsDesktop.App" => Runtime::WindowsDesktop,
+            name => bail!("{} needs unknown framework {}", config_path.display(), name),
+        };
+        let roll_forward = framework
+            .roll_forward
+            .as_deref()
+            .or(options.roll_forward.as_deref())
+            .unwrap_or("Minor");
+
+        components.push(Requirement {
+            version: framework.version.clone(),
+            runtime: Some(runtime),
+            arch,
+            global_tool: None,
+            roll_forward: Some(policy(roll_forward)?),
+        });
+    }
+    Ok(components)
+}
+
+/// The .NET runtime a library's `deps.json` targets, such as 8.0 for
+/// `.NETCoreApp,Version=v8.0`; none for .NET Standard or .NET Framework.
+fn target_framework(deps_path: &Path, arch: Architecture) -> Result<Option<Requirement>> {
+    let contents = fs::read_to_string(deps_path).with_context(|| format!("cannot read {}", deps_path.display()))?;
+    let deps: Deps = serde_json::from_str(&contents).with_context(|| format!("invalid {}", deps_path.display()))?;
+    let version = deps
+        .runtime_target
+        .and_then(|target| Some(target.name.strip_prefix(".NETCoreApp,Version=v")?.split('/').next()?.to_string()));
+    Ok(version.map(|version| Requirement {
+        version,
+        runtime: Some(Runtime::Dotnet),
+        arch,
+        global_tool: None,
+        roll_forward: Some(RollForward::Minor),
+    }))
+}
+
+/// Adds `requirement` to `components` unless a release satisfying one of
+/// them already satisfies both, keeping the higher of the two versions.
+fn merge(components: &mut Vec<Requirement>, requirement: Requirement) {
+    let version = |requirement: &Requirement| requirement.version.parse::<DotnetVersion>().ok();
+    let band = |requirement: &Requirement| {
+        let version = version(requirement)?;
+        let policy = requirement.roll_forward.unwrap_or(RollForward::Minor);
+        let minor = if matches!(policy, RollForward::LatestPatch | RollForward::Disable) { version.minor } else { None };
+        let patch = if policy == RollForward::Disable { version.patch } else { None };
+        Some((requirement.runtime, requirement.arch, policy, version.major, minor, patch))
+    };
+    let lowest = |requirement: &Requirement| {
+        version(requirement).map(|version| (version.major, version.minor.unwrap_or(0), version.patch.unwrap_or(0)))
+    };
+
+    let key = band(&requirement);
+    match components.iter_mut().find(|existing| key.is_some() && band(existing) == key) {
+        Some(existing) if lowest(&requirement) > lowest(existing) => *existing = requirement,
+        Some(_) => {}
+        None => components.push(requirement),
+    }
+}
+
+/// The architecture of the app whose exe, if any, is `app` or `app.exe`.
+fn app_arch(app: &Path, arch: Option<Architecture>) -> Result<Architecture> {
+    if let Some(arch) = arch {
+        return Ok(arch);
+    }
+    let mut windows_exe = app.as_os_str().to_owned();
+    windows_exe.push(".exe");
+    let exe = vec![PathBuf::from(windows_exe), app.to_path_buf()].into_iter().find(|exe| exe.is_file());
+    let found = match exe {
+        Some(exe) => exe_arch(&exe)?,
+        None => None,
+    };
+    Ok(found.unwrap_or_else(|| *Architecture::installable().last().expect("some architecture is installable")))
+}
+
+/// The architecture `exe` is built for, if it is a PE image.
+fn exe_arch(exe: &Path) -> Result<Option<Architecture>> {
+    match pe_machine(exe) {
+        Some(IMAGE_FILE_MACHINE_I386) => Ok(Some(Architecture::X86)),
+        Some(IMAGE_FILE_MACHINE_AMD64) => Ok(Some(Architecture::X64)),
+        Some(IMAGE_FILE_MACHINE_ARM64) => Ok(Some(Architecture::Arm64)),
+        Some(machine) => bail!("{} is built for unsupported machine type {:#06x}", exe.display(), machine),
+        None => Ok(None),
+    }
+}
+
+/// Collects the files in `dir` and its subdirectories, not following links.
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            walk(&entry.path(), files)?;
+        } else if file_type.is_file() {
+            files.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+/// The policy for a `rollForward` setting. LatestMajor is treated as Major:
+/// the lowest major version satisfying it is all an install has to provide.
+fn policy(roll_forward: &str) -> Result<RollForward> {
+    match roll_forward.to_ascii_lowercase().as_str() {
+        "latestmajor" => Ok(RollForward::Major),
+        policy => policy
+            .parse()
+            .map_err(|_| anyhow!("unknown rollForward policy {}", roll_forward)),
+    }
+}
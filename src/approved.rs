@@ -0,0 +1,40 @@
+use std::{fs, path::Path, sync::OnceLock};
+
+use anyhow::{Context, Result, bail};
+use semver::Version;
+
+static APPROVED: OnceLock<Vec<Version>> = OnceLock::new();
+
+/// Restricts resolution to the versions listed in `path`, one per line.
+/// Blank lines and lines starting with `#` are ignored.
+pub fn load(path: &Path) -> Result<()> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("cannot read approved versions {}", path.display()))?;
+
+    let mut versions = Vec::new();
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let version = Version::parse(line)
+            .with_context(|| format!("{}:{}: invalid version {}", path.display(), number + 1, line))?;
+        versions.push(version);
+    }
+    if versions.is_empty() {
+        bail!("{} approves no versions", path.display());
+    }
+
+    let _ = APPROVED.set(versions);
+    Ok(())
+}
+
+/// The approved versions, if resolution is restricted to a list.
+pub(crate) fn list() -> Option<&'static [Version]> {
+    APPROVED.get().map(Vec::as_slice)
+}
+
+/// Whether `version` may be resolved and installed.
+pub(crate) fn allows(version: &Version) -> bool {
+    list().is_none_or(|approved| approved.contains(version))
+}
@@ -0,0 +1,93 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::{Architecture, DotnetVersion, LockEntry, Runtime, lock};
+
+/// Name of the manifest inside a bundle directory.
+pub const MANIFEST_NAME: &str = "bundle.json";
+
+/// Lists the installers in a bundle directory for offline installs.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub runtimes: Vec<BundledRuntime>,
+    pub vcredists: Vec<BundledVcredist>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BundledRuntime {
+    #[serde(flatten)]
+    pub entry: LockEntry,
+    /// The installer's file name inside the bundle directory.
+    pub file: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BundledVcredist {
+    pub arch: String,
+    pub file: String,
+}
+
+/// A bundle directory with its manifest.
+pub struct Bundle {
+    pub dir: PathBuf,
+    pub manifest: Manifest,
+}
+
+impl Bundle {
+    /// Opens the bundle in `dir`, or starts an empty one if it has no manifest yet.
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        let path = dir.join(MANIFEST_NAME);
+
+        let manifest = if path.exists() {
+            let contents =
+                fs::read_to_string(&path).with_context(|| format!("cannot read {}", path.display()))?;
+            serde_json::from_str(&contents).with_context(|| format!("invalid bundle manifest {}", path.display()))?
+        } else {
+            Manifest::default()
+        };
+
+        Ok(Bundle { dir, manifest })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = self.dir.join(MANIFEST_NAME);
+        let contents = serde_json::to_string_pretty(&self.manifest)?;
+        fs::write(&path, contents + "\n").with_context(|| format!("cannot write {}", path.display()))
+    }
+
+    pub(crate) fn runtime(&self, runtime: Runtime, arch: Architecture, version: &DotnetVersion) -> Option<&BundledRuntime> {
+        let (runtime, arch, version) = lock::key(runtime, arch, version);
+        self.manifest.runtimes.iter().find(|bundled| {
+            bundled.entry.runtime == runtime && bundled.entry.arch == arch && bundled.entry.version == version
+        })
+    }
+
+    pub(crate) fn vcredist(&self, arch: Architecture) -> Option<&BundledVcredist> {
+        let arch = arch.to_string().to_lowercase();
+        self.manifest.vcredists.iter().find(|bundled| bundled.arch == arch)
+    }
+
+    /// The path of a file listed in the manifest.
+    pub(crate) fn path(&self, file: &str) -> Result<PathBuf> {
+        if Path::new(file).file_name().is_none_or(|name| name != file) {
+            bail!("invalid file name {} in the bundle manifest", file);
+        }
+
+        let path = self.dir.join(file);
+        if !path.is_file() {
+            bail!("{} is listed in the bundle manifest but missing", path.display());
+        }
+        Ok(path)
+    }
+}
+
+/// The file name a download is stored under in a bundle.
+pub(crate) fn file_name(url: &str) -> &str {
+    url.rsplit('/').next().unwrap_or(url)
+}
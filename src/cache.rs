@@ -0,0 +1,52 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+
+use crate::{temp, verify::hex};
+
+/// The per-user directory downloaded installers are kept in.
+pub fn dir() -> Option<PathBuf> {
+    let local_app_data = std::env::var_os("LOCALAPPDATA")?;
+    Some(PathBuf::from(local_app_data).join("dotnet5-redist").join("cache"))
+}
+
+/// Where the download of `url` is cached, keyed by its published hash if it
+/// has one and by the URL otherwise.
+pub(crate) fn path(url: &str, sha512: Option<&str>) -> Option<PathBuf> {
+    let key = match sha512 {
+        Some(sha512) => sha512.trim().to_lowercase(),
+        None => hex(&Sha256::digest(url.as_bytes())),
+    };
+    let file_name = url.rsplit('/').next().unwrap_or("installer.exe");
+
+    Some(dir()?.join(format!("{}-{}", &key[..key.len().min(32)], file_name)))
+}
+
+/// Copies a verified download into the cache.
+pub(crate) fn store(download: &Path, cached: &Path) -> io::Result<()> {
+    if let Some(parent) = cached.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    // Copy under a temporary name so other runs never see a partial file.
+    let partial = temp::partial(cached);
+    if let Err(e) = fs::copy(download, &partial) {
+        let _ = fs::remove_file(&partial);
+        return Err(e);
+    }
+    fs::rename(&partial, cached)
+}
+
+/// Removes all cached installers.
+pub fn clear() -> Result<()> {
+    let dir = match dir() {
+        Some(dir) if dir.exists() => dir,
+        _ => return Ok(()),
+    };
+
+    fs::remove_dir_all(&dir).with_context(|| format!("could not remove {}", dir.display()))
+}
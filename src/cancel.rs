@@ -0,0 +1,107 @@
+use std::{
+    fmt::Display,
+    future::Future,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use anyhow::Result;
+use smol::Timer;
+
+/// The exit code of a run the user cancelled: what Windows Installer uses
+/// for a user exit, and what shells report for an interrupt elsewhere.
+#[cfg(windows)]
+pub const EXIT_CODE: i32 = 1602;
+#[cfg(not(windows))]
+pub const EXIT_CODE: i32 = 130;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How many times the user has asked to stop.
+static REQUESTS: AtomicUsize = AtomicUsize::new(0);
+
+/// The run was cancelled before it finished.
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Turns Ctrl+C, Ctrl+Break and closing the console, or SIGINT and SIGTERM,
+/// into a request to cancel instead of ending the process on the spot, so
+/// that downloads are abandoned, temporary files removed and a running
+/// installer given the chance to roll back.
+pub fn install() {
+    #[cfg(windows)]
+    unsafe {
+        winapi::um::consoleapi::SetConsoleCtrlHandler(Some(windows::on_event), 1);
+    }
+
+    #[cfg(unix)]
+    for &signal in &[signal_hook::SIGINT, signal_hook::SIGTERM] {
+        // Only touches an atomic, which is safe in a signal handler.
+        let registered = unsafe {
+            signal_hook::register(signal, || {
+                REQUESTS.fetch_add(1, Ordering::SeqCst);
+            })
+        };
+        if let Err(e) = registered {
+            log::debug!("cannot handle signal {}: {}", signal, e);
+        }
+    }
+}
+
+/// Asks the run to stop, as Ctrl+C does, for embedders with their own cancel button.
+pub fn cancel() {
+    REQUESTS.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Whether the run has been asked to stop.
+pub fn is_cancelled() -> bool {
+    REQUESTS.load(Ordering::SeqCst) > 0
+}
+
+/// Whether the user asked again, not wanting to wait for a clean stop.
+pub(crate) fn is_insistent() -> bool {
+    REQUESTS.load(Ordering::SeqCst) > 1
+}
+
+/// Runs `work` until it finishes or the run is cancelled, in which case it
+/// is dropped where it stands, which removes its temporary files.
+pub(crate) async fn watch<T>(work: impl Future<Output = Result<T>>) -> Result<T> {
+    let cancelled = async {
+        while !is_cancelled() {
+            Timer::after(POLL_INTERVAL).await;
+        }
+        Err(Cancelled.into())
+    };
+    smol::future::or(work, cancelled).await
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::{sync::atomic::Ordering, time::Duration};
+
+    use winapi::{
+        shared::minwindef::{BOOL, DWORD, TRUE},
+        um::wincon::{CTRL_BREAK_EVENT, CTRL_C_EVENT},
+    };
+
+    use super::REQUESTS;
+
+    pub(super) unsafe extern "system" fn on_event(event: DWORD) -> BOOL {
+        REQUESTS.fetch_add(1, Ordering::SeqCst);
+        if event != CTRL_C_EVENT && event != CTRL_BREAK_EVENT {
+            // Windows ends the process as soon as this returns from closing
+            // the console, logging off or shutting down, and a few seconds
+            // later regardless, so hold it off while the run cleans up.
+            std::thread::sleep(Duration::from_secs(5));
+        }
+        TRUE
+    }
+}
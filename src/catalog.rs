@@ -0,0 +1,164 @@
+use std::{fs, path::PathBuf, sync::OnceLock};
+
+use anyhow::{Context, Result, bail};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Architecture, DotnetVersion, RollForward, Runtime, approved,
+    releases::{self, Artifact, Package},
+};
+
+static REFRESH: OnceLock<bool> = OnceLock::new();
+static CATALOG: OnceLock<Vec<Entry>> = OnceLock::new();
+static CHANNELS: OnceLock<Vec<Channel>> = OnceLock::new();
+
+/// A Windows installer listed in the release metadata.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub name: String,
+    pub version: String,
+    pub url: String,
+    pub hash: Option<String>,
+    /// Whether the entry comes from `catalog update` rather than the build.
+    #[serde(skip)]
+    pub local: bool,
+}
+
+/// A release channel and its newest releases when the binary was built.
+#[derive(Deserialize)]
+pub(crate) struct Channel {
+    pub(crate) channel: String,
+    pub(crate) release_type: Option<String>,
+    pub(crate) support_phase: Option<String>,
+    runtime: Option<String>,
+    aspnetcore: Option<String>,
+    windowsdesktop: Option<String>,
+    sdk: Option<String>,
+}
+
+impl Channel {
+    /// The newest release of `runtime` in the channel when the binary was built.
+    fn latest(&self, runtime: Runtime) -> Option<Version> {
+        let version = match runtime {
+            Runtime::Dotnet => &self.runtime,
+            Runtime::AspCore | Runtime::HostingBundle => &self.aspnetcore,
+            Runtime::WindowsDesktop => &self.windowsdesktop,
+            Runtime::Sdk => &self.sdk,
+            Runtime::NetFx48 => return None,
+        };
+        Version::parse(version.as_deref()?).ok()
+    }
+}
+
+/// Ignores the catalogs and always resolves against the online release metadata.
+pub fn set_refresh(refresh: bool) {
+    let _ = REFRESH.set(refresh);
+}
+
+/// Where `catalog update` stores the local catalog overlay.
+fn overlay_path() -> Option<PathBuf> {
+    let local_app_data = std::env::var_os("LOCALAPPDATA")?;
+    Some(PathBuf::from(local_app_data).join("dotnet5-redist").join("catalog.json"))
+}
+
+/// The catalog built into the binary followed by the local overlay, if any.
+pub fn entries() -> &'static [Entry] {
+    CATALOG.get_or_init(|| {
+        let mut entries: Vec<Entry> =
+            serde_json::from_str(include_str!(concat!(env!("OUT_DIR"), "/catalog.json"))).unwrap_or_default();
+
+        if let Some(path) = overlay_path().filter(|path| path.exists()) {
+            let overlay = fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|contents| Ok(serde_json::from_str::<Vec<Entry>>(&contents)?));
+            match overlay {
+                Ok(overlay) => entries.extend(overlay.into_iter().map(|entry| Entry { local: true, ..entry })),
+                Err(e) => log::warn!("ignoring local catalog {}: {:#}", path.display(), e),
+            }
+        }
+
+        entries
+    })
+}
+
+/// The release channels known when the binary was built, to resolve with
+/// when the release metadata cannot be reached.
+pub(crate) fn channels() -> &'static [Channel] {
+    CHANNELS.get_or_init(|| serde_json::from_str(include_str!(concat!(env!("OUT_DIR"), "/channels.json"))).unwrap_or_default())
+}
+
+/// The newest release of `runtime` satisfying `version` that the channels
+/// built into the binary know of, which may since have been patched.
+pub(crate) fn latest(
+    runtime: Runtime,
+    version: &DotnetVersion,
+    include_prerelease: bool,
+    roll_forward: RollForward,
+) -> Option<Version> {
+    let candidates = channels()
+        .iter()
+        .filter_map(|channel| channel.latest(runtime))
+        .filter(approved::allows)
+        .map(|version| (version, ()));
+    version.select(candidates, include_prerelease, roll_forward).map(|(version, ())| version)
+}
+
+/// Fetches the current release metadata, validates it and replaces the local
+/// overlay with it. Returns the number of installers now known locally.
+pub async fn update() -> Result<usize> {
+    let path = overlay_path().context("cannot find the local application data directory")?;
+    let entries = releases::windows_files().await?;
+
+    if entries.is_empty() {
+        bail!("the release metadata lists no Windows installers");
+    }
+    for entry in &entries {
+        Version::parse(&entry.version).with_context(|| format!("invalid version {} for {}", entry.version, entry.url))?;
+        if !entry.url.starts_with("https://") {
+            bail!("refusing insecure URL {}", entry.url);
+        }
+        match &entry.hash {
+            Some(hash) if hash.len() == 128 && hash.chars().all(|c| c.is_ascii_hexdigit()) => {}
+            _ => bail!("missing or malformed SHA512 hash for {}", entry.url),
+        }
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // Write under a temporary name so a failed update leaves the old overlay intact.
+    let partial = path.with_extension("partial");
+    fs::write(&partial, serde_json::to_string(&entries)?)?;
+    fs::rename(&partial, &path).with_context(|| format!("cannot write {}", path.display()))?;
+
+    Ok(entries.len())
+}
+
+/// Finds the installer a requirement rolls forward to in the catalogs.
+pub(crate) fn find(
+    runtime: Runtime,
+    arch: Architecture,
+    version: &DotnetVersion,
+    package: Package,
+    include_prerelease: bool,
+    roll_forward: RollForward,
+) -> Option<Artifact> {
+    if REFRESH.get().copied().unwrap_or(false) {
+        return None;
+    }
+
+    let name = releases::file_name(runtime, arch, package);
+    let candidates = entries()
+        .iter()
+        .filter(|entry| entry.name == name)
+        .filter_map(|entry| Some((Version::parse(&entry.version).ok()?, entry)))
+        .filter(|(version, _)| approved::allows(version));
+    version
+        .select(candidates, include_prerelease, roll_forward)
+        .map(|(version, entry)| Artifact {
+            version,
+            url: entry.url.clone(),
+            hash: entry.hash.clone(),
+        })
+}
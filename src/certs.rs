@@ -0,0 +1,348 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::BufReader,
+    path::Path,
+    sync::{Mutex, PoisonError},
+};
+
+use anyhow::{Context, Result, anyhow};
+use async_rustls::{
+    rustls::{
+        Certificate, RootCertStore, ServerCertVerified, ServerCertVerifier, TLSError, WebPKIVerifier, internal::pemfile,
+    },
+    webpki::DNSNameRef,
+};
+use sha2::{Digest, Sha256};
+
+use crate::verify::hex;
+
+/// Reads the certificates from a PEM bundle.
+pub(crate) fn read_pem(path: &Path) -> Result<Vec<Certificate>> {
+    let file = File::open(path).with_context(|| format!("cannot read CA bundle {}", path.display()))?;
+    let certs = pemfile::certs(&mut BufReader::new(file)).map_err(|()| anyhow!("invalid CA bundle {}", path.display()))?;
+    if certs.is_empty() {
+        return Err(anyhow!("no certificates in CA bundle {}", path.display()));
+    }
+    Ok(certs)
+}
+
+/// The root certificates the OS trusts, which include those of corporate
+/// TLS-inspection proxies that group policy deploys.
+#[cfg(windows)]
+pub(crate) fn system_roots() -> Vec<Certificate> {
+    use std::ptr::null;
+    use winapi::um::wincrypt::{CertCloseStore, CertEnumCertificatesInStore, CertOpenSystemStoreW};
+
+    let name: Vec<u16> = "ROOT\0".encode_utf16().collect();
+    let mut certs = Vec::new();
+    unsafe {
+        let store = CertOpenSystemStoreW(0, name.as_ptr());
+        if store.is_null() {
+            log::debug!("cannot open the ROOT certificate store: {}", std::io::Error::last_os_error());
+            return certs;
+        }
+        let mut context = CertEnumCertificatesInStore(store, null());
+        while !context.is_null() {
+            let der = std::slice::from_raw_parts((*context).pbCertEncoded, (*context).cbCertEncoded as usize);
+            certs.push(Certificate(der.to_vec()));
+            context = CertEnumCertificatesInStore(store, context);
+        }
+        CertCloseStore(store, 0);
+    }
+    certs
+}
+
+/// The bundle OpenSSL would use: `SSL_CERT_FILE`, else the first of the
+/// distributions' usual locations.
+#[cfg(not(windows))]
+pub(crate) fn system_roots() -> Vec<Certificate> {
+    let candidates = std::env::var_os("SSL_CERT_FILE").into_iter().map(Into::into).chain(
+        [
+            "/etc/ssl/certs/ca-certificates.crt",
+            "/etc/pki/tls/certs/ca-bundle.crt",
+            "/etc/ssl/ca-bundle.pem",
+            "/etc/ssl/cert.pem",
+        ]
+        .iter()
+        .map(Into::into),
+    );
+    for path in candidates.collect::<Vec<std::path::PathBuf>>() {
+        if !path.is_file() {
+            continue;
+        }
+        match read_pem(&path) {
+            Ok(certs) => return certs,
+            Err(e) => log::debug!("{:#}", e),
+        }
+    }
+    Vec::new()
+}
+
+/// Accepts whatever certificate a server presents, for `--insecure`. Each
+/// one that would not have verified is reported the first time it is seen,
+/// so the user knows whose certificate they trusted.
+pub(crate) struct Insecure {
+    verifier: WebPKIVerifier,
+    reported: Mutex<HashSet<Vec<u8>>>,
+}
+
+impl Insecure {
+    pub(crate) fn new() -> Self {
+        Insecure {
+            verifier: WebPKIVerifier::new(),
+            reported: Mutex::new(HashSet::new()),
+        }
+    }
+}
+
+impl ServerCertVerifier for Insecure {
+    fn verify_server_cert(
+        &self,
+        roots: &RootCertStore,
+        presented: &[Certificate],
+        dns_name: DNSNameRef,
+        ocsp_response: &[u8],
+    ) -> Result<ServerCertVerified, TLSError> {
+        let e = match self.verifier.verify_server_cert(roots, presented, dns_name, ocsp_response) {
+            Ok(verified) => return Ok(verified),
+            Err(e) => e,
+        };
+        if let Some(cert) = presented.first() {
+            let first = self.reported.lock().unwrap_or_else(PoisonError::into_inner).insert(cert.0.clone());
+            if first {
+                let host: &str = dns_name.into();
+                let (subject, issuer) = names(&cert.0).unwrap_or_default();
+                log::warn!(
+                    "trusting the unverified certificate of {} ({}): subject {:?}, issuer {:?}, SHA-256 {}",
+                    host,
+                    e,
+                    subject,
+                    issuer,
+                    hex(&Sha256::digest(&cert.0))
+                );
+            }
+        }
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+/// SHA-256 hashes of the SubjectPublicKeyInfo of certificates, HPKP's
+/// `pin-sha256`, by the host name they are pinned for. A name starting
+/// with `*.` stands for every subdomain.
+pub(crate) type Pins = HashMap<String, Vec<Vec<u8>>>;
+
+/// Reads a TOML file mapping host names to lists of base64 pins.
+pub(crate) fn read_pins(path: &Path) -> Result<Pins> {
+    let text = fs::read_to_string(path).with_context(|| format!("cannot read pins {}", path.display()))?;
+    let hosts: HashMap<String, Vec<String>> =
+        toml::from_str(&text).with_context(|| format!("invalid pins file {}", path.display()))?;
+    hosts
+        .into_iter()
+        .map(|(host, pins)| {
+            let pins = pins
+                .iter()
+                .map(|pin| match base64::decode(pin) {
+                    Ok(hash) if hash.len() == 32 => Ok(hash),
+                    _ => Err(anyhow!("{} in {} is not a base64 SHA-256 hash", pin, path.display())),
+                })
+                .collect::<Result<_>>()?;
+            Ok((host.to_lowercase(), pins))
+        })
+        .collect()
+}
+
+/// Verifies certificates as usual, then rejects the chains of pinned hosts
+/// whose certificate neither has one of their pins nor chains up to a
+/// certificate or root that has one, for `--pin-certs`.
+pub(crate) struct Pinned {
+    verifier: WebPKIVerifier,
+    pins: Pins,
+}
+
+impl Pinned {
+    pub(crate) fn new(pins: Pins) -> Self {
+        Pinned { verifier: WebPKIVerifier::new(), pins }
+    }
+
+    /// The pins of `host`, by its own name or else a wildcard for its parent.
+    fn pins_of(&self, host: &str) -> Option<&[Vec<u8>]> {
+        let host = host.to_lowercase();
+        let wildcard = host.split_once('.').map(|(_, parent)| format!("*.{}", parent));
+        self.pins.get(&host).or_else(|| self.pins.get(&wildcard?)).map(Vec::as_slice)
+    }
+
+    /// Whether the server's certificate has one of `pins`, or verifies with
+    /// a pinned certificate of the chain or a pinned root as the only trust
+    /// anchor. A pinned certificate merely sent along proves nothing, as
+    /// anyone can send it, so only those the certificate chains up to count.
+    fn chains_to_pin(
+        &self,
+        roots: &RootCertStore,
+        presented: &[Certificate],
+        dns_name: DNSNameRef,
+        pins: &[Vec<u8>],
+    ) -> bool {
+        let pinned = |spki: &[u8]| pins.contains(&Sha256::digest(spki).to_vec());
+        if presented.first().and_then(|cert| spki(&cert.0)).is_some_and(pinned) {
+            return true;
+        }
+        let chain = presented.iter().skip(1).filter(|cert| spki(&cert.0).is_some_and(pinned)).filter_map(|cert| {
+            let mut anchor = RootCertStore::empty();
+            anchor.add(cert).ok().map(|()| anchor)
+        });
+        let roots = roots
+            .roots
+            .iter()
+            .filter(|root| pinned(&sequence(root.to_trust_anchor().spki)))
+            .map(|root| RootCertStore { roots: vec![root.clone()] });
+        chain.chain(roots).any(|anchor| self.verifier.verify_server_cert(&anchor, presented, dns_name, &[]).is_ok())
+    }
+}
+
+impl ServerCertVerifier for Pinned {
+    fn verify_server_cert(
+        &self,
+        roots: &RootCertStore,
+        presented: &[Certificate],
+        dns_name: DNSNameRef,
+        ocsp_response: &[u8],
+    ) -> Result<ServerCertVerified, TLSError> {
+        let verified = self.verifier.verify_server_cert(roots, presented, dns_name, ocsp_response)?;
+        let host: &str = dns_name.into();
+        let pins = match self.pins_of(host) {
+            Some(pins) => pins,
+            None => return Ok(verified),
+        };
+        if self.chains_to_pin(roots, presented, dns_name, pins) {
+            return Ok(verified);
+        }
+        let hashes = presented.iter().filter_map(|cert| spki(&cert.0)).map(|spki| base64::encode(Sha256::digest(spki)));
+        let hashes = hashes.collect::<Vec<_>>().join(", ");
+        log::warn!("the certificate of {} chains up to no pinned key; its chain has {}", host, hashes);
+        Err(TLSError::General(format!("the certificate chain of {} matches none of its pins", host)))
+    }
+}
+
+/// `contents` as a DER SEQUENCE, tag and length included, such as the
+/// SubjectPublicKeyInfo of a trust anchor, which keeps only its contents.
+fn sequence(contents: &[u8]) -> Vec<u8> {
+    let len = contents.len().to_be_bytes();
+    let len = match contents.len() {
+        0..=0x7f => vec![contents.len() as u8],
+        _ => {
+            let significant = len.iter().skip_while(|&&b| b == 0).copied().collect::<Vec<_>>();
+            std::iter::once(0x80 | significant.len() as u8).chain(significant).collect()
+        }
+    };
+    std::iter::once(0x30).chain(len).chain(contents.iter().copied()).collect()
+}
+
+/// The DER SubjectPublicKeyInfo of a DER certificate, tag and length included.
+fn spki(der: &[u8]) -> Option<&[u8]> {
+    let (_, cert, _) = der_element(der)?;
+    let (_, mut tbs, _) = der_element(cert)?;
+    if tbs.first() == Some(&0xa0) {
+        tbs = der_element(tbs)?.2;
+    }
+    // The serial number, signature algorithm, issuer, validity and subject come first.
+    for _ in 0..5 {
+        tbs = der_element(tbs)?.2;
+    }
+    let rest = der_element(tbs)?.2;
+    Some(&tbs[..tbs.len() - rest.len()])
+}
+
+/// The common names of the subject and the issuer of a DER certificate.
+fn names(der: &[u8]) -> Option<(String, String)> {
+    let (_, cert, _) = der_element(der)?;
+    let (_, mut tbs, _) = der_element(cert)?;
+    // Skip the explicit version if there is one, then the serial number and
+    // the signature algorithm.
+    if tbs.first() == Some(&0xa0) {
+        tbs = der_element(tbs)?.2;
+    }
+    let tbs = der_element(der_element(tbs)?.2)?.2;
+    let (_, issuer, rest) = der_element(tbs)?;
+    let rest = der_element(rest)?.2;
+    let (_, subject, _) = der_element(rest)?;
+    Some((common_name(subject)?, common_name(issuer)?))
+}
+
+/// The common name in a DER Name, or its first attribute if it has none.
+fn common_name(mut name: &[u8]) -> Option<String> {
+    const COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+
+    let mut first = None;
+    while !name.is_empty() {
+        let (_, set, rest) = der_element(name)?;
+        name = rest;
+        let (_, attribute, _) = der_element(set)?;
+        let (_, oid, value) = der_element(attribute)?;
+        let value = String::from_utf8_lossy(der_element(value)?.1).into_owned();
+        if oid == COMMON_NAME {
+            return Some(value);
+        }
+        first.get_or_insert(value);
+    }
+    first
+}
+
+/// Splits the DER element at the start of `data` into its tag, its contents
+/// and what follows it.
+fn der_element(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = data.split_first()?;
+    let (&first, mut rest) = rest.split_first()?;
+    let len = if first < 0x80 {
+        first as usize
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count > 4 || rest.len() < count {
+            return None;
+        }
+        let len = rest[..count].iter().fold(0, |len, &b| len << 8 | b as usize);
+        rest = &rest[count..];
+        len
+    };
+    if rest.len() < len {
+        return None;
+    }
+    Some((tag, &rest[..len], &rest[len..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cert(name: &str) -> Certificate {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/certs").join(name);
+        read_pem(&path).unwrap().remove(0)
+    }
+
+    fn verify(presented: &[Certificate], pinned: &Certificate) -> Result<ServerCertVerified, TLSError> {
+        let mut roots = RootCertStore::empty();
+        roots.add(&cert("ca.pem")).unwrap();
+        let pins = vec![Sha256::digest(spki(&pinned.0).unwrap()).to_vec()];
+        let verifier = Pinned::new(std::iter::once(("localhost".to_owned(), pins)).collect());
+        verifier.verify_server_cert(&roots, presented, DNSNameRef::try_from_ascii_str("localhost").unwrap(), &[])
+    }
+
+    #[test]
+    fn pinned_leaf_or_root_it_chains_up_to_is_accepted() {
+        assert!(verify(&[cert("leaf.pem")], &cert("leaf.pem")).is_ok());
+        assert!(verify(&[cert("leaf.pem")], &cert("ca.pem")).is_ok());
+    }
+
+    #[test]
+    fn pinned_certificate_sent_along_unrelated_is_rejected() {
+        assert!(verify(&[cert("leaf.pem"), cert("other.pem")], &cert("other.pem")).is_err());
+        assert!(verify(&[cert("leaf.pem"), cert("other.pem")], &cert("ca.pem")).is_ok());
+    }
+
+    #[test]
+    fn sequence_restores_the_spki_of_a_trust_anchor() {
+        let ca = cert("ca.pem");
+        let anchor = async_rustls::webpki::trust_anchor_util::cert_der_as_trust_anchor(&ca.0).unwrap();
+        assert_eq!(sequence(anchor.spki), spki(&ca.0).unwrap());
+    }
+}
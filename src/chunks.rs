@@ -0,0 +1,270 @@
+use std::{
+    cell::{Cell, RefCell},
+    fs,
+    io::SeekFrom,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use anyhow::{Result, anyhow, bail};
+use http_types::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use smol::{
+    fs::{File, OpenOptions},
+    io,
+    lock::Mutex,
+    prelude::*,
+};
+
+use crate::{
+    DotnetRedistError,
+    download::DownloadBudget,
+    http,
+    progress::{Progress, Sink},
+    verify::{StreamingHash, hex},
+};
+
+/// Size of the ranges large downloads are fetched and verified in.
+const CHUNK_SIZE: u64 = 8 * 1024 * 1024;
+
+static SEGMENTS: OnceLock<usize> = OnceLock::new();
+
+/// Sets how many chunks of a large download are fetched at the same time,
+/// each over its own connection. Defaults to one.
+pub fn set_segments(segments: usize) {
+    let _ = SEGMENTS.set(segments.max(1));
+}
+
+/// The chunks of a download written so far, kept next to it so that an
+/// interrupted download only fetches what is missing.
+#[derive(Serialize, Deserialize)]
+struct State {
+    url: String,
+    size: u64,
+    /// The ETag or Last-Modified of the file the chunks came from.
+    validator: Option<String>,
+    chunk_size: u64,
+    /// The SHA-256 of each chunk written, computed as it was received.
+    chunks: Vec<Option<String>>,
+}
+
+/// The size and validator of `url` if it is large enough to download in
+/// chunks and its server supports range requests.
+pub(crate) async fn probe(url: &str) -> Option<(u64, Option<String>)> {
+    let response = http::head(url).await.ok()?;
+    if !response.status().is_success() {
+        return None;
+    }
+
+    let ranges = response
+        .header("Accept-Ranges")
+        .is_some_and(|value| value.last().as_str() == "bytes");
+    let size: u64 = response.header("Content-Length")?.last().as_str().parse().ok()?;
+    if !ranges || size <= CHUNK_SIZE {
+        return None;
+    }
+
+    let validator = ["ETag", "Last-Modified"]
+        .iter()
+        .find_map(|name| response.header(*name))
+        .map(|value| value.last().to_string())
+        .filter(|value| !value.starts_with("W/"));
+    Some((size, validator))
+}
+
+/// Downloads the `size` bytes of `url` to `path` in chunks, several at a
+/// time if so set, retrying a failed chunk on its own instead of the whole
+/// download. The chunks are fed to `hash` in order as they are written.
+pub(crate) async fn download(
+    url: &str,
+    path: &Path,
+    size: u64,
+    validator: Option<String>,
+    hash: &mut StreamingHash,
+    budget: &DownloadBudget,
+    progress: Sink<'_>,
+) -> Result<()> {
+    let state_path = state_path(path);
+    let count = size.div_ceil(CHUNK_SIZE) as usize;
+    let mut state = match load(&state_path) {
+        Some(state)
+            if state.url == url
+                && state.size == size
+                && state.validator == validator
+                && state.chunk_size == CHUNK_SIZE
+                && path.exists() =>
+        {
+            state
+        }
+        _ => State {
+            url: url.to_string(),
+            size,
+            validator,
+            chunk_size: CHUNK_SIZE,
+            chunks: vec![None; count],
+        },
+    };
+
+    let mut file = OpenOptions::new().read(true).write(true).create(true).open(path).await?;
+    file.set_len(size).await?;
+
+    // Chunks written by an earlier run may not have reached the disk intact.
+    // The ones before the first missing chunk are hashed while checking them.
+    *hash = StreamingHash::new();
+    let mut hashed = 0;
+    let mut done = 0;
+    for (index, chunk_hash) in state.chunks.iter_mut().enumerate() {
+        if let Some(expected) = chunk_hash {
+            let (start, end) = range(index, size);
+            let data = read(&mut file, start, end).await?;
+            if hex(&Sha256::digest(&data)) == *expected {
+                done += end - start;
+                if hashed == index {
+                    hash.update(&data);
+                    hashed += 1;
+                }
+            } else {
+                log::warn!("chunk {} of {} is damaged, fetching it again", index + 1, url);
+                *chunk_hash = None;
+            }
+        }
+    }
+    if done > 0 {
+        log::info!("resuming {} with {} of {} bytes already downloaded", url, done, size);
+    }
+
+    budget.check(size - done)?;
+    let progress = RefCell::new(Progress::new(progress, url, done, Some(size)));
+    let pending: Vec<usize> = (0..count).filter(|&index| state.chunks[index].is_none()).collect();
+    let next = Cell::new(0);
+    let failed = Cell::new(false);
+    let output = Mutex::new((file, state, hash, hashed));
+
+    // Each segment takes the next chunk nobody has started on, so a slow
+    // connection holds up no more than the chunk it is on.
+    let segment = || async {
+        while let Some(&index) = pending.get(next.get()).filter(|_| !failed.get()) {
+            next.set(next.get() + 1);
+            let (start, end) = range(index, size);
+            let data = match fetch_retrying(url, index, start, end, &progress).await {
+                Ok(data) => data,
+                Err(e) => {
+                    failed.set(true);
+                    return Err(e);
+                }
+            };
+
+            let mut output = output.lock().await;
+            let (file, state, hash, hashed) = &mut *output;
+            file.seek(SeekFrom::Start(start)).await?;
+            file.write_all(&data).await?;
+            file.sync_data().await?;
+            state.chunks[index] = Some(hex(&Sha256::digest(&data)));
+            save(&state_path, state)?;
+            budget.consume(data.len() as u64);
+
+            // Chunks that overtook this one, fetched by other segments, are
+            // read back to hash them in order.
+            if index == *hashed {
+                hash.update(&data);
+                *hashed += 1;
+                while state.chunks.get(*hashed).is_some_and(Option::is_some) {
+                    let (start, end) = range(*hashed, size);
+                    hash.update(&read(file, start, end).await?);
+                    *hashed += 1;
+                }
+            }
+        }
+        Ok(())
+    };
+    let segments = SEGMENTS.get().copied().unwrap_or(1).min(pending.len()).max(1);
+    let results: Vec<Result<()>> = crate::join_all((0..segments).map(|_| segment())).await;
+    progress.into_inner().finish();
+    results.into_iter().collect::<Result<()>>()?;
+
+    let _ = fs::remove_file(&state_path);
+    Ok(())
+}
+
+/// Fetches chunk `index`, bytes `start..end` of `url`, retrying it on its own
+/// if the transfer breaks off.
+async fn fetch_retrying(url: &str, index: usize, start: u64, end: u64, progress: &RefCell<Progress>) -> Result<Vec<u8>> {
+    let attempts = http::retry_policy().attempts;
+    let mut attempt = 1;
+    loop {
+        match fetch(url, start, end, progress).await {
+            Ok(data) => return Ok(data),
+            Err(e) if attempt < attempts && http::is_transient(&e) => {
+                log::warn!("chunk {} of {} interrupted: {:#}, retrying it", index + 1, url, e);
+                http::backoff(attempt).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Fetches bytes `start..end` of `url`, taking them back off `progress` if
+/// the transfer breaks off.
+async fn fetch(url: &str, start: u64, end: u64, progress: &RefCell<Progress>) -> Result<Vec<u8>> {
+    let mut response = http::get_slice(url, start, end).await?;
+    if response.status() != StatusCode::PartialContent {
+        bail!(DotnetRedistError::Network(anyhow!("{} answered a range request with {}", url, response.status())));
+    }
+
+    let len = end - start;
+    let mut data = Vec::with_capacity(len as usize);
+    let mut buf = vec![0; 64 * 1024];
+    while (data.len() as u64) < len {
+        let n = match response.read(&mut buf).await {
+            Ok(0) => Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+            result => result,
+        };
+        match n {
+            Ok(n) => {
+                data.extend_from_slice(&buf[..n]);
+                progress.borrow_mut().advance(n as u64);
+            }
+            Err(e) => {
+                progress.borrow_mut().retreat(data.len() as u64);
+                return Err(e.into());
+            }
+        }
+    }
+    if data.len() as u64 != len {
+        bail!("{} sent more than the requested range", url);
+    }
+
+    Ok(data)
+}
+
+async fn read(file: &mut File, start: u64, end: u64) -> io::Result<Vec<u8>> {
+    let mut data = vec![0; (end - start) as usize];
+    file.seek(SeekFrom::Start(start)).await?;
+    file.read_exact(&mut data).await?;
+    Ok(data)
+}
+
+fn range(index: usize, size: u64) -> (u64, u64) {
+    let start = index as u64 * CHUNK_SIZE;
+    (start, (start + CHUNK_SIZE).min(size))
+}
+
+fn state_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".chunks");
+    path.with_file_name(name)
+}
+
+fn load(path: &Path) -> Option<State> {
+    serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+/// Writes the state under a temporary name first so a crash never leaves it half written.
+fn save(path: &Path, state: &State) -> Result<()> {
+    let partial = path.with_extension("chunks-partial");
+    fs::write(&partial, serde_json::to_vec(state)?)?;
+    fs::rename(&partial, path)?;
+    Ok(())
+}
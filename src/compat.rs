@@ -0,0 +1,149 @@
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::{
+    Architecture, DotnetVersion, Requirements, RollForward, app,
+    container::{self, Container},
+    detect::{is_installed, is_vcruntime_installed},
+    emulation::FIRST_EMULATED_MAJOR,
+    os_support::minimum_windows,
+    platform::{is_64bit_os, is_arm64_host, windows_build},
+};
+
+/// Whether an app can start on this machine, and what stops it if not.
+#[derive(Serialize)]
+pub struct Compatibility {
+    pub app: PathBuf,
+    pub arch: String,
+    pub checks: Vec<Check>,
+    /// Whether every check passed.
+    pub compatible: bool,
+}
+
+/// One thing the app needs from the machine.
+#[derive(Serialize)]
+pub struct Check {
+    pub name: String,
+    pub ok: bool,
+    pub detail: String,
+}
+
+impl Display for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = if self.ok { "ok" } else { "FAILED" };
+        write!(f, "{:<8}{}: {}", status, self.name, self.detail)
+    }
+}
+
+/// Checks the app's architecture, the Windows version, the frameworks its
+/// `runtimeconfig.json` asks for and the VC++ runtime against this machine.
+///
+/// `app` and `arch` are as for [`app::requirements`].
+pub async fn check(app: &Path, arch: Option<Architecture>) -> Result<Compatibility> {
+    let requirements = app::requirements(app, arch)?;
+    let arch = requirements.components[0].arch;
+    let oldest_major = requirements
+        .components
+        .iter()
+        .filter_map(|requirement| requirement.version.parse::<DotnetVersion>().ok())
+        .map(|version| version.major)
+        .min()
+        .unwrap_or(0);
+
+    let mut checks = vec![arch_check(arch, oldest_major)];
+    if cfg!(windows) {
+        checks.push(os_check(oldest_major));
+    }
+    checks.extend(framework_checks(&requirements).await?);
+    if cfg!(windows) && container::detect() == Container::None {
+        let installed = is_vcruntime_installed(arch);
+        checks.push(Check {
+            name: "vcredist".into(),
+            ok: installed,
+            detail: if installed {
+                format!("the {} VC++ runtime is installed", arch)
+            } else {
+                format!("the {} VC++ runtime is missing or too old", arch)
+            },
+        });
+    }
+
+    Ok(Compatibility {
+        app: app.to_path_buf(),
+        arch: arch.to_string(),
+        compatible: checks.iter().all(|check| check.ok),
+        checks,
+    })
+}
+
+fn arch_check(arch: Architecture, oldest_major: u64) -> Check {
+    let (ok, detail) = match arch {
+        Architecture::X64 if !is_64bit_os() => (false, "x64 apps cannot run on a 32-bit OS".to_string()),
+        Architecture::Arm64 if !is_arm64_host() => (false, "ARM64 apps only run on ARM64 machines".to_string()),
+        Architecture::X64 if is_arm64_host() && oldest_major < FIRST_EMULATED_MAJOR => (
+            false,
+            format!(
+                ".NET {} does not support x64 emulation on ARM64; .NET {} or later is required",
+                oldest_major, FIRST_EMULATED_MAJOR
+            ),
+        ),
+        Architecture::X64 if is_arm64_host() => (true, "runs under x64 emulation on ARM64".to_string()),
+        _ => (true, format!("{} apps run on this machine", arch)),
+    };
+    Check {
+        name: "architecture".into(),
+        ok,
+        detail,
+    }
+}
+
+/// Compares the Windows build with the oldest the app's .NET release supports.
+fn os_check(oldest_major: u64) -> Check {
+    let (minimum, name) = minimum_windows(oldest_major);
+    let build = windows_build();
+
+    let (ok, detail) = match build {
+        Some(build) if build >= minimum => (true, format!("Windows build {}", build)),
+        Some(build) => (
+            false,
+            format!("Windows build {} is older than {}, which .NET {} needs", build, name, oldest_major),
+        ),
+        None => (true, "cannot read the Windows build; assuming it is supported".to_string()),
+    };
+    Check {
+        name: "os".into(),
+        ok,
+        detail,
+    }
+}
+
+async fn framework_checks(requirements: &Requirements) -> Result<Vec<Check>> {
+    let mut checks = Vec::new();
+    for requirement in &requirements.components {
+        let runtime = requirement.runtime.context("the app requires a framework of unknown kind")?;
+        let version: DotnetVersion = requirement.version.parse().context("invalid framework version")?;
+        let roll_forward = requirement.roll_forward.unwrap_or(RollForward::Minor);
+
+        let name = runtime.to_string().to_lowercase();
+        let installed = is_installed(requirement.arch, runtime, &version, false, roll_forward).await?;
+        let detail = if installed {
+            format!("{} {} or a release it rolls forward to ({}) is installed", name, version, roll_forward)
+        } else {
+            format!(
+                "needs {} {} or a release it rolls forward to ({}); `install --for-app` adds it",
+                name, version, roll_forward
+            )
+        };
+        checks.push(Check {
+            name,
+            ok: installed,
+            detail,
+        });
+    }
+    Ok(checks)
+}
@@ -0,0 +1,147 @@
+use std::{
+    convert::TryInto,
+    fmt::Display,
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use semver::Version;
+
+use crate::{
+    Architecture,
+    detect::get_root_install,
+    platform::{IMAGE_FILE_MACHINE_AMD64, IMAGE_FILE_MACHINE_ARM64, IMAGE_FILE_MACHINE_I386, is_64bit_os, is_arm64_host},
+    user,
+};
+
+/// An install that contradicts another one and can be removed.
+pub struct Conflict {
+    pub path: PathBuf,
+    reason: String,
+}
+
+impl Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.reason)
+    }
+}
+
+/// Finds runtimes of the wrong architecture inside a machine-wide root and
+/// per-user installs that duplicate a machine-wide band.
+pub fn find() -> Vec<Conflict> {
+    let mut conflicts = Vec::new();
+
+    for arch in Architecture::installable() {
+        find_foreign_arch(get_root_install(arch), arch, &mut conflicts);
+    }
+
+    if let Some(user_root) = user_root() {
+        let arch = match pe_machine(&user_root.join("dotnet.exe")) {
+            Some(IMAGE_FILE_MACHINE_I386) => Some(Architecture::X86),
+            Some(IMAGE_FILE_MACHINE_AMD64) if is_64bit_os() => Some(Architecture::X64),
+            Some(IMAGE_FILE_MACHINE_ARM64) if is_arm64_host() => Some(Architecture::Arm64),
+            _ => None,
+        };
+        if let Some(arch) = arch {
+            find_duplicate_bands(&user_root, get_root_install(arch), &mut conflicts);
+        }
+    }
+
+    conflicts
+}
+
+/// Removes the conflicting installs.
+pub fn fix(conflicts: &[Conflict]) -> Result<()> {
+    for conflict in conflicts {
+        fs::remove_dir_all(&conflict.path)
+            .with_context(|| format!("could not remove {}", conflict.path.display()))?;
+    }
+
+    Ok(())
+}
+
+/// The default location of per-user installs, if there are any.
+fn user_root() -> Option<PathBuf> {
+    user::root(Architecture::native()).ok().filter(|root| root.exists())
+}
+
+fn find_foreign_arch(root: &Path, arch: Architecture, conflicts: &mut Vec<Conflict>) {
+    let expected = match arch {
+        Architecture::X86 => IMAGE_FILE_MACHINE_I386,
+        Architecture::X64 => IMAGE_FILE_MACHINE_AMD64,
+        Architecture::Arm64 => IMAGE_FILE_MACHINE_ARM64,
+    };
+
+    let native_files = versions(&root.join("shared\\Microsoft.NETCore.App"))
+        .map(|(path, _)| (path.join("coreclr.dll"), path))
+        .chain(versions(&root.join("host\\fxr")).map(|(path, _)| (path.join("hostfxr.dll"), path)));
+
+    for (file, path) in native_files {
+        if let Some(machine) = pe_machine(&file) {
+            if machine != expected {
+                conflicts.push(Conflict {
+                    path,
+                    reason: format!("not an {} build but installed in the {} root", arch, arch),
+                });
+            }
+        }
+    }
+}
+
+fn find_duplicate_bands(user_root: &Path, machine_root: &Path, conflicts: &mut Vec<Conflict>) {
+    let frameworks = match fs::read_dir(user_root.join("shared")) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for framework in frameworks.filter_map(|entry| entry.ok()) {
+        let machine_framework = machine_root.join("shared").join(framework.file_name());
+        let machine_bands = versions(&machine_framework)
+            .map(|(_, version)| (version.major, version.minor))
+            .collect::<Vec<_>>();
+
+        for (path, version) in versions(&framework.path()) {
+            if machine_bands.contains(&(version.major, version.minor)) {
+                conflicts.push(Conflict {
+                    path,
+                    reason: format!(
+                        "{}.{} is also installed machine-wide in {}",
+                        version.major,
+                        version.minor,
+                        machine_framework.display()
+                    ),
+                });
+            }
+        }
+    }
+}
+
+/// Lists the version-named subdirectories of `dir`.
+fn versions(dir: &Path) -> impl Iterator<Item = (PathBuf, Version)> {
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().map(|t| t.is_dir()).unwrap_or(false))
+        .filter_map(|entry| {
+            let version = Version::parse(&entry.file_name().to_string_lossy()).ok()?;
+            Some((entry.path(), version))
+        })
+}
+
+/// Reads the target machine from a PE image's COFF header.
+pub(crate) fn pe_machine(path: &Path) -> Option<u16> {
+    let mut header = [0; 1024];
+    let len = fs::File::open(path).ok()?.read(&mut header).ok()?;
+    let header = &header[..len];
+
+    let pe_offset = u32::from_le_bytes(header.get(0x3c..0x40)?.try_into().ok()?) as usize;
+    if header.get(pe_offset..pe_offset + 4)? != b"PE\0\0" {
+        return None;
+    }
+
+    let machine = header.get(pe_offset + 4..pe_offset + 6)?;
+    Some(u16::from_le_bytes(machine.try_into().ok()?))
+}
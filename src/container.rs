@@ -0,0 +1,33 @@
+use clap::arg_enum;
+
+use crate::registry::{self, View};
+
+arg_enum! {
+    /// The kind of Windows container the tool runs in, if any.
+    #[derive(Copy, Clone, PartialEq, Eq, Debug)]
+    pub enum Container {
+        None,
+        ServerCore,
+        NanoServer,
+    }
+}
+
+/// Detects whether this process runs inside a Windows container, and which base image.
+pub(crate) fn detect() -> Container {
+    let in_container = registry::read_dword("SYSTEM\\CurrentControlSet\\Control", "ContainerType", View::Registry64)
+        .is_some()
+        || std::env::var("USERNAME").is_ok_and(|user| user == "ContainerAdministrator" || user == "ContainerUser");
+    if !in_container {
+        return Container::None;
+    }
+
+    let installation_type = registry::read_string(
+        "SOFTWARE\\Microsoft\\Windows NT\\CurrentVersion",
+        "InstallationType",
+        View::Registry64,
+    );
+    match installation_type.as_deref() {
+        Some("Nano Server") => Container::NanoServer,
+        _ => Container::ServerCore,
+    }
+}
@@ -0,0 +1,177 @@
+use std::{
+    backtrace::Backtrace,
+    fs, io,
+    path::PathBuf,
+    sync::{
+        OnceLock,
+        atomic::{AtomicI32, Ordering},
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The exit code of a run that crashed, distinct from installer failures.
+pub const EXIT_CODE: i32 = 70;
+
+/// The exit code crashes actually end with.
+static EXIT: AtomicI32 = AtomicI32::new(EXIT_CODE);
+
+/// Where reports go, and the command line to record in them.
+static CONTEXT: OnceLock<(PathBuf, String)> = OnceLock::new();
+
+/// Writes a crash report into `dir` when the tool panics or, on Windows,
+/// dies of an unhandled exception, then exits with [`EXIT_CODE`] or the code
+/// given to [`set_exit_code`].
+///
+/// Each report has the panic message or exception code, a backtrace and the
+/// command line; on Windows a minidump is written next to it.
+pub fn install(dir: PathBuf) {
+    let options = std::env::args().skip(1).collect::<Vec<_>>().join(" ");
+    let _ = CONTEXT.set((dir, options));
+
+    std::panic::set_hook(Box::new(|info| {
+        let message = format!("panic: {}\n\nbacktrace:\n{}", info, Backtrace::force_capture());
+        report(&message, std::ptr::null_mut());
+        std::process::exit(EXIT.load(Ordering::SeqCst));
+    }));
+
+    #[cfg(windows)]
+    unsafe {
+        winapi::um::errhandlingapi::SetUnhandledExceptionFilter(Some(windows::on_exception));
+    }
+}
+
+/// Makes crashes exit with `code` instead of [`EXIT_CODE`], for callers
+/// that only understand certain codes.
+pub fn set_exit_code(code: i32) {
+    EXIT.store(code, Ordering::SeqCst);
+}
+
+/// Writes the report and tells the user where it went.
+fn report(message: &str, exception: Exception) {
+    eprintln!("error: the tool crashed: {}", message.lines().next().unwrap_or_default());
+    match write(message, exception) {
+        Ok(path) => eprintln!("a crash report was written to {}", path.display()),
+        Err(e) => eprintln!("could not write a crash report: {}", e),
+    }
+}
+
+fn write(message: &str, exception: Exception) -> io::Result<PathBuf> {
+    let (dir, options) = CONTEXT.get().ok_or_else(|| io::Error::other("crash reporting is not set up"))?;
+    fs::create_dir_all(dir)?;
+
+    let stamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+    let path = dir.join(format!("crash-{}-{}.txt", stamp, std::process::id()));
+    fs::write(
+        &path,
+        format!(
+            "{} {}\noptions: {}\n\n{}\n",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION"),
+            options,
+            message
+        ),
+    )?;
+
+    #[cfg(windows)]
+    windows::minidump(&path.with_extension("dmp"), exception)?;
+    #[cfg(not(windows))]
+    let _ = exception;
+
+    Ok(path)
+}
+
+#[cfg(windows)]
+type Exception = *mut winapi::um::winnt::EXCEPTION_POINTERS;
+#[cfg(not(windows))]
+type Exception = *mut ();
+
+#[cfg(windows)]
+mod windows {
+    use std::{ffi::OsStr, io, os::windows::ffi::OsStrExt, path::Path, ptr::null_mut, sync::atomic::Ordering};
+
+    use winapi::{
+        shared::{
+            minwindef::{BOOL, DWORD, FALSE},
+            ntdef::{HANDLE, LONG},
+        },
+        um::{
+            fileapi::{CREATE_ALWAYS, CreateFileW},
+            handleapi::{CloseHandle, INVALID_HANDLE_VALUE},
+            processthreadsapi::{ExitProcess, GetCurrentProcess, GetCurrentProcessId, GetCurrentThreadId},
+            winnt::{EXCEPTION_POINTERS, FILE_ATTRIBUTE_NORMAL, GENERIC_WRITE},
+        },
+    };
+
+    use super::{EXIT, report};
+
+    const MINI_DUMP_NORMAL: DWORD = 0;
+
+    // dbghelp.h packs its structures to 4 bytes.
+    #[repr(C, packed(4))]
+    struct MinidumpExceptionInformation {
+        thread_id: DWORD,
+        exception_pointers: *mut EXCEPTION_POINTERS,
+        client_pointers: BOOL,
+    }
+
+    #[link(name = "dbghelp")]
+    extern "system" {
+        fn MiniDumpWriteDump(
+            process: HANDLE,
+            process_id: DWORD,
+            file: HANDLE,
+            dump_type: DWORD,
+            exception_param: *mut MinidumpExceptionInformation,
+            user_stream_param: *mut u8,
+            callback_param: *mut u8,
+        ) -> BOOL;
+    }
+
+    pub(super) unsafe extern "system" fn on_exception(info: *mut EXCEPTION_POINTERS) -> LONG {
+        let code = if info.is_null() || (*info).ExceptionRecord.is_null() {
+            0
+        } else {
+            (*(*info).ExceptionRecord).ExceptionCode
+        };
+        report(&format!("unhandled exception {:#010x}", code), info);
+        ExitProcess(EXIT.load(Ordering::SeqCst) as u32);
+        0
+    }
+
+    pub(super) fn minidump(path: &Path, exception: *mut EXCEPTION_POINTERS) -> io::Result<()> {
+        let wide: Vec<u16> = OsStr::new(path).encode_wide().chain(Some(0)).collect();
+        unsafe {
+            let file = CreateFileW(
+                wide.as_ptr(),
+                GENERIC_WRITE,
+                0,
+                null_mut(),
+                CREATE_ALWAYS,
+                FILE_ATTRIBUTE_NORMAL,
+                null_mut(),
+            );
+            if file == INVALID_HANDLE_VALUE {
+                return Err(io::Error::last_os_error());
+            }
+
+            let mut info = MinidumpExceptionInformation {
+                thread_id: GetCurrentThreadId(),
+                exception_pointers: exception,
+                client_pointers: FALSE,
+            };
+            let info = if exception.is_null() { null_mut() } else { &mut info as *mut _ };
+            let ok = MiniDumpWriteDump(
+                GetCurrentProcess(),
+                GetCurrentProcessId(),
+                file,
+                MINI_DUMP_NORMAL,
+                info,
+                null_mut(),
+                null_mut(),
+            );
+            let result = if ok == 0 { Err(io::Error::last_os_error()) } else { Ok(()) };
+            CloseHandle(file);
+            result
+        }
+    }
+}
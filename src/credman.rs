@@ -0,0 +1,49 @@
+/// A user name and password stored in the Windows Credential Manager.
+pub(crate) struct Credential {
+    pub user: String,
+    pub password: String,
+}
+
+/// Reads the generic credential stored under `target`, as created with
+/// `cmdkey /generic:<target> /user:<user> /pass` or the Credential Manager UI.
+#[cfg(windows)]
+pub(crate) fn read(target: &str) -> Option<Credential> {
+    use std::{ffi::OsStr, os::windows::ffi::OsStrExt, ptr::null_mut, slice};
+    use winapi::um::wincred::{CRED_TYPE_GENERIC, CredFree, CredReadW, PCREDENTIALW};
+
+    let target: Vec<u16> = OsStr::new(target).encode_wide().chain(Some(0)).collect();
+
+    unsafe {
+        let mut credential: PCREDENTIALW = null_mut();
+        if CredReadW(target.as_ptr(), CRED_TYPE_GENERIC, 0, &mut credential) == 0 {
+            return None;
+        }
+
+        let user = if (*credential).UserName.is_null() {
+            String::new()
+        } else {
+            let name = (*credential).UserName;
+            let len = (0..).take_while(|&i| *name.add(i) != 0).count();
+            String::from_utf16_lossy(slice::from_raw_parts(name, len))
+        };
+
+        // Passwords saved through the UI or cmdkey are UTF-16.
+        let blob = slice::from_raw_parts(
+            (*credential).CredentialBlob,
+            (*credential).CredentialBlobSize as usize,
+        );
+        let units = blob
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect::<Vec<_>>();
+        let password = String::from_utf16_lossy(&units);
+
+        CredFree(credential.cast());
+        Some(Credential { user, password })
+    }
+}
+
+#[cfg(not(windows))]
+pub(crate) fn read(_target: &str) -> Option<Credential> {
+    None
+}
@@ -0,0 +1,530 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use anyhow::Result;
+use semver::Version;
+use smol::prelude::*;
+
+use crate::{
+    Architecture, DotnetVersion, RollForward, Runtime,
+    conflicts::pe_machine,
+    hosting, netfx,
+    platform::{IMAGE_FILE_MACHINE_AMD64, IMAGE_FILE_MACHINE_ARM64, IMAGE_FILE_MACHINE_I386, is_64bit_os, is_arm64_host, is_wow64},
+    registry::{self, View},
+    repair,
+};
+
+static MACHINE_WIDE_ONLY: OnceLock<bool> = OnceLock::new();
+
+/// Only looks for runtimes in the machine-wide install, not in the private
+/// installs the environment points at.
+pub fn set_machine_wide_only(machine_wide_only: bool) {
+    let _ = MACHINE_WIDE_ONLY.set(machine_wide_only);
+}
+
+pub(crate) async fn is_installed(
+    arch: Architecture,
+    runtime: Runtime,
+    dotnet_version: &DotnetVersion,
+    include_prerelease: bool,
+    roll_forward: RollForward,
+) -> Result<bool> {
+    if runtime == Runtime::NetFx48 {
+        return Ok(netfx::is_installed());
+    }
+    // Without IIS the bundle installs no module, so only the runtime can be missing.
+    if runtime == Runtime::HostingBundle && hosting::is_iis_installed() && !hosting::is_module_installed() {
+        log::debug!("the ASP.NET Core Module is missing");
+        return Ok(false);
+    }
+
+    let satisfies = |version: &Version| dotnet_version.is_satisfied_by(version, include_prerelease, roll_forward);
+    let found = is_on_disk(arch, runtime, &satisfies, dotnet_version.major).await?;
+
+    // What the host lists is what apps can actually load, so it overrules the
+    // files whenever it can be run.
+    let installed = match listed_by_host(arch, runtime, dotnet_version.major).await {
+        Some(versions) => {
+            let listed = versions.iter().any(satisfies);
+            if found && !listed {
+                log::warn!(
+                    "{} {} {} is on disk but the dotnet host doesn't list it; treating it as missing",
+                    runtime, arch, dotnet_version
+                );
+            }
+            listed
+        }
+        None => found,
+    };
+    if installed {
+        return Ok(true);
+    }
+
+    // Microsoft Update services the packages in place, and a package it is
+    // in the middle of servicing can be missing files for a while.
+    if let Some(version) = repair::msi_versions(runtime, arch).into_iter().filter(satisfies).max() {
+        log::warn!(
+            "Windows Installer lists {} {} {} but its files are missing, as while Microsoft Update services it; \
+             counting it as installed, pass --force to reinstall it",
+            runtime, arch, version
+        );
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+/// Whether the private install in `root` holds a release satisfying the version.
+pub(crate) async fn is_installed_in(
+    root: &Path,
+    runtime: Runtime,
+    dotnet_version: &DotnetVersion,
+    include_prerelease: bool,
+    roll_forward: RollForward,
+) -> Result<bool> {
+    let satisfies = |version: &Version| dotnet_version.is_satisfied_by(version, include_prerelease, roll_forward);
+    match runtime_path(runtime) {
+        Some(runtime_path) => is_in_root(root, &runtime_path, runtime, &satisfies).await,
+        None => Ok(false),
+    }
+}
+
+async fn is_on_disk(
+    arch: Architecture,
+    runtime: Runtime,
+    satisfies: &dyn Fn(&Version) -> bool,
+    major: u64,
+) -> Result<bool> {
+    let runtime_path = match runtime_path(runtime) {
+        Some(runtime_path) => runtime_path,
+        None => return Ok(netfx::is_installed()),
+    };
+
+    // The installers record what they installed and where, which also finds
+    // relocated installs; the files must still be there to count.
+    if let Some(location) = registered_location(arch) {
+        let found = registered_versions(arch, runtime).into_iter().any(|version| {
+            satisfies(&version) && is_complete(&location.join(&runtime_path).join(version.to_string()), runtime)
+        });
+        if found {
+            return Ok(true);
+        }
+    }
+
+    // Installs made without the installers, such as zip or dotnet-install
+    // ones, are only found on disk.
+    for root_path in search_roots(arch, major) {
+        if is_in_root(&root_path, &runtime_path, runtime, satisfies).await? {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Where `runtime` keeps its versions inside an install root; `None` for the
+/// Framework, which has no install root.
+pub(crate) fn runtime_path(runtime: Runtime) -> Option<PathBuf> {
+    let path = match runtime {
+        Runtime::Dotnet => ["shared", "Microsoft.NETCore.App"].iter().collect(),
+        Runtime::AspCore | Runtime::HostingBundle => ["shared", "Microsoft.AspNetCore.App"].iter().collect(),
+        Runtime::WindowsDesktop => ["shared", "Microsoft.WindowsDesktop.App"].iter().collect(),
+        Runtime::Sdk => "sdk".into(),
+        Runtime::NetFx48 => return None,
+    };
+    Some(path)
+}
+
+/// Whether `root` holds a complete version of `runtime` that satisfies the requirement.
+async fn is_in_root(
+    root: &Path,
+    runtime_path: &Path,
+    runtime: Runtime,
+    satisfies: &dyn Fn(&Version) -> bool,
+) -> Result<bool> {
+    let path = root.join(runtime_path);
+    if !path.exists() {
+        return Ok(false);
+    }
+
+    let mut entries = smol::fs::read_dir(path).await?;
+    while let Some(entry) = entries.try_next().await? {
+        // The sdk folder also holds non-version entries such as NuGetFallbackFolder.
+        let version = match Version::parse(&entry.file_name().to_string_lossy()) {
+            Ok(version) => version,
+            Err(_) => continue,
+        };
+        let file_type = entry.file_type().await?;
+
+        if file_type.is_dir() && satisfies(&version) && is_complete(&entry.path(), runtime) {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// The complete versions of `runtime` in the install root `root`.
+pub(crate) fn versions_in(root: &Path, runtime: Runtime) -> Vec<Version> {
+    let dir = match runtime_path(runtime) {
+        Some(runtime_path) => root.join(runtime_path),
+        None => return Vec::new(),
+    };
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    entries
+        .flatten()
+        .filter(|entry| is_complete(&entry.path(), runtime))
+        .filter_map(|entry| Version::parse(&entry.file_name().to_string_lossy()).ok())
+        .collect()
+}
+
+/// A version of a runtime found in an install root.
+pub struct Installed {
+    pub runtime: Runtime,
+    pub arch: Architecture,
+    pub version: Version,
+    pub root: PathBuf,
+}
+
+impl std::fmt::Display for Installed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} [{}]",
+            self.runtime.to_string().to_lowercase(),
+            self.arch,
+            self.version,
+            self.root.display()
+        )
+    }
+}
+
+/// The runtimes and SDKs of `arch` in the machine-wide install and, unless
+/// only that is searched, the private installs the environment points at,
+/// oldest first within each root.
+pub fn installed(arch: Architecture) -> Vec<Installed> {
+    let mut roots = vec![get_root_install(arch).to_path_buf()];
+    if !MACHINE_WIDE_ONLY.get().copied().unwrap_or(false) {
+        for root in dotnet_root(arch).into_iter().chain(install_dir(arch)) {
+            if !roots.contains(&root) {
+                roots.push(root);
+            }
+        }
+    }
+
+    let mut installed = Vec::new();
+    for root in roots {
+        for runtime in [Runtime::Dotnet, Runtime::AspCore, Runtime::WindowsDesktop, Runtime::Sdk] {
+            let mut versions = versions_in(&root, runtime);
+            versions.sort();
+            installed.extend(versions.into_iter().map(|version| Installed { runtime, arch, version, root: root.clone() }));
+        }
+    }
+    installed
+}
+
+/// The versions of `runtime` that `dotnet --list-runtimes` or `--list-sdks`
+/// reports for `arch`, or `None` if there is no host that runs.
+async fn listed_by_host(arch: Architecture, runtime: Runtime, major: u64) -> Option<Vec<Version>> {
+    let dotnet = host(search_roots(arch, major).first()?);
+    if !dotnet.is_file() {
+        return None;
+    }
+
+    let (flag, framework) = match runtime {
+        Runtime::Dotnet => ("--list-runtimes", Some("Microsoft.NETCore.App")),
+        Runtime::AspCore | Runtime::HostingBundle => ("--list-runtimes", Some("Microsoft.AspNetCore.App")),
+        Runtime::WindowsDesktop => ("--list-runtimes", Some("Microsoft.WindowsDesktop.App")),
+        Runtime::Sdk => ("--list-sdks", None),
+        Runtime::NetFx48 => return None,
+    };
+    let output = match smol::process::Command::new(&dotnet).arg(flag).output().await {
+        Ok(output) if output.status.success() => output,
+        Ok(output) => {
+            log::debug!("{} {} exited with {}", dotnet.display(), flag, output.status);
+            return None;
+        }
+        Err(e) => {
+            log::debug!("cannot run {}: {}", dotnet.display(), e);
+            return None;
+        }
+    };
+
+    // Lines look like `Microsoft.NETCore.App 8.0.11 [C:\Program Files\dotnet\shared\Microsoft.NETCore.App]`,
+    // or `8.0.404 [C:\Program Files\dotnet\sdk]` for SDKs.
+    let versions = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            if let Some(framework) = framework {
+                if fields.next()? != framework {
+                    return None;
+                }
+            }
+            Version::parse(fields.next()?).ok()
+        })
+        .collect();
+    Some(versions)
+}
+
+fn installed_versions_key(arch: Architecture) -> String {
+    format!(
+        "SOFTWARE\\dotnet\\Setup\\InstalledVersions\\{}",
+        arch.to_string().to_lowercase()
+    )
+}
+
+/// The install root the installers recorded for `arch`.
+fn registered_location(arch: Architecture) -> Option<PathBuf> {
+    registry::read_string(&installed_versions_key(arch), "InstallLocation", View::Registry32).map(PathBuf::from)
+}
+
+/// The versions of `runtime` the installers recorded for `arch`.
+fn registered_versions(arch: Architecture, runtime: Runtime) -> Vec<Version> {
+    let subkey = match runtime {
+        Runtime::Dotnet => "sharedfx\\Microsoft.NETCore.App",
+        Runtime::AspCore | Runtime::HostingBundle => "sharedfx\\Microsoft.AspNetCore.App",
+        Runtime::WindowsDesktop => "sharedfx\\Microsoft.WindowsDesktop.App",
+        Runtime::Sdk => "sdk",
+        Runtime::NetFx48 => return Vec::new(),
+    };
+    let key = format!("{}\\{}", installed_versions_key(arch), subkey);
+
+    registry::value_names(&key, View::Registry32)
+        .iter()
+        .filter_map(|name| Version::parse(name).ok())
+        .collect()
+}
+
+/// Whether a version directory holds a usable install rather than the
+/// remains of an interrupted install or uninstall.
+fn is_complete(dir: &Path, runtime: Runtime) -> bool {
+    let marker = match runtime {
+        Runtime::Dotnet => "Microsoft.NETCore.App.deps.json",
+        Runtime::AspCore | Runtime::HostingBundle => "Microsoft.AspNetCore.App.deps.json",
+        Runtime::WindowsDesktop => "Microsoft.WindowsDesktop.App.deps.json",
+        Runtime::Sdk => "dotnet.dll",
+        Runtime::NetFx48 => return false,
+    };
+    dir.join(marker).is_file()
+}
+
+/// The install roots the host searches for a framework of the given major
+/// version, as seen by apps launched with the current environment.
+///
+/// Apps using a private `DOTNET_ROOT` only see the global install through
+/// multilevel lookup, which .NET 7 removed and `DOTNET_MULTILEVEL_LOOKUP=0`
+/// turns off for earlier versions. The `dotnet-install` script's directory
+/// is searched last, as the apps it was run for find it some other way.
+pub(crate) fn search_roots(arch: Architecture, major: u64) -> Vec<PathBuf> {
+    let global = get_root_install(arch).to_path_buf();
+    if MACHINE_WIDE_ONLY.get().copied().unwrap_or(false) {
+        return vec![global];
+    }
+
+    let mut roots = match dotnet_root(arch).filter(|root| *root != global) {
+        None => vec![global],
+        Some(private) if multilevel_lookup(major) => vec![private, global],
+        Some(private) => vec![private],
+    };
+    if let Some(dir) = install_dir(arch).filter(|dir| !roots.contains(dir)) {
+        roots.push(dir);
+    }
+    roots
+}
+
+/// Where `DOTNET_INSTALL_DIR` says `dotnet-install` put the runtimes, unless
+/// its host is of another architecture.
+fn install_dir(arch: Architecture) -> Option<PathBuf> {
+    let dir = PathBuf::from(std::env::var_os("DOTNET_INSTALL_DIR").filter(|dir| !dir.is_empty())?);
+    let expected = match arch {
+        Architecture::X86 => IMAGE_FILE_MACHINE_I386,
+        Architecture::X64 => IMAGE_FILE_MACHINE_AMD64,
+        Architecture::Arm64 => IMAGE_FILE_MACHINE_ARM64,
+    };
+    match pe_machine(&host(&dir)) {
+        Some(machine) if machine != expected => None,
+        _ => Some(dir),
+    }
+}
+
+/// The private install root the host is pointed at for `arch`, if any.
+fn dotnet_root(arch: Architecture) -> Option<PathBuf> {
+    let names: &[&str] = match arch {
+        Architecture::X86 if is_64bit_os() => &["DOTNET_ROOT_X86", "DOTNET_ROOT(x86)", "DOTNET_ROOT"],
+        Architecture::X86 => &["DOTNET_ROOT_X86", "DOTNET_ROOT"],
+        Architecture::X64 => &["DOTNET_ROOT_X64", "DOTNET_ROOT"],
+        Architecture::Arm64 => &["DOTNET_ROOT_ARM64", "DOTNET_ROOT"],
+    };
+
+    names
+        .iter()
+        .filter_map(std::env::var_os)
+        .find(|root| !root.is_empty())
+        .map(PathBuf::from)
+}
+
+fn multilevel_lookup(major: u64) -> bool {
+    major <= 6 && std::env::var("DOTNET_MULTILEVEL_LOOKUP").map_or(true, |value| value != "0")
+}
+
+/// The oldest VC++ 2015-2022 runtime .NET supports: 2015 Update 3 for x86,
+/// and for x64 and ARM64 the 2019 release that added `vcruntime140_1.dll`.
+pub(crate) fn vcruntime_minimum(arch: Architecture) -> Version {
+    match arch {
+        Architecture::X86 => Version::new(14, 0, 24215),
+        Architecture::X64 | Architecture::Arm64 => Version::new(14, 20, 27508),
+    }
+}
+
+pub(crate) fn is_vcruntime_installed(arch: Architecture) -> bool {
+    let minimum = vcruntime_minimum(arch);
+    let registered = registered_vcruntime(arch);
+    if registered.as_ref().is_some_and(|version| *version >= minimum) {
+        return true;
+    }
+
+    match vcruntime_version(arch) {
+        Some(version) if version >= minimum => true,
+        Some(version) => {
+            log::info!("VC++ runtime {} {} is older than the required {}, reinstalling it", arch, version, minimum);
+            false
+        }
+        None => false,
+    }
+}
+
+/// The newest VC++ runtime that is either registered or in the system directory.
+pub(crate) fn vcruntime_version(arch: Architecture) -> Option<Version> {
+    registered_vcruntime(arch).into_iter().chain(vcruntime_dll_version(arch)).max()
+}
+
+/// The version of the VC++ runtime DLLs in the system directory, the oldest
+/// one counting. `None` if any of them is missing.
+fn vcruntime_dll_version(arch: Architecture) -> Option<Version> {
+    let system = match (arch, is_wow64()) {
+        (Architecture::X64 | Architecture::Arm64, true) => Path::new("C:\\Windows\\SysNative"),
+        (Architecture::X64 | Architecture::Arm64, false) => Path::new("C:\\Windows\\System32"),
+        (Architecture::X86, true) => Path::new("C:\\Windows\\System32"),
+        (Architecture::X86, false) => Path::new("C:\\Windows\\SysWOW64"),
+    };
+    let dlls: &[&str] = match arch {
+        Architecture::X86 => &["vcruntime140.dll"],
+        Architecture::X64 | Architecture::Arm64 => &["vcruntime140.dll", "vcruntime140_1.dll"],
+    };
+
+    dlls.iter()
+        .map(|dll| file_version(&system.join(dll)))
+        .collect::<Option<Vec<_>>>()?
+        .into_iter()
+        .min()
+}
+
+/// The VC++ runtime version the redistributable recorded when it installed.
+fn registered_vcruntime(arch: Architecture) -> Option<Version> {
+    let key = match arch {
+        Architecture::X86 => "SOFTWARE\\Microsoft\\VisualStudio\\14.0\\VC\\Runtimes\\x86",
+        Architecture::X64 => "SOFTWARE\\Microsoft\\VisualStudio\\14.0\\VC\\Runtimes\\x64",
+        Architecture::Arm64 => "SOFTWARE\\Microsoft\\VisualStudio\\14.0\\VC\\Runtimes\\arm64",
+    };
+    if registry::read_dword(key, "Installed", View::Registry32)? != 1 {
+        return None;
+    }
+
+    Some(Version::new(
+        registry::read_dword(key, "Major", View::Registry32)?.into(),
+        registry::read_dword(key, "Minor", View::Registry32)?.into(),
+        registry::read_dword(key, "Bld", View::Registry32)?.into(),
+    ))
+}
+
+/// Where the Linux packages and archive installs put every architecture's runtimes.
+pub(crate) const LINUX_ROOT: &str = "/usr/share/dotnet";
+
+/// The machine-wide install root for `arch`: where the installers recorded
+/// they put it, which may have been moved, or else the default.
+pub(crate) fn get_root_install(arch: Architecture) -> &'static Path {
+    static REGISTERED: [OnceLock<Option<PathBuf>>; 3] = [OnceLock::new(), OnceLock::new(), OnceLock::new()];
+    let registered = REGISTERED[arch as usize].get_or_init(|| {
+        let location = registered_location(arch).filter(|location| location.is_dir())?;
+        if location != default_root_install(arch) {
+            log::debug!("the {} runtimes are installed in {}", arch, location.display());
+        }
+        Some(location)
+    });
+    match registered {
+        Some(location) => location,
+        None => default_root_install(arch),
+    }
+}
+
+fn default_root_install(arch: Architecture) -> &'static Path {
+    if cfg!(target_os = "macos") {
+        return match arch {
+            Architecture::X64 if is_arm64_host() => Path::new("/usr/local/share/dotnet/x64"),
+            _ => Path::new("/usr/local/share/dotnet"),
+        };
+    } else if !cfg!(windows) {
+        return Path::new(LINUX_ROOT);
+    }
+
+    match (arch, is_64bit_os()) {
+        // x64 runtimes live in their own subdirectory next to the native ARM64 ones.
+        (Architecture::X64, true) if is_arm64_host() => Path::new("C:\\Program Files\\dotnet\\x64"),
+        (Architecture::X64, true) | (Architecture::X86, false) | (Architecture::Arm64, _) => Path::new("C:\\Program Files\\dotnet"),
+        (Architecture::X86, true) => Path::new("C:\\Program Files (x86)\\dotnet"),
+        _ => unreachable!()
+    }
+}
+
+/// The `dotnet` host in the install root `root`.
+pub(crate) fn host(root: &Path) -> PathBuf {
+    root.join("dotnet").with_extension(std::env::consts::EXE_EXTENSION)
+}
+
+
+/// Reads the file version from a PE image's version resource.
+#[cfg(windows)]
+pub(crate) fn file_version(path: &Path) -> Option<Version> {
+    use std::{ffi::OsStr, os::windows::ffi::OsStrExt, ptr::null_mut};
+    use winapi::um::winver::{GetFileVersionInfoSizeW, GetFileVersionInfoW, VerQueryValueW};
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let size = unsafe { GetFileVersionInfoSizeW(wide.as_ptr(), null_mut()) };
+    if size == 0 {
+        return None;
+    }
+
+    let mut data = vec![0u8; size as usize];
+    if unsafe { GetFileVersionInfoW(wide.as_ptr(), 0, size, data.as_mut_ptr().cast()) } == 0 {
+        return None;
+    }
+
+    let root: Vec<u16> = OsStr::new("\\").encode_wide().chain(Some(0)).collect();
+    let mut info = null_mut();
+    let mut len = 0;
+    if unsafe { VerQueryValueW(data.as_ptr().cast(), root.as_ptr(), &mut info, &mut len) } == 0
+        || (len as usize) < 4 * std::mem::size_of::<u32>()
+    {
+        return None;
+    }
+
+    // VS_FIXEDFILEINFO starts with a signature and struct version, followed
+    // by the most and least significant halves of the file version.
+    let fields = unsafe { std::slice::from_raw_parts(info as *const u32, 4) };
+    let (ms, ls) = (fields[2], fields[3]);
+    Some(Version::new(
+        u64::from(ms >> 16),
+        u64::from(ms & 0xffff),
+        u64::from(ls >> 16),
+    ))
+}
+
+#[cfg(not(windows))]
+pub(crate) fn file_version(_path: &Path) -> Option<Version> {
+    None
+}
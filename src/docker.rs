@@ -0,0 +1,61 @@
+use std::fmt::Write;
+
+use crate::{Architecture, Container, releases::Artifact};
+
+/// Renders Dockerfile lines that download, verify and install each of `artifacts`.
+///
+/// Server Core images run the exe installers silently. Nano Server has neither
+/// PowerShell nor installer support, so the zip layouts are verified and
+/// extracted into one directory in a Server Core stage and copied over instead.
+pub(crate) fn render(artifacts: &[Artifact], arch: Architecture, container: Container) -> String {
+    let mut out = String::from("# escape=`\n");
+
+    match container {
+        Container::NanoServer => {
+            out.push_str("FROM mcr.microsoft.com/windows/servercore:ltsc2022 AS dotnet-installer\n");
+            for artifact in artifacts {
+                push_download(&mut out, artifact, "dotnet.zip");
+                out.push_str("    Expand-Archive -Force dotnet.zip -DestinationPath dotnet; `\n");
+                out.push_str("    Remove-Item -Force dotnet.zip\"\n");
+            }
+            out.push('\n');
+
+            let dir = match arch {
+                Architecture::X86 => "/Program Files (x86)/dotnet",
+                Architecture::X64 | Architecture::Arm64 => "/Program Files/dotnet",
+            };
+            let _ = writeln!(out, "# In the Nano Server stage:");
+            let _ = writeln!(out, "COPY --from=dotnet-installer [\"/dotnet\", \"{}\"]", dir);
+            out.push_str("USER ContainerAdministrator\n");
+            let _ = writeln!(out, "RUN setx /M PATH \"%PATH%;C:{}\"", dir.replace('/', "\\"));
+            out.push_str("USER ContainerUser\n");
+        }
+        Container::ServerCore | Container::None => {
+            for artifact in artifacts {
+                push_download(&mut out, artifact, "dotnet.exe");
+                out.push_str(
+                    "    Start-Process dotnet.exe -ArgumentList '/install', '/quiet', '/norestart' -NoNewWindow -Wait; `\n",
+                );
+                out.push_str("    Remove-Item -Force dotnet.exe\"\n");
+            }
+        }
+    }
+
+    out
+}
+
+fn push_download(out: &mut String, artifact: &Artifact, file: &str) {
+    let _ = writeln!(out, "# .NET {}", artifact.version);
+    if artifact.hash.is_none() {
+        out.push_str("# No published SHA512 hash, so the download is not verified.\n");
+    }
+    out.push_str("RUN powershell -Command \"$ErrorActionPreference = 'Stop'; $ProgressPreference = 'SilentlyContinue'; `\n");
+    let _ = writeln!(out, "    Invoke-WebRequest -OutFile {} {}; `", file, artifact.url);
+    if let Some(hash) = &artifact.hash {
+        let _ = writeln!(
+            out,
+            "    if ((Get-FileHash {} -Algorithm sha512).Hash -ne '{}') {{ throw 'SHA512 mismatch for {}' }}; `",
+            file, hash, file
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use std::{
+    fmt::Display,
+    fs,
+    path::Path,
+};
+
+use http_types::Url;
+use serde::Serialize;
+
+use crate::{
+    Architecture, BASE_URL, conflicts,
+    detect::{get_root_install, vcruntime_minimum, vcruntime_version},
+    elevation::is_elevated,
+    http,
+    platform::{is_64bit_os, is_arm64_host, is_wow64, os_description},
+    space, temp,
+};
+
+/// Something about the machine worth pasting into a support request.
+#[derive(Serialize)]
+pub struct Fact {
+    pub name: String,
+    pub value: String,
+}
+
+impl Display for Fact {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:<32}{}", self.name, self.value)
+    }
+}
+
+/// Gathers what support asks for first: the OS, elevation, the VC++
+/// runtime, the installed runtimes, conflicts, the proxy and free space.
+/// Connectivity is left to [`crate::firewall`].
+pub fn diagnose() -> Vec<Fact> {
+    let mut facts = Vec::new();
+    let mut fact = |name: &str, value: String| {
+        facts.push(Fact {
+            name: name.to_string(),
+            value,
+        })
+    };
+
+    fact("os", os_description());
+    let native = if is_arm64_host() {
+        "ARM64"
+    } else if is_64bit_os() {
+        "x64"
+    } else {
+        "x86"
+    };
+    let process = if is_wow64() { " under WOW64" } else { "" };
+    fact("architecture", format!("{} OS, {} process{}", native, std::env::consts::ARCH, process));
+    fact("elevated", if is_elevated() { "yes" } else { "no" }.to_string());
+
+    // Only Windows has a root per architecture.
+    let arches = Architecture::installable();
+    if cfg!(windows) {
+        for &arch in &arches {
+            let minimum = vcruntime_minimum(arch);
+            let value = match vcruntime_version(arch) {
+                Some(version) if version >= minimum => version.to_string(),
+                Some(version) => format!("{}, older than the required {}", version, minimum),
+                None => "missing".to_string(),
+            };
+            fact(&format!("vcruntime {}", arch), value);
+        }
+    }
+
+    for &arch in &arches {
+        let root = get_root_install(arch);
+        if !root.exists() {
+            fact(&format!("dotnet {}", arch), format!("{} (missing)", root.display()));
+            continue;
+        }
+        fact(&format!("dotnet {}", arch), root.display().to_string());
+        for (product, versions) in installed(root) {
+            fact(&format!("  {}", product), versions.join(", "));
+        }
+    }
+    for name in &["DOTNET_ROOT", "DOTNET_ROOT_X86", "DOTNET_ROOT(x86)", "DOTNET_ROOT_X64"] {
+        if let Some(value) = std::env::var_os(name) {
+            fact(name, value.to_string_lossy().into_owned());
+        }
+    }
+
+    for conflict in conflicts::find() {
+        fact("conflict", conflict.to_string());
+    }
+
+    let proxy = Url::parse(BASE_URL).ok().and_then(|url| http::proxy_for(&url));
+    fact("proxy", match proxy {
+        Some(mut proxy) => {
+            // Credentials in the URL don't belong in a ticket.
+            let _ = proxy.set_password(None);
+            let _ = proxy.set_username("");
+            proxy.to_string()
+        }
+        None => "none".to_string(),
+    });
+
+    let mut volumes = arches.iter().map(|&arch| get_root_install(arch).to_path_buf()).collect::<Vec<_>>();
+    volumes.push(temp::dir());
+    volumes.dedup();
+    for path in volumes {
+        let value = match space::free_space(&space::existing_ancestor(&path)) {
+            Some(bytes) => format!("{:.1} GB", bytes as f64 / 1_000_000_000.0),
+            None => "unknown".to_string(),
+        };
+        fact(&format!("free space {}", path.display()), value);
+    }
+
+    facts
+}
+
+/// The versions of each framework and of the SDK under an install root.
+fn installed(root: &Path) -> Vec<(String, Vec<String>)> {
+    let mut products = Vec::new();
+    let frameworks = fs::read_dir(root.join("shared")).into_iter().flatten().filter_map(|entry| entry.ok());
+    let dirs = frameworks
+        .map(|entry| (entry.file_name().to_string_lossy().into_owned(), entry.path()))
+        .chain(Some(("sdk".to_string(), root.join("sdk"))));
+
+    for (product, dir) in dirs {
+        let mut versions = fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| semver::Version::parse(&entry.file_name().to_string_lossy()).ok())
+            .collect::<Vec<_>>();
+        if versions.is_empty() {
+            continue;
+        }
+        versions.sort_by(|a, b| b.cmp(a));
+        products.push((product, versions.iter().map(ToString::to_string).collect()));
+    }
+    products.sort();
+    products
+}
@@ -0,0 +1,434 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use http_types::StatusCode;
+use smol::{
+    fs::{File, OpenOptions},
+    io,
+    prelude::*,
+};
+use tempfile::TempDir;
+
+use crate::{
+    DotnetRedistError, bundle, cache, chunks, http,
+    installer::{self, Kind, Ui},
+    progress::{Listener, Progress, Sink},
+    releases::Package,
+    report::Component,
+    temp,
+    timing::{self, Phase},
+    verify::{self, StreamingHash},
+};
+
+/// Remaining number of bytes installers may download in this run, if capped.
+/// Shared by the downloads running at the same time.
+pub(crate) struct DownloadBudget(Cell<Option<u64>>);
+
+impl DownloadBudget {
+    pub(crate) fn new(cap: Option<u64>) -> Self {
+        DownloadBudget(Cell::new(cap))
+    }
+
+    /// Fails if downloading `len` more bytes would exceed the cap.
+    pub(crate) fn check(&self, len: u64) -> Result<()> {
+        if let Some(remaining) = self.0.get() {
+            if len > remaining {
+                bail!(
+                    "download of {} bytes exceeds the remaining --max-download-size budget of {} bytes",
+                    len,
+                    remaining
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn consume(&self, len: u64) {
+        if let Some(remaining) = self.0.get() {
+            self.0.set(Some(remaining.saturating_sub(len)));
+        }
+    }
+
+    pub(crate) fn remaining(&self) -> Option<u64> {
+        self.0.get()
+    }
+}
+
+/// Downloads, verifies and runs the installers of one run.
+pub(crate) struct Downloader<'a> {
+    pub(crate) budget: DownloadBudget,
+    /// Accepted Authenticode signers, or `None` to skip the signature check.
+    pub(crate) publishers: Option<&'a [String]>,
+    pub(crate) progress: bool,
+    /// Told of each download's progress, if anything is listening.
+    pub(crate) listener: Option<Listener>,
+    pub(crate) cache: bool,
+    /// How much of their UI installers show.
+    pub(crate) ui: Ui,
+    /// The language installers show their UI in, as a Windows LCID.
+    pub(crate) lcid: Option<u32>,
+    /// Given by the user for every installer that takes switches, last.
+    pub(crate) extra_args: Vec<String>,
+    /// Where installers write their own logs, if anywhere.
+    pub(crate) log_dir: Option<PathBuf>,
+    /// Has the installers run next repair what they find installed instead of leaving it be.
+    pub(crate) repair: Cell<bool>,
+    /// Downloads fetched ahead of installing them, by URL.
+    pub(crate) prefetched: RefCell<HashMap<String, (TempDir, PathBuf)>>,
+    /// What fetching each URL took, for the report.
+    pub(crate) fetched: RefCell<HashMap<String, Fetched>>,
+}
+
+/// The bytes a fetch downloaded, none if the cache had them, and how long it took.
+#[derive(Clone, Copy)]
+pub(crate) struct Fetched {
+    pub(crate) bytes: u64,
+    pub(crate) duration: Duration,
+}
+
+impl Downloader<'_> {
+    /// Downloads the installer at `url`, verifies it and runs it.
+    pub(crate) async fn install(&self, url: &str, sha512: Option<&str>, kind: Kind) -> Result<Option<i32>> {
+        let (dir, download_path) = self.fetch_temp(url, sha512, Package::installer()).await?;
+        if let Some(publishers) = self.publishers {
+            verify::authenticode(&download_path, publishers)
+                .context("refusing to run installer; pass --skip-signature-check to override")?;
+        }
+        let result = installer::run(&download_path, kind, self.ui, &self.args_for(bundle::file_name(url), kind));
+        // A killed installer may hold on to its files for a moment, which
+        // dropping the directory would silently leave behind.
+        if let Err(e) = dir.close() {
+            log::warn!("could not remove the downloaded installer: {}", e);
+        }
+        result
+    }
+
+    /// Downloads `url` as a `package` and checks its hash ahead of installing
+    /// it, so that it can download while something else does.
+    pub(crate) async fn prefetch(&self, url: &str, sha512: Option<&str>, package: Package, progress: bool) -> Result<()> {
+        let dir = temp::create()?;
+        let path = dir.path().join(temp_name(url, package));
+        self.fetch_with(url, sha512, &path, progress).await?;
+        self.prefetched.borrow_mut().insert(url.to_string(), (dir, path));
+        Ok(())
+    }
+
+    /// A temporary copy of `url` matching `sha512`, prefetched or downloaded now.
+    async fn fetch_temp(&self, url: &str, sha512: Option<&str>, package: Package) -> Result<(TempDir, PathBuf)> {
+        let prefetched = self.prefetched.borrow_mut().remove(url);
+        if let Some(prefetched) = prefetched {
+            return Ok(prefetched);
+        }
+
+        let dir = temp::create()?;
+        let path = dir.path().join(temp_name(url, package));
+        self.fetch(url, sha512, &path).await?;
+        Ok((dir, path))
+    }
+
+    /// The language switch, `/repair` when repairing, `/log` into the log
+    /// directory for the installer called `name` and the user's own
+    /// arguments, for installers that take switches.
+    fn args_for(&self, name: &str, kind: Kind) -> Vec<String> {
+        let mut args = Vec::new();
+        if let (Some(lcid), Some(switch)) = (self.lcid, kind.lang_switch()) {
+            args.push(switch.to_string());
+            args.push(lcid.to_string());
+        }
+        if self.repair.get() && kind.takes_switches() {
+            args.push("/repair".to_string());
+        }
+        if let Some(dir) = self.log_dir.as_ref().filter(|_| kind.takes_switches()) {
+            let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+            let stamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+            let log = dir.join(format!("{}-{}.log", stem, stamp));
+            log::info!("{} logs to {}", name, log.display());
+            args.push("/log".to_string());
+            args.push(log.display().to_string());
+        }
+        if kind.takes_switches() {
+            args.extend(self.extra_args.iter().cloned());
+        }
+        args
+    }
+
+    /// Downloads the runtime archive at `url`, verifies its hash and extracts
+    /// it into `dir`. Archives carry no Authenticode signature of their own.
+    pub(crate) async fn extract(&self, url: &str, sha512: Option<&str>, dir: &Path) -> Result<Option<i32>> {
+        let (_temp, path) = self.fetch_temp(url, sha512, Package::archive()).await?;
+        installer::extract(&path, dir)
+    }
+
+    /// Downloads the installer at `url` into `dir` and verifies its hash and signature.
+    pub(crate) async fn download_verified(&self, url: &str, sha512: Option<&str>, dir: &Path) -> Result<PathBuf> {
+        let download_path = dir.join(temp_name(url, Package::installer()));
+        self.fetch(url, sha512, &download_path).await?;
+
+        if let Some(publishers) = self.publishers {
+            verify::authenticode(&download_path, publishers)
+                .context("refusing to run installer; pass --skip-signature-check to override")?;
+        }
+        Ok(download_path)
+    }
+
+    /// Verifies and runs an installer that is already on disk.
+    pub(crate) async fn install_file(&self, path: &Path, sha512: Option<&str>, kind: Kind) -> Result<Option<i32>> {
+        if !path.is_file() {
+            bail!("installer {} does not exist", path.display());
+        }
+
+        if let Some(sha512) = sha512 {
+            verify::sha512(path, sha512).await?;
+        }
+        if let Some(publishers) = self.publishers {
+            verify::authenticode(path, publishers)
+                .context("refusing to run installer; pass --skip-signature-check to override")?;
+        }
+        let name = path.file_name().map_or_else(|| "installer".into(), |name| name.to_string_lossy());
+        installer::run(path, kind, self.ui, &self.args_for(&name, kind))
+    }
+
+    /// Puts a copy of `url` at `path` that matches `sha512`, taking it from
+    /// the cache if possible.
+    pub(crate) async fn fetch(&self, url: &str, sha512: Option<&str>, path: &Path) -> Result<()> {
+        self.fetch_with(url, sha512, path, self.progress).await
+    }
+
+    /// Adds what fetching their installers took to the components.
+    pub(crate) fn record(&self, components: &mut [Component]) {
+        let fetched = self.fetched.borrow();
+        for component in components {
+            if let Some(fetched) = component.url.as_ref().and_then(|url| fetched.get(url)) {
+                component.download_bytes = Some(fetched.bytes);
+                component.download_ms = Some(fetched.duration.as_millis() as u64);
+            }
+        }
+    }
+
+    async fn fetch_with(&self, url: &str, sha512: Option<&str>, path: &Path, bar: bool) -> Result<()> {
+        let start = Instant::now();
+        let mut span = timing::span(Phase::Download, url);
+        let downloaded = self.fetch_uncounted(url, sha512, path, bar).await?;
+        let bytes = if downloaded { smol::fs::metadata(path).await.map_or(0, |metadata| metadata.len()) } else { 0 };
+        span.bytes(bytes);
+        self.fetched.borrow_mut().insert(url.to_string(), Fetched { bytes, duration: start.elapsed() });
+        Ok(())
+    }
+
+    /// Fetches `url` as `fetch` does, returning whether it was downloaded rather than taken from the cache.
+    async fn fetch_uncounted(&self, url: &str, sha512: Option<&str>, path: &Path, bar: bool) -> Result<bool> {
+        let progress = Sink {
+            bar,
+            listener: self.listener.as_ref(),
+        };
+        let cached = if self.cache { cache::path(url, sha512) } else { None };
+
+        if let Some(cached) = cached.as_ref().filter(|cached| cached.exists()) {
+            smol::fs::copy(cached, path).await?;
+            let verified = match sha512 {
+                Some(sha512) => verify::sha512(path, sha512).await,
+                None => Ok(()),
+            };
+            match verified {
+                Ok(()) => return Ok(false),
+                Err(e) => {
+                    log::warn!("discarding cached {}: {:#}", cached.display(), e);
+                    let _ = smol::fs::remove_file(cached).await;
+                    smol::fs::remove_file(path).await?;
+                }
+            }
+        }
+
+        // Written under a name of its own until verified, so that a crash
+        // never leaves something that looks like a complete download.
+        let partial = temp::partial(path);
+        if let Err(e) = self.download_to(url, sha512, &partial, cached.as_deref(), progress).await {
+            let _ = smol::fs::remove_file(&partial).await;
+            return Err(e);
+        }
+        smol::fs::rename(&partial, path).await?;
+
+        if let Some(cached) = cached {
+            if let Err(e) = cache::store(path, &cached) {
+                log::warn!("could not cache {}: {}", url, e);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Downloads `url` to `path` and checks it against `sha512`, in chunks
+    /// kept next to `cached` if the server supports it.
+    async fn download_to(
+        &self,
+        url: &str,
+        sha512: Option<&str>,
+        path: &Path,
+        cached: Option<&Path>,
+        progress: Sink<'_>,
+    ) -> Result<()> {
+        if let Some((size, validator)) = chunks::probe(url).await {
+            // Kept in the cache directory when caching, so a later run can
+            // pick up the chunks an interrupted one already fetched.
+            let work = match cached {
+                Some(cached) => cached.with_extension("download"),
+                None => path.to_path_buf(),
+            };
+            if let Some(parent) = work.parent() {
+                smol::fs::create_dir_all(parent).await?;
+            }
+            let mut hash = StreamingHash::new();
+            chunks::download(url, &work, size, validator, &mut hash, &self.budget, progress).await?;
+            if work != path {
+                smol::fs::copy(&work, path).await?;
+                smol::fs::remove_file(&work).await?;
+            }
+            if let Some(sha512) = sha512 {
+                hash.verify(path, sha512).await?;
+            }
+        } else {
+            // Connection failures are retried by `http::get`, but a transfer that
+            // breaks off mid-body is resumed here from what was already written.
+            let attempts = http::retry_policy().attempts;
+            let mut validator = None;
+            let mut hash = StreamingHash::new();
+            let mut attempt = 1;
+            loop {
+                match download(url, path, &mut validator, &mut hash, &self.budget, progress).await {
+                    Ok(()) => break,
+                    Err(e) if attempt < attempts && http::is_transient(&e) => {
+                        log::warn!("download of {} interrupted: {:#}, resuming", url, e);
+                        http::backoff(attempt).await;
+                        attempt += 1;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            if let Some(sha512) = sha512 {
+                hash.verify(path, sha512).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The name a download is saved under while installing it: the URL's for
+/// archives, whose extension the tools extracting them go by, and a fixed
+/// one for installers.
+fn temp_name(url: &str, package: Package) -> String {
+    match package {
+        Package::Zip | Package::TarGz => bundle::file_name(url).to_string(),
+        Package::Exe | Package::Pkg => format!("installer.{}", package.extension()),
+    }
+}
+
+/// Downloads `url` to `path`, resuming after any bytes already in the file.
+///
+/// `validator` remembers the ETag or Last-Modified of the first response so a
+/// resumed transfer is only appended if the file has not changed in between.
+/// `hash` is fed everything written to the file.
+async fn download(
+    url: &str,
+    path: &Path,
+    validator: &mut Option<String>,
+    hash: &mut StreamingHash,
+    budget: &DownloadBudget,
+    progress: Sink<'_>,
+) -> Result<()> {
+    let partial = match smol::fs::metadata(path).await {
+        Ok(metadata) => metadata.len(),
+        Err(_) => 0,
+    };
+
+    let response = if partial > 0 {
+        http::get_range(url, partial, validator.as_deref()).await?
+    } else {
+        http::get(url).await?
+    };
+
+    // Servers without range support answer with the whole file.
+    let (mut file, offset) = match response.status() {
+        StatusCode::PartialContent if partial > 0 => {
+            (OpenOptions::new().append(true).open(path).await?, partial)
+        }
+        StatusCode::Ok => {
+            if partial > 0 {
+                log::warn!("server does not support resuming, restarting download of {}", url);
+            }
+            (File::create(path).await?, 0)
+        }
+        status => return Err(DotnetRedistError::Network(anyhow!("could not download {}: {}", url, status)).into()),
+    };
+    hash.resume_at(offset);
+
+    if validator.is_none() {
+        *validator = ["ETag", "Last-Modified"]
+            .iter()
+            .find_map(|name| response.header(*name))
+            .map(|value| value.last().to_string())
+            .filter(|value| !value.starts_with("W/"));
+    }
+
+    let total = response.len().map(|len| offset + len as u64);
+    let mut progress = Progress::new(progress, url, offset, total);
+
+    let copied = if let Some(total) = total {
+        budget.check(total)?;
+        let copied = copy(response, &mut file, hash, &mut progress).await;
+        progress.finish();
+        file.flush().await?;
+        let copied = copied?;
+        // A connection dropped mid-body can look like the end of it, which
+        // would leave a truncated installer to run.
+        if offset + copied < total {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("{} ended after {} of {} bytes", url, offset + copied, total),
+            )
+            .into());
+        }
+        copied
+    } else {
+        // Without a Content-Length, stop reading one byte past the cap to detect overruns.
+        let limit = budget
+            .remaining()
+            .map_or(u64::MAX, |remaining| (remaining + 1).saturating_sub(offset));
+        let copied = copy(response.take(limit), &mut file, hash, &mut progress).await;
+        progress.finish();
+        file.flush().await?;
+        let copied = copied?;
+        budget.check(offset + copied)?;
+        copied
+    };
+    budget.consume(offset + copied);
+
+    Ok(())
+}
+
+/// Copies `reader` into `file`, hashing each chunk and reporting it to `progress`.
+async fn copy(
+    reader: impl AsyncRead + Unpin,
+    file: &mut File,
+    hash: &mut StreamingHash,
+    progress: &mut Progress,
+) -> io::Result<u64> {
+    let mut reader = reader;
+    let mut buf = vec![0; 64 * 1024];
+    let mut copied = 0;
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(copied);
+        }
+        file.write_all(&buf[..n]).await?;
+        hash.update(&buf[..n]);
+        copied += n as u64;
+        progress.advance(n as u64);
+    }
+}
@@ -0,0 +1,132 @@
+use std::ffi::OsString;
+
+use anyhow::{Result, bail};
+
+/// Whether this process has administrator rights. Always true off Windows.
+#[cfg(windows)]
+pub fn is_elevated() -> bool {
+    use std::{mem, ptr::null_mut};
+    use winapi::um::{
+        handleapi::CloseHandle,
+        processthreadsapi::{GetCurrentProcess, OpenProcessToken},
+        securitybaseapi::GetTokenInformation,
+        winnt::{TOKEN_ELEVATION, TOKEN_QUERY, TokenElevation},
+    };
+
+    unsafe {
+        let mut token = null_mut();
+        if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) == 0 {
+            return false;
+        }
+
+        let mut elevation: TOKEN_ELEVATION = mem::zeroed();
+        let mut size = 0;
+        let ok = GetTokenInformation(
+            token,
+            TokenElevation,
+            (&mut elevation as *mut TOKEN_ELEVATION).cast(),
+            mem::size_of::<TOKEN_ELEVATION>() as u32,
+            &mut size,
+        );
+        CloseHandle(token);
+
+        ok != 0 && elevation.TokenIsElevated != 0
+    }
+}
+
+#[cfg(not(windows))]
+pub fn is_elevated() -> bool {
+    true
+}
+
+/// Runs this executable again with the same arguments through the UAC
+/// prompt, waits for it and returns its exit code.
+pub fn relaunch_elevated() -> Result<i32> {
+    run_elevated(&std::env::args_os().skip(1).collect::<Vec<_>>())
+}
+
+/// Runs this executable with `args` through the UAC prompt, waits for it
+/// and returns its exit code.
+#[cfg(windows)]
+pub fn run_elevated(args: &[OsString]) -> Result<i32> {
+    use std::{ffi::OsStr, io, mem, os::windows::ffi::OsStrExt};
+    use winapi::um::{
+        handleapi::CloseHandle,
+        processthreadsapi::GetExitCodeProcess,
+        shellapi::{SEE_MASK_NOCLOSEPROCESS, SHELLEXECUTEINFOW, ShellExecuteExW},
+        synchapi::WaitForSingleObject,
+        winbase::INFINITE,
+        winuser::SW_SHOWNORMAL,
+    };
+
+    fn wide(s: &OsStr) -> Vec<u16> {
+        s.encode_wide().chain(Some(0)).collect()
+    }
+
+    let exe = std::env::current_exe()?;
+    let params = args
+        .iter()
+        .map(|arg| quote(&arg.to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let (verb, file, params) = (wide(OsStr::new("runas")), wide(exe.as_os_str()), wide(OsStr::new(&params)));
+    // runas starts in System32, where relative paths such as the lockfile's would land.
+    let dir = wide(std::env::current_dir()?.as_os_str());
+
+    unsafe {
+        let mut info: SHELLEXECUTEINFOW = mem::zeroed();
+        info.cbSize = mem::size_of::<SHELLEXECUTEINFOW>() as u32;
+        info.fMask = SEE_MASK_NOCLOSEPROCESS;
+        info.lpVerb = verb.as_ptr();
+        info.lpFile = file.as_ptr();
+        info.lpParameters = params.as_ptr();
+        info.lpDirectory = dir.as_ptr();
+        info.nShow = SW_SHOWNORMAL;
+        if ShellExecuteExW(&mut info) == 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        if info.hProcess.is_null() {
+            bail!("the elevated process could not be tracked");
+        }
+
+        WaitForSingleObject(info.hProcess, INFINITE);
+        let mut code = 0;
+        let ok = GetExitCodeProcess(info.hProcess, &mut code);
+        CloseHandle(info.hProcess);
+        if ok == 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        Ok(code as i32)
+    }
+}
+
+#[cfg(not(windows))]
+pub fn run_elevated(_args: &[OsString]) -> Result<i32> {
+    bail!("elevation is only supported on Windows")
+}
+
+/// Quotes an argument so the C runtime's command line parser reads it back unchanged.
+#[cfg(windows)]
+pub(crate) fn quote(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+        return arg.to_string();
+    }
+
+    let mut quoted = String::from('"');
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        if c == '\\' {
+            backslashes += 1;
+            continue;
+        }
+        // Backslashes are only special right before a quote.
+        let escapes = if c == '"' { backslashes * 2 + 1 } else { backslashes };
+        quoted.push_str(&"\\".repeat(escapes));
+        quoted.push(c);
+        backslashes = 0;
+    }
+    quoted.push_str(&"\\".repeat(backslashes * 2));
+    quoted.push('"');
+    quoted
+}
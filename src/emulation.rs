@@ -0,0 +1,38 @@
+use anyhow::{Result, anyhow, bail};
+
+use crate::{
+    Architecture, DotnetRedistError, DotnetVersion,
+    detect::get_root_install,
+    registry::{self, View},
+};
+
+/// The first major release whose x64 runtime can be installed side by side
+/// with the native runtime on ARM64.
+pub(crate) const FIRST_EMULATED_MAJOR: u64 = 6;
+
+const X64_INSTALL_KEY: &str = "SOFTWARE\\dotnet\\Setup\\InstalledVersions\\x64";
+
+/// Checks that an install emulated as x64 on ARM64 is of a release that supports it.
+pub(crate) fn check(version: &DotnetVersion, emulated: bool) -> Result<()> {
+    if emulated && version.major < FIRST_EMULATED_MAJOR {
+        bail!(DotnetRedistError::UnsupportedPlatform(anyhow!(
+            ".NET {} does not support x64 emulation on ARM64; .NET {} or later is required",
+            version,
+            FIRST_EMULATED_MAJOR
+        )));
+    }
+
+    Ok(())
+}
+
+/// Points the x64 host at the emulation-specific install root so that x64
+/// apps find the runtime there instead of the native ARM64 one.
+pub(crate) fn register() -> Result<()> {
+    let root = get_root_install(Architecture::X64).to_string_lossy();
+    if registry::read_string(X64_INSTALL_KEY, "InstallLocation", View::Registry32).as_deref() == Some(&*root) {
+        return Ok(());
+    }
+
+    registry::write_string(X64_INSTALL_KEY, "InstallLocation", &root, View::Registry32)?;
+    Ok(())
+}
@@ -0,0 +1,115 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use clap::arg_enum;
+
+use crate::{
+    Architecture,
+    platform::{is_64bit_os, is_arm64_host},
+    registry,
+};
+
+arg_enum! {
+    /// Where to make runtimes installed to a directory of their own
+    /// discoverable: the user's or the machine's environment, or shell
+    /// commands printed for the caller to run.
+    #[derive(Copy, Clone, PartialEq, Eq)]
+    pub enum EnvironmentScope {
+        User,
+        Machine,
+        Print,
+    }
+}
+
+/// The variable that points the host at a private install root for `arch`.
+/// Hosts before .NET 6 only read `DOTNET_ROOT(x86)` for 32-bit apps.
+pub(crate) fn variable(arch: Architecture) -> &'static str {
+    match arch {
+        Architecture::X86 if cfg!(windows) && is_64bit_os() => "DOTNET_ROOT(x86)",
+        Architecture::X64 if is_arm64_host() => "DOTNET_ROOT_X64",
+        _ => "DOTNET_ROOT",
+    }
+}
+
+/// Sets `DOTNET_ROOT` to `root` and puts `root` first on `PATH` in `scope`,
+/// returning the shell commands that do so when they are to be printed.
+///
+/// Only runtimes of the machine's own architecture go on `PATH`, so that a
+/// 32-bit `dotnet` never shadows the native one.
+pub(crate) fn configure(scope: EnvironmentScope, arch: Architecture, root: &Path) -> Result<Vec<String>> {
+    let name = variable(arch);
+    let on_path = arch == Architecture::native();
+
+    let machine = match scope {
+        EnvironmentScope::Print => return Ok(commands(name, root, on_path)),
+        _ if !cfg!(windows) => {
+            log::info!(
+                "add these lines to your shell profile for apps to use {}:\n{}",
+                root.display(),
+                commands(name, root, on_path).join("\n")
+            );
+            return Ok(Vec::new());
+        }
+        EnvironmentScope::Machine => true,
+        EnvironmentScope::User => false,
+    };
+    let whose = if machine { "the machine" } else { "the user" };
+
+    registry::write_environment(name, &root.display().to_string(), machine)
+        .with_context(|| format!("could not set {} for {}", name, whose))?;
+    if on_path {
+        let path = registry::read_environment("Path", machine).unwrap_or_default();
+        let listed = path.split(';').any(|entry| Path::new(entry.trim_end_matches('\\')) == root);
+        if !listed {
+            let path = if path.is_empty() { root.display().to_string() } else { format!("{};{}", root.display(), path) };
+            registry::write_environment("Path", &path, machine)
+                .with_context(|| format!("could not add {} to the PATH of {}", root.display(), whose))?;
+        }
+    }
+    broadcast_change();
+    log::info!("pointed {} and PATH at {} for {}; apps started from now on use it", name, root.display(), whose);
+    std::env::set_var(name, root);
+    Ok(Vec::new())
+}
+
+/// The commands setting the variables in the shell this platform uses:
+/// PowerShell on Windows, a POSIX shell elsewhere.
+fn commands(name: &str, root: &Path, on_path: bool) -> Vec<String> {
+    let root = root.display();
+    let mut commands = Vec::new();
+    if cfg!(windows) {
+        commands.push(format!("${{env:{}}} = '{}'", name, root));
+        if on_path {
+            commands.push(format!("$env:PATH = '{};' + $env:PATH", root));
+        }
+    } else {
+        commands.push(format!("export {}='{}'", name, root));
+        if on_path {
+            commands.push(format!("export PATH='{}':\"$PATH\"", root));
+        }
+    }
+    commands
+}
+
+/// Tells Explorer to reload the environment, so programs it starts see the change.
+#[cfg(windows)]
+pub(crate) fn broadcast_change() {
+    use winapi::um::winuser::{HWND_BROADCAST, SMTO_ABORTIFHUNG, SendMessageTimeoutW, WM_SETTINGCHANGE};
+
+    let environment: Vec<u16> = "Environment\0".encode_utf16().collect();
+    let mut result = 0;
+    unsafe {
+        SendMessageTimeoutW(
+            HWND_BROADCAST,
+            WM_SETTINGCHANGE,
+            0,
+            environment.as_ptr() as isize,
+            SMTO_ABORTIFHUNG,
+            5000,
+            &mut result,
+        );
+    }
+}
+
+#[cfg(not(windows))]
+pub(crate) fn broadcast_change() {}
@@ -0,0 +1,118 @@
+use thiserror::Error;
+
+use crate::{cancel, exit, installer};
+
+/// The classes of failure the library API returns, for embedders to branch on.
+///
+/// Each wraps the underlying error and passes its message and causes through.
+#[derive(Debug, Error)]
+pub enum DotnetRedistError {
+    /// A request could not be made, or the server answered with an error.
+    #[error(transparent)]
+    Network(anyhow::Error),
+    /// No release, installer or approved version satisfies the requested version.
+    #[error(transparent)]
+    VersionNotFound(anyhow::Error),
+    /// An installer ran and exited with the failure `code`.
+    #[error("{error:#}")]
+    InstallerFailed { code: i32, error: anyhow::Error },
+    /// An installer ran longer than the timeout and was killed.
+    #[error(transparent)]
+    InstallerTimedOut(anyhow::Error),
+    /// The OS, architecture or container cannot take what was asked for.
+    #[error(transparent)]
+    UnsupportedPlatform(anyhow::Error),
+    /// A download's hash or signature, or a locked resolution, didn't match.
+    #[error(transparent)]
+    VerificationFailed(anyhow::Error),
+    /// The request contradicts itself or is missing something.
+    #[error(transparent)]
+    InvalidRequest(anyhow::Error),
+    /// A volume lacks the room for the downloads and what they install.
+    #[error(transparent)]
+    InsufficientDiskSpace(anyhow::Error),
+    /// The requested or installed version has known security advisories and
+    /// vulnerable versions were refused.
+    #[error(transparent)]
+    Vulnerable(anyhow::Error),
+    /// The run was cancelled, by Ctrl+C or [`cancel::cancel`](crate::cancel::cancel).
+    #[error(transparent)]
+    Cancelled(anyhow::Error),
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl DotnetRedistError {
+    /// The [`exit`](crate::exit) code of a run that failed with this error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            DotnetRedistError::Network(_) => exit::NETWORK,
+            DotnetRedistError::VersionNotFound(_) => exit::VERSION_NOT_FOUND,
+            DotnetRedistError::InstallerFailed { .. } => exit::INSTALLER_FAILED,
+            DotnetRedistError::InstallerTimedOut(_) => exit::INSTALLER_TIMED_OUT,
+            DotnetRedistError::UnsupportedPlatform(_) => exit::UNSUPPORTED_PLATFORM,
+            DotnetRedistError::VerificationFailed(_) => exit::VERIFICATION_FAILED,
+            DotnetRedistError::InvalidRequest(_) => exit::INVALID_REQUEST,
+            DotnetRedistError::InsufficientDiskSpace(_) => exit::INSUFFICIENT_DISK_SPACE,
+            DotnetRedistError::Vulnerable(_) => exit::VULNERABLE,
+            DotnetRedistError::Cancelled(_) => exit::CANCELLED,
+            DotnetRedistError::Other(_) => exit::OTHER,
+        }
+    }
+
+    /// The name of the class of this error, as in [`exit_code`](Self::exit_code).
+    pub fn class(&self) -> &'static str {
+        match self {
+            DotnetRedistError::Network(_) => "network",
+            DotnetRedistError::VersionNotFound(_) => "version-not-found",
+            DotnetRedistError::InstallerFailed { .. } => "installer-failed",
+            DotnetRedistError::InstallerTimedOut(_) => "installer-timed-out",
+            DotnetRedistError::UnsupportedPlatform(_) => "unsupported-platform",
+            DotnetRedistError::VerificationFailed(_) => "verification-failed",
+            DotnetRedistError::InvalidRequest(_) => "invalid-request",
+            DotnetRedistError::InsufficientDiskSpace(_) => "insufficient-disk-space",
+            DotnetRedistError::Vulnerable(_) => "vulnerable",
+            DotnetRedistError::Cancelled(_) => "cancelled",
+            DotnetRedistError::Other(_) => "other",
+        }
+    }
+
+    /// Classifies all of `error` by the first classified error in its chain,
+    /// or with `fallback` if there is none.
+    ///
+    /// Failures are classified where they happen by wrapping them in the
+    /// matching variant, and again at the API boundary with this.
+    pub(crate) fn classify(error: anyhow::Error, fallback: fn(anyhow::Error) -> DotnetRedistError) -> DotnetRedistError {
+        if let Some(&installer::Failed(code)) = error.chain().find_map(|cause| cause.downcast_ref()) {
+            return DotnetRedistError::InstallerFailed { code, error };
+        }
+        if error.chain().any(|cause| cause.is::<installer::TimedOut>()) {
+            return DotnetRedistError::InstallerTimedOut(error);
+        }
+        if error.chain().any(|cause| cause.is::<cancel::Cancelled>()) {
+            return DotnetRedistError::Cancelled(error);
+        }
+
+        let class = match error.chain().find_map(|cause| cause.downcast_ref::<DotnetRedistError>()) {
+            Some(DotnetRedistError::Network(_)) => DotnetRedistError::Network,
+            Some(DotnetRedistError::VersionNotFound(_)) => DotnetRedistError::VersionNotFound,
+            Some(DotnetRedistError::UnsupportedPlatform(_)) => DotnetRedistError::UnsupportedPlatform,
+            Some(DotnetRedistError::VerificationFailed(_)) => DotnetRedistError::VerificationFailed,
+            Some(DotnetRedistError::InvalidRequest(_)) => DotnetRedistError::InvalidRequest,
+            Some(DotnetRedistError::InsufficientDiskSpace(_)) => DotnetRedistError::InsufficientDiskSpace,
+            Some(DotnetRedistError::Vulnerable(_)) => DotnetRedistError::Vulnerable,
+            Some(DotnetRedistError::InstallerFailed { .. })
+            | Some(DotnetRedistError::InstallerTimedOut(_))
+            | Some(DotnetRedistError::Cancelled(_))
+            | Some(DotnetRedistError::Other(_))
+            | None => fallback,
+        };
+        class(error)
+    }
+}
+
+impl From<anyhow::Error> for DotnetRedistError {
+    fn from(error: anyhow::Error) -> Self {
+        DotnetRedistError::classify(error, DotnetRedistError::Other)
+    }
+}
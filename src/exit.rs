@@ -0,0 +1,40 @@
+use crate::{
+    DotnetRedistError,
+    report::{Action, Report},
+};
+
+/// Everything asked for was installed.
+pub const OK: i32 = 0;
+/// A failure of no other class.
+pub const OTHER: i32 = 1;
+/// Everything asked for was already installed, so nothing was.
+pub const ALREADY_INSTALLED: i32 = 10;
+pub const NETWORK: i32 = 20;
+pub const VERSION_NOT_FOUND: i32 = 21;
+pub const VERIFICATION_FAILED: i32 = 22;
+pub const VULNERABLE: i32 = 23;
+pub const INSTALLER_FAILED: i32 = 30;
+/// The install succeeded, but the machine must reboot before the runtime can be used.
+pub const REBOOT_REQUIRED: i32 = 31;
+pub const INSTALLER_TIMED_OUT: i32 = 32;
+pub const UNSUPPORTED_PLATFORM: i32 = 40;
+pub const INSUFFICIENT_DISK_SPACE: i32 = 41;
+pub const CANCELLED: i32 = 50;
+pub const INVALID_REQUEST: i32 = 60;
+
+/// The stable exit code of a run that failed with `error`, if it did, and reported `report`,
+/// telling failures apart by the class of the first [`DotnetRedistError`]
+/// in the error's chain. Unlike installer exit codes, these codes only
+/// change in a major version.
+pub fn code(error: Option<&anyhow::Error>, report: &Report) -> i32 {
+    match error {
+        Some(e) => e.chain().find_map(|cause| cause.downcast_ref::<DotnetRedistError>()).map_or(OTHER, DotnetRedistError::exit_code),
+        None if report.reboot_required => REBOOT_REQUIRED,
+        None if !report.components.is_empty()
+            && report.components.iter().all(|component| matches!(component.action, Action::Skipped)) =>
+        {
+            ALREADY_INSTALLED
+        }
+        None => OK,
+    }
+}
@@ -0,0 +1,45 @@
+use crate::registry::{self, View};
+
+/// Windows Installer machine policies, which also reach the MSI packages
+/// inside the runtime bundles; bundles don't pass MSI properties such as
+/// `MSIFASTINSTALL` on to them.
+const INSTALLER_POLICIES: &str = "SOFTWARE\\Policies\\Microsoft\\Windows\\Installer";
+/// Skips the system restore point before each install, and the rollback
+/// script that would undo a failed install.
+const POLICIES: &[&str] = &["LimitSystemRestoreCheckpointing", "DisableRollback"];
+
+/// Makes installs skip restore points and rollback for as long as it lives,
+/// then puts the policies back the way they were.
+pub(crate) struct FastInstall {
+    previous: Vec<(&'static str, Option<u32>)>,
+}
+
+impl FastInstall {
+    /// Sets the policies, warning instead of failing for any it cannot set.
+    pub(crate) fn enter() -> FastInstall {
+        let mut previous = Vec::new();
+        for &name in POLICIES {
+            let value = registry::read_dword(INSTALLER_POLICIES, name, View::Registry64);
+            match registry::write_dword(INSTALLER_POLICIES, name, 1, View::Registry64) {
+                Ok(()) => previous.push((name, value)),
+                Err(e) => log::warn!("could not set the Windows Installer policy {}: {}", name, e),
+            }
+        }
+        log::info!("installing without restore points or rollback; a failed install may leave files behind");
+        FastInstall { previous }
+    }
+}
+
+impl Drop for FastInstall {
+    fn drop(&mut self) {
+        for (name, value) in &self.previous {
+            let result = match value {
+                Some(value) => registry::write_dword(INSTALLER_POLICIES, name, *value, View::Registry64),
+                None => registry::delete_value(INSTALLER_POLICIES, name, View::Registry64),
+            };
+            if let Err(e) = result {
+                log::warn!("could not restore the Windows Installer policy {}: {}", name, e);
+            }
+        }
+    }
+}
@@ -0,0 +1,161 @@
+//! A C ABI for installer frameworks that would rather call the installer in
+//! process than bundle and spawn the executable, such as Inno Setup, NSIS,
+//! pahkat or a C# bootstrapper. Build it as a DLL with
+//!
+//! ```text
+//! cargo rustc --lib --release --features ffi --crate-type cdylib
+//! ```
+//!
+//! and declare it with `include/dotnet5_webinst.h`.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ffi::{CStr, CString},
+    os::raw::{c_char, c_int},
+    panic::{self, AssertUnwindSafe},
+    ptr,
+    sync::Mutex,
+};
+
+use anyhow::{Context, Result, anyhow};
+
+use crate::{
+    Architecture, DotnetRedistError, InstallRequest, ProgressEvent, Report, Runtime, cancel,
+    installer::ERROR_SUCCESS_REBOOT_REQUIRED, report::Action, resolve_channel,
+};
+
+/// Working out which release the requested version resolves to.
+pub const STAGE_RESOLVING: c_int = 0;
+/// Started or resumed a download.
+pub const STAGE_DOWNLOAD_STARTED: c_int = 1;
+/// Part of a download arrived.
+pub const STAGE_DOWNLOADING: c_int = 2;
+/// Started installing a component.
+pub const STAGE_INSTALLING: c_int = 3;
+/// A component is installed.
+pub const STAGE_DONE: c_int = 4;
+
+/// Receives the stage of the install, the component or URL it is about, and
+/// for downloads the bytes received so far and the total, 0 if unknown.
+pub type ProgressCallback = Option<extern "C" fn(stage: c_int, subject: *const c_char, bytes: u64, total: u64)>;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Installs `runtime` of `version` for `arch` unless it is present, as the
+/// `install` command does, calling `progress` with each step if given.
+///
+/// `arch` is x86 or x64, the machine's own if null; `runtime` a name such as
+/// aspcore, dotnet if null; `version` anything `--version` takes, the latest
+/// LTS release if null. Machine-wide installs need the caller to be elevated.
+///
+/// Returns 0 on success, 3010 when a reboot is needed, a failing installer's
+/// exit code, the cancelled code if [`dotnet_redist_cancel`] was called, or 1
+/// for any other failure, which [`dotnet_redist_last_error`] then describes.
+///
+/// # Safety
+///
+/// `arch`, `runtime` and `version` must each be null or a NUL-terminated
+/// UTF-8 string that stays valid during the call.
+#[no_mangle]
+pub unsafe extern "C" fn dotnet_redist_ensure(
+    arch: *const c_char,
+    runtime: *const c_char,
+    version: *const c_char,
+    progress: ProgressCallback,
+) -> c_int {
+    let mut report = Report::default();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| ensure(arch, runtime, version, progress, &mut report)))
+        .unwrap_or_else(|_| Err(anyhow!("the installer crashed")));
+
+    let cancelled = result
+        .as_ref()
+        .err()
+        .is_some_and(|e| matches!(e.downcast_ref(), Some(DotnetRedistError::Cancelled(_))));
+    let code = match &result {
+        Err(_) if cancelled => cancel::EXIT_CODE,
+        Err(_) => report
+            .components
+            .iter()
+            .find(|component| matches!(component.action, Action::Failed))
+            .and_then(|component| component.exit_code)
+            .unwrap_or(1),
+        Ok(()) if report.reboot_required => ERROR_SUCCESS_REBOOT_REQUIRED as c_int,
+        Ok(()) => 0,
+    };
+    LAST_ERROR.with(|last| {
+        // Messages never hold NULs, but one is better cut than lost.
+        *last.borrow_mut() = result.err().map(|e| CString::new(format!("{:#}", e).replace('\0', " ")).unwrap_or_default());
+    });
+    code
+}
+
+/// The message of the last failed [`dotnet_redist_ensure`] on this thread,
+/// or null if it succeeded. Valid until the next call on the thread.
+#[no_mangle]
+pub extern "C" fn dotnet_redist_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
+
+/// Asks a running [`dotnet_redist_ensure`] to stop, abandoning downloads and
+/// letting an installer roll back. Later calls in the process are cancelled too.
+#[no_mangle]
+pub extern "C" fn dotnet_redist_cancel() {
+    cancel::cancel();
+}
+
+unsafe fn ensure(
+    arch: *const c_char,
+    runtime: *const c_char,
+    version: *const c_char,
+    progress: ProgressCallback,
+    report: &mut Report,
+) -> Result<()> {
+    let arch = match string(arch, "arch")? {
+        Some(arch) => arch.parse().map_err(|e| anyhow!("invalid arch {}: {}", arch, e))?,
+        None => *Architecture::installable().last().unwrap_or(&Architecture::X64),
+    };
+    let runtime = match string(runtime, "runtime")? {
+        Some(runtime) => runtime.parse().map_err(|e| anyhow!("invalid runtime {}: {}", runtime, e))?,
+        None => Runtime::Dotnet,
+    };
+    let version = smol::block_on(resolve_channel(string(version, "version")?.unwrap_or("latest")))?;
+
+    let mut builder = InstallRequest::builder().arch(arch).runtime(runtime).version(&version).quiet(true);
+    if let Some(callback) = progress {
+        builder = builder.on_progress(forward(callback));
+    }
+    smol::block_on(builder.build()?.install(report))?;
+    Ok(())
+}
+
+unsafe fn string<'a>(s: *const c_char, name: &str) -> Result<Option<&'a str>> {
+    if s.is_null() {
+        return Ok(None);
+    }
+    let s = CStr::from_ptr(s).to_str().with_context(|| format!("{} is not UTF-8", name))?;
+    Ok(Some(s))
+}
+
+/// Passes progress events on to `callback`, with the total of each download.
+fn forward(callback: extern "C" fn(c_int, *const c_char, u64, u64)) -> impl Fn(&ProgressEvent) + Send + Sync {
+    let totals = Mutex::new(HashMap::new());
+    move |event| {
+        let (stage, subject, bytes, total) = match event {
+            ProgressEvent::ResolvingVersion { component } => (STAGE_RESOLVING, component, 0, 0),
+            ProgressEvent::DownloadStarted { url, total } => {
+                totals.lock().unwrap().insert(url.clone(), total.unwrap_or(0));
+                (STAGE_DOWNLOAD_STARTED, url, 0, total.unwrap_or(0))
+            }
+            ProgressEvent::DownloadProgress { url, bytes } => {
+                (STAGE_DOWNLOADING, url, *bytes, totals.lock().unwrap().get(url).copied().unwrap_or(0))
+            }
+            ProgressEvent::Installing { component } => (STAGE_INSTALLING, component, 0, 0),
+            ProgressEvent::Done { component } => (STAGE_DONE, component, 0, 0),
+        };
+        let subject = CString::new(subject.as_str()).unwrap_or_default();
+        callback(stage, subject.as_ptr(), bytes, total);
+    }
+}
@@ -0,0 +1,94 @@
+use std::fmt::Display;
+
+use anyhow::{Context, Result};
+use http_types::Url;
+
+use crate::{http, mirrors, preflight};
+
+/// An outbound connection installs may need to make.
+pub struct Endpoint {
+    pub host: String,
+    pub port: u16,
+    /// The protocol spoken over TCP.
+    pub protocol: &'static str,
+    pub purpose: &'static str,
+    /// The proxy connections to the endpoint go through, if any.
+    pub proxy: Option<Url>,
+}
+
+impl Display for Endpoint {
+    /// One allowlist line: host, port and transport, protocol and purpose.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:<40} {:>5}/tcp  {:<5}  # {}",
+            self.host, self.port, self.protocol, self.purpose
+        )
+    }
+}
+
+/// Lists every host, port and protocol the tool connects to with the current
+/// network settings, including mirrors and the proxy.
+pub fn endpoints() -> Result<Vec<Endpoint>> {
+    let mut urls = mirrors::endpoints()
+        .into_iter()
+        .map(|endpoint| (endpoint.to_string(), "release metadata and installers"))
+        .collect::<Vec<_>>();
+    // Release metadata links installers on these hosts too, though a feed
+    // stands in for the newer download host.
+    urls.push(("https://aka.ms/".to_string(), "VC++ redistributable alias"));
+    urls.push((
+        "https://download.visualstudio.microsoft.com/".to_string(),
+        "VC++ redistributable and runtime installers",
+    ));
+    if !mirrors::has_feed() {
+        urls.push(("https://builds.dotnet.microsoft.com/".to_string(), "runtime installers"));
+    }
+    for mirror in mirrors::list() {
+        urls.push((mirror.clone(), "configured mirror"));
+    }
+
+    let mut endpoints: Vec<Endpoint> = Vec::new();
+    for (url, purpose) in urls {
+        let url = Url::parse(&url).with_context(|| format!("invalid URL {}", url))?;
+        let host = url.host_str().context("cannot parse host")?.to_string();
+        let port = url.port_or_known_default().context("cannot guess port")?;
+        if endpoints.iter().any(|endpoint| endpoint.host == host && endpoint.port == port) {
+            continue;
+        }
+        endpoints.push(Endpoint {
+            protocol: if url.scheme() == "https" { "HTTPS" } else { "HTTP" },
+            proxy: http::proxy_for(&url),
+            host,
+            port,
+            purpose,
+        });
+    }
+
+    // With a proxy only the proxy itself needs to be reachable through the firewall.
+    let mut proxies: Vec<Endpoint> = Vec::new();
+    for proxy in endpoints.iter().filter_map(|endpoint| endpoint.proxy.as_ref()) {
+        let host = proxy.host_str().context("cannot parse proxy host")?.to_string();
+        let port = proxy.port_or_known_default().context("cannot guess proxy port")?;
+        if !proxies.iter().any(|endpoint| endpoint.host == host && endpoint.port == port) {
+            proxies.push(Endpoint {
+                host,
+                port,
+                protocol: "HTTP",
+                purpose: "proxy, tunnelling the connections above with CONNECT",
+                proxy: None,
+            });
+        }
+    }
+    endpoints.extend(proxies);
+
+    Ok(endpoints)
+}
+
+/// Connects to `endpoint` the way installs would, through the proxy if
+/// there is one, and reports the layer that fails.
+pub async fn check(endpoint: &Endpoint) -> Result<()> {
+    preflight::check_host(&endpoint.host, endpoint.port, endpoint.proxy.as_ref(), endpoint.protocol == "HTTPS")
+        .await
+        .map_err(|(layer, e)| e.context(format!("failed at {}", layer)))
+}
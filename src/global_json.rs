@@ -0,0 +1,68 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result, bail};
+use semver::Version;
+use serde::Deserialize;
+
+use crate::releases;
+
+#[derive(Deserialize)]
+struct GlobalJson {
+    sdk: Option<Sdk>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Sdk {
+    version: Option<String>,
+    roll_forward: Option<String>,
+}
+
+/// Picks the SDK release that satisfies the `global.json` in `dir` under its
+/// `rollForward` policy, returning its exact version.
+///
+/// Only the pinned version's `major.minor` channel is searched: the newest
+/// release there satisfies the policies that could roll further, too.
+pub async fn sdk_version(dir: &Path) -> Result<String> {
+    let path = dir.join("global.json");
+    let contents = fs::read_to_string(&path).with_context(|| format!("cannot read {}", path.display()))?;
+    let global: GlobalJson =
+        serde_json::from_str(&contents).with_context(|| format!("invalid {}", path.display()))?;
+    let sdk = global.sdk.with_context(|| format!("{} does not pin an SDK", path.display()))?;
+    let pinned = sdk.version.with_context(|| format!("{} has no sdk.version", path.display()))?;
+    let pinned = Version::parse(&pinned).with_context(|| format!("invalid sdk.version {}", pinned))?;
+    let roll_forward = sdk.roll_forward.unwrap_or_else(|| "patch".to_string()).to_ascii_lowercase();
+
+    if roll_forward == "disable" {
+        return Ok(pinned.to_string());
+    }
+
+    let band = |version: &Version| version.patch / 100;
+    let candidates = releases::sdk_versions(pinned.major, pinned.minor)
+        .await?
+        .into_iter()
+        .filter(|version| *version >= pinned)
+        .collect::<Vec<_>>();
+
+    // Candidates are newest first.
+    let chosen = match roll_forward.as_str() {
+        "patch" | "latestpatch" => candidates.iter().find(|version| band(version) == band(&pinned)),
+        // The lowest band at or above the pinned one, at its newest patch.
+        "feature" | "minor" | "major" => candidates
+            .iter()
+            .map(band)
+            .min()
+            .and_then(|lowest| candidates.iter().find(|version| band(version) == lowest)),
+        "latestfeature" | "latestminor" | "latestmajor" => candidates.first(),
+        _ => bail!("unknown rollForward policy {}", roll_forward),
+    };
+
+    match chosen {
+        Some(version) => Ok(version.to_string()),
+        None => bail!(
+            "no released SDK satisfies version {} with rollForward {}",
+            pinned,
+            roll_forward
+        ),
+    }
+}
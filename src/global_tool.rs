@@ -0,0 +1,134 @@
+use anyhow::{Context, Result, anyhow, bail};
+use serde::Deserialize;
+
+use crate::{DotnetRedistError, http, mirrors};
+
+/// End of central directory record of a zip archive.
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+/// Central directory file header of a zip archive.
+const CENTRAL_DIRECTORY_HEADER: u32 = 0x0201_4b50;
+
+#[derive(Deserialize)]
+struct Versions {
+    versions: Vec<String>,
+}
+
+/// Works out the SDK channel, such as `8.0`, that the newest stable release
+/// of the global tool `package_id` needs, from the target frameworks it
+/// ships `tools/<tfm>/any/DotnetToolSettings.xml` for.
+///
+/// A tool built for several frameworks gets the newest of them, which is
+/// the one `dotnet tool install` picks when its SDK has it.
+pub async fn sdk_channel(package_id: &str) -> Result<String> {
+    let id = package_id.to_ascii_lowercase();
+    let base = format!("{}/{}", mirrors::nuget_endpoint(), id);
+
+    let versions: Versions = serde_json::from_slice(&fetch(&format!("{}/index.json", base)).await?)
+        .with_context(|| format!("invalid NuGet version list for {}", package_id))?;
+    // Versions are listed oldest first.
+    let version = versions
+        .versions
+        .iter()
+        .rev()
+        .find(|version| !version.contains('-'))
+        .or_else(|| versions.versions.last())
+        .with_context(|| format!("{} has no published versions", package_id))?
+        .to_ascii_lowercase();
+
+    let package = fetch(&format!("{0}/{1}/{2}.{1}.nupkg", base, version, id)).await?;
+    let names = entry_names(&package).with_context(|| format!("invalid package {} {}", package_id, version))?;
+    names
+        .iter()
+        .filter_map(|name| tool_framework(name))
+        .filter_map(|tfm| channel(&tfm))
+        .max()
+        .map(|(major, minor)| format!("{}.{}", major, minor))
+        .with_context(|| format!("{} {} is not a .NET global tool package", package_id, version))
+}
+
+async fn fetch(url: &str) -> Result<Vec<u8>> {
+    let mut response = http::get(url).await?;
+    if !response.status().is_success() {
+        bail!(DotnetRedistError::Network(anyhow!("could not download {}: {}", url, response.status())));
+    }
+    response.body_bytes().await.map_err(anyhow::Error::msg)
+}
+
+/// The names in the central directory of the zip archive `data`.
+fn entry_names(data: &[u8]) -> Result<Vec<String>> {
+    let u16_at = |pos: usize| data.get(pos..pos + 2).map(|b| u16::from_le_bytes([b[0], b[1]]) as usize);
+    let u32_at = |pos: usize| data.get(pos..pos + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+
+    // The record is the last thing in the archive but for a comment of up to 64 KiB.
+    let end = (0..data.len().saturating_sub(21))
+        .rev()
+        .take(0x10000)
+        .find(|&pos| u32_at(pos) == Some(END_OF_CENTRAL_DIRECTORY))
+        .context("not a zip archive")?;
+    let count = u16_at(end + 10).context("truncated zip archive")?;
+    let mut pos = u32_at(end + 16).context("truncated zip archive")? as usize;
+
+    let mut names = Vec::with_capacity(count);
+    for _ in 0..count {
+        if u32_at(pos) != Some(CENTRAL_DIRECTORY_HEADER) {
+            bail!("corrupt zip central directory");
+        }
+        let header = || anyhow!("truncated zip central directory");
+        let name_len = u16_at(pos + 28).ok_or_else(header)?;
+        let extra_len = u16_at(pos + 30).ok_or_else(header)?;
+        let comment_len = u16_at(pos + 32).ok_or_else(header)?;
+        let name = data.get(pos + 46..pos + 46 + name_len).ok_or_else(header)?;
+        names.push(String::from_utf8_lossy(name).into_owned());
+        pos += 46 + name_len + extra_len + comment_len;
+    }
+    Ok(names)
+}
+
+/// The target framework of a `tools/<tfm>/any/DotnetToolSettings.xml` entry.
+fn tool_framework(name: &str) -> Option<String> {
+    let mut parts = name.split('/');
+    match (parts.next(), parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some("tools"), Some(tfm), Some("any"), Some(file), None) if file.eq_ignore_ascii_case("DotnetToolSettings.xml") => {
+            Some(tfm.to_ascii_lowercase())
+        }
+        _ => None,
+    }
+}
+
+/// The `major.minor` of a .NET Core target framework such as `net8.0`,
+/// `net8.0-windows` or `netcoreapp3.1`.
+fn channel(tfm: &str) -> Option<(u64, u64)> {
+    let version = tfm.split('-').next()?;
+    let version = version.strip_prefix("netcoreapp").or_else(|| version.strip_prefix("net"))?;
+    let (major, minor) = version.split_once('.')?;
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channel_of_core_frameworks_only() {
+        assert_eq!(channel("net8.0"), Some((8, 0)));
+        assert_eq!(channel("net6.0-windows"), Some((6, 0)));
+        assert_eq!(channel("netcoreapp3.1"), Some((3, 1)));
+        assert_eq!(channel("netstandard2.0"), None);
+        assert_eq!(channel("net48"), None);
+    }
+
+    #[test]
+    fn tool_framework_from_the_settings_entry() {
+        assert_eq!(tool_framework("tools/net8.0/any/DotnetToolSettings.xml").as_deref(), Some("net8.0"));
+        assert_eq!(tool_framework("tools/net8.0/any/divvun-tool.dll"), None);
+        assert_eq!(tool_framework("lib/net8.0/any/DotnetToolSettings.xml"), None);
+    }
+
+    #[test]
+    fn entry_names_of_the_fixture_package() {
+        let package = include_bytes!("../tests/fixtures/nuget/divvun.tool/1.0.0/divvun.tool.1.0.0.nupkg");
+        let names = entry_names(package).unwrap();
+        assert!(names.contains(&"tools/net8.0/any/DotnetToolSettings.xml".to_string()));
+        assert!(entry_names(b"not a zip").is_err());
+    }
+}
@@ -0,0 +1,24 @@
+use std::path::PathBuf;
+
+use crate::registry::{self, View};
+
+const IIS_KEY: &str = "SOFTWARE\\Microsoft\\InetStp";
+const MODULE_KEY: &str = "SOFTWARE\\Microsoft\\IIS Extensions\\IIS AspNetCore Module V2";
+
+/// Whether IIS is installed, which the hosting bundle needs to install the
+/// ASP.NET Core Module.
+pub(crate) fn is_iis_installed() -> bool {
+    let major = registry::read_dword(IIS_KEY, "MajorVersion", View::Registry64);
+    log::debug!("IIS major version: {:?}", major);
+    major.is_some()
+}
+
+/// Whether the ASP.NET Core Module V2 is registered and the shim IIS loads
+/// is in place.
+pub(crate) fn is_module_installed() -> bool {
+    let shim = std::env::var_os("SystemRoot")
+        .map(|root| PathBuf::from(root).join("System32\\inetsrv\\aspnetcorev2.dll"))
+        .is_some_and(|shim| shim.is_file());
+    log::debug!("ASP.NET Core Module shim present: {}", shim);
+    shim && registry::key_exists(MODULE_KEY, View::Registry64)
+}
@@ -0,0 +1,98 @@
+use std::{collections::HashMap, fmt::Display, sync::OnceLock};
+
+use anyhow::{Result, bail};
+
+/// The message catalogs built into the tool, by language subtag. English
+/// comes first and has every message.
+static CATALOGS: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en.toml")),
+    ("nb", include_str!("../locales/nb.toml")),
+];
+
+/// The catalogs to look messages up in, most specific first.
+static MESSAGES: OnceLock<Vec<HashMap<String, String>>> = OnceLock::new();
+
+/// Selects the language of messages: `lang` if given, such as `nb` or
+/// `en-US`, and the user's OS language otherwise. Only the first call has
+/// an effect.
+pub fn init(lang: Option<&str>) -> Result<()> {
+    let catalogs = match lang {
+        Some(lang) => {
+            let catalogs = catalogs(lang);
+            if catalogs.len() == 1 && !lang.to_lowercase().starts_with("en") {
+                let available = CATALOGS.iter().map(|(tag, _)| *tag).collect::<Vec<_>>();
+                bail!("no translation for {}; available: {}", lang, available.join(", "));
+            }
+            catalogs
+        }
+        None => catalogs(&system_language().unwrap_or_default()),
+    };
+    let _ = MESSAGES.set(catalogs);
+    Ok(())
+}
+
+/// The message `id` in the selected language, with each `{name}` replaced
+/// by the value of the argument of that name. Messages missing from a
+/// translation fall back to English.
+pub fn tr(id: &str, args: &[(&str, &dyn Display)]) -> String {
+    let catalogs = MESSAGES.get_or_init(|| catalogs(&system_language().unwrap_or_default()));
+    let mut message = match catalogs.iter().find_map(|catalog| catalog.get(id)) {
+        Some(message) => message.clone(),
+        None => {
+            log::debug!("no message {}", id);
+            return id.to_string();
+        }
+    };
+    for (name, value) in args {
+        message = message.replace(&format!("{{{}}}", name), &value.to_string());
+    }
+    message
+}
+
+/// The catalogs for a language tag, from the full tag through its primary
+/// subtag down to English.
+fn catalogs(lang: &str) -> Vec<HashMap<String, String>> {
+    let lang = lang.to_lowercase().replace('_', "-");
+    let primary = lang.split('-').next().unwrap_or_default();
+    let mut tags = vec![lang.as_str(), primary];
+    tags.dedup();
+
+    tags.into_iter()
+        .filter(|&tag| tag != "en")
+        .chain(Some("en"))
+        .filter_map(|tag| CATALOGS.iter().find(|(name, _)| *name == tag))
+        .filter_map(|(tag, source)| match toml::from_str(source) {
+            Ok(catalog) => Some(catalog),
+            Err(e) => {
+                log::warn!("invalid {} message catalog: {}", tag, e);
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(windows)]
+fn system_language() -> Option<String> {
+    use winapi::um::winnls::GetUserDefaultLocaleName;
+
+    // LOCALE_NAME_MAX_LENGTH
+    let mut buf = [0u16; 85];
+    match unsafe { GetUserDefaultLocaleName(buf.as_mut_ptr(), buf.len() as i32) } {
+        0 => None,
+        len => Some(String::from_utf16_lossy(&buf[..len as usize - 1])),
+    }
+}
+
+/// The POSIX locale variables, in order of precedence, as in `nb_NO.UTF-8`.
+#[cfg(not(windows))]
+fn system_language() -> Option<String> {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())?;
+    let lang = locale.split(['.', '@']).next().unwrap_or_default();
+    match lang {
+        "C" | "POSIX" | "" => None,
+        lang => Some(lang.to_string()),
+    }
+}
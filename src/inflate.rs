@@ -0,0 +1,285 @@
+use anyhow::{Result, anyhow, bail};
+
+/// Base lengths and extra bits of length symbols 257 to 285.
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+/// Base distances and extra bits of distance symbols 0 to 29.
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+/// The order code length code lengths come in, in a dynamic block.
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+/// Decompresses a gzip member (RFC 1952), checking its CRC and length.
+pub(crate) fn gunzip(data: &[u8]) -> Result<Vec<u8>> {
+    const FHCRC: u8 = 2;
+    const FEXTRA: u8 = 4;
+    const FNAME: u8 = 8;
+    const FCOMMENT: u8 = 16;
+
+    if data.len() < 18 || data[..3] != [0x1f, 0x8b, 8] {
+        bail!("not gzip data");
+    }
+    let flags = data[3];
+    let mut pos = 10;
+    if flags & FEXTRA != 0 {
+        let len = u16::from_le_bytes([data[pos], data[pos + 1]]) as usize;
+        pos += 2 + len;
+    }
+    for &flag in &[FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            let end = data.get(pos..).and_then(|rest| rest.iter().position(|&b| b == 0));
+            pos += end.ok_or_else(|| anyhow!("gzip header ends early"))? + 1;
+        }
+    }
+    if flags & FHCRC != 0 {
+        pos += 2;
+    }
+
+    let (out, used) = inflate(data.get(pos..).ok_or_else(|| anyhow!("gzip header ends early"))?)?;
+    let trailer = data
+        .get(pos + used..pos + used + 8)
+        .ok_or_else(|| anyhow!("gzip data ends early"))?;
+    let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let len = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+    if crc != crc32(&out) || len != out.len() as u32 {
+        bail!("gzip data is corrupt");
+    }
+    Ok(out)
+}
+
+/// Decompresses zlib data (RFC 1950), checking its Adler-32. Some servers
+/// send `deflate` bodies without the zlib wrapper, so raw deflate is accepted too.
+pub(crate) fn zlib(data: &[u8]) -> Result<Vec<u8>> {
+    let wrapped = data.len() >= 6 && data[0] & 0x0f == 8 && (u16::from(data[0]) << 8 | u16::from(data[1])) % 31 == 0;
+    if !wrapped {
+        return Ok(inflate(data)?.0);
+    }
+    if data[1] & 0x20 != 0 {
+        bail!("zlib data needs a preset dictionary");
+    }
+
+    let (out, used) = inflate(&data[2..])?;
+    let trailer = data.get(2 + used..2 + used + 4).ok_or_else(|| anyhow!("zlib data ends early"))?;
+    if u32::from_be_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]) != adler32(&out) {
+        bail!("zlib data is corrupt");
+    }
+    Ok(out)
+}
+
+/// Decompresses raw deflate data (RFC 1951), returning it and the number of
+/// bytes of `data` it took up.
+fn inflate(data: &[u8]) -> Result<(Vec<u8>, usize)> {
+    let mut input = Bits { data, pos: 0, buf: 0, count: 0 };
+    let mut out = Vec::with_capacity(data.len() * 4);
+    loop {
+        let last = input.bits(1)? == 1;
+        match input.bits(2)? {
+            0 => stored(&mut input, &mut out)?,
+            1 => {
+                let (lengths, distances) = fixed();
+                codes(&mut input, &mut out, &lengths, &distances)?;
+            }
+            2 => {
+                let (lengths, distances) = dynamic(&mut input)?;
+                codes(&mut input, &mut out, &lengths, &distances)?;
+            }
+            _ => bail!("invalid deflate block type"),
+        }
+        if last {
+            return Ok((out, input.pos));
+        }
+    }
+}
+
+/// Reads deflate's bit stream, least significant bit first.
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buf: u32,
+    count: u32,
+}
+
+impl Bits<'_> {
+    fn bits(&mut self, need: u32) -> Result<u32> {
+        let mut value = self.buf;
+        while self.count < need {
+            let byte = *self.data.get(self.pos).ok_or_else(|| anyhow!("deflate data ends early"))?;
+            self.pos += 1;
+            value |= u32::from(byte) << self.count;
+            self.count += 8;
+        }
+        self.buf = value >> need;
+        self.count -= need;
+        Ok(value & ((1 << need) - 1))
+    }
+}
+
+/// A canonical Huffman code: how many codes there are of each length, and
+/// the symbols in code order.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self> {
+        let mut counts = [0u16; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = (left << 1) - i32::from(count);
+            if left < 0 {
+                bail!("invalid deflate code lengths");
+            }
+        }
+
+        let mut offsets = [0u16; 16];
+        for len in 1..15 {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, input: &mut Bits) -> Result<u16> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= input.bits(1)? as i32;
+            let count = i32::from(count);
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        bail!("invalid deflate code")
+    }
+}
+
+fn stored(input: &mut Bits, out: &mut Vec<u8>) -> Result<()> {
+    // Stored blocks start on a byte boundary.
+    input.buf = 0;
+    input.count = 0;
+    let header = input.data.get(input.pos..input.pos + 4).ok_or_else(|| anyhow!("deflate data ends early"))?;
+    let len = u16::from_le_bytes([header[0], header[1]]);
+    if len != !u16::from_le_bytes([header[2], header[3]]) {
+        bail!("invalid stored deflate block length");
+    }
+    input.pos += 4;
+    let block = input
+        .data
+        .get(input.pos..input.pos + len as usize)
+        .ok_or_else(|| anyhow!("deflate data ends early"))?;
+    out.extend_from_slice(block);
+    input.pos += len as usize;
+    Ok(())
+}
+
+fn fixed() -> (Huffman, Huffman) {
+    let mut lengths = [8u8; 288];
+    lengths[144..256].iter_mut().for_each(|len| *len = 9);
+    lengths[256..280].iter_mut().for_each(|len| *len = 7);
+    let lengths = Huffman::new(&lengths).expect("the fixed code is valid");
+    let distances = Huffman::new(&[5; 30]).expect("the fixed code is valid");
+    (lengths, distances)
+}
+
+fn dynamic(input: &mut Bits) -> Result<(Huffman, Huffman)> {
+    let length_count = input.bits(5)? as usize + 257;
+    let distance_count = input.bits(5)? as usize + 1;
+    let code_count = input.bits(4)? as usize + 4;
+    if length_count > 286 || distance_count > 30 {
+        bail!("invalid deflate code counts");
+    }
+
+    let mut code_lengths = [0u8; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_count] {
+        code_lengths[index] = input.bits(3)? as u8;
+    }
+    let code = Huffman::new(&code_lengths)?;
+
+    let mut lengths = Vec::with_capacity(length_count + distance_count);
+    while lengths.len() < length_count + distance_count {
+        let (len, repeat) = match code.decode(input)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => (*lengths.last().ok_or_else(|| anyhow!("invalid deflate code lengths"))?, 3 + input.bits(2)?),
+            17 => (0, 3 + input.bits(3)?),
+            _ => (0, 11 + input.bits(7)?),
+        };
+        if lengths.len() + repeat as usize > length_count + distance_count {
+            bail!("invalid deflate code lengths");
+        }
+        lengths.extend(std::iter::repeat_n(len, repeat as usize));
+    }
+    if lengths[256] == 0 {
+        bail!("deflate block has no end code");
+    }
+
+    Ok((Huffman::new(&lengths[..length_count])?, Huffman::new(&lengths[length_count..])?))
+}
+
+fn codes(input: &mut Bits, out: &mut Vec<u8>, lengths: &Huffman, distances: &Huffman) -> Result<()> {
+    loop {
+        let symbol = lengths.decode(input)? as usize;
+        if symbol < 256 {
+            out.push(symbol as u8);
+            continue;
+        }
+        if symbol == 256 {
+            return Ok(());
+        }
+
+        let symbol = symbol - 257;
+        if symbol >= LENGTH_BASE.len() {
+            bail!("invalid deflate length code");
+        }
+        let len = LENGTH_BASE[symbol] as usize + input.bits(u32::from(LENGTH_EXTRA[symbol]))? as usize;
+        let symbol = distances.decode(input)? as usize;
+        if symbol >= DISTANCE_BASE.len() {
+            bail!("invalid deflate distance code");
+        }
+        let distance = DISTANCE_BASE[symbol] as usize + input.bits(u32::from(DISTANCE_EXTRA[symbol]))? as usize;
+        if distance > out.len() {
+            bail!("deflate distance reaches before the start of the data");
+        }
+        // The copy may overlap what it produces, so it goes a byte at a time.
+        let start = out.len() - distance;
+        for i in 0..len {
+            out.push(out[start + i]);
+        }
+    }
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut table = [0u32; 256];
+    for (n, entry) in table.iter_mut().enumerate() {
+        let mut c = n as u32;
+        for _ in 0..8 {
+            c = if c & 1 != 0 { 0xedb8_8320 ^ (c >> 1) } else { c >> 1 };
+        }
+        *entry = c;
+    }
+    !data.iter().fold(!0u32, |crc, &b| table[((crc ^ u32::from(b)) & 0xff) as usize] ^ (crc >> 8))
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), &byte| {
+        let a = (a + u32::from(byte)) % 65521;
+        (a, (b + a) % 65521)
+    });
+    b << 16 | a
+}
@@ -0,0 +1,493 @@
+use std::{
+    ffi::OsStr,
+    fmt::Display,
+    path::{Path, PathBuf},
+    process::{Child, Command, ExitStatus},
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Result, anyhow};
+use clap::arg_enum;
+
+use crate::{
+    Architecture, Runtime,
+    cancel::{self, Cancelled},
+    detect::{self, LINUX_ROOT, get_root_install},
+    releases::Package,
+    temp,
+    timing::{self, Phase},
+};
+
+/// Windows Installer code for a successful install that needs a reboot.
+pub const ERROR_SUCCESS_REBOOT_REQUIRED: i32 = 3010;
+/// Windows Installer code for a successful install that started a reboot.
+pub const ERROR_SUCCESS_REBOOT_INITIATED: i32 = 1641;
+/// Windows Installer code for another installation being in progress.
+pub(crate) const ERROR_INSTALL_ALREADY_RUNNING: i32 = 1618;
+
+/// How long an installer gets to roll back once the run is cancelled.
+const CANCEL_GRACE: Duration = Duration::from_secs(60);
+/// How long to wait for another installation to finish by default.
+const DEFAULT_MSI_WAIT: Duration = Duration::from_secs(10 * 60);
+/// How often to check whether another installation has finished.
+const MSI_POLL: Duration = Duration::from_secs(2);
+
+static TIMEOUT: OnceLock<Duration> = OnceLock::new();
+static MSI_WAIT: OnceLock<Duration> = OnceLock::new();
+
+/// Kills installers that are still running after `timeout`, instead of
+/// waiting for them however long they take.
+pub fn set_timeout(timeout: Duration) {
+    let _ = TIMEOUT.set(timeout);
+}
+
+/// Waits up to `timeout` for another Windows Installer transaction, such as
+/// one Windows Update started, to finish before running an installer, which
+/// would otherwise fail with 1618.
+pub fn set_msi_wait(timeout: Duration) {
+    let _ = MSI_WAIT.set(timeout);
+}
+
+/// The installer exited with a failure code.
+#[derive(Debug)]
+pub struct Failed(pub i32);
+
+impl Display for Failed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "installer exited with code {}", self.0)?;
+        if let Some(meaning) = describe(self.0) {
+            write!(f, ": {}", meaning)?;
+        }
+        Ok(())
+    }
+}
+
+/// Explains the standard exit codes of Windows Installer and Burn bundles.
+pub fn describe(code: i32) -> Option<&'static str> {
+    let meaning = match code {
+        0 => "success",
+        5 => "access denied; run as an administrator",
+        1602 => "cancelled by the user",
+        1603 => "fatal error during installation",
+        ERROR_INSTALL_ALREADY_RUNNING => "another installation is already in progress",
+        1625 => "installation is forbidden by system policy",
+        1633 => "this platform is not supported",
+        1638 => "another version of this product is already installed",
+        1639 => "invalid command line",
+        ERROR_SUCCESS_REBOOT_INITIATED => "success; a reboot has been started",
+        ERROR_SUCCESS_REBOOT_REQUIRED => "success; a reboot is required to finish",
+        _ => return None,
+    };
+    Some(meaning)
+}
+
+/// Whether an installer exit code means success pending a reboot.
+pub fn requires_reboot(code: i32) -> bool {
+    code == ERROR_SUCCESS_REBOOT_REQUIRED || code == ERROR_SUCCESS_REBOOT_INITIATED
+}
+
+impl std::error::Error for Failed {}
+
+/// The installer was killed after running longer than the timeout.
+#[derive(Debug)]
+pub struct TimedOut(pub Duration);
+
+impl Display for TimedOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "installer was killed after running for {:?}", self.0)
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+arg_enum! {
+    /// How much of their own UI installers show while they run: none, a
+    /// progress bar without asking anything, or their interactive UI.
+    #[derive(Copy, Clone, PartialEq, Eq)]
+    pub enum Ui {
+        Quiet,
+        Passive,
+        Full,
+    }
+}
+
+/// How an installer is driven from the command line.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Kind {
+    /// A WiX Burn bundle, as the runtimes and the VC++ redistributable ship.
+    Burn,
+    /// The .NET Framework setup chainer, which has its own switches.
+    Framework,
+    /// A macOS installer package, run through `installer`.
+    Pkg,
+    /// A Linux archive of the install layout, extracted into the install root.
+    Tarball,
+}
+
+impl Kind {
+    pub fn of(runtime: Runtime) -> Kind {
+        match (runtime, Package::installer()) {
+            (Runtime::NetFx48, _) => Kind::Framework,
+            (_, Package::Pkg) => Kind::Pkg,
+            (_, Package::TarGz) => Kind::Tarball,
+            (_, Package::Exe | Package::Zip) => Kind::Burn,
+        }
+    }
+
+    /// Whether the installer takes Windows-style switches, including `/log`.
+    pub fn takes_switches(self) -> bool {
+        matches!(self, Kind::Burn | Kind::Framework)
+    }
+
+    /// The switch selecting the UI language, with the LCID after it.
+    pub fn lang_switch(self) -> Option<&'static str> {
+        match self {
+            Kind::Burn => Some("/lang"),
+            Kind::Framework => Some("/lcid"),
+            Kind::Pkg | Kind::Tarball => None,
+        }
+    }
+
+    fn switches(self, ui: Ui) -> &'static [&'static str] {
+        match (self, ui) {
+            (Kind::Burn, Ui::Quiet) => &["/norestart", "/quiet"],
+            (Kind::Burn, Ui::Passive) => &["/norestart", "/passive"],
+            (Kind::Framework, Ui::Quiet) => &["/q", "/norestart"],
+            (Kind::Framework, Ui::Passive) => &["/passive", "/norestart"],
+            (Kind::Burn | Kind::Framework, Ui::Full) => &["/norestart"],
+            (Kind::Pkg | Kind::Tarball, _) => &[],
+        }
+    }
+}
+
+/// Runs a downloaded installer showing `ui`, returning its exit code.
+///
+/// `extra_args` are appended to the switches selecting the UI.
+pub fn run(path: &Path, kind: Kind, ui: Ui, extra_args: &[String]) -> Result<Option<i32>> {
+    match kind {
+        Kind::Pkg => {
+            let args = [OsStr::new("-pkg"), path.as_os_str(), OsStr::new("-target"), OsStr::new("/")];
+            return run_with(Path::new("/usr/sbin/installer"), &args, &[0]);
+        }
+        Kind::Tarball => {
+            let root = Path::new(LINUX_ROOT);
+            let code = extract(path, root)?;
+            link_host(root);
+            return Ok(code);
+        }
+        Kind::Burn | Kind::Framework => {}
+    }
+
+    let args = kind
+        .switches(ui)
+        .iter()
+        .map(|arg| arg.to_string())
+        .chain(extra_args.iter().cloned())
+        .collect::<Vec<_>>();
+
+    // Another installation can also start between the check and the launch,
+    // so its 1618 is waited out the same way.
+    let deadline = Instant::now() + *MSI_WAIT.get().unwrap_or(&DEFAULT_MSI_WAIT);
+    loop {
+        wait_for_msi(deadline)?;
+        let result = run_with(path, &args, &[0, ERROR_SUCCESS_REBOOT_REQUIRED, ERROR_SUCCESS_REBOOT_INITIATED]);
+        let busy = matches!(&result, Err(e) if matches!(e.downcast_ref(), Some(Failed(ERROR_INSTALL_ALREADY_RUNNING))));
+        if !busy || Instant::now() >= deadline {
+            return result;
+        }
+        log::warn!("another installation started first; waiting for it to finish");
+        std::thread::sleep(MSI_POLL);
+    }
+}
+
+/// Waits until no Windows Installer transaction is running or `deadline`
+/// passes, after which the installer is left to report the conflict.
+fn wait_for_msi(deadline: Instant) -> Result<()> {
+    let mut logged = false;
+    while msi_busy() {
+        if Instant::now() >= deadline {
+            log::warn!("another installation is still in progress; trying anyway");
+            return Ok(());
+        }
+        if !logged {
+            log::warn!("another installation is in progress; waiting for it to finish");
+            logged = true;
+        }
+        if cancel::is_cancelled() {
+            return Err(Cancelled.into());
+        }
+        std::thread::sleep(MSI_POLL);
+    }
+    if logged {
+        log::info!("the other installation has finished");
+    }
+    Ok(())
+}
+
+/// Whether a Windows Installer transaction is running, which holds the
+/// `_MSIExecute` mutex. It is only looked at, never taken, so as not to
+/// block the transaction.
+#[cfg(windows)]
+fn msi_busy() -> bool {
+    use winapi::um::{handleapi::CloseHandle, synchapi::OpenMutexW, winnt::SYNCHRONIZE};
+
+    let name: Vec<u16> = "Global\\_MSIExecute\0".encode_utf16().collect();
+    unsafe {
+        let mutex = OpenMutexW(SYNCHRONIZE, 0, name.as_ptr());
+        if mutex.is_null() {
+            return false;
+        }
+        CloseHandle(mutex);
+    }
+    true
+}
+
+#[cfg(not(windows))]
+fn msi_busy() -> bool {
+    false
+}
+
+/// Extracts a runtime archive into `dir`, next to any versions already there.
+///
+/// `tar` reads both the Linux archives and, as shipped with Windows 10 1803
+/// and later, the Windows zips; older Windows falls back to PowerShell.
+pub fn extract(path: &Path, dir: &Path) -> Result<Option<i32>> {
+    std::fs::create_dir_all(dir)?;
+    let args = [OsStr::new("-xf"), path.as_os_str(), OsStr::new("-C"), dir.as_os_str()];
+    match run_with(Path::new("tar"), &args, &[0]) {
+        Err(e) if cfg!(windows) && e.downcast_ref::<std::io::Error>().is_some() => {
+            log::debug!("cannot run tar: {:#}, extracting with PowerShell", e);
+            let script = format!(
+                "Expand-Archive -Force -LiteralPath '{}' -DestinationPath '{}'",
+                path.display().to_string().replace('\'', "''"),
+                dir.display().to_string().replace('\'', "''")
+            );
+            run_with(Path::new("powershell.exe"), &["-NoProfile", "-NonInteractive", "-Command", &script], &[0])
+        }
+        result => result,
+    }
+}
+
+/// Links `dotnet` in `root` onto the PATH as the Linux distribution packages do.
+fn link_host(root: &Path) {
+    #[cfg(unix)]
+    {
+        let link = Path::new("/usr/bin/dotnet");
+        if link.symlink_metadata().is_err() {
+            if let Err(e) = std::os::unix::fs::symlink(detect::host(root), link) {
+                log::warn!("could not link {}: {}; add {} to the PATH", link.display(), e, root.display());
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = root;
+}
+
+/// The Windows LCID of a BCP 47 locale, by which installers select their UI language.
+pub fn locale_lcid(locale: &str) -> Result<u32> {
+    lcid(locale).ok_or_else(|| anyhow!("unknown locale {}", locale))
+}
+
+#[cfg(windows)]
+fn lcid(locale: &str) -> Option<u32> {
+    use std::{ffi::OsStr, os::windows::ffi::OsStrExt};
+    use winapi::um::winnls::LocaleNameToLCID;
+
+    let wide: Vec<u16> = OsStr::new(locale).encode_wide().chain(Some(0)).collect();
+    match unsafe { LocaleNameToLCID(wide.as_ptr(), 0) } {
+        0 => None,
+        lcid => Some(lcid),
+    }
+}
+
+#[cfg(not(windows))]
+fn lcid(_locale: &str) -> Option<u32> {
+    None
+}
+
+/// Runs an installer with `args`, treating `success_codes` as success.
+pub fn run_with(path: &Path, args: &[impl AsRef<OsStr>], success_codes: &[i32]) -> Result<Option<i32>> {
+    // Some bundles fail to relaunch themselves from paths with spaces or
+    // non-ASCII characters (localized user profiles), so prefer the 8.3 form.
+    let path = short_path(path).unwrap_or_else(|| path.to_path_buf());
+    if cancel::is_cancelled() {
+        return Err(Cancelled.into());
+    }
+    let _span = timing::span(Phase::Install, path.file_name().unwrap_or(path.as_os_str()).to_string_lossy());
+    let mut command = Command::new(path);
+    command.args(args);
+    if let Some(dir) = temp::overridden() {
+        // Bundles extract their payloads to the temporary directory too.
+        command.env("TEMP", dir).env("TMP", dir).env("TMPDIR", dir);
+    }
+    log::debug!("running {:?}", command);
+    let mut child = command.spawn()?;
+    let status = wait(&mut child)?;
+    log::debug!("installer exited with {}", status);
+    crate::logging::record(serde_json::json!({
+        "event": "installer_exited",
+        "installer": command.get_program().to_string_lossy(),
+        "exit_code": status.code(),
+    }));
+
+    match status.code() {
+        Some(code) if success_codes.contains(&code) => Ok(Some(code)),
+        Some(code) => Err(Failed(code).into()),
+        None => Ok(None),
+    }
+}
+
+/// Waits for `child` to exit, killing it and everything it started once
+/// the timeout has passed. Hung installers are typically waiting on a hidden
+/// dialog or on another installation.
+///
+/// When the run is cancelled the installer, which gets the same Ctrl+C, is
+/// given time to roll back, and killed if it takes longer or the user
+/// cancels again.
+fn wait(child: &mut Child) -> Result<ExitStatus> {
+    let start = Instant::now();
+    let mut cancelled_at = None;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            if cancelled_at.is_some() {
+                log::info!("cancelled installer exited with {}", status);
+                return Err(Cancelled.into());
+            }
+            return Ok(status);
+        }
+        if let Some(&timeout) = TIMEOUT.get().filter(|&&timeout| start.elapsed() >= timeout) {
+            log::warn!("installer is still running after {:?}; killing it", timeout);
+            kill_tree(child);
+            let _ = child.wait();
+            return Err(TimedOut(timeout).into());
+        }
+        if cancel::is_cancelled() {
+            let since = *cancelled_at.get_or_insert_with(|| {
+                log::warn!("waiting up to {:?} for the installer to roll back; cancel again to kill it", CANCEL_GRACE);
+                Instant::now()
+            });
+            if cancel::is_insistent() || since.elapsed() >= CANCEL_GRACE {
+                log::warn!("killing the cancelled installer");
+                kill_tree(child);
+                let _ = child.wait();
+                return Err(Cancelled.into());
+            }
+        }
+        std::thread::sleep(Duration::from_millis(200));
+    }
+}
+
+/// Burn bundles relaunch themselves and run their packages in child
+/// processes, which killing the bundle alone leaves running. Elsewhere the
+/// children of the installer are killed along with it.
+fn kill_tree(child: &mut Child) {
+    let pid = child.id().to_string();
+    if cfg!(windows) {
+        let killed = Command::new("taskkill")
+            .args(["/pid", &pid, "/t", "/f"])
+            .output()
+            .is_ok_and(|output| output.status.success());
+        if killed {
+            return;
+        }
+    } else {
+        let _ = Command::new("pkill").args(["-KILL", "-P", &pid]).output();
+    }
+    if let Err(e) = child.kill() {
+        log::warn!("could not kill installer: {}", e);
+    }
+}
+
+/// Installs a dotnet global tool unless it is already present.
+pub fn install_global_tool(arch: Architecture, package_id: &str) -> Result<()> {
+    let dotnet = detect::host(get_root_install(arch));
+
+    let output = Command::new(&dotnet).args(["tool", "list", "-g"]).output()?;
+    let installed = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .any(|id| id.eq_ignore_ascii_case(package_id));
+
+    if installed {
+        return Ok(());
+    }
+
+    let status = Command::new(&dotnet)
+        .args(["tool", "install", "-g", package_id])
+        .status()?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(anyhow!("could not install global tool {}", package_id))
+    }
+}
+
+#[cfg(windows)]
+fn short_path(path: &Path) -> Option<PathBuf> {
+    use std::{
+        ffi::OsString,
+        os::windows::ffi::{OsStrExt, OsStringExt},
+    };
+    use winapi::um::fileapi::GetShortPathNameW;
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let len = unsafe { GetShortPathNameW(wide.as_ptr(), std::ptr::null_mut(), 0) };
+    if len == 0 {
+        return None;
+    }
+
+    let mut buf = vec![0u16; len as usize];
+    let len = unsafe { GetShortPathNameW(wide.as_ptr(), buf.as_mut_ptr(), buf.len() as u32) };
+    if len == 0 || len as usize >= buf.len() {
+        return None;
+    }
+
+    buf.truncate(len as usize);
+    Some(PathBuf::from(OsString::from_wide(&buf)))
+}
+
+#[cfg(not(windows))]
+fn short_path(_path: &Path) -> Option<PathBuf> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    /// Writes an installer stub to `dir` that saves its arguments next to itself.
+    #[cfg(unix)]
+    fn stub(dir: &Path) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = dir.join("installer stub");
+        fs::write(&path, "#!/bin/sh\necho \"$@\" > \"$(dirname \"$0\")/ran\"\nexit 0\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[cfg(windows)]
+    fn stub(dir: &Path) -> PathBuf {
+        let path = dir.join("installer stub.cmd");
+        fs::write(&path, "@echo %*> \"%~dp0ran\"\r\n@exit /b 0\r\n").unwrap();
+        path
+    }
+
+    #[test]
+    fn installer_runs_from_a_directory_with_spaces_and_non_ascii_characters() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().join("Brukar Ø æ");
+        fs::create_dir(&dir).unwrap();
+        let installer = stub(&dir);
+
+        if let Some(short) = short_path(&installer) {
+            assert!(short.to_str().is_some_and(|short| short.is_ascii() && !short.contains(' ')), "{:?}", short);
+        }
+        assert_eq!(run_with(&installer, &["/quiet", "/norestart"], &[0]).unwrap(), Some(0));
+        assert_eq!(fs::read_to_string(dir.join("ran")).unwrap().trim(), "/quiet /norestart");
+    }
+}
@@ -0,0 +1,92 @@
+use std::{
+    fs::{self, File, OpenOptions, TryLockError},
+    path::PathBuf,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use smol::Timer;
+
+/// How long to wait for another run to finish installing by default.
+const DEFAULT_WAIT: Duration = Duration::from_secs(10 * 60);
+/// How often to check whether the other run has finished.
+const POLL: Duration = Duration::from_secs(1);
+
+static WAIT: OnceLock<Duration> = OnceLock::new();
+static LOCK_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// Waits up to `timeout` for another run installing on this machine, such
+/// as one an app's own installer started, to finish before installing.
+pub fn set_wait(timeout: Duration) {
+    let _ = WAIT.set(timeout);
+}
+
+/// Locks `path` instead of the machine-wide lock file, taking turns only
+/// with runs that do the same, such as tests.
+pub fn set_lock_path(path: PathBuf) {
+    let _ = LOCK_PATH.set(path);
+}
+
+/// The file every run on the machine locks while installing: in ProgramData
+/// on Windows, where the temporary directory is the user's own.
+fn lock_path() -> PathBuf {
+    if let Some(path) = LOCK_PATH.get() {
+        path.clone()
+    } else if cfg!(windows) {
+        let data = std::env::var_os("ProgramData").unwrap_or_else(|| "C:\\ProgramData".into());
+        PathBuf::from(data).join("dotnet5-redist").join("instance.lock")
+    } else {
+        std::env::temp_dir().join("dotnet5-redist.lock")
+    }
+}
+
+/// Takes the machine-wide lock held while installing, waiting for the run
+/// holding it to finish first. It is released when the file is dropped.
+///
+/// If the lock cannot be taken in time, or at all, the run goes ahead
+/// without it, as it would have before; what the other run installed in the
+/// meantime is found installed all the same.
+pub(crate) async fn acquire() -> Result<Option<File>> {
+    let path = lock_path();
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    // Another user may own the file, and a read-only handle locks it just as well.
+    let file = match OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path) {
+        Ok(file) => file,
+        Err(_) => match File::open(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                log::debug!("cannot open {}, installing without it: {}", path.display(), e);
+                return Ok(None);
+            }
+        },
+    };
+
+    let deadline = Instant::now() + *WAIT.get().unwrap_or(&DEFAULT_WAIT);
+    let mut logged = false;
+    loop {
+        match file.try_lock() {
+            Ok(()) => break,
+            Err(TryLockError::WouldBlock) => {}
+            Err(TryLockError::Error(e)) => {
+                log::debug!("cannot lock {}, installing without it: {}", path.display(), e);
+                return Ok(None);
+            }
+        }
+        if Instant::now() >= deadline {
+            log::warn!("another run is still installing; going ahead anyway");
+            return Ok(None);
+        }
+        if !logged {
+            log::warn!("another run is installing; waiting for it to finish");
+            logged = true;
+        }
+        Timer::after(POLL).await;
+    }
+    if logged {
+        log::info!("the other run has finished");
+    }
+    Ok(Some(file))
+}
@@ -0,0 +1,91 @@
+use std::{
+    fs::OpenOptions,
+    io::{self, Write},
+    sync::{Mutex, OnceLock},
+};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::{ProgressEvent, Report};
+
+/// Where the messages for the parent process go, once connected.
+static CHANNEL: OnceLock<Mutex<Box<dyn Write + Send>>> = OnceLock::new();
+static ON_STDOUT: OnceLock<bool> = OnceLock::new();
+
+/// A message to the parent process, told apart by its `type`.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Message<'a> {
+    /// A step of the install or the progress of a download.
+    Progress(&'a ProgressEvent),
+    /// The outcome of the run, sent last.
+    Report(&'a Report),
+}
+
+/// Sends the run's progress and outcome to a parent process showing them in
+/// its own UI, over the named pipe `target` or, given `stdout`, standard output.
+///
+/// Each message is a JSON object prefixed with its length in bytes as a
+/// 32-bit little-endian integer. Its `type` is `progress`, with the event's
+/// fields and its name as `event`, or `report`, with the fields of the
+/// `--output json` report.
+pub fn connect(target: &str) -> Result<()> {
+    let writer: Box<dyn Write + Send> = if target == "stdout" {
+        Box::new(io::stdout())
+    } else {
+        let path = pipe_path(target);
+        Box::new(OpenOptions::new().write(true).open(&path).with_context(|| format!("cannot open {}", path))?)
+    };
+    let _ = CHANNEL.set(Mutex::new(writer));
+    let _ = ON_STDOUT.set(target == "stdout");
+    Ok(())
+}
+
+/// Whether the messages go to standard output, which nothing else may then write to.
+pub fn owns_stdout() -> bool {
+    ON_STDOUT.get().copied().unwrap_or(false)
+}
+
+/// The path of the named pipe called `name`; on other systems, a FIFO at that path.
+fn pipe_path(name: &str) -> String {
+    if cfg!(windows) && !name.starts_with("\\\\") {
+        format!("\\\\.\\pipe\\{}", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// Tells the parent process of a step of the install, if one is listening.
+pub fn progress(event: &ProgressEvent) {
+    send(&Message::Progress(event));
+}
+
+/// Tells the parent process the outcome of the run, if one is listening.
+pub fn report(report: &Report) {
+    send(&Message::Report(report));
+}
+
+/// Writes `message` whole, giving up on the parent if it went away.
+fn send(message: &Message) {
+    let channel = match CHANNEL.get() {
+        Some(channel) => channel,
+        None => return,
+    };
+    let json = match serde_json::to_vec(message) {
+        Ok(json) => json,
+        Err(e) => {
+            log::debug!("cannot serialize a progress message: {}", e);
+            return;
+        }
+    };
+
+    let mut writer = channel.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let written = writer
+        .write_all(&(json.len() as u32).to_le_bytes())
+        .and_then(|()| writer.write_all(&json))
+        .and_then(|()| writer.flush());
+    if let Err(e) = written {
+        log::debug!("cannot send a progress message to the parent process: {}", e);
+    }
+}
@@ -0,0 +1,2226 @@
+use std::{
+    fmt::Display,
+    future::Future,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::Instant,
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use clap::arg_enum;
+use semver::{Identifier, Version};
+
+mod app;
+mod approved;
+mod bundle;
+mod cache;
+pub mod cancel;
+mod catalog;
+mod certs;
+mod chunks;
+mod compat;
+mod conflicts;
+mod container;
+pub mod crash;
+mod credman;
+mod detect;
+mod docker;
+mod doctor;
+mod download;
+mod elevation;
+mod emulation;
+mod environment;
+mod error;
+pub mod exit;
+mod fast_install;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod firewall;
+mod global_json;
+mod global_tool;
+mod hosting;
+mod http;
+pub mod i18n;
+mod inflate;
+mod installer;
+mod instance;
+pub mod ipc;
+pub mod logging;
+mod metadata;
+mod mirrors;
+mod netfx;
+pub mod lock;
+mod os_support;
+mod plan;
+mod platform;
+mod plugin;
+mod pool;
+mod preflight;
+mod progress;
+mod range;
+mod rds;
+mod reboot;
+mod registry;
+mod releases;
+mod remediate;
+mod repair;
+pub mod report;
+mod requirements;
+mod resolve;
+mod sbom;
+mod schannel;
+mod snapshot;
+mod space;
+mod sspi;
+mod sysproxy;
+pub mod telemetry;
+mod temp;
+pub mod timing;
+mod update;
+mod updater;
+mod user;
+mod verify;
+mod winget;
+
+pub use app::{requirements as app_requirements, scan as scan_app_dir};
+pub use approved::load as load_approved_versions;
+pub use bundle::{Bundle, BundledRuntime, BundledVcredist, Manifest};
+pub use cache::clear as clear_cache;
+pub use chunks::set_segments as set_download_segments;
+pub use compat::{Check as CompatCheck, Compatibility, check as check_compatibility};
+pub use catalog::{Entry as CatalogEntry, entries as catalog_entries, set_refresh as set_refresh_catalog, update as update_catalog};
+pub use container::Container;
+pub use detect::{Installed as InstalledRuntime, installed as installed_runtimes, set_machine_wide_only};
+pub use doctor::{Fact as DoctorFact, diagnose};
+pub use elevation::{is_elevated, relaunch_elevated, run_elevated};
+pub use environment::EnvironmentScope;
+pub use error::DotnetRedistError;
+pub use global_json::sdk_version as global_json_sdk_version;
+pub use global_tool::sdk_channel as global_tool_sdk_channel;
+pub use http::{
+    ConnectionLimits, DefaultHttpClient, HttpClient, HttpFuture, IpFamily, RetryPolicy, TlsBackend, set_ca_bundle,
+    set_connection_limits, set_http_client, set_insecure, set_ip_family, set_pinned_certs, set_proxy,
+    set_proxy_credential_target, set_retry_policy, set_timeout, set_tls_backend,
+};
+pub use installer::{
+    ERROR_SUCCESS_REBOOT_INITIATED, ERROR_SUCCESS_REBOOT_REQUIRED, Ui as InstallerUi, set_msi_wait,
+    set_timeout as set_installer_timeout,
+};
+pub use instance::{set_lock_path as set_instance_lock, set_wait as set_instance_wait};
+pub use lock::{LockEntry, Lockfile};
+pub use mirrors::{set_daily_feed, set_feed, set_mirrors, set_nuget_feed};
+pub use plan::{Missing, Plan, PlannedComponent};
+pub use plugin::Plugin;
+pub use progress::Event as ProgressEvent;
+pub use range::VersionRange;
+pub use releases::resolve_channel;
+pub use reboot::PendingReboot;
+pub use report::Report;
+pub use requirements::{ManifestCheck, Requirement, Requirements};
+pub use sbom::write as write_sbom;
+pub use snapshot::{export as export_snapshot, load as load_snapshot};
+pub use temp::set_dir as set_temp_dir;
+pub use update::{Update, available as available_updates};
+pub use updater::{TASK_NAME as UPDATER_TASK_NAME, install as install_updater, remove as remove_updater};
+pub use verify::identify as identify_installer;
+
+use detect::{get_root_install, is_installed, is_installed_in, is_vcruntime_installed, runtime_path, versions_in};
+use download::{DownloadBudget, Downloader};
+use installer::Kind;
+use platform::{is_64bit_os, is_arm64_host, is_server_core};
+use progress::{Event, Listener};
+use report::{Action, Component};
+use timing::Phase;
+use releases::{Artifact, Package};
+use resolve::{resolve_blob, resolve_daily, resolve_installer};
+
+/// A requested version: `major[.minor[.patch]]`, a full version naming a
+/// prerelease such as `6.0.0-rc.2.21480.5`, or a semver range such as
+/// `>=5.0.3, <6`.
+///
+/// Build metadata is accepted but plays no part in matching.
+#[derive(Clone)]
+pub struct DotnetVersion {
+    pub major: u64,
+    pub minor: Option<u64>,
+    pub patch: Option<u64>,
+    /// Prerelease identifiers, only ever set along with the patch.
+    pub pre: Vec<Identifier>,
+    /// The range releases must be in, which replaces the roll forward policy
+    /// in deciding what satisfies the version. The other fields then hold
+    /// the lowest version in it.
+    pub range: Option<VersionRange>,
+}
+
+impl DotnetVersion {
+    /// Whether `version` is a release of this version: every component given
+    /// is the same. Prereleases match only the exact prerelease requested, or
+    /// any when `include_prerelease` is set and none is requested.
+    pub fn matches(&self, version: &Version, include_prerelease: bool) -> bool {
+        if let Some(range) = &self.range {
+            return range.matches(version, include_prerelease);
+        }
+        version.major == self.major
+            && self.minor.is_none_or(|minor| minor == version.minor)
+            && self.patch.is_none_or(|patch| patch == version.patch)
+            && self.accepts_prerelease_of(version, include_prerelease)
+    }
+
+    /// Whether `version` serves this version under `roll_forward`: at least
+    /// the version requested, with missing components taken as zero, and
+    /// within the band the policy may roll forward in.
+    pub fn is_satisfied_by(&self, version: &Version, include_prerelease: bool, roll_forward: RollForward) -> bool {
+        if self.range.is_some() || roll_forward == RollForward::Disable {
+            return self.matches(version, include_prerelease);
+        }
+
+        let minimum = Version {
+            major: self.major,
+            minor: self.minor.unwrap_or(0),
+            patch: self.patch.unwrap_or(0),
+            pre: self.pre.clone(),
+            build: Vec::new(),
+        };
+        let in_band = match roll_forward {
+            RollForward::LatestPatch => version.major == minimum.major && version.minor == minimum.minor,
+            RollForward::Minor | RollForward::LatestMinor => version.major == minimum.major,
+            RollForward::Major | RollForward::Disable => true,
+        };
+        // `Version` ordering ranks prereleases below their release and ignores
+        // build metadata, but a partial version covers the prereleases of the
+        // first release it takes in as well.
+        let at_least = if self.patch.is_none() && include_prerelease {
+            (version.major, version.minor, version.patch) >= (minimum.major, minimum.minor, minimum.patch)
+        } else {
+            *version >= minimum
+        };
+        in_band && at_least && self.accepts_prerelease_of(version, include_prerelease)
+    }
+
+    /// Whether the `major.minor` channel can hold releases satisfying this version under `roll_forward`.
+    pub(crate) fn accepts_channel(&self, major: u64, minor: u64, roll_forward: RollForward) -> bool {
+        if let Some(range) = &self.range {
+            return range.accepts_channel(major, minor);
+        }
+        let lowest = (self.major, self.minor.unwrap_or(0));
+        match roll_forward {
+            RollForward::Disable => major == self.major && self.minor.is_none_or(|m| m == minor),
+            RollForward::LatestPatch => (major, minor) == lowest,
+            RollForward::Minor | RollForward::LatestMinor => major == self.major && (major, minor) >= lowest,
+            RollForward::Major => (major, minor) >= lowest,
+        }
+    }
+
+    /// Picks the release the .NET host would roll forward to from `candidates`.
+    pub(crate) fn select<T>(
+        &self,
+        candidates: impl IntoIterator<Item = (Version, T)>,
+        include_prerelease: bool,
+        roll_forward: RollForward,
+    ) -> Option<(Version, T)> {
+        let candidates = candidates
+            .into_iter()
+            .filter(|(version, _)| self.is_satisfied_by(version, include_prerelease, roll_forward))
+            .collect::<Vec<_>>();
+
+        // Minor and Major stay on the lowest channel that has a release, but
+        // take the newest patch there.
+        let channel = match roll_forward {
+            RollForward::Minor | RollForward::Major => {
+                candidates.iter().map(|(version, _)| (version.major, version.minor)).min()
+            }
+            _ => None,
+        };
+        candidates
+            .into_iter()
+            .filter(|(version, _)| channel.is_none_or(|channel| channel == (version.major, version.minor)))
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+    }
+
+    fn accepts_prerelease_of(&self, version: &Version, include_prerelease: bool) -> bool {
+        if self.pre.is_empty() {
+            version.pre.is_empty() || include_prerelease
+        } else {
+            version.pre.is_empty() || version.pre == self.pre
+        }
+    }
+}
+
+impl Display for DotnetVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(range) = &self.range {
+            return range.fmt(f);
+        }
+        f.write_fmt(format_args!("{}", self.major))?;
+        
+        if let Some(minor) = self.minor {
+            f.write_fmt(format_args!(".{}", minor))?;
+
+            if let Some(patch) = self.patch {
+                f.write_fmt(format_args!(".{}", patch))?;
+            }
+        }
+
+        for (i, identifier) in self.pre.iter().enumerate() {
+            f.write_fmt(format_args!("{}{}", if i == 0 { '-' } else { '.' }, identifier))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for DotnetVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.contains(['<', '>', '=', '~', '^', '*', 'x', 'X', ',', ' ', '|']) {
+            let range = VersionRange::parse(s)?;
+            let lowest = range.lowest();
+            return Ok(DotnetVersion {
+                major: lowest.major,
+                minor: Some(lowest.minor),
+                patch: Some(lowest.patch),
+                pre: lowest.pre,
+                range: Some(range),
+            });
+        }
+        if s.contains(['-', '+']) {
+            let version = Version::parse(s).context("a prerelease needs a full major.minor.patch version")?;
+            return Ok(DotnetVersion {
+                major: version.major,
+                minor: Some(version.minor),
+                patch: Some(version.patch),
+                pre: version.pre,
+                range: None,
+            });
+        }
+
+        let parts = s
+            .split('.')
+            .map(FromStr::from_str)
+            .collect::<Result<Vec<u64>, _>>()?;
+        let version = match *parts.as_slice() {
+            [major] => DotnetVersion {
+                major,
+                minor: None,
+                patch: None,
+                pre: Vec::new(),
+                range: None,
+            },
+            [major, minor] => DotnetVersion {
+                major,
+                minor: Some(minor),
+                patch: None,
+                pre: Vec::new(),
+                range: None,
+            },
+            [major, minor, patch] => DotnetVersion {
+                major,
+                minor: Some(minor),
+                patch: Some(patch),
+                pre: Vec::new(),
+                range: None,
+            },
+            _ => return Err(anyhow!("invalid version number")),
+        };
+
+        Ok(version)
+    }
+}
+
+arg_enum! {
+    /// How far past the requested version a release may be, as in the .NET
+    /// host's `rollForward` setting: LatestPatch stays on the requested minor
+    /// version, Minor and LatestMinor on the requested major version, Major
+    /// goes anywhere newer and Disable takes only the version itself.
+    #[derive(Copy, Clone, PartialEq, Eq)]
+    pub enum RollForward {
+        LatestPatch,
+        Minor,
+        LatestMinor,
+        Major,
+        Disable,
+    }
+}
+
+arg_enum! {
+    /// Which builds a version resolves to: Ga takes releases, Preview takes
+    /// previews and release candidates too, and Daily takes the newest daily
+    /// build of the channel from the daily builds feed.
+    #[derive(Copy, Clone, PartialEq, Eq)]
+    pub enum Quality {
+        Ga,
+        Preview,
+        Daily,
+    }
+}
+
+arg_enum! {
+    #[derive(Copy, Clone, PartialEq, Eq)]
+    pub enum Runtime {
+        Dotnet,
+        AspCore,
+        WindowsDesktop,
+        Sdk,
+        NetFx48,
+        HostingBundle,
+    }
+}
+
+arg_enum! {
+    #[derive(Copy, Clone, PartialEq, Eq)]
+    pub enum Architecture {
+        X86,
+        X64,
+        Arm64,
+    }
+}
+
+impl Architecture {
+    /// The architectures runtimes can be installed for on this machine, the
+    /// native one last: x86 and x64 on 64-bit Windows, and ARM64 too on ARM64
+    /// machines, which run x64 emulated; x86 on 32-bit Windows; and x64
+    /// elsewhere, or x64 and ARM64 there on ARM64.
+    pub fn installable() -> Vec<Architecture> {
+        let mut arches = if !cfg!(windows) {
+            vec![Architecture::X64]
+        } else if is_64bit_os() {
+            vec![Architecture::X86, Architecture::X64]
+        } else {
+            vec![Architecture::X86]
+        };
+        if is_arm64_host() {
+            arches.push(Architecture::Arm64);
+        }
+        arches
+    }
+
+    /// The architecture that suits this machine: ARM64 on ARM64 machines, x64
+    /// on other 64-bit systems and x86 on 32-bit Windows.
+    pub fn native() -> Architecture {
+        Architecture::native_on(is_arm64_host(), is_64bit_os())
+    }
+
+    /// The architecture [`native`](Architecture::native) picks on an ARM64
+    /// machine or another one, which is 64-bit or not.
+    fn native_on(arm64_host: bool, is_64bit: bool) -> Architecture {
+        if arm64_host {
+            Architecture::Arm64
+        } else if cfg!(windows) && !is_64bit {
+            Architecture::X86
+        } else {
+            Architecture::X64
+        }
+    }
+
+    /// Whether runtimes of this architecture run emulated, and so install
+    /// into their own root, on this machine.
+    pub fn is_emulated(self) -> bool {
+        self == Architecture::X64 && is_arm64_host()
+    }
+}
+
+const BASE_URL: &str = "https://dotnetcli.blob.core.windows.net/dotnet";
+const CDN_URL: &str = "https://dotnetcli.azureedge.net/dotnet";
+/// Permanent aliases of the current VC++ 2015-2022 redistributable.
+const VCREDIST_X86_ALIAS: &str = "https://aka.ms/vs/17/release/vc_redist.x86.exe";
+const VCREDIST_X64_ALIAS: &str = "https://aka.ms/vs/17/release/vc_redist.x64.exe";
+const VCREDIST_ARM64_ALIAS: &str = "https://aka.ms/vs/17/release/vc_redist.arm64.exe";
+/// Builds of the redistributable to fall back to when the aliases cannot be followed.
+const VCREDIST_X86_URL: &str = "https://download.visualstudio.microsoft.com/download/pr/8ecb9800-52fd-432d-83ee-d6e037e96cc2/50A3E92ADE4C2D8F310A2812D46322459104039B9DEADBD7FDD483B5C697C0C8/VC_redist.x86.exe";
+const VCREDIST_X64_URL: &str = "https://download.visualstudio.microsoft.com/download/pr/89a3b9df-4a09-492e-8474-8f92c115c51d/B1A32C71A6B7D5978904FB223763263EA5A7EB23B2C44A0D60E90D234AD99178/VC_redist.x64.exe";
+
+/// A validated description of what to install, built with [`InstallRequest::builder`].
+pub struct InstallRequest {
+    version: DotnetVersion,
+    runtimes: Vec<Runtime>,
+    arch: Architecture,
+    global_tool: Option<String>,
+    install_tool: bool,
+    preflight: bool,
+    disk_check: bool,
+    max_download_size: Option<u64>,
+    verify_hash: bool,
+    trusted_publishers: Option<Vec<String>>,
+    remediation_attempts: u32,
+    fix_conflicts: bool,
+    quiet: bool,
+    listener: Option<Listener>,
+    lockfile: Option<Lockfile>,
+    emulated: bool,
+    container: Option<Container>,
+    cache: bool,
+    installer_path: Option<PathBuf>,
+    bundle: Option<Bundle>,
+    plugins: Vec<Plugin>,
+    installer_ui: InstallerUi,
+    lcid: Option<u32>,
+    extra_installer_args: Vec<String>,
+    installer_log_dir: Option<PathBuf>,
+    pending_reboot: PendingReboot,
+    include_prerelease: bool,
+    quality: Quality,
+    blob_version: Option<Version>,
+    product_version: Option<String>,
+    latest_patch: bool,
+    security_only: bool,
+    roll_forward: Option<RollForward>,
+    force: bool,
+    repair: bool,
+    fail_on_vulnerable: bool,
+    vcredist: bool,
+    vcredist_url: Option<String>,
+    fast_install: bool,
+    allow_winget: bool,
+    install_dir: Option<PathBuf>,
+    user: bool,
+    environment: Option<EnvironmentScope>,
+}
+
+#[derive(Default)]
+pub struct InstallRequestBuilder {
+    version: Option<String>,
+    runtimes: Vec<Runtime>,
+    arch: Option<Architecture>,
+    global_tool: Option<String>,
+    install_tool: bool,
+    preflight: bool,
+    skip_disk_check: bool,
+    max_download_size: Option<u64>,
+    no_verify: bool,
+    trusted_publishers: Vec<String>,
+    no_default_publisher: bool,
+    skip_signature_check: bool,
+    remediation_attempts: Option<u32>,
+    fix_conflicts: bool,
+    quiet: bool,
+    listener: Option<Listener>,
+    lockfile: Option<Lockfile>,
+    container: Option<Container>,
+    no_cache: bool,
+    installer_path: Option<PathBuf>,
+    bundle: Option<PathBuf>,
+    plugin_dir: Option<PathBuf>,
+    locale: Option<String>,
+    installer_ui: Option<InstallerUi>,
+    extra_installer_args: Vec<String>,
+    installer_log_dir: Option<PathBuf>,
+    pending_reboot: Option<PendingReboot>,
+    include_prerelease: bool,
+    quality: Option<Quality>,
+    blob_version: Option<String>,
+    product_version: Option<String>,
+    latest_patch: bool,
+    security_only: bool,
+    roll_forward: Option<RollForward>,
+    force: bool,
+    repair: bool,
+    fail_on_vulnerable: bool,
+    skip_vcredist: bool,
+    vcredist_only: bool,
+    vcredist_url: Option<String>,
+    fast_install: bool,
+    allow_winget: bool,
+    install_dir: Option<PathBuf>,
+    user: bool,
+    environment: Option<EnvironmentScope>,
+}
+
+impl InstallRequestBuilder {
+    /// The requested version as `major[.minor[.patch]]`, a full prerelease version, or a semver range such as
+    /// `>=5.0.3, <6`, `~5.0` or `5.0.*`.
+    pub fn version(mut self, version: &str) -> Self {
+        self.version = Some(version.to_string());
+        self
+    }
+
+    /// Adds a runtime to install; runtimes are installed in the order given.
+    pub fn runtime(mut self, runtime: Runtime) -> Self {
+        if !self.runtimes.contains(&runtime) {
+            self.runtimes.push(runtime);
+        }
+        self
+    }
+
+    pub fn arch(mut self, arch: Architecture) -> Self {
+        self.arch = Some(arch);
+        self
+    }
+
+    /// Ensures the SDK band needed by this dotnet global tool package.
+    pub fn global_tool(mut self, package_id: &str) -> Self {
+        self.global_tool = Some(package_id.to_string());
+        self
+    }
+
+    /// Also runs `dotnet tool install -g` for the global tool.
+    pub fn install_tool(mut self, install_tool: bool) -> Self {
+        self.install_tool = install_tool;
+        self
+    }
+
+    /// Checks connectivity to the download hosts before anything else.
+    pub fn preflight(mut self, preflight: bool) -> Self {
+        self.preflight = preflight;
+        self
+    }
+
+    /// Installs even if the temp or install volume seems too full for the
+    /// downloads and what they install.
+    pub fn skip_disk_check(mut self, skip: bool) -> Self {
+        self.skip_disk_check = skip;
+        self
+    }
+
+    /// Caps the number of bytes installers may download.
+    pub fn max_download_size(mut self, bytes: u64) -> Self {
+        self.max_download_size = Some(bytes);
+        self
+    }
+
+    /// Skips SHA512 verification of downloaded installers.
+    pub fn no_verify(mut self, no_verify: bool) -> Self {
+        self.no_verify = no_verify;
+        self
+    }
+
+    /// Accepts `publisher` as an Authenticode signer besides Microsoft.
+    pub fn trusted_publisher(mut self, publisher: &str) -> Self {
+        self.trusted_publishers.push(publisher.to_string());
+        self
+    }
+
+    /// Accepts only the publishers given with `trusted_publisher` as signers,
+    /// not Microsoft as well.
+    pub fn no_default_publisher(mut self, no_default_publisher: bool) -> Self {
+        self.no_default_publisher = no_default_publisher;
+        self
+    }
+
+    /// Runs installers even if their Authenticode signature cannot be verified.
+    pub fn skip_signature_check(mut self, skip: bool) -> Self {
+        self.skip_signature_check = skip;
+        self
+    }
+
+    /// Retries known transient failures, making at most `attempts` attempts.
+    pub fn auto_remediate(mut self, attempts: u32) -> Self {
+        self.remediation_attempts = Some(attempts);
+        self
+    }
+
+    /// Removes conflicting installs found during detection.
+    pub fn fix_conflicts(mut self, fix: bool) -> Self {
+        self.fix_conflicts = fix;
+        self
+    }
+
+    /// Suppresses download progress bars.
+    pub fn quiet(mut self, quiet: bool) -> Self {
+        self.quiet = quiet;
+        self
+    }
+
+    /// Calls `listener` with each step of the install and the progress of
+    /// each download, for frontends to show progress bars by.
+    pub fn on_progress(mut self, listener: impl Fn(&ProgressEvent) + Send + Sync + 'static) -> Self {
+        self.listener = Some(Arc::new(listener));
+        self
+    }
+
+    /// Installs exactly the versions `lockfile` pins instead of resolving them.
+    pub fn locked(mut self, lockfile: Lockfile) -> Self {
+        self.lockfile = Some(lockfile);
+        self
+    }
+
+    /// Overrides container detection, which decides whether the VC++
+    /// redistributable is checked and whether exe installers can run.
+    pub fn container(mut self, container: Container) -> Self {
+        self.container = Some(container);
+        self
+    }
+
+    /// Always downloads installers instead of reusing cached copies.
+    pub fn no_cache(mut self, no_cache: bool) -> Self {
+        self.no_cache = no_cache;
+        self
+    }
+
+    /// Runs this pre-downloaded installer instead of resolving and downloading one.
+    pub fn installer_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.installer_path = Some(path.into());
+        self
+    }
+
+    /// Installs from the offline bundle in `dir` instead of downloading.
+    pub fn bundle(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.bundle = Some(dir.into());
+        self
+    }
+
+    /// Also installs the custom components described by the `*.toml` files in `dir`.
+    pub fn plugin_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.plugin_dir = Some(dir.into());
+        self
+    }
+
+    /// Runs the runtime and .NET Framework installers in this BCP 47
+    /// language, such as `nb-NO`, instead of the system UI language.
+    pub fn locale(mut self, locale: &str) -> Self {
+        self.locale = Some(locale.to_string());
+        self
+    }
+
+    /// Shows the installers' own progress or interactive UI instead of
+    /// running them quietly.
+    pub fn installer_ui(mut self, ui: InstallerUi) -> Self {
+        self.installer_ui = Some(ui);
+        self
+    }
+
+    /// Appends `arg` to the command line of every installer that takes
+    /// switches, after the tool's own.
+    pub fn installer_arg(mut self, arg: &str) -> Self {
+        self.extra_installer_args.push(arg.to_string());
+        self
+    }
+
+    /// Has the runtime installers write their logs into `dir`.
+    pub fn installer_log_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.installer_log_dir = Some(dir.into());
+        self
+    }
+
+    /// Leaves the VC++ redistributable alone, for machines that manage it separately.
+    pub fn skip_vcredist(mut self, skip: bool) -> Self {
+        self.skip_vcredist = skip;
+        self
+    }
+
+    /// Installs only the VC++ redistributable, without any runtime or version.
+    pub fn vcredist_only(mut self) -> Self {
+        self.vcredist_only = true;
+        self
+    }
+
+    /// Downloads the VC++ redistributable from `url` instead of the build
+    /// Microsoft's alias currently points to.
+    pub fn vcredist_url(mut self, url: &str) -> Self {
+        self.vcredist_url = Some(url.to_string());
+        self
+    }
+
+    /// Skips system restore points and rollback while installing, which is
+    /// faster on slow disks but can leave a failed install half done.
+    pub fn fast_install(mut self, fast: bool) -> Self {
+        self.fast_install = fast;
+        self
+    }
+
+    /// Installs a runtime with winget instead, where it is present, when its
+    /// installer cannot be resolved or downloaded.
+    pub fn allow_winget(mut self, allow: bool) -> Self {
+        self.allow_winget = allow;
+        self
+    }
+
+    /// Extracts the runtimes' archives into `dir` for apps to use through
+    /// `DOTNET_ROOT`, instead of installing them for the whole machine.
+    pub fn install_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.install_dir = Some(dir.into());
+        self
+    }
+
+    /// Installs the runtimes for the current user only, which needs no
+    /// elevation, and points the user's `DOTNET_ROOT` at them. The VC++
+    /// redistributable is machine-wide, so it is left alone.
+    pub fn user(mut self, user: bool) -> Self {
+        self.user = user;
+        self
+    }
+
+    /// Makes the runtimes installed to a directory of their own discoverable
+    /// by pointing `DOTNET_ROOT` at it and putting it first on `PATH`, for the
+    /// user or the machine, or by reporting shell commands that do.
+    pub fn environment(mut self, scope: EnvironmentScope) -> Self {
+        self.environment = Some(scope);
+        self
+    }
+
+    /// What to do if Windows is waiting for a reboot; warns by default.
+    pub fn pending_reboot(mut self, policy: PendingReboot) -> Self {
+        self.pending_reboot = Some(policy);
+        self
+    }
+
+    /// Lets previews and release candidates satisfy and resolve the version.
+    pub fn include_prerelease(mut self, include: bool) -> Self {
+        self.include_prerelease = include;
+        self
+    }
+
+    /// Which builds the version resolves to; releases by default. Previews
+    /// and daily builds also satisfy the version once installed.
+    pub fn quality(mut self, quality: Quality) -> Self {
+        self.quality = Some(quality);
+        self
+    }
+
+    /// Takes the installer from the download server's `version` directory
+    /// instead of resolving it, for releases whose metadata is inconsistent.
+    /// Without it, a product version applies to the requested version, which
+    /// must then be given in full.
+    pub fn blob_version(mut self, version: &str) -> Self {
+        self.blob_version = Some(version.to_string());
+        self
+    }
+
+    /// Names the installer's file with `version` instead of looking up the
+    /// product version of the release it is taken from.
+    pub fn product_version(mut self, version: &str) -> Self {
+        self.product_version = Some(version.to_string());
+        self
+    }
+
+    /// Installs the newest release within the requested version even if an
+    /// older one already satisfies it, to pick up security patches.
+    pub fn latest_patch(mut self, latest_patch: bool) -> Self {
+        self.latest_patch = latest_patch;
+        self
+    }
+
+    /// With `latest_patch`, keeps an installed release satisfying the version
+    /// unless a newer release of its channel is flagged as a security release.
+    pub fn security_only(mut self, security_only: bool) -> Self {
+        self.security_only = security_only;
+        self
+    }
+
+    /// Decides which installed releases satisfy the version and which
+    /// release is installed, as the .NET host would roll forward.
+    ///
+    /// Without it, an installed release of the same major version at least
+    /// as new satisfies the version, and the newest release starting with
+    /// the requested version is installed.
+    pub fn roll_forward(mut self, roll_forward: RollForward) -> Self {
+        self.roll_forward = Some(roll_forward);
+        self
+    }
+
+    /// Reinstalls the runtimes even where a release satisfying the version is
+    /// already installed, to repair installs that are there but broken.
+    pub fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Runs the installers of the installed releases matching the version
+    /// again with `/repair`, instead of installing anything new.
+    pub fn repair(mut self, repair: bool) -> Self {
+        self.repair = repair;
+        self
+    }
+
+    /// Replaces installed releases with known security advisories by the
+    /// newest release, and refuses to install such releases, instead of
+    /// warning about them.
+    pub fn fail_on_vulnerable(mut self, fail: bool) -> Self {
+        self.fail_on_vulnerable = fail;
+        self
+    }
+
+    /// Checks the request; failures the builder doesn't classify otherwise
+    /// are `InvalidRequest`.
+    pub fn build(self) -> Result<InstallRequest, DotnetRedistError> {
+        self.try_build()
+            .map_err(|e| DotnetRedistError::classify(e, DotnetRedistError::InvalidRequest))
+    }
+
+    fn try_build(mut self) -> Result<InstallRequest> {
+        let arch = self.arch.context("an architecture is required")?;
+
+        if self.user {
+            if self.install_dir.is_some() {
+                bail!("a per-user install goes into the user's own directory, not a given one");
+            }
+            if self.vcredist_only {
+                bail!("the VC++ redistributable can only be installed for the whole machine");
+            }
+            self.install_dir = Some(user::root(arch)?);
+        }
+
+        if self.repair && self.install_dir.is_some() {
+            bail!("only machine-wide installs can be repaired; pass --force to reinstall into a directory");
+        }
+        if self.repair && (self.installer_path.is_some() || self.bundle.is_some()) {
+            bail!("a repair runs the installed releases' own installers, not given ones");
+        }
+        if self.environment.is_some() && self.install_dir.is_none() {
+            bail!("only runtimes installed to a directory of their own need their environment set");
+        }
+        if self.vcredist_only && self.skip_vcredist {
+            bail!("the VC++ redistributable cannot be both skipped and the only thing installed");
+        }
+        if self.vcredist_only && !cfg!(windows) {
+            bail!(DotnetRedistError::UnsupportedPlatform(anyhow!("the VC++ redistributable is only needed on Windows")));
+        }
+        // Without runtimes nothing is resolved, so the version is never looked at.
+        let version = match (self.version, self.vcredist_only) {
+            (Some(_), true) => bail!("installing only the VC++ redistributable takes no version"),
+            (None, true) => DotnetVersion { major: 0, minor: None, patch: None, pre: Vec::new(), range: None },
+            (version, false) => version.context("a version is required")?.parse().context("invalid version")?,
+        };
+
+        // Global tools are installed through the SDK, which also carries the matching runtime.
+        let runtimes = match (self.runtimes.is_empty(), &self.global_tool) {
+            (true, None) if self.vcredist_only => Vec::new(),
+            _ if self.vcredist_only => bail!("installing only the VC++ redistributable takes no runtime or global tool"),
+            (false, Some(_)) => bail!("a runtime cannot be combined with a global tool"),
+            (false, None) => self.runtimes,
+            (true, Some(_)) => vec![Runtime::Sdk],
+            (true, None) => bail!("a runtime or global tool is required"),
+        };
+
+        if !cfg!(windows) {
+            if let Some(runtime) = runtimes.iter().find(|&&runtime| matches!(runtime, Runtime::WindowsDesktop | Runtime::NetFx48 | Runtime::HostingBundle)) {
+                bail!(DotnetRedistError::UnsupportedPlatform(anyhow!("{} is only available on Windows", runtime)));
+            }
+        }
+
+        if self.install_dir.is_some() {
+            if self.installer_path.is_some() || self.bundle.is_some() {
+                bail!("an install directory is filled from archives, not from an installer or bundle");
+            }
+            if runtimes.contains(&Runtime::NetFx48) {
+                bail!("the .NET Framework can only be installed for the whole machine");
+            }
+            if runtimes.contains(&Runtime::HostingBundle) {
+                bail!("the hosting bundle can only be installed for the whole machine");
+            }
+        }
+
+        let quality = self.quality.unwrap_or(Quality::Ga);
+        if quality == Quality::Daily && runtimes.contains(&Runtime::NetFx48) {
+            bail!("the .NET Framework has no daily builds");
+        }
+
+        if self.installer_path.is_some() && runtimes.len() > 1 {
+            bail!("an installer path can only stand in for a single runtime");
+        }
+
+        let blob_version = match (&self.blob_version, &self.product_version, &version) {
+            (Some(blob_version), _, _) => Some(Version::parse(blob_version).context("invalid blob version")?),
+            (None, Some(_), DotnetVersion { major, minor: Some(minor), patch: Some(patch), pre, range: None }) => {
+                Some(Version { pre: pre.clone(), ..Version::new(*major, *minor, *patch) })
+            }
+            (None, Some(_), _) => bail!("a product version needs the full version it is for, or a blob version"),
+            (None, None, _) => None,
+        };
+        if blob_version.is_some() {
+            if runtimes.len() > 1 || runtimes.contains(&Runtime::NetFx48) {
+                bail!("a blob or product version can only stand in for resolving a single .NET runtime");
+            }
+            if self.installer_path.is_some() || self.bundle.is_some() || self.lockfile.is_some() || quality == Quality::Daily {
+                bail!("a blob or product version only applies to installers resolved from the download server");
+            }
+        }
+
+        if self.install_tool && self.global_tool.is_none() {
+            bail!("installing a tool requires a global tool package id");
+        }
+
+        if self.remediation_attempts == Some(0) {
+            bail!("at least one remediation attempt is required");
+        }
+
+        let bundle = match self.bundle {
+            Some(dir) => {
+                if !dir.join(bundle::MANIFEST_NAME).exists() {
+                    bail!("{} is not a bundle directory", dir.display());
+                }
+                Some(Bundle::open(dir)?)
+            }
+            None => None,
+        };
+
+        let lcid = self.locale.as_deref().map(installer::locale_lcid).transpose()?;
+
+        let plugins = match &self.plugin_dir {
+            Some(dir) => plugin::load_dir(dir)?,
+            None => Vec::new(),
+        };
+
+        let trusted_publishers = if self.skip_signature_check {
+            None
+        } else if self.no_default_publisher {
+            if self.trusted_publishers.is_empty() {
+                bail!("without Microsoft as a trusted publisher, another one is needed");
+            }
+            Some(self.trusted_publishers)
+        } else {
+            let microsoft = "Microsoft Corporation";
+            let others = self.trusted_publishers.into_iter().filter(|publisher| publisher != microsoft);
+            Some(std::iter::once(microsoft.to_string()).chain(others).collect())
+        };
+
+        // The Framework doesn't use the VC++ runtime, and only Windows has it.
+        let vcredist = cfg!(windows)
+            && !self.skip_vcredist
+            && !self.user
+            && (self.vcredist_only || runtimes.iter().any(|&runtime| runtime != Runtime::NetFx48));
+
+        Ok(InstallRequest {
+            version,
+            runtimes,
+            arch,
+            global_tool: self.global_tool,
+            install_tool: self.install_tool,
+            preflight: self.preflight,
+            disk_check: !self.skip_disk_check,
+            max_download_size: self.max_download_size,
+            verify_hash: !self.no_verify,
+            trusted_publishers,
+            remediation_attempts: self.remediation_attempts.unwrap_or(1),
+            fix_conflicts: self.fix_conflicts,
+            quiet: self.quiet,
+            listener: self.listener,
+            lockfile: self.lockfile,
+            emulated: arch.is_emulated(),
+            container: self.container,
+            cache: !self.no_cache,
+            installer_path: self.installer_path,
+            bundle,
+            plugins,
+            installer_ui: self.installer_ui.unwrap_or(InstallerUi::Quiet),
+            lcid,
+            extra_installer_args: self.extra_installer_args,
+            installer_log_dir: self.installer_log_dir,
+            pending_reboot: self.pending_reboot.unwrap_or(PendingReboot::Warn),
+            include_prerelease: self.include_prerelease || quality != Quality::Ga,
+            quality,
+            blob_version,
+            product_version: self.product_version,
+            latest_patch: self.latest_patch,
+            security_only: self.security_only,
+            roll_forward: self.roll_forward,
+            force: self.force,
+            repair: self.repair,
+            fail_on_vulnerable: self.fail_on_vulnerable,
+            vcredist,
+            vcredist_url: self.vcredist_url,
+            fast_install: self.fast_install,
+            allow_winget: self.allow_winget,
+            install_dir: self.install_dir,
+            user: self.user,
+            environment: self.environment,
+        })
+    }
+}
+
+impl InstallRequest {
+    pub fn builder() -> InstallRequestBuilder {
+        InstallRequestBuilder::default()
+    }
+
+    /// Resolves the installers this request would download, for recording in
+    /// a lockfile, or the ones its lockfile pins.
+    pub async fn lock(&self) -> Result<Vec<LockEntry>, DotnetRedistError> {
+        let mut entries = Vec::new();
+        for &runtime in &self.runtimes {
+            let artifact = self.artifact(runtime).await?;
+            let (runtime, arch, version) = lock::key(runtime, self.arch, &self.version);
+
+            entries.push(LockEntry {
+                runtime,
+                arch,
+                version,
+                resolved_version: artifact.version.to_string(),
+                url: artifact.url,
+                hash: artifact.hash,
+            });
+        }
+
+        Ok(entries)
+    }
+
+    /// Renders Dockerfile lines installing this request's runtimes into a Windows container image.
+    pub async fn dockerfile(&self, container: Container) -> Result<String, DotnetRedistError> {
+        let package = match container {
+            Container::NanoServer => Package::Zip,
+            Container::ServerCore | Container::None => Package::Exe,
+        };
+        if self.runtimes.contains(&Runtime::NetFx48) {
+            return Err(DotnetRedistError::UnsupportedPlatform(anyhow!(
+                ".NET Framework images come with it; base the image on mcr.microsoft.com/dotnet/framework/runtime:4.8 instead"
+            )));
+        }
+
+        let mut artifacts = Vec::new();
+        for &runtime in &self.runtimes {
+            artifacts.push(self.resolve(runtime, package).await?);
+        }
+
+        Ok(docker::render(&artifacts, self.arch, container))
+    }
+
+    /// Detects which components are missing, appending them to `missing`.
+    /// Nothing is resolved or downloaded, so a version that is satisfied
+    /// by an older patch counts as present even with `latest_patch`.
+    pub async fn missing(&self, missing: &mut Vec<Missing>) -> Result<(), DotnetRedistError> {
+        let mut push = |name: String, version: Option<String>| {
+            let component = Missing { name, arch: self.arch.to_string(), version };
+            if !missing.contains(&component) {
+                missing.push(component);
+            }
+        };
+
+        if self.vcredist && self.container() == Container::None && !is_vcruntime_installed(self.arch) {
+            push("vcredist".into(), None);
+        }
+        for &runtime in &self.runtimes {
+            if !self.is_present(runtime, &self.version).await? {
+                push(runtime.to_string().to_lowercase(), Some(self.version.to_string()));
+            }
+        }
+        for plugin in &self.plugins {
+            if !plugin.is_installed()? {
+                push(plugin.name.clone(), None);
+            }
+        }
+        Ok(())
+    }
+
+    /// Plans each of `requests` as [`plan`](InstallRequest::plan) does, at
+    /// the same time, appending their components to `plan` in order.
+    pub async fn plan_all(requests: &[InstallRequest], plan: &mut Plan) -> Result<(), DotnetRedistError> {
+        let planned = join_all(requests.iter().map(|request| async move {
+            let mut plan = Plan::default();
+            request.plan(&mut plan).await.map(|()| plan)
+        }))
+        .await;
+        for request_plan in planned {
+            for component in request_plan?.components {
+                plan.push(component);
+            }
+        }
+        Ok(())
+    }
+
+    /// Works out which components are missing and estimates the download size,
+    /// installed size and install time of each, appending them to `plan`.
+    pub async fn plan(&self, plan: &mut Plan) -> Result<(), DotnetRedistError> {
+        let container = self.container();
+        if self.vcredist && container == Container::None && !is_vcruntime_installed(self.arch) {
+            let (source, download_bytes, package_bytes) = match &self.bundle {
+                Some(bundle) => {
+                    let bundled = bundle
+                        .vcredist(self.arch)
+                        .with_context(|| format!("the bundle has no {} VC++ redistributable", self.arch))?;
+                    let path = bundle.path(&bundled.file)?;
+                    (path.display().to_string(), Some(0), local_size(&path))
+                }
+                None => {
+                    let url = self.vcredist_url().await;
+                    let size = http::content_length(&url).await.ok().flatten();
+                    (url, size, size)
+                }
+            };
+            let (installed_bytes, duration_secs) = plan::estimate(None, package_bytes);
+            plan.push(PlannedComponent {
+                name: "vcredist".into(),
+                arch: self.arch.to_string(),
+                version: None,
+                source: Some(source),
+                download_bytes,
+                installed_bytes,
+                duration_secs,
+                machine_wide: true,
+            });
+        }
+
+        // Each runtime's metadata is fetched at the same time as the others'.
+        let planned = join_all(self.runtimes.iter().map(|&runtime| self.plan_runtime(runtime))).await;
+        for components in planned {
+            for component in components? {
+                plan.push(component);
+            }
+        }
+
+        for plugin in &self.plugins {
+            if plugin.is_installed()? {
+                continue;
+            }
+
+            let size = http::content_length(&plugin.install.url).await.ok().flatten();
+            let (installed_bytes, duration_secs) = plan::estimate(None, size);
+            plan.push(PlannedComponent {
+                name: plugin.name.clone(),
+                arch: self.arch.to_string(),
+                version: None,
+                source: Some(plugin.install.url.clone()),
+                download_bytes: size,
+                installed_bytes,
+                duration_secs,
+                machine_wide: true,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// The components planned for `runtime`: its missing release, or the
+    /// registered releases a repair runs again.
+    async fn plan_runtime(&self, runtime: Runtime) -> Result<Vec<PlannedComponent>> {
+        if self.repair {
+            let mut components = Vec::new();
+            for registered in self.registered(runtime) {
+                let (_, duration_secs) = plan::estimate(Some(runtime), None);
+                components.push(PlannedComponent {
+                    name: runtime.to_string().to_lowercase(),
+                    arch: self.arch.to_string(),
+                    version: Some(registered.version.to_string()),
+                    source: registered.cached.as_ref().map(|path| path.display().to_string()),
+                    download_bytes: registered.cached.is_some().then_some(0),
+                    installed_bytes: None,
+                    duration_secs,
+                    machine_wide: true,
+                });
+            }
+            return Ok(components);
+        }
+        if self.installed_along(runtime).is_some() {
+            return Ok(Vec::new());
+        }
+        let wanted = self.wanted_version(runtime).await?;
+        if !self.force && self.is_present(runtime, &wanted).await? {
+            return Ok(Vec::new());
+        }
+
+        let (version, source, download_bytes, package_bytes) = match (&self.installer_path, &self.bundle) {
+            (Some(path), _) => (None, path.display().to_string(), Some(0), local_size(path)),
+            (None, Some(bundle)) => {
+                let bundled = bundle.runtime(runtime, self.arch, &self.version).with_context(|| {
+                    format!("the bundle has no {} {} {}", runtime.to_string().to_lowercase(), self.arch, self.version)
+                })?;
+                let path = bundle.path(&bundled.file)?;
+                let size = local_size(&path);
+                (Some(bundled.entry.resolved_version.clone()), path.display().to_string(), Some(0), size)
+            }
+            (None, None) => match self.artifact(runtime).await {
+                Ok(artifact) => {
+                    let size = http::content_length(&artifact.url).await.ok().flatten();
+                    (Some(artifact.version.to_string()), artifact.url, size, size)
+                }
+                Err(e) => match self.winget_package(runtime, None).filter(|_| is_network(&e)) {
+                    Some(id) => (None, format!("winget {}", id), None, None),
+                    None => return Err(e),
+                },
+            },
+        };
+        let (installed_bytes, duration_secs) = plan::estimate(Some(runtime), package_bytes);
+        Ok(vec![PlannedComponent {
+            name: runtime.to_string().to_lowercase(),
+            arch: self.arch.to_string(),
+            version: version.or_else(|| Some(self.version.to_string())),
+            source: Some(source),
+            download_bytes,
+            installed_bytes,
+            duration_secs,
+            machine_wide: self.install_dir.is_none(),
+        }])
+    }
+
+    /// Installs whatever is missing, appending what happened to `report`.
+    ///
+    /// Runs installing on the same machine take turns, so whatever the run
+    /// before installed is found present instead of installed twice.
+    pub async fn install(&self, report: &mut Report) -> Result<(), DotnetRedistError> {
+        let _instance = cancel::watch(instance::acquire()).await?;
+        let recorded = report.components.len();
+        let mut attempt = 1;
+        loop {
+            report.components.truncate(recorded);
+            let e = match cancel::watch(self.install_once(report)).await {
+                Ok(()) => {
+                    report.reboot_required |= report.components[recorded..]
+                        .iter()
+                        .any(|component| component.exit_code.is_some_and(installer::requires_reboot));
+                    return Ok(());
+                }
+                Err(e) => e,
+            };
+
+            if attempt >= self.remediation_attempts {
+                return Err(e.into());
+            }
+
+            let failure = match remediate::Failure::classify(&e) {
+                Some(failure) => failure,
+                None => return Err(e.into()),
+            };
+
+            log::warn!("attempt {}/{} failed: {:#}", attempt, self.remediation_attempts, e);
+            failure.remediate(attempt).await;
+            attempt += 1;
+        }
+    }
+
+    /// Uninstalls the installed releases of the runtimes matching a
+    /// `major.minor` version except the newest, which apps rolling forward
+    /// on patches load instead, taking turns with runs installing.
+    pub async fn cleanup(&self, report: &mut Report) -> Result<(), DotnetRedistError> {
+        let _instance = cancel::watch(instance::acquire()).await?;
+        Ok(cancel::watch(self.cleanup_once(report)).await?)
+    }
+
+    async fn cleanup_once(&self, report: &mut Report) -> Result<()> {
+        if self.version.minor.is_none() || self.version.patch.is_some() || self.version.range.is_some() {
+            bail!(DotnetRedistError::InvalidRequest(anyhow!(
+                "cleaning up takes a major.minor version such as 8.0, not {}",
+                self.version
+            )));
+        }
+        if !cfg!(windows) || self.install_dir.is_some() {
+            bail!(DotnetRedistError::UnsupportedPlatform(anyhow!(
+                "only runtimes the Windows installers installed can be cleaned up"
+            )));
+        }
+
+        for &runtime in &self.runtimes {
+            let mut registered = self.registered(runtime).into_iter();
+            let newest = match registered.next() {
+                Some(newest) => newest,
+                None => continue,
+            };
+            for superseded in registered {
+                log::info!("uninstalling {}, superseded by {}", superseded.name, newest.version);
+                let component = self.uninstalled_component(runtime, &superseded.version, report);
+                self.uninstall_bundle(component, &superseded).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Uninstalls the installed releases of the runtimes matching the
+    /// version: from the install directory if there is one, or else those
+    /// the Windows installers installed. Takes turns with runs installing.
+    pub async fn uninstall(&self, report: &mut Report) -> Result<(), DotnetRedistError> {
+        let _instance = cancel::watch(instance::acquire()).await?;
+        Ok(cancel::watch(self.uninstall_once(report)).await?)
+    }
+
+    async fn uninstall_once(&self, report: &mut Report) -> Result<()> {
+        if self.install_dir.is_none() && !cfg!(windows) {
+            bail!(DotnetRedistError::UnsupportedPlatform(anyhow!(
+                "only runtimes in an install directory or installed by the Windows installers can be uninstalled"
+            )));
+        }
+
+        for &runtime in &self.runtimes {
+            let found = match &self.install_dir {
+                Some(dir) => {
+                    let versions = versions_in(dir, runtime)
+                        .into_iter()
+                        .filter(|version| self.version.matches(version, self.include_prerelease))
+                        .collect::<Vec<_>>();
+                    for version in &versions {
+                        let path = dir.join(runtime_path(runtime).unwrap()).join(version.to_string());
+                        log::info!("removing {}", path.display());
+                        let component = self.uninstalled_component(runtime, version, report);
+                        component.url = Some(path.display().to_string());
+                        let remove = async { std::fs::remove_dir_all(&path).map(|()| None).map_err(Into::into) };
+                        self.track(component, remove).await?;
+                        component.action = Action::Uninstalled;
+                    }
+                    !versions.is_empty()
+                }
+                None => {
+                    let registered = self.registered(runtime);
+                    for bundle in &registered {
+                        log::info!("uninstalling {}", bundle.name);
+                        let component = self.uninstalled_component(runtime, &bundle.version, report);
+                        self.uninstall_bundle(component, bundle).await?;
+                    }
+                    !registered.is_empty()
+                }
+            };
+            if !found {
+                log::warn!("no installed {} {} {} to uninstall", runtime, self.arch, self.version);
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the report entry for uninstalling `version` of the runtime.
+    fn uninstalled_component<'a>(&self, runtime: Runtime, version: &Version, report: &'a mut Report) -> &'a mut Component {
+        report.components.push(Component {
+            requested_version: Some(self.version.to_string()),
+            detected: true,
+            resolved_version: Some(version.to_string()),
+            ..Component::new(runtime.to_string().to_lowercase(), self.arch)
+        });
+        report.components.last_mut().unwrap()
+    }
+
+    /// Runs the cached copy of an installed bundle with `/uninstall`, which
+    /// is how Burn uninstalls it; skipped with a warning if the copy is gone.
+    async fn uninstall_bundle(&self, component: &mut Component, bundle: &repair::Registered) -> Result<()> {
+        let path = match &bundle.cached {
+            Some(path) => path,
+            None => {
+                log::warn!("cannot uninstall {}: its installer is gone from the package cache", bundle.name);
+                return Ok(());
+            }
+        };
+        component.url = Some(path.display().to_string());
+        let uninstall = async { installer::run(path, Kind::Burn, self.installer_ui, &["/uninstall".to_string()]) };
+        self.track(component, uninstall).await?;
+        component.action = Action::Uninstalled;
+        Ok(())
+    }
+
+    /// Fails before downloading anything if the temp volume cannot take the
+    /// installers or the install volume what they install, by the plan's estimates.
+    fn check_disk_space(&self, plan: &Plan) -> Result<()> {
+        let (machine_wide, own_dir): (Vec<_>, Vec<_>) = plan.components.iter().partition(|c| c.machine_wide);
+
+        // Windows Installer also keeps a copy of each machine-wide package.
+        let system_bytes = machine_wide
+            .iter()
+            .map(|c| c.installed_bytes.unwrap_or(0) + c.download_bytes.unwrap_or(0))
+            .sum();
+        let mut needs = vec![
+            (temp::dir(), plan.download_bytes),
+            (get_root_install(self.arch).to_path_buf(), system_bytes),
+        ];
+        if let Some(dir) = &self.install_dir {
+            needs.push((dir.clone(), own_dir.iter().map(|c| c.installed_bytes.unwrap_or(0)).sum()));
+        }
+        space::check(&needs)
+    }
+
+    async fn install_once(&self, report: &mut Report) -> Result<()> {
+        let downloader = Downloader {
+            budget: DownloadBudget::new(self.max_download_size),
+            publishers: self.trusted_publishers.as_deref(),
+            progress: !self.quiet,
+            listener: self.listener.clone(),
+            cache: self.cache,
+            ui: self.installer_ui,
+            lcid: self.lcid,
+            extra_args: self.extra_installer_args.clone(),
+            log_dir: self.installer_log_dir.clone(),
+            repair: Default::default(),
+            prefetched: Default::default(),
+            fetched: Default::default(),
+        };
+        let recorded = report.components.len();
+        let result = self.install_with(report, &downloader).await;
+        downloader.record(&mut report.components[recorded..]);
+        result
+    }
+
+    async fn install_with(&self, report: &mut Report, downloader: &Downloader<'_>) -> Result<()> {
+        if self.arch == Architecture::X64 && !is_64bit_os() {
+            bail!(DotnetRedistError::UnsupportedPlatform(anyhow!("Cannot install 64-bit dotnet on 32-bit windows")));
+        }
+        if self.arch == Architecture::Arm64 && !is_arm64_host() {
+            bail!(DotnetRedistError::UnsupportedPlatform(anyhow!("Cannot install ARM64 dotnet on a machine that is not ARM64")));
+        }
+        if !self.runtimes.is_empty() {
+            emulation::check(&self.version, self.emulated)?;
+            if self.runtimes.iter().any(|&runtime| runtime != Runtime::NetFx48) {
+                os_support::check(self.version.major)?;
+            }
+            if self.runtimes.contains(&Runtime::WindowsDesktop) && is_server_core() {
+                bail!(DotnetRedistError::UnsupportedPlatform(anyhow!(
+                    "the Windows Desktop runtime needs the Desktop Experience, which Server Core lacks; \
+                     install the dotnet runtime instead for console apps and services, or aspcore for web apps"
+                )));
+            }
+        }
+
+        if self.preflight {
+            let vcredist_sources = match (&self.vcredist_url, self.arch) {
+                _ if !self.vcredist => Vec::new(),
+                (Some(url), _) => vec![url.as_str()],
+                (None, Architecture::X86) => vec![VCREDIST_X86_ALIAS, VCREDIST_X86_URL],
+                (None, Architecture::X64) => vec![VCREDIST_X64_ALIAS, VCREDIST_X64_URL],
+                (None, Architecture::Arm64) => vec![VCREDIST_ARM64_ALIAS],
+            };
+            preflight::run(&[mirrors::endpoints(), vcredist_sources].concat()).await?;
+        }
+        if self.disk_check || downloader.budget.remaining().is_some() {
+            let mut plan = Plan::default();
+            self.plan(&mut plan).await?;
+            // releases.json sizes, so an oversized run fails before its first transfer.
+            downloader.budget.check(plan.download_bytes)?;
+            if self.disk_check {
+                self.check_disk_space(&plan)?;
+            }
+        }
+
+        let conflicts = conflicts::find();
+        for conflict in &conflicts {
+            log::warn!("conflicting install {}", conflict);
+        }
+        report.conflicts = conflicts.iter().map(ToString::to_string).collect();
+        if self.fix_conflicts {
+            conflicts::fix(&conflicts)?;
+        }
+
+        reboot::check(self.pending_reboot)?;
+        let _install_mode = rds::InstallMode::enter();
+        let _fast_install = self.fast_install.then(fast_install::FastInstall::enter);
+
+        let container = self.container();
+
+        if self.vcredist {
+            self.prefetch(container, downloader).await;
+            self.install_vcredist(container, report, downloader).await?;
+        } else if self.user && cfg!(windows) && container == Container::None && !is_vcruntime_installed(self.arch) {
+            log::warn!("the VC++ redistributable is missing and only an administrator can install it; apps may not start");
+        } else if cfg!(windows) {
+            log::info!("skipping the VC++ redistributable");
+        }
+
+        for &runtime in &self.runtimes {
+            if self.repair {
+                self.repair_runtime(runtime, report, downloader).await?;
+                continue;
+            }
+            if let Some(other) = self.installed_along(runtime) {
+                log::info!("{} {} is installed along with {}", runtime, self.arch, other);
+                continue;
+            }
+            let wanted = self.wanted_version(runtime).await?;
+            let mut installed = self.is_present(runtime, &wanted).await?;
+            log::debug!("{} {} {} installed: {}", runtime, self.arch, wanted, installed);
+            if let Some(version) = self.installed_version(runtime, &wanted).filter(|_| installed) {
+                if let Some(vulnerability) = self.vulnerability(runtime, &version).await {
+                    let message = vulnerability_message(runtime, &version, &vulnerability);
+                    if self.fail_on_vulnerable {
+                        log::warn!("{}; replacing it", message);
+                        installed = false;
+                    } else {
+                        log::warn!("{}; pass --latest-patch to install the fix", message);
+                    }
+                }
+            }
+            report.components.push(Component {
+                requested_version: Some(self.version.to_string()),
+                detected: installed,
+                ..Component::new(runtime.to_string().to_lowercase(), self.arch)
+            });
+            if installed && !self.force {
+                continue;
+            }
+            if installed {
+                log::warn!(
+                    "{} {} {} is already installed in {}; reinstalling it over the installed files",
+                    runtime,
+                    self.arch,
+                    wanted,
+                    self.install_root().display()
+                );
+            }
+            downloader.repair.set(installed);
+            if runtime == Runtime::HostingBundle && container == Container::None && !hosting::is_iis_installed() {
+                log::warn!(
+                    "IIS is not installed, so the hosting bundle leaves out the ASP.NET Core Module; \
+                     install IIS first, then run this again to add it"
+                );
+            }
+
+            let component = report.components.last_mut().unwrap();
+            if container == Container::NanoServer && (self.installer_path.is_some() || self.bundle.is_some()) {
+                bail!(DotnetRedistError::UnsupportedPlatform(anyhow!(
+                    "{} {} is missing, but exe installers cannot run on nanoserver; \
+                     drop --installer-path and --bundle to install it from the zip layout",
+                    component.name,
+                    self.version
+                )));
+            }
+
+            match (&self.installer_path, &self.bundle) {
+                (Some(path), _) => {
+                    component.url = Some(path.display().to_string());
+                    self.track(component, downloader.install_file(path, None, Kind::of(runtime))).await?;
+                }
+                (None, Some(bundle)) => self.install_bundled(runtime, bundle, component, downloader).await?,
+                (None, None) => match self.download_runtime(runtime, component, downloader).await {
+                    Err(e) if is_network(&e) => self.install_with_winget(runtime, component, e).await?,
+                    result => result?,
+                },
+            }
+        }
+        downloader.repair.set(false);
+        match (&self.install_dir, self.environment) {
+            (Some(dir), Some(scope)) => report.environment.extend(environment::configure(scope, self.arch, dir)?),
+            (Some(dir), None) if self.user => user::register(self.arch, dir)?,
+            _ => {}
+        }
+        if self.emulated {
+            emulation::register()?;
+        }
+
+        for plugin in &self.plugins {
+            let installed = plugin.is_installed()?;
+            report.components.push(Component {
+                detected: installed,
+                url: Some(plugin.install.url.clone()),
+                hash: plugin.install.sha512.clone(),
+                ..Component::new(plugin.name.clone(), self.arch)
+            });
+            if !installed {
+                let component = report.components.last_mut().unwrap();
+                self.track(component, install_plugin(plugin, downloader)).await?;
+            }
+        }
+
+        if let (Some(package_id), true) = (&self.global_tool, self.install_tool) {
+            installer::install_global_tool(self.arch, package_id)?;
+        }
+
+        Ok(())
+    }
+
+    fn container(&self) -> Container {
+        self.container.unwrap_or_else(container::detect)
+    }
+
+    /// Where runtimes are extracted from archives instead of installed: the
+    /// requested directory, or the machine-wide root in Nano Server
+    /// containers, which cannot run the exe installers.
+    fn archive_dir(&self) -> Option<PathBuf> {
+        match (&self.install_dir, self.container()) {
+            (Some(dir), _) => Some(dir.clone()),
+            (None, Container::NanoServer) => Some(get_root_install(self.arch).to_path_buf()),
+            (None, _) => None,
+        }
+    }
+
+    /// Where the runtimes are installed: the requested directory or the machine-wide root.
+    fn install_root(&self) -> &Path {
+        self.install_dir.as_deref().unwrap_or_else(|| get_root_install(self.arch))
+    }
+
+    fn package(&self) -> Package {
+        if self.archive_dir().is_some() {
+            Package::archive()
+        } else {
+            Package::installer()
+        }
+    }
+
+    /// Whether the runtime is installed where this request installs it.
+    async fn is_present(&self, runtime: Runtime, wanted: &DotnetVersion) -> Result<bool> {
+        let roll_forward = self.detect_roll_forward();
+        let present = match &self.install_dir {
+            Some(dir) => is_installed_in(dir, runtime, wanted, self.include_prerelease, roll_forward).await?,
+            None => is_installed(self.arch, runtime, wanted, self.include_prerelease, roll_forward).await?,
+        };
+        if present || runtime != Runtime::Dotnet {
+            return Ok(present);
+        }
+
+        for other in [Runtime::WindowsDesktop, Runtime::AspCore] {
+            if self.brings_dotnet(other) && is_installed(self.arch, other, wanted, self.include_prerelease, roll_forward).await? {
+                log::debug!("{} {} {} comes with the installed {}", runtime, self.arch, wanted, other);
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Whether installing `runtime` also installs the .NET runtime of the
+    /// same version, as the Windows Desktop and ASP.NET Core installers do.
+    /// Their archives hold only their own framework.
+    fn brings_dotnet(&self, runtime: Runtime) -> bool {
+        cfg!(windows)
+            && self.install_dir.is_none()
+            && self.container() != Container::NanoServer
+            && matches!(runtime, Runtime::WindowsDesktop | Runtime::AspCore | Runtime::HostingBundle)
+    }
+
+    /// The other runtime of this request whose installer installs `runtime`
+    /// along with itself, so that it needs no install of its own.
+    fn installed_along(&self, runtime: Runtime) -> Option<Runtime> {
+        if runtime != Runtime::Dotnet || self.repair {
+            return None;
+        }
+        self.runtimes.iter().copied().find(|&other| self.brings_dotnet(other))
+    }
+
+    fn detect_roll_forward(&self) -> RollForward {
+        // Only the pinned version itself satisfies a lockfile.
+        if self.lockfile.is_some() {
+            return RollForward::Disable;
+        }
+        self.roll_forward.unwrap_or(RollForward::Minor)
+    }
+
+    /// Resolving by prefix alone, without an explicit policy, is Disable
+    /// applied to however much of the version was given.
+    fn resolve_roll_forward(&self) -> RollForward {
+        self.roll_forward.unwrap_or(RollForward::Disable)
+    }
+
+    /// The version detection has to find: the requested one, the one the
+    /// lockfile pins, or with `latest_patch` the newest release within it,
+    /// when that can be resolved and, with `security_only`, fixes advisories
+    /// the installed release has.
+    async fn wanted_version(&self, runtime: Runtime) -> Result<DotnetVersion> {
+        if (!self.latest_patch && self.lockfile.is_none()) || self.installer_path.is_some() || self.bundle.is_some() {
+            return Ok(self.version.clone());
+        }
+        if self.security_only && self.lockfile.is_none() {
+            if let Some(installed) = self.installed_version(runtime, &self.version) {
+                if self.vulnerability(runtime, &installed).await.is_none() {
+                    log::info!("{} {} {} has no newer security release; keeping it", runtime, self.arch, installed);
+                    return Ok(self.version.clone());
+                }
+            }
+        }
+
+        let artifact = self.artifact(runtime).await?;
+        Ok(DotnetVersion {
+            major: artifact.version.major,
+            minor: Some(artifact.version.minor),
+            patch: Some(artifact.version.patch),
+            pre: artifact.version.pre,
+            range: None,
+        })
+    }
+
+    /// The `package` of `runtime` the requested version resolves to, from the
+    /// daily builds feed if daily builds were asked for.
+    async fn resolve(&self, runtime: Runtime, package: Package) -> Result<Artifact> {
+        if let Some(blob_version) = &self.blob_version {
+            return resolve_blob(runtime, self.arch, blob_version, self.product_version.as_deref(), package).await;
+        }
+        let roll_forward = self.resolve_roll_forward();
+        match self.quality {
+            Quality::Daily => resolve_daily(runtime, self.arch, &self.version, package, roll_forward).await,
+            Quality::Ga | Quality::Preview => {
+                resolve_installer(runtime, self.arch, &self.version, package, self.include_prerelease, roll_forward).await
+            }
+        }
+    }
+
+    /// The installer of `runtime`: the one the lockfile pins, or else the
+    /// one the requested version resolves to.
+    async fn artifact(&self, runtime: Runtime) -> Result<Artifact> {
+        let name = format!("{} {} {}", runtime.to_string().to_lowercase(), self.arch, self.version);
+        let _span = timing::span(Phase::Resolve, name);
+        let lockfile = match &self.lockfile {
+            Some(lockfile) => lockfile,
+            None => return self.resolve(runtime, self.package()).await,
+        };
+
+        let locked = lockfile.find(runtime, self.arch, &self.version).with_context(|| {
+            format!("{} {} {} is not in the lockfile", runtime.to_string().to_lowercase(), self.arch, self.version)
+        })?;
+        Ok(Artifact {
+            version: Version::parse(&locked.resolved_version)
+                .with_context(|| format!("invalid version {} in the lockfile", locked.resolved_version))?,
+            url: locked.url.clone(),
+            hash: locked.hash.clone(),
+        })
+    }
+
+    /// The VC++ redistributable installer: the one asked for, else the build
+    /// Microsoft's alias points to, else the pinned build, or for ARM64, which
+    /// has none pinned, the alias itself.
+    async fn vcredist_url(&self) -> String {
+        if let Some(url) = &self.vcredist_url {
+            return url.clone();
+        }
+
+        let (alias, pinned) = match self.arch {
+            Architecture::X86 => (VCREDIST_X86_ALIAS, Some(VCREDIST_X86_URL)),
+            Architecture::X64 => (VCREDIST_X64_ALIAS, Some(VCREDIST_X64_URL)),
+            Architecture::Arm64 => (VCREDIST_ARM64_ALIAS, None),
+        };
+        match (http::final_url(alias).await, pinned) {
+            (Ok(url), _) => {
+                log::debug!("{} points to {}", alias, url);
+                url
+            }
+            (Err(e), Some(pinned)) => {
+                log::warn!("cannot follow {}: {:#}, using the pinned VC++ redistributable", alias, e);
+                pinned.to_string()
+            }
+            (Err(e), None) => {
+                log::warn!("cannot follow {}: {:#}", alias, e);
+                alias.to_string()
+            }
+        }
+    }
+
+    /// Installs the VC++ redistributable unless a recent enough one is present.
+    async fn install_vcredist(&self, container: Container, report: &mut Report, downloader: &Downloader<'_>) -> Result<()> {
+        // Container images don't ship the redistributable in the usual places
+        // and their runtimes don't need it, so the check would only mislead.
+        let vcredist_installed = container == Container::None && is_vcruntime_installed(self.arch);
+        report.components.push(Component {
+            detected: vcredist_installed,
+            ..Component::new("vcredist", self.arch)
+        });
+        if container != Container::None {
+            log::info!("skipping the VC++ redistributable in a {} container", container);
+        } else if !vcredist_installed {
+            let component = report.components.last_mut().unwrap();
+            match &self.bundle {
+                Some(bundle) => {
+                    let bundled = bundle
+                        .vcredist(self.arch)
+                        .with_context(|| format!("the bundle has no {} VC++ redistributable", self.arch))?;
+                    let path = bundle.path(&bundled.file)?;
+                    component.url = Some(path.display().to_string());
+                    self.track(component, downloader.install_file(&path, None, Kind::Burn)).await?;
+                }
+                None => {
+                    let url = self.vcredist_url().await;
+                    component.url = Some(url.clone());
+                    self.track(component, downloader.install(&url, None, Kind::Burn)).await?
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Verifies and runs the runtime's installer from an offline bundle.
+    async fn install_bundled(
+        &self,
+        runtime: Runtime,
+        bundle: &Bundle,
+        component: &mut Component,
+        downloader: &Downloader<'_>,
+    ) -> Result<()> {
+        let bundled = bundle
+            .runtime(runtime, self.arch, &self.version)
+            .with_context(|| format!("the bundle has no {} {} {}", component.name, self.arch, self.version))?;
+        let path = bundle.path(&bundled.file)?;
+
+        component.resolved_version = Some(bundled.entry.resolved_version.clone());
+        component.url = Some(path.display().to_string());
+        component.hash = bundled.entry.hash.clone();
+
+        let sha512 = if self.verify_hash {
+            bundled.entry.hash.as_deref()
+        } else {
+            None
+        };
+        self.track(component, downloader.install_file(&path, sha512, Kind::of(runtime))).await
+    }
+
+    /// Downloads the runtimes' installers and the VC++ redistributable into
+    /// `bundle` for installing offline later.
+    pub async fn bundle_into(&self, bundle: &mut Bundle) -> Result<(), DotnetRedistError> {
+        let downloader = Downloader {
+            budget: DownloadBudget::new(self.max_download_size),
+            publishers: None,
+            progress: !self.quiet,
+            listener: self.listener.clone(),
+            cache: self.cache,
+            ui: self.installer_ui,
+            lcid: self.lcid,
+            extra_args: self.extra_installer_args.clone(),
+            log_dir: None,
+            repair: Default::default(),
+            prefetched: Default::default(),
+            fetched: Default::default(),
+        };
+        std::fs::create_dir_all(&bundle.dir).with_context(|| format!("could not create {}", bundle.dir.display()))?;
+
+        if cfg!(windows) && bundle.vcredist(self.arch).is_none() {
+            let url = self.vcredist_url().await;
+            let file = bundle::file_name(&url).to_string();
+            downloader.fetch(&url, None, &bundle.dir.join(&file)).await?;
+            bundle.manifest.vcredists.push(BundledVcredist {
+                arch: self.arch.to_string().to_lowercase(),
+                file,
+            });
+        }
+
+        for entry in self.lock().await? {
+            let file = bundle::file_name(&entry.url).to_string();
+            downloader
+                .fetch(&entry.url, entry.hash.as_deref(), &bundle.dir.join(&file))
+                .await?;
+            log::info!("bundled {} {} {} as {}", entry.runtime, entry.arch, entry.resolved_version, file);
+
+            let (runtime, arch, version) = (entry.runtime.clone(), entry.arch.clone(), entry.version.clone());
+            bundle.manifest.runtimes.retain(|bundled| {
+                bundled.entry.runtime != runtime || bundled.entry.arch != arch || bundled.entry.version != version
+            });
+            bundle.manifest.runtimes.push(BundledRuntime { entry, file });
+        }
+        Ok(bundle.save()?)
+    }
+
+    /// The hash a runtime's download is verified against, if any.
+    fn sha512<'a>(&self, runtime: Runtime, artifact: &'a Artifact) -> Result<Option<&'a str>> {
+        Ok(match (&artifact.hash, self.verify_hash) {
+            (_, false) => None,
+            (Some(hash), true) => Some(hash.as_str()),
+            // Nothing publishes a hash for the Framework installer; its signature is still checked.
+            (None, true) if runtime == Runtime::NetFx48 && self.trusted_publishers.is_some() => None,
+            (None, true) => bail!(DotnetRedistError::VerificationFailed(anyhow!(
+                "no published SHA512 hash for {}; pass --no-verify to install it unverified",
+                artifact.url
+            ))),
+        })
+    }
+
+    /// Downloads the VC++ redistributable and the missing runtimes at the
+    /// same time, ahead of installing them one after the other. Failures
+    /// are left for the installs to hit again and report.
+    async fn prefetch(&self, container: Container, downloader: &Downloader<'_>) {
+        if container != Container::None || self.bundle.is_some() || is_vcruntime_installed(self.arch) {
+            return;
+        }
+
+        let vcredist = async {
+            let url = self.vcredist_url().await;
+            // The runtimes' bar is the one worth watching; this is the smaller download.
+            if let Err(e) = downloader.prefetch(&url, None, Package::Exe, false).await {
+                log::debug!("could not download the VC++ redistributable ahead: {:#}", e);
+            }
+        };
+        let runtimes = async {
+            if self.installer_path.is_some() || self.repair {
+                return;
+            }
+            for &runtime in &self.runtimes {
+                let prefetched = async {
+                    let wanted = self.wanted_version(runtime).await?;
+                    if !self.force && self.is_present(runtime, &wanted).await? {
+                        return Ok(());
+                    }
+                    let artifact = self.artifact(runtime).await?;
+                    downloader.prefetch(&artifact.url, self.sha512(runtime, &artifact)?, self.package(), downloader.progress).await
+                };
+                if let Err(e) = prefetched.await {
+                    log::debug!("could not download {} ahead: {:#}", runtime, e);
+                }
+            }
+        };
+        smol::future::zip(vcredist, runtimes).await;
+    }
+
+    /// Resolves the runtime's installer, then downloads, verifies and runs it.
+    async fn download_runtime(
+        &self,
+        runtime: Runtime,
+        component: &mut Component,
+        downloader: &Downloader<'_>,
+    ) -> Result<()> {
+        self.emit(Event::ResolvingVersion { component: component.name.clone() });
+        let artifact = self.artifact(runtime).await?;
+        if let Some(vulnerability) = self.vulnerability(runtime, &artifact.version).await {
+            let message = vulnerability_message(runtime, &artifact.version, &vulnerability);
+            if self.fail_on_vulnerable {
+                bail!(DotnetRedistError::Vulnerable(anyhow!("{}; refusing to install it", message)));
+            }
+            log::warn!("{}", message);
+        }
+        logging::record(serde_json::json!({
+            "event": "resolved",
+            "component": component.name,
+            "version": artifact.version.to_string(),
+            "url": artifact.url,
+        }));
+        component.resolved_version = Some(artifact.version.to_string());
+        component.url = Some(artifact.url.clone());
+        component.hash = artifact.hash.clone();
+
+        let sha512 = self.sha512(runtime, &artifact)?;
+        match self.archive_dir() {
+            Some(dir) => {
+                self.track(component, downloader.extract(&artifact.url, sha512, &dir)).await?;
+                if self.install_dir.is_some() && !self.user && self.environment.is_none() {
+                    log::info!("extracted {} into {}; point DOTNET_ROOT there for apps to use it", component.name, dir.display());
+                }
+                Ok(())
+            }
+            None => self.track(component, downloader.install(&artifact.url, sha512, Kind::of(runtime))).await,
+        }
+    }
+
+    /// The winget package of `runtime` at `version`, or else the requested
+    /// version, if winget may install it and is there to.
+    fn winget_package(&self, runtime: Runtime, version: Option<&Version>) -> Option<String> {
+        if !self.allow_winget || self.archive_dir().is_some() {
+            return None;
+        }
+        let (major, minor) = match version {
+            Some(version) => (version.major, version.minor),
+            None => (self.version.major, self.version.minor.unwrap_or(0)),
+        };
+        winget::package_id(runtime, major, minor).filter(|_| winget::is_available())
+    }
+
+    /// Installs `runtime` with winget after resolving or downloading its
+    /// installer failed with `error`, which is returned if winget isn't there.
+    async fn install_with_winget(&self, runtime: Runtime, component: &mut Component, error: anyhow::Error) -> Result<()> {
+        let version = component.resolved_version.as_deref().and_then(|version| Version::parse(version).ok());
+        let id = match self.winget_package(runtime, version.as_ref()) {
+            Some(id) => id,
+            None => return Err(error),
+        };
+        log::warn!("{:#}; installing {} with winget instead", error, id);
+        self.track(component, async { winget::install(&id, self.arch, version.as_ref()) }).await
+    }
+
+    /// The installed bundles of the runtime matching the version, newest first.
+    fn registered(&self, runtime: Runtime) -> Vec<repair::Registered> {
+        repair::registered(runtime, self.arch)
+            .into_iter()
+            .filter(|registered| self.version.matches(&registered.version, self.include_prerelease))
+            .collect()
+    }
+
+    /// Runs the installers of the installed releases of the runtime matching
+    /// the version again with `/repair`, from Burn's package cache or else
+    /// downloaded again.
+    async fn repair_runtime(&self, runtime: Runtime, report: &mut Report, downloader: &Downloader<'_>) -> Result<()> {
+        let name = runtime.to_string().to_lowercase();
+        if !cfg!(windows) || runtime == Runtime::NetFx48 {
+            bail!(DotnetRedistError::UnsupportedPlatform(anyhow!(
+                "only runtimes the Windows installers installed can be repaired; pass --force to reinstall {}",
+                name
+            )));
+        }
+        let registered = self.registered(runtime);
+        if registered.is_empty() {
+            bail!(DotnetRedistError::VersionNotFound(anyhow!(
+                "no installed {} {} {} to repair",
+                name,
+                self.arch,
+                self.version
+            )));
+        }
+
+        downloader.repair.set(true);
+        for registered in registered {
+            log::info!("repairing {}", registered.name);
+            report.components.push(Component {
+                requested_version: Some(self.version.to_string()),
+                detected: true,
+                resolved_version: Some(registered.version.to_string()),
+                ..Component::new(name.clone(), self.arch)
+            });
+            let component = report.components.last_mut().unwrap();
+            match &registered.cached {
+                Some(path) => {
+                    component.url = Some(path.display().to_string());
+                    self.track(component, downloader.install_file(path, None, Kind::Burn)).await?;
+                }
+                None => {
+                    log::info!("{} is gone from the package cache; downloading it again", registered.name);
+                    let version = registered.version.to_string().parse()?;
+                    let artifact =
+                        resolve_installer(runtime, self.arch, &version, Package::installer(), true, RollForward::Disable)
+                            .await?;
+                    component.url = Some(artifact.url.clone());
+                    component.hash = artifact.hash.clone();
+                    let sha512 = self.sha512(runtime, &artifact)?;
+                    self.track(component, downloader.install(&artifact.url, sha512, Kind::Burn)).await?;
+                }
+            }
+            if matches!(component.action, Action::Installed) {
+                component.action = Action::Repaired;
+            }
+        }
+        downloader.repair.set(false);
+        Ok(())
+    }
+
+    /// The newest release in the install root satisfying `wanted`, if detection found it there.
+    fn installed_version(&self, runtime: Runtime, wanted: &DotnetVersion) -> Option<Version> {
+        let roll_forward = self.detect_roll_forward();
+        versions_in(self.install_root(), runtime)
+            .into_iter()
+            .filter(|version| wanted.is_satisfied_by(version, self.include_prerelease, roll_forward))
+            .max()
+    }
+
+    /// The known security advisories affecting `version` of the runtime;
+    /// `None` as well if the release metadata cannot be had.
+    async fn vulnerability(&self, runtime: Runtime, version: &Version) -> Option<releases::Vulnerability> {
+        match releases::vulnerability(runtime, version).await {
+            Ok(vulnerability) => vulnerability,
+            Err(e) => {
+                log::debug!("cannot check {} {} for security advisories: {:#}", runtime, version, e);
+                None
+            }
+        }
+    }
+
+    /// Runs a component's installation, recording its outcome, exit code and duration.
+    async fn track(&self, component: &mut Component, install: impl Future<Output = Result<Option<i32>>>) -> Result<()> {
+        self.emit(Event::Installing { component: component.name.clone() });
+        // Stays so if cancelling drops the installation before it finishes.
+        component.action = Action::Cancelled;
+        let start = Instant::now();
+        let result = install.await;
+        component.duration_ms = Some(start.elapsed().as_millis() as u64);
+
+        match result {
+            Ok(exit_code) => {
+                component.action = Action::Installed;
+                component.exit_code = exit_code;
+                self.emit(Event::Done { component: component.name.clone() });
+                Ok(())
+            }
+            Err(e) if e.is::<cancel::Cancelled>() => Err(e),
+            Err(e) => {
+                component.action = Action::Failed;
+                component.exit_code = e.downcast_ref::<installer::Failed>().map(|failed| failed.0);
+                Err(e)
+            }
+        }
+    }
+
+    fn emit(&self, event: Event) {
+        logging::record(serde_json::to_value(&event).unwrap_or_default());
+        if let Some(listener) = &self.listener {
+            listener(&event);
+        }
+    }
+}
+
+fn vulnerability_message(runtime: Runtime, version: &Version, vulnerability: &releases::Vulnerability) -> String {
+    format!(
+        "{} {} has known vulnerabilities ({}), fixed in {}",
+        runtime.to_string().to_lowercase(),
+        version,
+        vulnerability.cves.join(", "),
+        vulnerability.patched
+    )
+}
+
+/// Runs `futures` at the same time, returning their outputs in order.
+pub(crate) async fn join_all<T>(futures: impl IntoIterator<Item = impl Future<Output = T>>) -> Vec<T> {
+    let executor = smol::LocalExecutor::new();
+    let tasks = futures.into_iter().map(|future| executor.spawn(future)).collect::<Vec<_>>();
+    executor
+        .run(async {
+            let mut outputs = Vec::new();
+            for task in tasks {
+                outputs.push(task.await);
+            }
+            outputs
+        })
+        .await
+}
+
+/// Whether `error` is a failure to reach a server or get an answer from it.
+fn is_network(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| matches!(cause.downcast_ref(), Some(DotnetRedistError::Network(_))))
+}
+
+fn local_size(path: &Path) -> Option<u64> {
+    std::fs::metadata(path).ok().map(|metadata| metadata.len())
+}
+
+async fn install_plugin(plugin: &Plugin, downloader: &Downloader<'_>) -> Result<Option<i32>> {
+    let dir = temp::create()?;
+    let path = downloader
+        .download_verified(&plugin.install.url, plugin.install.sha512.as_deref(), dir.path())
+        .await?;
+    installer::run_with(&path, &plugin.install.args, &plugin.install.success_codes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(s: &str) -> Version {
+        Version::parse(s).unwrap()
+    }
+
+    fn requested(s: &str) -> DotnetVersion {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn auto_arch_is_the_machines_own() {
+        assert_eq!(Architecture::native_on(true, true).to_string(), "Arm64");
+        assert_eq!(Architecture::native_on(false, true).to_string(), "X64");
+        let thirty_two_bit = if cfg!(windows) { "X86" } else { "X64" };
+        assert_eq!(Architecture::native_on(false, false).to_string(), thirty_two_bit);
+    }
+
+    #[test]
+    fn arm64_installers_are_named_for_it() {
+        assert_eq!(releases::file_name(Runtime::Dotnet, Architecture::Arm64, Package::Exe), "dotnet-runtime-win-arm64.exe");
+        assert_eq!(releases::file_name(Runtime::Sdk, Architecture::Arm64, Package::Zip), "dotnet-sdk-win-arm64.zip");
+    }
+
+    #[test]
+    fn partial_versions_leave_the_rest_open() {
+        let major = requested("6");
+        assert_eq!((major.major, major.minor, major.patch), (6, None, None));
+        assert!(major.matches(&version("6.0.36"), false));
+        assert!(major.matches(&version("6.1.0"), false));
+        assert!(!major.matches(&version("7.0.0"), false));
+
+        let minor = requested("6.0");
+        assert_eq!((minor.major, minor.minor, minor.patch), (6, Some(0), None));
+        assert!(minor.matches(&version("6.0.36"), false));
+        assert!(!minor.matches(&version("6.1.0"), false));
+    }
+
+    #[test]
+    fn prerelease_needs_a_full_version() {
+        let rc = requested("6.0.0-rc");
+        assert_eq!((rc.major, rc.minor, rc.patch), (6, Some(0), Some(0)));
+        assert_eq!(rc.pre, [Identifier::AlphaNumeric("rc".to_owned())]);
+        assert_eq!(rc.to_string(), "6.0.0-rc");
+        assert!("6.0-rc".parse::<DotnetVersion>().is_err());
+    }
+
+    #[test]
+    fn prereleases_are_only_included_on_request() {
+        let rc = version("6.0.0-rc.2.21480.5");
+        assert!(!requested("6.0").matches(&rc, false));
+        assert!(requested("6.0").matches(&rc, true));
+        assert!(!requested("6.0").is_satisfied_by(&rc, false, RollForward::LatestPatch));
+        assert!(requested("6.0").is_satisfied_by(&rc, true, RollForward::LatestPatch));
+    }
+
+    #[test]
+    fn requested_prerelease_matches_itself_and_its_release() {
+        let requested = requested("6.0.0-rc.2.21480.5");
+        assert!(requested.matches(&version("6.0.0-rc.2.21480.5"), false));
+        assert!(requested.matches(&version("6.0.0"), false));
+        assert!(!requested.matches(&version("6.0.0-rc.1.21451.13"), false));
+    }
+
+    #[test]
+    fn prereleases_order_below_their_release() {
+        assert!(version("6.0.0-rc.2.21480.5") < version("6.0.0"));
+        assert!(version("6.0.0-rc.1.21451.13") < version("6.0.0-rc.2.21480.5"));
+
+        let rc = requested("6.0.0-rc.2.21480.5");
+        assert!(rc.is_satisfied_by(&version("6.0.0"), false, RollForward::LatestPatch));
+        assert!(!requested("6.0.0").is_satisfied_by(&version("6.0.0-rc.2.21480.5"), true, RollForward::LatestPatch));
+
+        let candidates = ["6.0.0-rc.2.21480.5", "6.0.0", "6.0.1"].iter().map(|v| (version(v), *v));
+        assert_eq!(rc.select(candidates, false, RollForward::LatestPatch).map(|(_, v)| v), Some("6.0.1"));
+    }
+
+    #[test]
+    fn build_metadata_plays_no_part() {
+        let requested = requested("6.0.0-rc.2+build.7");
+        assert!(requested.matches(&version("6.0.0-rc.2+other"), false));
+        assert!(requested.matches(&version("6.0.0-rc.2"), false));
+        assert!(self::requested("6.0").matches(&version("6.0.1+abc"), false));
+        assert!(self::requested("6.0.1").is_satisfied_by(&version("6.0.1+abc"), false, RollForward::LatestPatch));
+    }
+}
@@ -0,0 +1,103 @@
+use std::{
+    fs,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{Architecture, DotnetVersion, Runtime, report};
+
+/// Default location of the lockfile, relative to the working directory.
+pub const DEFAULT_PATH: &str = "dotnet-redist.lock";
+
+/// Resolved installers pinned for reproducible installs.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Lockfile {
+    /// The version of the tool that last recorded what it installed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_version: Option<String>,
+    /// When that was, in seconds since the Unix epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub installed_at: Option<u64>,
+    pub entries: Vec<LockEntry>,
+}
+
+/// What one requirement resolved to when it was locked.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockEntry {
+    pub runtime: String,
+    pub arch: String,
+    pub version: String,
+    pub resolved_version: String,
+    pub url: String,
+    pub hash: Option<String>,
+}
+
+impl Lockfile {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents =
+            fs::read_to_string(path).with_context(|| format!("cannot read lockfile {}", path.display()))?;
+        serde_json::from_str(&contents).with_context(|| format!("invalid lockfile {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let contents = serde_json::to_string_pretty(self)?;
+        fs::write(path, contents + "\n").with_context(|| format!("cannot write lockfile {}", path.display()))
+    }
+
+    /// Returns the entry for a requirement, if it has been locked.
+    pub fn find(&self, runtime: Runtime, arch: Architecture, version: &DotnetVersion) -> Option<&LockEntry> {
+        let (runtime, arch, version) = key(runtime, arch, version);
+        self.entries
+            .iter()
+            .find(|entry| entry.runtime == runtime && entry.arch == arch && entry.version == version)
+    }
+
+    /// Adds `entry`, replacing any previous entry for the same requirement.
+    pub fn insert(&mut self, entry: LockEntry) {
+        self.entries.retain(|existing| {
+            existing.runtime != entry.runtime || existing.arch != entry.arch || existing.version != entry.version
+        });
+        self.entries.push(entry);
+    }
+
+    /// Pins the runtimes `report` says were installed, stamped with this
+    /// tool's version and the time, and returns how many there were.
+    pub fn record(&mut self, report: &report::Report) -> usize {
+        let installed = report.components.iter().filter(|component| {
+            matches!(component.action, report::Action::Installed) && component.requested_version.is_some()
+        });
+
+        let mut count = 0;
+        for component in installed {
+            if let (Some(version), Some(resolved_version), Some(url)) =
+                (&component.requested_version, &component.resolved_version, &component.url)
+            {
+                self.insert(LockEntry {
+                    runtime: component.name.clone(),
+                    arch: component.arch.to_lowercase(),
+                    version: version.clone(),
+                    resolved_version: resolved_version.clone(),
+                    url: url.clone(),
+                    hash: component.hash.clone(),
+                });
+                count += 1;
+            }
+        }
+
+        self.tool_version = Some(env!("CARGO_PKG_VERSION").to_string());
+        self.installed_at = SystemTime::now().duration_since(UNIX_EPOCH).ok().map(|elapsed| elapsed.as_secs());
+        count
+    }
+}
+
+/// The runtime, architecture and requested version identifying a lock entry.
+pub(crate) fn key(runtime: Runtime, arch: Architecture, version: &DotnetVersion) -> (String, String, String) {
+    (
+        runtime.to_string().to_lowercase(),
+        arch.to_string().to_lowercase(),
+        version.to_string(),
+    )
+}
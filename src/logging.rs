@@ -0,0 +1,156 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    sync::{Mutex, OnceLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use clap::arg_enum;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde_json::{Map, Value};
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+arg_enum! {
+    /// How the log file is written: Text for people to read, or Json, one
+    /// object per line for log collectors, with the `timestamp`, `level` and
+    /// `target` and either the `message` or, for the steps of an install,
+    /// the `event` and its fields.
+    #[derive(Copy, Clone, PartialEq, Eq)]
+    pub enum Format {
+        Text,
+        Json,
+    }
+}
+
+/// Prints warnings and progress to stderr, with debug detail under
+/// `--verbose`, and records everything in the log file if there is one.
+struct Logger {
+    verbose: bool,
+    format: Format,
+    file: Option<Mutex<File>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // Dependencies are only interesting for their warnings.
+        metadata.level() <= Level::Warn || metadata.target().starts_with(env!("CARGO_CRATE_NAME"))
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        if record.level() <= Level::Info || self.verbose {
+            match record.level() {
+                Level::Error => eprintln!("error: {}", record.args()),
+                Level::Warn => eprintln!("warning: {}", record.args()),
+                Level::Info => eprintln!("{}", record.args()),
+                Level::Debug | Level::Trace => eprintln!("debug: {}", record.args()),
+            }
+        }
+
+        if let Some(file) = &self.file {
+            let elapsed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+            let mut file = file.lock().unwrap();
+            let _ = match self.format {
+                Format::Text => writeln!(
+                    file,
+                    "[{}.{:03}] {:5} {}: {}",
+                    elapsed.as_secs(),
+                    elapsed.subsec_millis(),
+                    record.level(),
+                    record.target(),
+                    record.args()
+                ),
+                Format::Json => {
+                    let mut fields = Map::new();
+                    fields.insert("message".into(), record.args().to_string().into());
+                    writeln!(file, "{}", json_line(record.level(), record.target(), fields))
+                }
+            };
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            let _ = file.lock().unwrap().flush();
+        }
+    }
+}
+
+/// Routes the `log` records of this run to stderr and, if given, `log_file`,
+/// which is appended to in `format`.
+pub fn init(verbose: bool, log_file: Option<&Path>, format: Format) -> Result<()> {
+    let file = match log_file {
+        Some(path) => Some(Mutex::new(
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("cannot open log file {}", path.display()))?,
+        )),
+        None => None,
+    };
+    let max_level = if verbose || file.is_some() {
+        LevelFilter::Debug
+    } else {
+        LevelFilter::Info
+    };
+
+    if LOGGER.set(Logger { verbose, format, file }).is_err() {
+        anyhow::bail!("logging is set up already");
+    }
+    log::set_logger(LOGGER.get().unwrap())?;
+    log::set_max_level(max_level);
+    Ok(())
+}
+
+/// Records a step of the install, such as a download or an installer's exit
+/// code, with `fields` as its own JSON fields in a JSON log file; `fields`
+/// is an object naming the step as its `event`. Text logs get the step from
+/// the messages about it.
+pub(crate) fn record(fields: Value) {
+    let logger = match LOGGER.get() {
+        Some(logger) if logger.format == Format::Json => logger,
+        _ => return,
+    };
+    let (file, fields) = match (&logger.file, fields) {
+        (Some(file), Value::Object(fields)) => (file, fields),
+        _ => return,
+    };
+    let line = json_line(Level::Info, concat!(env!("CARGO_CRATE_NAME"), "::step"), fields);
+    let _ = writeln!(file.lock().unwrap(), "{}", line);
+}
+
+fn json_line(level: Level, target: &str, fields: Map<String, Value>) -> Value {
+    let elapsed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let mut line = Map::new();
+    line.insert("timestamp".into(), (elapsed.as_millis() as f64 / 1000.0).into());
+    line.insert("level".into(), level.to_string().into());
+    line.insert("target".into(), target.into());
+    line.extend(fields);
+    Value::Object(line)
+}
+
+/// Hides the console window Windows opened for this run, as when a bundle
+/// starts the tool, so that nothing flashes up; a console shared with a
+/// shell or another program is left alone.
+pub fn hide_console() {
+    #[cfg(windows)]
+    unsafe {
+        use winapi::um::{
+            wincon::{GetConsoleProcessList, GetConsoleWindow},
+            winuser::{SW_HIDE, ShowWindow},
+        };
+
+        let window = GetConsoleWindow();
+        let mut processes = [0; 2];
+        if !window.is_null() && GetConsoleProcessList(processes.as_mut_ptr(), processes.len() as u32) == 1 {
+            ShowWindow(window, SW_HIDE);
+        }
+    }
+}
@@ -1,303 +1,2134 @@
-use std::{fmt::Display, path::Path, process::Command, str::FromStr};
+use std::{
+    ffi::OsString,
+    io::{IsTerminal, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 
-use anyhow::{anyhow, bail};
-use anyhow::{Error, Result};
+use anyhow::{Result, anyhow, bail};
 use clap::arg_enum;
-use http_types::StatusCode;
-use semver::{Version, VersionReq};
-use smol::{fs::File, prelude::*};
+use dotnet5_webinst::{
+    Architecture, Bundle, ConnectionLimits, Container, DotnetRedistError, ERROR_SUCCESS_REBOOT_INITIATED, ERROR_SUCCESS_REBOOT_REQUIRED,
+    EnvironmentScope, InstallRequest, InstallRequestBuilder, InstallerUi, IpFamily, Lockfile, ManifestCheck, Missing, PendingReboot, Plan,
+    ProgressEvent, Quality, Report, Requirements, RetryPolicy, RollForward, Runtime, TlsBackend, UPDATER_TASK_NAME, cancel, exit, i18n::{self, tr}, ipc, lock, logging::Format as LogFormat, report,
+};
+use http_types::Url;
 use structopt::StructOpt;
-use tempfile::tempdir;
 
-mod http;
+/// Windows Installer code for an install the user cancelled.
+const ERROR_INSTALL_USEREXIT: i32 = 1602;
+/// Windows Installer code for an install that failed.
+const ERROR_INSTALL_FAILURE: i32 = 1603;
 
+/// Failure codes a Burn bundle reports as they are, rather than as a plain
+/// fatal error: a user exit, a fatal error, another install in progress,
+/// a package policy refusal, an unsupported platform and a newer version
+/// already installed.
+const BURN_FAILURE_CODES: &[i32] = &[1602, 1603, 1618, 1625, 1633, 1638];
+
+/// `check` exit code when a runtime is missing.
+const CHECK_RUNTIME_MISSING: i32 = 2;
+/// `check` exit code when only the VC++ redistributable is missing.
+const CHECK_VCREDIST_MISSING: i32 = 3;
+
+// Parsed once, so the size of the install arguments doesn't matter.
+#[allow(clippy::large_enum_variant)]
 #[derive(StructOpt)]
-struct Arg {
+enum Command {
+    /// Install the requested runtimes unless they are already present (the default)
+    ///
+    /// Exits with a failing installer's exit code, 3010 if a reboot is needed to finish, or 70 if the tool crashed;
+    /// --exit-codes stable exits with a code for each class of failure instead.
+    Install(InstallArg),
+    /// Detect whether the requested runtimes are present and print what would be installed, without installing anything
+    ///
+    /// Exits with 0 if nothing is missing, 2 if a runtime is missing, or 3 if only the VC++ redistributable is.
+    Check(CheckArg),
+    /// List the installed runtimes and SDKs with their versions and where they are
+    List(ListArg),
+    /// Resolve the requested runtime and pin its version, URL and hash in a lockfile
+    Lock(LockArg),
+    /// Print the version and URL the requested runtime resolves to, optionally asserting the version
+    Resolve(ResolveArg),
+    /// Download installers for every combination of the given runtimes, architectures and versions into a directory for offline installs
+    Bundle(BundleArg),
+    /// Identify an installer or archive by its SHA512 hash, and fail unless it is one Microsoft published
+    Verify(VerifyArg),
+    /// Manage the local installer cache
+    Cache(CacheCommand),
+    /// Manage the catalog of known releases used to resolve versions without fetching metadata
+    Catalog(CatalogCommand),
+    /// Record the runtimes and SDKs installed here, or install those a recorded machine has and this one lacks
+    Snapshot(SnapshotCommand),
+    /// Print Dockerfile lines that install the requested runtime into a Windows container image
+    GenerateDocker(GenerateDockerArg),
+    /// Diagnose the machine and network for support requests
+    Doctor(DoctorArg),
+    /// Report whether an app will start on this machine, and what is missing if not
+    Compat(CompatArg),
+    /// Keep the requested runtimes on the newest patch of their version, checking again at every interval
+    Ensure(EnsureArg),
+    /// Install only the VC++ redistributable the runtimes need
+    Vcredist(VcredistArg),
+    /// Repair the installed releases of the requested runtimes by running their installers again, as install --repair
+    Repair(InstallArg),
+    /// Uninstall the installed patches of a runtime's major.minor version except the newest
+    Cleanup(CleanupArg),
+    /// Install the newest patch of every major.minor version of the machine-wide runtimes where a newer one is out
+    Update(UpdateArg),
+    /// Uninstall the installed releases of a runtime matching the version
+    Uninstall(UninstallArg),
+    /// Register a scheduled task running ensure --once with a requirements manifest, keeping its runtimes patched unattended
+    ///
+    /// The task runs ensure rather than update, which takes no manifest and patches every machine-wide runtime: ensure
+    /// installs what the manifest lists and moves it to the newest patch, and leaves other runtimes alone.
+    InstallUpdater(InstallUpdaterArg),
+    /// Remove the scheduled task install-updater registered
+    RemoveUpdater(RemoveUpdaterArg),
+    /// Install the frameworks an app needs if any are missing, then start it and exit with its exit code
+    ///
+    /// The app's exe or dll and its arguments come after --, as in run -- app.exe --its-flag. A dll is started with dotnet.
+    Run(RunArg),
+    /// Print a completion script for a shell; clink adds completions to cmd
+    Completions(CompletionsArg),
+}
+
+#[derive(StructOpt)]
+struct VcredistArg {
+    #[structopt(short, long, possible_values = &Architecture::variants(), case_insensitive = true)]
+    arch: Architecture,
+    /// Installer to download instead of the one aka.ms/vs/17/release currently points to
+    #[structopt(long)]
+    vcredist_url: Option<String>,
+    #[structopt(flatten)]
+    network: Network,
+    #[structopt(flatten)]
+    logging: Logging,
+    /// Only print whether it would be installed, with size and time estimates
+    #[structopt(long)]
+    plan: bool,
+    /// Go ahead without asking to confirm the plan, which is only asked in a terminal
     #[structopt(short, long)]
-    version: DotnetVersion,
+    yes: bool,
+    #[structopt(flatten)]
+    installing: Installing,
+    /// Skip SHA512 verification of the downloaded installer (emergencies only)
+    #[structopt(long)]
+    no_verify: bool,
+    /// Don't draw download progress bars
+    #[structopt(short, long)]
+    quiet: bool,
+    /// How much of its own UI the installer shows: none, a progress bar, or its interactive UI
+    #[structopt(long, default_value = "Quiet", possible_values = &InstallerUi::variants(), case_insensitive = true)]
+    ui: InstallerUi,
+    /// Kill the installer if it is still running after this long, in seconds or with an s, m, h or d suffix
+    #[structopt(long, parse(try_from_str = parse_interval))]
+    installer_timeout: Option<Duration>,
+    /// Download and unpack installers here instead of %TEMP%, if that is too small or blocks running programs
+    #[structopt(long, parse(from_os_str))]
+    temp_dir: Option<PathBuf>,
+    /// Argument appended to the installer's command line; repeat for several
+    #[structopt(long = "installer-arg", number_of_values = 1, allow_hyphen_values = true)]
+    installer_args: Vec<String>,
+    /// Download the installer even if a cached copy exists, and don't cache it
+    #[structopt(long)]
+    no_cache: bool,
+}
+
+#[derive(StructOpt)]
+struct CleanupArg {
     #[structopt(short, long, possible_values = &Runtime::variants(), case_insensitive = true)]
     runtime: Runtime,
     #[structopt(short, long, possible_values = &Architecture::variants(), case_insensitive = true)]
     arch: Architecture,
+    /// The major.minor version whose superseded patches to uninstall, such as 8.0
+    #[structopt(short, long)]
+    version: String,
+    #[structopt(flatten)]
+    logging: Logging,
+    #[structopt(flatten)]
+    installing: Installing,
+    /// How much of their own UI the uninstallers show: none, a progress bar, or their interactive UI
+    #[structopt(long, default_value = "Quiet", possible_values = &InstallerUi::variants(), case_insensitive = true)]
+    ui: InstallerUi,
+}
 
+#[derive(StructOpt)]
+struct ListArg {
+    /// Architectures to list; all this machine runs by default
+    #[structopt(name = "arch", short, long = "arch", use_delimiter = true, possible_values = &Architecture::variants(), case_insensitive = true)]
+    arches: Vec<Architecture>,
+    /// Only list the machine-wide install, not the ones DOTNET_ROOT or DOTNET_INSTALL_DIR point at
+    #[structopt(long)]
+    machine_wide_only: bool,
+    #[structopt(flatten)]
+    logging: Logging,
+    /// Emit the installed runtimes as JSON on stdout
+    #[structopt(long, default_value = "human", possible_values = &OutputFormat::variants(), case_insensitive = true)]
+    output: OutputFormat,
 }
 
-#[derive(Copy, Clone)]
-struct DotnetVersion {
-    major: u64,
-    minor: Option<u64>,
-    patch: Option<u64>,
+#[derive(StructOpt)]
+struct UninstallArg {
+    #[structopt(short, long, possible_values = &Runtime::variants(), case_insensitive = true)]
+    runtime: Runtime,
+    #[structopt(short, long, possible_values = &Architecture::variants(), case_insensitive = true)]
+    arch: Architecture,
+    /// The versions to uninstall, such as 8.0.10, or 8.0 for all its patches
+    #[structopt(short, long)]
+    version: String,
+    /// Remove the versions from this directory instead of running the uninstallers of machine-wide ones
+    #[structopt(long, parse(from_os_str))]
+    install_dir: Option<PathBuf>,
+    #[structopt(flatten)]
+    logging: Logging,
+    #[structopt(flatten)]
+    installing: Installing,
+    /// How much of their own UI the uninstallers show: none, a progress bar, or their interactive UI
+    #[structopt(long, default_value = "Quiet", possible_values = &InstallerUi::variants(), case_insensitive = true)]
+    ui: InstallerUi,
 }
 
-impl Display for DotnetVersion {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!("{}", self.major))?;
-        
-        if let Some(minor) = self.minor {
-            f.write_fmt(format_args!(".{}", minor))?;
+#[derive(StructOpt)]
+struct UpdateArg {
+    /// Architectures to update; all this machine runs by default
+    #[structopt(name = "arch", short, long = "arch", use_delimiter = true, possible_values = &Architecture::variants(), case_insensitive = true)]
+    arches: Vec<Architecture>,
+    /// Only print the patches that would be installed
+    #[structopt(long)]
+    dry_run: bool,
+    /// Only install patches of versions a newer security release was made for
+    #[structopt(long)]
+    security_only: bool,
+    /// After a successful run, write a CycloneDX SBOM of the patches it installed to this file
+    #[structopt(long, parse(from_os_str))]
+    sbom: Option<PathBuf>,
+    /// Go ahead without asking to confirm the plan, which is only asked in a terminal
+    #[structopt(short, long)]
+    yes: bool,
+    #[structopt(flatten)]
+    network: Network,
+    #[structopt(flatten)]
+    logging: Logging,
+    #[structopt(flatten)]
+    installing: Installing,
+    /// Don't draw download progress bars
+    #[structopt(short, long)]
+    quiet: bool,
+    /// How much of their own UI the installers show: none, a progress bar, or their interactive UI
+    #[structopt(long, default_value = "Quiet", possible_values = &InstallerUi::variants(), case_insensitive = true)]
+    ui: InstallerUi,
+}
 
-            if let Some(patch) = self.patch {
-                f.write_fmt(format_args!(".{}", patch))?;
-            }
-        }
+#[derive(StructOpt)]
+struct InstallUpdaterArg {
+    /// Requirements manifest the task installs and keeps on the newest patch
+    #[structopt(long, parse(from_os_str))]
+    manifest: PathBuf,
+    /// Time between runs, in minutes, hours or days with an m, h or d suffix
+    #[structopt(long, default_value = "24h", parse(try_from_str = parse_interval))]
+    interval: Duration,
+    /// Log file the task appends to at every run
+    #[structopt(long, parse(from_os_str))]
+    log_file: Option<PathBuf>,
+    /// Fail instead of asking for administrator rights through UAC
+    #[structopt(long)]
+    no_elevate: bool,
+}
 
-        Ok(())
-    }
+#[derive(StructOpt)]
+struct RemoveUpdaterArg {
+    /// Fail instead of asking for administrator rights through UAC
+    #[structopt(long)]
+    no_elevate: bool,
 }
 
-impl FromStr for DotnetVersion {
-    type Err = anyhow::Error;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let parts = s
-            .split('.')
-            .map(FromStr::from_str)
-            .collect::<Result<Vec<u64>, _>>()?;
-        let version = match *parts.as_slice() {
-            [major] => DotnetVersion {
-                major,
-                minor: None,
-                patch: None,
-            },
-            [major, minor] => DotnetVersion {
-                major,
-                minor: Some(minor),
-                patch: None,
-            },
-            [major, minor, patch] => DotnetVersion {
-                major,
-                minor: Some(minor),
-                patch: Some(patch),
-            },
-            _ => return Err(anyhow!("invalid version number")),
-        };
+#[derive(StructOpt)]
+struct RunArg {
+    #[structopt(flatten)]
+    network: Network,
+    #[structopt(flatten)]
+    logging: Logging,
+    /// Architecture to install for, instead of the one the app's exe is built for
+    #[structopt(long, possible_values = &Architecture::variants(), case_insensitive = true)]
+    arch: Option<Architecture>,
+    /// Install the frameworks into this directory and point the app at it with DOTNET_ROOT, instead of machine-wide
+    #[structopt(long, parse(from_os_str))]
+    install_dir: Option<PathBuf>,
+    #[structopt(flatten)]
+    installing: Installing,
+    /// The app's exe or dll, then its arguments
+    #[structopt(required = true, last = true, parse(from_os_str))]
+    command: Vec<OsString>,
+}
 
-        Ok(version)
-    }
+#[derive(StructOpt)]
+struct CompletionsArg {
+    #[structopt(possible_values = &CompletionShell::variants(), case_insensitive = true)]
+    shell: CompletionShell,
 }
 
 arg_enum! {
-    #[derive(Copy, Clone)]
-    enum Runtime {
-        Dotnet,
-        AspCore,
-        WindowsDesktop,
+    #[derive(Copy, Clone, PartialEq, Eq)]
+    enum CompletionShell {
+        PowerShell,
+        Clink,
+        Bash,
+        Zsh,
+        Fish,
     }
 }
 
+#[derive(StructOpt)]
+struct EnsureArg {
+    #[structopt(flatten)]
+    install: InstallArg,
+    /// Time between checks, in seconds or with an s, m, h or d suffix
+    #[structopt(long, default_value = "24h", parse(try_from_str = parse_interval))]
+    interval: Duration,
+    /// Check and install once and exit, for running from a scheduled task
+    #[structopt(long)]
+    once: bool,
+}
+
+#[derive(StructOpt)]
+struct CheckArg {
+    #[structopt(flatten)]
+    requirement: Requirement,
+    #[structopt(flatten)]
+    network: Network,
+    #[structopt(flatten)]
+    logging: Logging,
+    /// Don't count a missing VC++ redistributable
+    #[structopt(long)]
+    skip_vcredist: bool,
+    /// Look for the runtimes in this directory instead of the machine-wide install
+    #[structopt(long, parse(from_os_str))]
+    install_dir: Option<PathBuf>,
+    /// Look for the runtimes in the current user's install
+    #[structopt(long, conflicts_with = "install-dir")]
+    user: bool,
+    /// Only count runtimes in the machine-wide install, not ones DOTNET_ROOT or DOTNET_INSTALL_DIR point at
+    #[structopt(long, conflicts_with_all = &["install-dir", "user"])]
+    machine_wide_only: bool,
+    /// Emit the missing components as JSON on stdout
+    #[structopt(long, default_value = "human", possible_values = &OutputFormat::variants(), case_insensitive = true)]
+    output: OutputFormat,
+}
+
+#[derive(StructOpt)]
+struct CompatArg {
+    /// The app's exe or dll, or its runtimeconfig.json
+    #[structopt(long, parse(from_os_str))]
+    app: PathBuf,
+    /// Architecture of the app, if it cannot be read from the exe
+    #[structopt(short, long, possible_values = &Architecture::variants(), case_insensitive = true)]
+    arch: Option<Architecture>,
+    /// Emit the report as JSON on stdout
+    #[structopt(long, default_value = "human", possible_values = &OutputFormat::variants(), case_insensitive = true)]
+    output: OutputFormat,
+    #[structopt(flatten)]
+    logging: Logging,
+}
+
+#[derive(StructOpt)]
+struct DoctorArg {
+    /// Only try the outbound connections and list them for the firewall, without the machine diagnostics
+    #[structopt(long)]
+    firewall: bool,
+    #[structopt(flatten)]
+    network: Network,
+    #[structopt(flatten)]
+    logging: Logging,
+}
+
+#[derive(StructOpt)]
+struct BundleArg {
+    /// Directory to download into; its bundle.json manifest is created or extended
+    #[structopt(long, parse(from_os_str))]
+    dir: PathBuf,
+    #[structopt(short, long = "version", required = true, number_of_values = 1)]
+    versions: Vec<String>,
+    #[structopt(short, long = "runtime", required = true, number_of_values = 1, use_delimiter = true, possible_values = &Runtime::variants(), case_insensitive = true)]
+    runtimes: Vec<Runtime>,
+    #[structopt(short, long = "arch", required = true, number_of_values = 1, possible_values = &Architecture::variants(), case_insensitive = true)]
+    arches: Vec<Architecture>,
+    #[structopt(flatten)]
+    network: Network,
+    #[structopt(flatten)]
+    logging: Logging,
+    /// Don't draw download progress bars
+    #[structopt(short, long)]
+    quiet: bool,
+    /// VC++ redistributable installer to bundle instead of the one aka.ms/vs/17/release currently points to
+    #[structopt(long)]
+    vcredist_url: Option<String>,
+}
+
+#[derive(StructOpt)]
+enum CacheCommand {
+    /// Remove all cached installers
+    Clear,
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(StructOpt)]
+enum CatalogCommand {
+    /// Fetch the latest release metadata into the local catalog
+    Update {
+        #[structopt(flatten)]
+        network: Network,
+        #[structopt(flatten)]
+        logging: Logging,
+    },
+    /// List the releases known to the built-in and local catalogs
+    Show,
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(StructOpt)]
+enum SnapshotCommand {
+    /// Write the machine-wide runtimes and SDKs, pinned at their exact versions, to a JSON file
+    Export {
+        /// File to write the snapshot to
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+        /// Architectures to record; all this machine runs by default
+        #[structopt(name = "arch", short, long = "arch", use_delimiter = true, possible_values = &Architecture::variants(), case_insensitive = true)]
+        arches: Vec<Architecture>,
+        #[structopt(flatten)]
+        logging: Logging,
+    },
+    /// Install the runtimes and SDKs a snapshot lists that are missing here
+    Apply(SnapshotApplyArg),
+}
+
+#[derive(StructOpt)]
+struct SnapshotApplyArg {
+    /// Snapshot written by snapshot export
+    #[structopt(parse(from_os_str))]
+    file: PathBuf,
+    #[structopt(flatten)]
+    network: Network,
+    #[structopt(flatten)]
+    logging: Logging,
+    /// Only print what would be installed, with download size, installed size and time estimates
+    #[structopt(long)]
+    plan: bool,
+    /// Go ahead without asking to confirm the plan, which is only asked in a terminal
+    #[structopt(short, long)]
+    yes: bool,
+    #[structopt(flatten)]
+    installing: Installing,
+}
+
+/// What to install.
+#[derive(StructOpt)]
+struct Requirement {
+    /// `major[.minor[.patch]]`, a full prerelease version, a semver range such as ">=5.0.3, <6", ~5.0 or 5.0.*, or lts,
+    /// sts or current for the newest supported channel of that kind; latest, the default, is the newest LTS channel
+    #[structopt(short, long)]
+    version: Option<String>,
+    /// Install the newest release of this major version, instead of --version
+    #[structopt(long, conflicts_with_all = &["version", "roll-forward", "manifest", "for-app", "scan", "global-json"])]
+    major: Option<u64>,
+    /// Runtime to install; repeat it or separate several with commas to install them in order
+    #[structopt(name = "runtime", short, long = "runtime", use_delimiter = true, possible_values = &Runtime::variants(), case_insensitive = true, required_unless_one = &["global-tool", "manifest", "for-app", "scan", "global-json"])]
+    runtimes: Vec<Runtime>,
+    /// Architecture to install for; separate several with commas, or give all for every one this machine runs. auto,
+    /// the default, is this machine's own: arm64 on ARM64, x64 on other 64-bit systems and x86 on 32-bit Windows
+    #[structopt(name = "arch", short, long = "arch", use_delimiter = true, possible_values = &ArchChoice::variants(), case_insensitive = true)]
+    arches: Vec<ArchChoice>,
+    /// Ensure the SDK needed to install and run this dotnet global tool package; without --version, its channel is
+    /// the newest target framework the package's newest stable release is built for
+    #[structopt(long, conflicts_with = "runtime")]
+    global_tool: Option<String>,
+    /// TOML or JSON file listing the required components, or an http(s) URL of one checked with --manifest-key or
+    /// --manifest-sha256, instead of --version, --runtime and --arch
+    #[structopt(long, parse(from_os_str), conflicts_with_all = &["version", "runtime", "arch", "global-tool", "for-app"])]
+    manifest: Option<PathBuf>,
+    /// Base64 Ed25519 public key that the signature at the manifest URL with .sig appended must verify with
+    #[structopt(long, requires = "manifest", conflicts_with = "manifest-sha256")]
+    manifest_key: Option<String>,
+    /// SHA-256 hash the manifest at the URL must have, pinning it instead of checking its signature
+    #[structopt(long, requires = "manifest")]
+    manifest_sha256: Option<String>,
+    /// Install the frameworks an app's exe or runtimeconfig.json needs, instead of --version and --runtime
+    #[structopt(long, parse(from_os_str), conflicts_with_all = &["version", "runtime", "global-tool"])]
+    for_app: Option<PathBuf>,
+    /// Install the frameworks every app in this published folder needs, read from its runtimeconfig.json and deps.json files
+    #[structopt(long, parse(from_os_str), conflicts_with_all = &["version", "runtime", "global-tool", "manifest", "for-app"])]
+    scan: Option<PathBuf>,
+    /// Install the SDK pinned by the global.json in this directory, honoring its rollForward policy
+    #[structopt(long, parse(from_os_str), conflicts_with_all = &["version", "runtime", "manifest", "for-app", "scan"])]
+    global_json: Option<PathBuf>,
+    /// Let previews and release candidates satisfy the version, and install the newest one if it is newer than any release
+    #[structopt(long)]
+    include_prerelease: bool,
+    /// Builds to resolve the version to: ga releases, previews too, or the newest daily build from the daily builds feed
+    #[structopt(long, default_value = "Ga", possible_values = &Quality::variants(), case_insensitive = true)]
+    quality: Quality,
+    /// Which releases satisfy the version, as in the host's rollForward; overrides the manifest's and the app's
+    #[structopt(long, possible_values = &RollForward::variants(), case_insensitive = true)]
+    roll_forward: Option<RollForward>,
+    /// Take the installer from this version's directory on the download server instead of resolving it, for releases
+    /// whose metadata is inconsistent
+    #[structopt(long, conflicts_with_all = &["manifest", "for-app", "scan", "global-json"])]
+    blob_version: Option<String>,
+    /// Product version the installer's file is named with, instead of the one looked up; applies to --blob-version, or
+    /// else to the full --version
+    #[structopt(long, conflicts_with_all = &["manifest", "for-app", "scan", "global-json"])]
+    product_version: Option<String>,
+}
+
+#[derive(StructOpt)]
+struct Network {
+    /// HTTP proxy to tunnel all requests through, overriding HTTP_PROXY/HTTPS_PROXY; may include user:password@
+    #[structopt(long)]
+    proxy: Option<Url>,
+    /// Windows Credential Manager entry holding the proxy credentials, instead of the proxy host name
+    #[structopt(long)]
+    proxy_cred_target: Option<String>,
+    /// Total attempts for each request or download before giving up on transient failures
+    #[structopt(long, default_value = "3")]
+    retry_attempts: u32,
+    /// Delay in milliseconds before the first retry, doubled for each further one
+    #[structopt(long, default_value = "1000")]
+    retry_backoff_ms: u64,
+    /// Mirror of https://dotnetcli.azureedge.net/dotnet to fall back to when the official endpoints fail
+    #[structopt(long = "mirror", number_of_values = 1)]
+    mirrors: Vec<String>,
+    /// PEM file of CA certificates to trust besides the bundled and the system's, such as a TLS-inspection proxy's
+    #[structopt(long, parse(from_os_str))]
+    ca_bundle: Option<PathBuf>,
+    /// DANGEROUS: skip TLS certificate verification, for TLS-inspection proxies whose root cannot be trusted any
+    /// other way; every certificate accepted unverified is logged
+    #[structopt(long)]
+    insecure: bool,
+    /// Use Windows' own TLS stack, Schannel, so that its trust store, group policy's protocol and cipher settings and
+    /// FIPS mode apply; add extra CA certificates to the Windows store instead of --ca-bundle
+    #[structopt(long, conflicts_with = "ca-bundle")]
+    schannel: bool,
+    /// TOML file of SPKI SHA-256 pins by host, as "host" = ["base64", ...]; connections to a listed host fail unless
+    /// its certificate, or a certificate or root it chains up to, has one of its pins
+    #[structopt(long, parse(from_os_str), conflicts_with_all = &["insecure", "schannel"])]
+    pin_certs: Option<PathBuf>,
+    /// Internal mirror of https://dotnetcli.azureedge.net/dotnet to use instead of the official endpoints
+    #[structopt(long, env = "DOTNET5_REDIST_FEED_URL")]
+    feed_url: Option<String>,
+    /// Mirror of https://ci.dot.net/public to take daily builds from with --quality daily
+    #[structopt(long, env = "DOTNET5_REDIST_DAILY_FEED_URL")]
+    daily_feed_url: Option<String>,
+    /// NuGet v3 flat container to look up --global-tool packages in instead of https://api.nuget.org/v3-flatcontainer
+    #[structopt(long, env = "DOTNET5_REDIST_NUGET_FEED_URL")]
+    nuget_feed_url: Option<String>,
+    /// Connect over IPv4 only, to tell whether a broken IPv6 setup is what fails
+    #[structopt(long, conflicts_with = "ipv6-only")]
+    ipv4_only: bool,
+    /// Connect over IPv6 only, to tell whether a broken IPv4 setup is what fails
+    #[structopt(long)]
+    ipv6_only: bool,
+    /// Seconds a DNS lookup, connect, handshake or read may stall before it fails
+    #[structopt(long, default_value = "30")]
+    http_timeout: u64,
+    /// Connections that may be open to any one host at once
+    #[structopt(long, default_value = "4")]
+    max_connections_per_host: usize,
+    /// Connections that may be open to all hosts together at once
+    #[structopt(long, default_value = "8")]
+    max_connections: usize,
+    /// Ranges of a large download fetched at the same time, each over its own connection
+    #[structopt(long, default_value = "1")]
+    download_segments: usize,
+    /// Resolve versions from the online release metadata even if the built-in catalog has them
+    #[structopt(long)]
+    refresh_catalog: bool,
+    /// File listing the only versions that may be resolved and installed, one per line
+    #[structopt(long, parse(from_os_str))]
+    approved_versions: Option<PathBuf>,
+    /// POST an anonymous JSON record of each run's outcome here: the tool version, runtimes, versions, outcome,
+    /// error class and duration
+    #[structopt(long, env = "DOTNET5_REDIST_REPORT_URL")]
+    report_url: Option<String>,
+}
+
+#[derive(StructOpt)]
+struct Logging {
+    /// Print debug detail about requests, detection and installer runs
+    #[structopt(long)]
+    verbose: bool,
+    /// Append a detailed log to this file; runtime installers write their logs next to it
+    #[structopt(long, parse(from_os_str))]
+    log_file: Option<PathBuf>,
+    /// Format of the log file: text, or json for one object per line with the steps of the install as fields
+    #[structopt(long, default_value = "text", possible_values = &LogFormat::variants(), case_insensitive = true)]
+    log_format: LogFormat,
+    /// Language of messages, such as nb or en-US, instead of the system's
+    #[structopt(long)]
+    lang: Option<String>,
+    /// Write how long lookups, connections, TLS handshakes, resolution, downloads and installers took to this JSON file
+    #[structopt(long, parse(from_os_str))]
+    timing_report: Option<PathBuf>,
+}
+
+/// Options of the commands that install or uninstall.
+#[derive(StructOpt)]
+struct Installing {
+    /// Emit the run's results as JSON on stdout
+    #[structopt(long, default_value = "human", possible_values = &OutputFormat::variants(), case_insensitive = true)]
+    output: OutputFormat,
+    /// Wait this long for another Windows Installer transaction, such as Windows Update's, to finish first
+    #[structopt(long, default_value = "10m", parse(try_from_str = parse_interval))]
+    msi_wait: Duration,
+    /// Wait this long for another run of this tool on the machine to finish installing or uninstalling first
+    #[structopt(long, default_value = "10m", parse(try_from_str = parse_interval))]
+    instance_wait: Duration,
+    /// Fail instead of asking for administrator rights through UAC when they are needed
+    #[structopt(long)]
+    no_elevate: bool,
+    /// Exit codes: the failing installer's own and 3010 for a reboot, or stable ones by failure class: 0 installed,
+    /// 10 already installed, 20-23 network, version, verification and advisories, 30-32 installer failed, reboot
+    /// required and installer timed out, 40-41 platform and disk space, 50 cancelled, 60 invalid request, 1 other
+    #[structopt(long, default_value = "installer", possible_values = &ExitCodes::variants(), case_insensitive = true)]
+    exit_codes: ExitCodes,
+}
+
+#[derive(StructOpt)]
+struct InstallArg {
+    #[structopt(flatten)]
+    requirement: Requirement,
+    #[structopt(flatten)]
+    network: Network,
+    #[structopt(flatten)]
+    logging: Logging,
+    /// Check connectivity to the download hosts before doing anything else
+    #[structopt(long)]
+    preflight: bool,
+    /// Run `dotnet tool install -g` for the --global-tool package once its SDK is present
+    #[structopt(long, requires = "global-tool")]
+    install_tool: bool,
+    /// Maximum number of bytes installers may download in this run
+    #[structopt(long)]
+    max_download_size: Option<u64>,
+    /// Install even if the temp or install drive seems too full
+    #[structopt(long)]
+    skip_disk_check: bool,
+    /// Only print what would be installed, with download size, installed size and time estimates
+    #[structopt(long)]
+    plan: bool,
+    /// Go ahead without asking to confirm the plan, which is only asked in a terminal
+    #[structopt(short, long)]
+    yes: bool,
+    /// Only print the URL of each installer the version resolves to, and its SHA512 hash if known, without downloading
+    #[structopt(long, conflicts_with = "plan")]
+    print_url: bool,
+    #[structopt(flatten)]
+    installing: Installing,
+    /// Skip SHA512 verification of downloaded installers (emergencies only)
+    #[structopt(long)]
+    no_verify: bool,
+    /// Authenticode publisher accepted as the signer of downloaded installers, besides Microsoft Corporation
+    #[structopt(long = "trusted-publisher", number_of_values = 1)]
+    trusted_publishers: Vec<String>,
+    /// Accept only the --trusted-publisher signers, not Microsoft Corporation as well
+    #[structopt(long, requires = "trusted-publisher")]
+    no_default_publisher: bool,
+    /// Run installers even if their Authenticode signature cannot be verified
+    #[structopt(long)]
+    skip_signature_check: bool,
+    /// Retry known transient failures (busy MSI, locked files, network) with a fix applied
+    #[structopt(long)]
+    auto_remediate: bool,
+    /// Maximum number of attempts with --auto-remediate
+    #[structopt(long, default_value = "3")]
+    remediation_attempts: u32,
+    /// Remove per-user duplicates and wrong-architecture runtimes found during detection
+    #[structopt(long)]
+    fix_conflicts: bool,
+    /// Don't draw download progress bars
+    #[structopt(short, long)]
+    quiet: bool,
+    /// How much of their own UI installers show: none, a progress bar, or their interactive UI
+    #[structopt(long, default_value = "Quiet", possible_values = &InstallerUi::variants(), case_insensitive = true)]
+    ui: InstallerUi,
+    /// Kill installers still running after this long, in seconds or with an s, m, h or d suffix
+    #[structopt(long, parse(try_from_str = parse_interval))]
+    installer_timeout: Option<Duration>,
+    /// Download and unpack installers here instead of %TEMP%, if that is too small or blocks running programs
+    #[structopt(long, parse(from_os_str))]
+    temp_dir: Option<PathBuf>,
+    /// Argument appended to the command line of every installer, such as a log path; repeat for several
+    #[structopt(long = "installer-arg", number_of_values = 1, allow_hyphen_values = true)]
+    installer_args: Vec<String>,
+    /// Container the tool runs in, instead of detecting it
+    #[structopt(long, possible_values = &Container::variants(), case_insensitive = true)]
+    container: Option<Container>,
+    /// Run this pre-downloaded runtime installer instead of resolving and downloading one
+    #[structopt(long, parse(from_os_str))]
+    installer_path: Option<PathBuf>,
+    /// Install from an offline bundle directory created with `bundle`
+    #[structopt(long, parse(from_os_str), conflicts_with = "installer-path")]
+    bundle: Option<PathBuf>,
+    /// Extract the runtimes' archives into this directory for apps to use through DOTNET_ROOT, instead of installing them machine-wide
+    #[structopt(long, parse(from_os_str), conflicts_with_all = &["installer-path", "bundle"])]
+    install_dir: Option<PathBuf>,
+    /// Install the runtimes for the current user only, without elevation, and point the user's DOTNET_ROOT at them
+    #[structopt(long, conflicts_with_all = &["installer-path", "bundle", "install-dir"])]
+    user: bool,
+    /// Only count runtimes in the machine-wide install, not ones DOTNET_ROOT or DOTNET_INSTALL_DIR point at
+    #[structopt(long, conflicts_with_all = &["install-dir", "user"])]
+    machine_wide_only: bool,
+    /// Point DOTNET_ROOT and PATH at the --install-dir or --user install for the user or the machine, or print commands
+    /// that do
+    #[structopt(long, possible_values = &EnvironmentScope::variants(), case_insensitive = true)]
+    environment: Option<EnvironmentScope>,
+    /// Directory of *.toml plugins describing custom components to detect and install
+    #[structopt(long, parse(from_os_str))]
+    plugin_dir: Option<PathBuf>,
+    /// Language of the installers' UI as a BCP 47 tag such as nb-NO, instead of the system language
+    #[structopt(long)]
+    locale: Option<String>,
+    /// Send length-prefixed JSON progress and the final report to a parent process over this named pipe, or stdout
+    #[structopt(long, value_name = "pipe-name|stdout")]
+    progress_ipc: Option<String>,
+    /// Write a crash report and minidump into this directory if the tool itself crashes
+    #[structopt(long, parse(from_os_str))]
+    crash_dir: Option<PathBuf>,
+    /// Run as an ExePackage of a WiX Burn bundle: silent, without a console window, logging to --log-file or
+    /// %TEMP%\dotnet5-webinst.log, and exiting only with codes Burn understands
+    #[structopt(long, conflicts_with_all = &["plan", "print-url", "exit-codes"])]
+    bootstrapper: bool,
+    /// What to do when Windows is waiting for a reboot before installing
+    #[structopt(long, default_value = "Warn", possible_values = &PendingReboot::variants(), case_insensitive = true)]
+    pending_reboot: PendingReboot,
+    /// Download installers even if a cached copy exists, and don't cache them
+    #[structopt(long)]
+    no_cache: bool,
+    /// Install exactly the versions the lockfile pins instead of resolving them
+    #[structopt(long)]
+    locked: bool,
+    /// After a successful run, pin the versions it installed in the lockfile, with the tool version and time
+    #[structopt(long)]
+    record: bool,
+    /// Install the newest patch of the requested version even if an older one is present
+    #[structopt(long)]
+    latest_patch: bool,
+    /// With --latest-patch, keep the installed patch unless a newer one is flagged as a security release
+    #[structopt(long)]
+    security_only: bool,
+    /// Fail instead of warning when the requested version has known security advisories, and replace installed
+    /// versions that have them
+    #[structopt(long)]
+    fail_on_vulnerable: bool,
+    /// Reinstall the runtimes even if a matching version is present, to repair broken installs
+    #[structopt(long)]
+    force: bool,
+    /// Run the installers of the installed releases matching the version again to repair them, instead of installing
+    #[structopt(long, conflicts_with = "force")]
+    repair: bool,
+    /// VC++ redistributable installer to download instead of the one aka.ms/vs/17/release currently points to
+    #[structopt(long)]
+    vcredist_url: Option<String>,
+    /// Skip system restore points and rollback while installing; faster on slow disks, but a failed install can be left half done
+    #[structopt(long)]
+    fast_install: bool,
+    /// Install runtimes with winget, where it is present, if their installers cannot be downloaded
+    #[structopt(long)]
+    allow_winget: bool,
+    /// Leave the VC++ redistributable alone, for machines that manage it separately
+    #[structopt(long, conflicts_with = "vcredist-url")]
+    skip_vcredist: bool,
+    /// Lockfile read by --locked and written by --record
+    #[structopt(long, default_value = lock::DEFAULT_PATH)]
+    lockfile: PathBuf,
+    /// After a successful run, write a CycloneDX SBOM of the components it installed to this file
+    #[structopt(long, parse(from_os_str))]
+    sbom: Option<PathBuf>,
+}
+
+#[derive(StructOpt)]
+struct LockArg {
+    #[structopt(flatten)]
+    requirement: Requirement,
+    #[structopt(flatten)]
+    network: Network,
+    #[structopt(flatten)]
+    logging: Logging,
+    #[structopt(long, default_value = lock::DEFAULT_PATH)]
+    lockfile: PathBuf,
+}
+
+#[derive(StructOpt)]
+struct ResolveArg {
+    #[structopt(flatten)]
+    requirement: Requirement,
+    #[structopt(flatten)]
+    network: Network,
+    #[structopt(flatten)]
+    logging: Logging,
+    /// Fail unless every resolved version matches this pattern, such as 8.0.* or 8.*
+    #[structopt(long)]
+    assert: Option<String>,
+}
+
+#[derive(StructOpt)]
+struct VerifyArg {
+    /// Installer or archive to check, such as one brought in on removable media
+    #[structopt(parse(from_os_str))]
+    file: PathBuf,
+    #[structopt(flatten)]
+    network: Network,
+    #[structopt(flatten)]
+    logging: Logging,
+}
+
 arg_enum! {
     #[derive(Copy, Clone, PartialEq, Eq)]
-    enum Architecture {
+    enum ArchChoice {
         X86,
         X64,
+        Arm64,
+        All,
+        Auto,
     }
 }
 
-const BASE_URL: &str = "https://dotnetcli.blob.core.windows.net/dotnet";
-const CDN_URL: &str = "https://dotnetcli.azureedge.net/dotnet";
+arg_enum! {
+    #[derive(Copy, Clone, PartialEq, Eq)]
+    enum OutputFormat {
+        Human,
+        Json,
+    }
+}
 
-fn main() -> Result<()> {
-    smol::block_on(async {
-        let arg: Arg = Arg::from_args();
+arg_enum! {
+    /// Which exit codes a run ends with: installers' own, or the stable ones of [`exit`].
+    #[derive(Copy, Clone, PartialEq, Eq)]
+    enum ExitCodes {
+        Installer,
+        Stable,
+    }
+}
 
-        if arg.arch == Architecture::X64 && !is_64bit_os() {
-            bail!("Cannot install 64-bit dotnet on 32-bit windows");
+#[derive(StructOpt)]
+struct GenerateDockerArg {
+    #[structopt(flatten)]
+    requirement: Requirement,
+    #[structopt(flatten)]
+    network: Network,
+    #[structopt(flatten)]
+    logging: Logging,
+    /// Base image of the container; nanoserver gets the zip layout instead of the installer
+    #[structopt(long, default_value = "ServerCore", possible_values = &Container::variants(), case_insensitive = true)]
+    container: Container,
+}
+
+impl Requirement {
+    /// One builder per required component: the manifest's, or the one described on the command line.
+    fn builders(&self) -> Result<Vec<InstallRequestBuilder>> {
+        // Without --arch, the app's own architecture is taken.
+        let arch = || match *self.arches().as_slice() {
+            _ if self.arches.is_empty() => Ok(None),
+            [arch] => Ok(Some(arch)),
+            _ => Err(anyhow!("--for-app and --scan take a single --arch")),
+        };
+        let requirements = match (&self.manifest, &self.for_app, &self.scan) {
+            (Some(path), _, _) => Some(self.manifest(path)?),
+            (None, Some(app), _) => Some(dotnet5_webinst::app_requirements(app, arch()?)?),
+            (None, None, Some(dir)) => Some(dotnet5_webinst::scan_app_dir(dir, arch()?)?),
+            (None, None, None) => None,
+        };
+        if let Some(requirements) = requirements {
+            let mut builders = Vec::new();
+            for component in &requirements.components {
+                let builder = component
+                    .builder()
+                    .version(&smol::block_on(dotnet5_webinst::resolve_channel(&component.version))?)
+                    .include_prerelease(self.include_prerelease)
+                    .quality(self.quality);
+                let builder = match self.roll_forward {
+                    Some(roll_forward) => builder.roll_forward(roll_forward),
+                    None => builder,
+                };
+                builders.push(builder);
+            }
+            return Ok(builders);
         }
 
-        if !is_vcruntime_installed(arg.arch) {
-            let url = match arg.arch {
-                Architecture::X86 => "https://download.visualstudio.microsoft.com/download/pr/8ecb9800-52fd-432d-83ee-d6e037e96cc2/50A3E92ADE4C2D8F310A2812D46322459104039B9DEADBD7FDD483B5C697C0C8/VC_redist.x86.exe",
-                Architecture::X64 => "https://download.visualstudio.microsoft.com/download/pr/89a3b9df-4a09-492e-8474-8f92c115c51d/B1A32C71A6B7D5978904FB223763263EA5A7EB23B2C44A0D60E90D234AD99178/VC_redist.x64.exe",
+        let version = if let Some(major) = self.major {
+            major.to_string()
+        } else if let Some(version) = &self.version {
+            smol::block_on(dotnet5_webinst::resolve_channel(version))?
+        } else if let Some(dir) = &self.global_json {
+            smol::block_on(dotnet5_webinst::global_json_sdk_version(dir))?
+        } else if let Some(package_id) = &self.global_tool {
+            smol::block_on(dotnet5_webinst::global_tool_sdk_channel(package_id))?
+        } else {
+            smol::block_on(dotnet5_webinst::resolve_channel("latest"))?
+        };
+
+        let mut builders = Vec::new();
+        for arch in self.arches() {
+            let mut builder = InstallRequest::builder()
+                .arch(arch)
+                .include_prerelease(self.include_prerelease)
+                .quality(self.quality)
+                .version(&version);
+            if self.major.is_some() {
+                builder = builder.roll_forward(RollForward::LatestMinor);
+            }
+            if self.global_json.is_some() && self.global_tool.is_none() {
+                builder = builder.runtime(Runtime::Sdk);
+            }
+            for &runtime in &self.runtimes {
+                builder = builder.runtime(runtime);
+            }
+            if let Some(package_id) = &self.global_tool {
+                builder = builder.global_tool(package_id);
+            }
+            if let Some(roll_forward) = self.roll_forward {
+                builder = builder.roll_forward(roll_forward);
+            }
+            if let Some(blob_version) = &self.blob_version {
+                builder = builder.blob_version(blob_version);
+            }
+            if let Some(product_version) = &self.product_version {
+                builder = builder.product_version(product_version);
+            }
+            builders.push(builder);
+        }
+        Ok(builders)
+    }
+
+    /// The manifest at `path`, downloaded and checked if it is a URL.
+    fn manifest(&self, path: &Path) -> Result<Requirements> {
+        let url = match path.to_str().filter(|path| path.starts_with("https://") || path.starts_with("http://")) {
+            Some(url) => url,
+            None => return Requirements::load(path),
+        };
+        let check = match (&self.manifest_key, &self.manifest_sha256) {
+            (Some(key), _) => ManifestCheck::Signature(key.clone()),
+            (None, Some(sha256)) => ManifestCheck::Sha256(sha256.clone()),
+            (None, None) => bail!("a manifest URL needs --manifest-key or --manifest-sha256 to check it with"),
+        };
+        smol::block_on(Requirements::fetch(url, &check))
+    }
+
+    /// The architectures `--arch` names, in order, with `all` and `auto`
+    /// expanded; this machine's own if none is named.
+    fn arches(&self) -> Vec<Architecture> {
+        if self.arches.is_empty() {
+            return vec![Architecture::native()];
+        }
+        let mut arches = Vec::new();
+        for choice in &self.arches {
+            let chosen = match choice {
+                ArchChoice::X86 => vec![Architecture::X86],
+                ArchChoice::X64 => vec![Architecture::X64],
+                ArchChoice::Arm64 => vec![Architecture::Arm64],
+                ArchChoice::All => Architecture::installable(),
+                ArchChoice::Auto => vec![Architecture::native()],
             };
+            for arch in chosen {
+                if !arches.contains(&arch) {
+                    arches.push(arch);
+                }
+            }
+        }
+        arches
+    }
+}
+
+impl Logging {
+    fn init(&self) -> Result<()> {
+        i18n::init(self.lang.as_deref())?;
+        if self.log_format == LogFormat::Json && self.log_file.is_none() {
+            bail!("--log-format json needs a --log-file to write to");
+        }
+        if let Some(path) = &self.timing_report {
+            dotnet5_webinst::timing::set_report(path.clone());
+        }
+        dotnet5_webinst::logging::init(self.verbose, self.log_file.as_deref(), self.log_format)
+    }
+
+    /// The options as command line arguments again, for a run this one starts.
+    fn args(&self, args: &mut Args) {
+        args.flag("--verbose", self.verbose);
+        args.option("--log-file", self.log_file.as_ref());
+        args.value("--log-format", self.log_format.to_string());
+        args.option("--lang", self.lang.as_ref());
+        args.option("--timing-report", self.timing_report.as_ref());
+    }
+
+    /// The directory the log file is in, for the installers' logs.
+    fn dir(&self) -> Option<PathBuf> {
+        let parent = self.log_file.as_ref()?.parent()?;
+        Some(if parent.as_os_str().is_empty() {
+            PathBuf::from(".")
+        } else {
+            parent.to_path_buf()
+        })
+    }
+}
+
+impl Installing {
+    /// Sets how long to wait for Windows Installer and for other runs to finish.
+    fn apply(&self) {
+        dotnet5_webinst::set_msi_wait(self.msi_wait);
+        dotnet5_webinst::set_instance_wait(self.instance_wait);
+    }
 
-            download_install(url).await?;
+    /// The options as command line arguments again, for a run this one starts.
+    fn args(&self, args: &mut Args) {
+        args.value("--output", self.output.to_string());
+        args.value("--msi-wait", format!("{}s", self.msi_wait.as_secs()));
+        args.value("--instance-wait", format!("{}s", self.instance_wait.as_secs()));
+        args.flag("--no-elevate", self.no_elevate);
+        args.value("--exit-codes", self.exit_codes.to_string());
+    }
+
+    /// Passes on the outcome of a command, or exits with the stable code of
+    /// its error if `--exit-codes stable` asks for that.
+    fn finish(&self, result: Result<(), DotnetRedistError>) -> Result<()> {
+        if let (Err(e), ExitCodes::Stable) = (&result, self.exit_codes) {
+            eprintln!("{}", tr("error", &[("error", &format!("{:?}", e))]));
+            std::process::exit(e.exit_code());
         }
+        Ok(result?)
+    }
+}
 
-        if !is_installed(arg.arch, arg.runtime, &arg.version).await? {
-            let version = find_best_version(arg.runtime, arg.version).await?;
-            let product_version = find_product_version(arg.runtime, &version).await?;
+/// Command line arguments rebuilt from parsed options, to hand them on to
+/// another run of this tool.
+#[derive(Default)]
+struct Args(Vec<OsString>);
 
-            let url = download_url(arg.arch, arg.runtime, version, &product_version);
-            download_install(&url).await?;
+impl Args {
+    fn flag(&mut self, name: &str, set: bool) {
+        if set {
+            self.0.push(name.into());
         }
+    }
 
+    fn value(&mut self, name: &str, value: impl Into<OsString>) {
+        self.0.push(name.into());
+        self.0.push(value.into());
+    }
+
+    fn option(&mut self, name: &str, value: Option<impl Into<OsString>>) {
+        if let Some(value) = value {
+            self.value(name, value);
+        }
+    }
+}
+
+impl Network {
+    fn apply(&self) -> Result<()> {
+        if let Some(proxy) = &self.proxy {
+            dotnet5_webinst::set_proxy(proxy.clone());
+        }
+        if let Some(target) = &self.proxy_cred_target {
+            dotnet5_webinst::set_proxy_credential_target(target.clone());
+        }
+        dotnet5_webinst::set_retry_policy(RetryPolicy {
+            attempts: self.retry_attempts.max(1),
+            backoff: Duration::from_millis(self.retry_backoff_ms),
+        });
+        dotnet5_webinst::set_timeout(Duration::from_secs(self.http_timeout));
+        dotnet5_webinst::set_connection_limits(ConnectionLimits {
+            per_host: self.max_connections_per_host.max(1),
+            total: self.max_connections.max(1),
+        });
+        dotnet5_webinst::set_download_segments(self.download_segments);
+        dotnet5_webinst::set_mirrors(self.mirrors.clone());
+        if self.ipv4_only {
+            dotnet5_webinst::set_ip_family(IpFamily::V4Only);
+        } else if self.ipv6_only {
+            dotnet5_webinst::set_ip_family(IpFamily::V6Only);
+        }
+        if let Some(path) = &self.ca_bundle {
+            dotnet5_webinst::set_ca_bundle(path)?;
+        }
+        dotnet5_webinst::set_insecure(self.insecure);
+        if let Some(path) = &self.pin_certs {
+            dotnet5_webinst::set_pinned_certs(path)?;
+        }
+        if self.schannel {
+            if !cfg!(windows) {
+                bail!("--schannel is only available on Windows");
+            }
+            dotnet5_webinst::set_tls_backend(TlsBackend::Schannel);
+        }
+        if let Some(feed) = &self.feed_url {
+            dotnet5_webinst::set_feed(feed.clone());
+        }
+        if let Some(feed) = &self.daily_feed_url {
+            dotnet5_webinst::set_daily_feed(feed.clone());
+        }
+        if let Some(feed) = &self.nuget_feed_url {
+            dotnet5_webinst::set_nuget_feed(feed.clone());
+        }
+        dotnet5_webinst::set_refresh_catalog(self.refresh_catalog);
+        if let Some(url) = &self.report_url {
+            dotnet5_webinst::telemetry::set_url(url.clone());
+        }
+        if let Some(path) = &self.approved_versions {
+            dotnet5_webinst::load_approved_versions(path)?;
+        }
         Ok(())
-    })
+    }
+
+    /// The options as command line arguments again, for a run this one starts.
+    fn args(&self, args: &mut Args) {
+        args.option("--proxy", self.proxy.as_ref().map(Url::as_str));
+        args.option("--proxy-cred-target", self.proxy_cred_target.as_ref());
+        args.value("--retry-attempts", self.retry_attempts.to_string());
+        args.value("--retry-backoff-ms", self.retry_backoff_ms.to_string());
+        for mirror in &self.mirrors {
+            args.value("--mirror", mirror);
+        }
+        args.option("--ca-bundle", self.ca_bundle.as_ref());
+        args.flag("--insecure", self.insecure);
+        args.flag("--schannel", self.schannel);
+        args.option("--pin-certs", self.pin_certs.as_ref());
+        args.option("--feed-url", self.feed_url.as_ref());
+        args.option("--daily-feed-url", self.daily_feed_url.as_ref());
+        args.option("--nuget-feed-url", self.nuget_feed_url.as_ref());
+        args.flag("--ipv4-only", self.ipv4_only);
+        args.flag("--ipv6-only", self.ipv6_only);
+        args.value("--http-timeout", self.http_timeout.to_string());
+        args.value("--max-connections-per-host", self.max_connections_per_host.to_string());
+        args.value("--max-connections", self.max_connections.to_string());
+        args.value("--download-segments", self.download_segments.to_string());
+        args.flag("--refresh-catalog", self.refresh_catalog);
+        args.option("--approved-versions", self.approved_versions.as_ref());
+        args.option("--report-url", self.report_url.as_ref());
+    }
 }
 
-async fn download_install(url: &str) -> Result<()> {
-    let dir = tempdir()?;
-    let download_path = dir.path().join("installer.exe");
-    let mut file = File::create(&download_path).await?;
-    let response = http::get(&url).await?;
+impl InstallArg {
+    fn requests(&self) -> Result<Vec<InstallRequest>> {
+        let mut requests = Vec::new();
+        for (i, builder) in self.requirement.builders()?.into_iter().enumerate() {
+            // Plugins don't depend on the runtime, so they only need installing once.
+            requests.push(self.request(builder, i == 0)?);
+        }
+        Ok(requests)
+    }
 
-    if response.status() == StatusCode::Ok {
-        smol::io::copy(response, &mut file).await?;
-        file.flush().await?;
-        std::mem::drop(file);
-        Command::new(download_path).arg("/norestart").arg("/quiet").status()?;
-        Ok(())
-    } else {
-        Err(anyhow!("could not download file"))
+    fn request(&self, builder: InstallRequestBuilder, with_plugins: bool) -> Result<InstallRequest> {
+        let mut builder = builder
+            .install_tool(self.install_tool)
+            .preflight(self.preflight)
+            .skip_disk_check(self.skip_disk_check)
+            .no_verify(self.no_verify)
+            .skip_signature_check(self.skip_signature_check)
+            .no_default_publisher(self.no_default_publisher)
+            .fix_conflicts(self.fix_conflicts)
+            .quiet(self.quiet)
+            .installer_ui(self.ui)
+            .no_cache(self.no_cache)
+            .pending_reboot(self.pending_reboot)
+            .latest_patch(self.latest_patch)
+            .security_only(self.security_only)
+            .fail_on_vulnerable(self.fail_on_vulnerable)
+            .force(self.force)
+            .repair(self.repair)
+            .skip_vcredist(self.skip_vcredist)
+            .fast_install(self.fast_install)
+            .allow_winget(self.allow_winget)
+            .user(self.user);
+
+        if let Some(bytes) = self.max_download_size {
+            builder = builder.max_download_size(bytes);
+        }
+        for publisher in &self.trusted_publishers {
+            builder = builder.trusted_publisher(publisher);
+        }
+        for arg in &self.installer_args {
+            builder = builder.installer_arg(arg);
+        }
+        if self.auto_remediate {
+            builder = builder.auto_remediate(self.remediation_attempts);
+        }
+        if let Some(path) = &self.installer_path {
+            builder = builder.installer_path(path);
+        }
+        if let Some(dir) = &self.bundle {
+            builder = builder.bundle(dir);
+        }
+        if let Some(dir) = &self.install_dir {
+            builder = builder.install_dir(dir);
+        }
+        if let Some(scope) = self.environment {
+            builder = builder.environment(scope);
+        }
+        if let Some(url) = &self.vcredist_url {
+            builder = builder.vcredist_url(url);
+        }
+        if let (Some(dir), true) = (&self.plugin_dir, with_plugins) {
+            builder = builder.plugin_dir(dir);
+        }
+        if let Some(locale) = &self.locale {
+            builder = builder.locale(locale);
+        }
+        if let Some(container) = self.container {
+            builder = builder.container(container);
+        }
+        if let Some(dir) = self.logging.dir() {
+            builder = builder.installer_log_dir(dir);
+        }
+        if self.locked {
+            builder = builder.locked(Lockfile::load(&self.lockfile)?);
+        }
+        if self.bootstrapper || self.progress_ipc.is_some() {
+            let bootstrapper = self.bootstrapper;
+            builder = builder.on_progress(move |event| {
+                if bootstrapper {
+                    log_progress(event);
+                }
+                ipc::progress(event);
+            });
+        }
+
+        Ok(builder.build()?)
+    }
+
+    /// Settles the options for running inside a WiX Burn bundle: no console
+    /// window, prompts or progress bars, a log file to follow the run by, and
+    /// crashes reported as a failed install.
+    fn bootstrap(&mut self) {
+        dotnet5_webinst::logging::hide_console();
+        self.quiet = true;
+        self.installing.no_elevate = true;
+        self.ui = InstallerUi::Quiet;
+        if self.logging.log_file.is_none() {
+            let dir = self.temp_dir.clone().unwrap_or_else(std::env::temp_dir);
+            self.logging.log_file = Some(dir.join("dotnet5-webinst.log"));
+        }
+        if self.crash_dir.is_none() {
+            self.crash_dir = self.logging.dir();
+        }
+        dotnet5_webinst::crash::set_exit_code(ERROR_INSTALL_FAILURE);
+    }
+}
+
+/// Writes the steps of an install to the log, where a bundle's log viewer
+/// can follow them instead of progress bars.
+fn log_progress(event: &ProgressEvent) {
+    match event {
+        ProgressEvent::ResolvingVersion { component } => log::info!("resolving the version of {}", component),
+        ProgressEvent::DownloadStarted { url, total: Some(total) } => log::info!("downloading {} ({} bytes)", url, total),
+        ProgressEvent::DownloadStarted { url, total: None } => log::info!("downloading {}", url),
+        ProgressEvent::DownloadProgress { .. } => {}
+        ProgressEvent::Installing { component } => log::info!("installing {}", component),
+        ProgressEvent::Done { component } => log::info!("installed {}", component),
     }
 }
 
-async fn is_installed(arch: Architecture, runtime: Runtime, dotnet_version: &DotnetVersion) -> Result<bool> {
+/// Asks on the terminal whether to go ahead with the plan printed.
+fn confirm() -> Result<bool> {
+    eprint!("{} ", tr("plan-confirm", &[]));
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    let answer = answer.trim().to_lowercase();
+    Ok(tr("plan-confirm-answers", &[]).split(',').any(|yes| yes.trim() == answer))
+}
 
-    let version_req = VersionReq::parse(&dotnet_version.to_string())?;
-    let runtime_path = match runtime {
-        Runtime::Dotnet => "shared\\Microsoft.NETCore.App",
-        Runtime::AspCore => "shared\\Microsoft.AspNetCore.App",
-        Runtime::WindowsDesktop => "shared\\Microsoft.WindowsDesktop.App",
+/// Command line arguments, with `install` implied when no subcommand is given.
+fn args() -> Vec<OsString> {
+    let mut args: Vec<OsString> = std::env::args_os().collect();
+    let subcommands = Command::clap().p.subcommands;
+    let explicit = args.get(1).and_then(|arg| arg.to_str()).is_some_and(|arg| {
+        ["help", "-h", "--help", "-V", "--version"].contains(&arg)
+            || subcommands.iter().any(|subcommand| subcommand.get_name() == arg)
+    });
+    if !explicit {
+        args.insert(1, "install".into());
+    }
+    args
+}
+
+fn main() -> Result<()> {
+    match Command::from_iter(args()) {
+        Command::Install(arg) => install(arg),
+        Command::Check(arg) => check(arg),
+        Command::List(arg) => list(arg),
+        Command::Lock(arg) => lock(arg),
+        Command::Resolve(arg) => resolve(arg),
+        Command::Bundle(arg) => bundle(arg),
+        Command::Verify(arg) => verify(arg),
+        Command::Cache(CacheCommand::Clear) => dotnet5_webinst::clear_cache(),
+        Command::Catalog(CatalogCommand::Update { network, logging }) => {
+            logging.init()?;
+            update_catalog(network)
+        }
+        Command::Catalog(CatalogCommand::Show) => {
+            for entry in dotnet5_webinst::catalog_entries() {
+                let source = if entry.local { tr("catalog-local", &[]) } else { tr("catalog-built-in", &[]) };
+                println!("{} {} {} ({})", entry.name, entry.version, entry.url, source);
+            }
+            Ok(())
+        }
+        Command::Snapshot(SnapshotCommand::Export { file, arches, logging }) => {
+            logging.init()?;
+            let arches = if arches.is_empty() { Architecture::installable() } else { arches };
+            std::fs::write(&file, dotnet5_webinst::export_snapshot(&arches)?)
+                .map_err(|e| anyhow!("cannot write {}: {}", file.display(), e))
+        }
+        Command::Snapshot(SnapshotCommand::Apply(arg)) => apply_snapshot(arg),
+        Command::GenerateDocker(arg) => generate_docker(arg),
+        Command::Doctor(arg) => doctor(arg),
+        Command::Compat(arg) => compat(arg),
+        Command::Ensure(arg) => ensure(arg),
+        Command::Vcredist(arg) => vcredist(arg),
+        Command::Cleanup(arg) => cleanup(arg),
+        Command::Update(arg) => update(arg),
+        Command::Uninstall(arg) => uninstall(arg),
+        Command::InstallUpdater(arg) => install_updater(arg),
+        Command::RemoveUpdater(arg) => remove_updater(arg),
+        Command::Run(arg) => run_app(arg),
+        Command::Repair(mut arg) => {
+            arg.repair = true;
+            install(arg)
+        }
+        Command::Completions(arg) => {
+            completions(arg.shell);
+            Ok(())
+        }
+    }
+}
+
+fn completions(shell: CompletionShell) {
+    let name = env!("CARGO_PKG_NAME");
+    let shell = match shell {
+        CompletionShell::PowerShell => clap::Shell::PowerShell,
+        CompletionShell::Bash => clap::Shell::Bash,
+        CompletionShell::Zsh => clap::Shell::Zsh,
+        CompletionShell::Fish => clap::Shell::Fish,
+        CompletionShell::Clink => {
+            print!("clink.argmatcher(\"{}\")\n{}\n", name, clink_argmatcher(&Command::clap()));
+            return;
+        }
     };
+    Command::clap().gen_completions_to(name, shell, &mut std::io::stdout());
+}
+
+/// A clink Lua argmatcher completing the subcommands and flags of `app`.
+/// clap has no clink generator, so this walks its parsed definition.
+fn clink_argmatcher(app: &clap::App) -> String {
+    let mut matcher = String::new();
+    let subcommands = app
+        .p
+        .subcommands
+        .iter()
+        .map(|subcommand| format!("\"{}\" .. clink.argmatcher(){}", subcommand.p.meta.name, clink_argmatcher(subcommand)))
+        .collect::<Vec<_>>();
+    if !subcommands.is_empty() {
+        matcher.push_str(&format!(":addarg({{ {} }})", subcommands.join(", ")));
+    }
+
+    let flags = app
+        .p
+        .flags
+        .iter()
+        .map(|flag| &flag.s)
+        .chain(app.p.opts.iter().map(|opt| &opt.s))
+        .filter_map(|switched| switched.long)
+        .map(|long| format!("\"--{}\"", long))
+        .collect::<Vec<_>>();
+    if !flags.is_empty() {
+        matcher.push_str(&format!(":addflags({})", flags.join(", ")));
+    }
+    matcher
+}
+
+fn install(mut arg: InstallArg) -> Result<()> {
+    if arg.bootstrapper {
+        arg.bootstrap();
+    }
+    if let Some(dir) = &arg.crash_dir {
+        dotnet5_webinst::crash::install(dir.clone());
+    }
+    arg.logging.init()?;
+    arg.network.apply()?;
+    if let Some(timeout) = arg.installer_timeout {
+        dotnet5_webinst::set_installer_timeout(timeout);
+    }
+    arg.installing.apply();
+    dotnet5_webinst::set_machine_wide_only(arg.machine_wide_only);
+    if let Some(dir) = &arg.temp_dir {
+        dotnet5_webinst::set_temp_dir(dir)?;
+    }
+
+    if let Some(target) = &arg.progress_ipc {
+        if target == "stdout" && arg.installing.output == OutputFormat::Json {
+            bail!("--progress-ipc stdout already sends the report; drop --output json");
+        }
+        ipc::connect(target)?;
+    }
+
+    if arg.print_url {
+        return print_urls(arg.requests()?);
+    }
+
+    let record = Some(arg.lockfile.as_path()).filter(|_| arg.record);
+    let sbom = arg.sbom.as_deref();
+    let options = RunOptions { plan_only: arg.plan, yes: arg.yes, bootstrapper: arg.bootstrapper, record, sbom };
+    run(arg.requests(), &arg.installing, options)
+}
+
+/// Prints the URL of each installer `requests` would download, followed by
+/// its hash when the release metadata has one, for fetching it elsewhere.
+fn print_urls(requests: Vec<InstallRequest>) -> Result<()> {
+    for request in requests {
+        for entry in smol::block_on(request.lock())? {
+            match entry.hash {
+                Some(hash) => println!("{} {}", entry.url, hash),
+                None => println!("{}", entry.url),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check(arg: CheckArg) -> Result<()> {
+    arg.logging.init()?;
+    arg.network.apply()?;
+    dotnet5_webinst::set_machine_wide_only(arg.machine_wide_only);
+
+    let mut missing: Vec<Missing> = Vec::new();
+    for builder in arg.requirement.builders()? {
+        let mut builder = builder.skip_vcredist(arg.skip_vcredist).user(arg.user);
+        if let Some(dir) = &arg.install_dir {
+            builder = builder.install_dir(dir);
+        }
+        smol::block_on(builder.build()?.missing(&mut missing))?;
+    }
+
+    if arg.output == OutputFormat::Human {
+        for component in &missing {
+            println!("{}", tr("check-missing", &[("component", component)]));
+        }
+        if missing.is_empty() {
+            println!("{}", tr("check-nothing-missing", &[]));
+        }
+    } else {
+        println!("{}", serde_json::to_string_pretty(&missing)?);
+    }
+
+    if missing.iter().any(|component| component.name != "vcredist") {
+        std::process::exit(CHECK_RUNTIME_MISSING);
+    }
+    if !missing.is_empty() {
+        std::process::exit(CHECK_VCREDIST_MISSING);
+    }
+    Ok(())
+}
+
+fn list(arg: ListArg) -> Result<()> {
+    arg.logging.init()?;
+    dotnet5_webinst::set_machine_wide_only(arg.machine_wide_only);
 
-    let root_path = get_root_install(arch);
-    if !root_path.exists() {
-        return Ok(false)
+    let arches = if arg.arches.is_empty() { Architecture::installable() } else { arg.arches.clone() };
+    let installed: Vec<_> = arches.into_iter().flat_map(dotnet5_webinst::installed_runtimes).collect();
+    if arg.output == OutputFormat::Human {
+        for runtime in &installed {
+            println!("{}", runtime);
+        }
+    } else {
+        let installed: Vec<_> = installed
+            .iter()
+            .map(|installed| {
+                serde_json::json!({
+                    "runtime": installed.runtime.to_string().to_lowercase(),
+                    "arch": installed.arch.to_string(),
+                    "version": installed.version.to_string(),
+                    "root": installed.root,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&installed)?);
     }
+    Ok(())
+}
 
-    let mut entries = smol::fs::read_dir(root_path.join(runtime_path)).await?;
-    
-    while let Some(entry) = entries.try_next().await? {
-        let version = Version::parse(&entry.file_name().to_string_lossy())?;
-        let file_type = entry.file_type().await?;
+fn apply_snapshot(arg: SnapshotApplyArg) -> Result<()> {
+    arg.logging.init()?;
+    arg.network.apply()?;
+    arg.installing.apply();
 
-        if file_type.is_dir() && version_req.matches(&version) {
-            return Ok(true);
+    let requests = dotnet5_webinst::load_snapshot(&arg.file).and_then(|snapshot| {
+        snapshot.components.iter().map(|component| Ok(component.builder().build()?)).collect::<Result<Vec<_>>>()
+    });
+    run(requests, &arg.installing, RunOptions { plan_only: arg.plan, yes: arg.yes, ..RunOptions::default() })
+}
+
+fn vcredist(arg: VcredistArg) -> Result<()> {
+    arg.logging.init()?;
+    arg.network.apply()?;
+    if let Some(timeout) = arg.installer_timeout {
+        dotnet5_webinst::set_installer_timeout(timeout);
+    }
+    arg.installing.apply();
+    if let Some(dir) = &arg.temp_dir {
+        dotnet5_webinst::set_temp_dir(dir)?;
+    }
+
+    let mut builder = InstallRequest::builder()
+        .vcredist_only()
+        .arch(arg.arch)
+        .no_verify(arg.no_verify)
+        .quiet(arg.quiet)
+        .installer_ui(arg.ui)
+        .no_cache(arg.no_cache);
+    if let Some(url) = &arg.vcredist_url {
+        builder = builder.vcredist_url(url);
+    }
+    for installer_arg in &arg.installer_args {
+        builder = builder.installer_arg(installer_arg);
+    }
+    let requests = builder.build().map(|request| vec![request]).map_err(Into::into);
+    run(requests, &arg.installing, RunOptions { plan_only: arg.plan, yes: arg.yes, ..RunOptions::default() })
+}
+
+fn cleanup(arg: CleanupArg) -> Result<()> {
+    arg.logging.init()?;
+    arg.installing.apply();
+
+    let request = InstallRequest::builder()
+        .runtime(arg.runtime)
+        .arch(arg.arch)
+        .version(&arg.version)
+        .installer_ui(arg.ui)
+        .build()?;
+    ensure_elevated(arg.installing.no_elevate)?;
+    cancel::install();
+
+    let mut report = Report::default();
+    let result = smol::block_on(request.cleanup(&mut report));
+    print_report(arg.installing.output, &mut report, &result)?;
+    arg.installing.finish(result)
+}
+
+/// Runs this command again through the UAC prompt and exits with its code
+/// unless this process is elevated, or fails instead with --no-elevate.
+fn ensure_elevated(no_elevate: bool) -> Result<()> {
+    if !dotnet5_webinst::is_elevated() {
+        if no_elevate {
+            bail!(tr("elevation-required", &[]));
         }
+        eprintln!("{}", tr("elevation-requesting", &[]));
+        std::process::exit(dotnet5_webinst::relaunch_elevated()?);
     }
+    Ok(())
+}
 
-    return Ok(false);
+/// Prints what was done to each component, or the whole report with the
+/// outcome of `result` as JSON on stdout.
+fn print_report(output: OutputFormat, report: &mut Report, result: &Result<(), DotnetRedistError>) -> Result<()> {
+    if output == OutputFormat::Human {
+        for component in &report.components {
+            eprintln!("{}", component);
+        }
+    } else {
+        if let Err(e) = result {
+            report.status = report::Status::Failed;
+            report.error = Some(format!("{:#}", e));
+        }
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    }
+    Ok(())
 }
 
-fn is_vcruntime_installed(arch: Architecture) -> bool {
-    let path = match (arch, is_syswow64()) {
-        (Architecture::X64, true) => Path::new("C:\\Windows\\SysNative\\vcruntime140.dll"),
-        (Architecture::X64, false) => Path::new("C:\\Windows\\System32\\vcruntime140.dll"),
-        (Architecture::X86, true) => Path::new("C:\\Windows\\System32\\vcruntime140.dll"),
-        (Architecture::X86, false) => Path::new("C:\\Windows\\SysWOW64\\vcruntime140.dll"),
+fn install_updater(arg: InstallUpdaterArg) -> Result<()> {
+    ensure_elevated(arg.no_elevate)?;
+
+    dotnet5_webinst::install_updater(&arg.manifest, arg.interval, arg.log_file.as_deref())?;
+    let interval = format!("{:?}", arg.interval);
+    eprintln!(
+        "{}",
+        tr("updater-installed", &[("name", &UPDATER_TASK_NAME), ("manifest", &arg.manifest.display()), ("interval", &interval)])
+    );
+    Ok(())
+}
+
+fn remove_updater(arg: RemoveUpdaterArg) -> Result<()> {
+    ensure_elevated(arg.no_elevate)?;
+
+    if dotnet5_webinst::remove_updater()? {
+        eprintln!("{}", tr("updater-removed", &[("name", &UPDATER_TASK_NAME)]));
+    } else {
+        eprintln!("{}", tr("updater-missing", &[("name", &UPDATER_TASK_NAME)]));
+    }
+    Ok(())
+}
+
+fn run_app(arg: RunArg) -> Result<()> {
+    arg.logging.init()?;
+    arg.network.apply()?;
+    arg.installing.apply();
+    let (app, app_args) = arg.command.split_first().expect("clap requires the app");
+    let app = Path::new(app);
+
+    let requirements = dotnet5_webinst::app_requirements(app, arg.arch)?;
+    let mut requests = Vec::new();
+    for component in &requirements.components {
+        let mut builder = component.builder().version(&smol::block_on(dotnet5_webinst::resolve_channel(&component.version))?);
+        if let Some(dir) = &arg.install_dir {
+            builder = builder.install_dir(dir);
+        }
+        requests.push(builder.build()?);
+    }
+    let mut plan = Plan::default();
+    smol::block_on(InstallRequest::plan_all(&requests, &mut plan))?;
+
+    if plan.components.iter().any(|component| component.machine_wide) && !dotnet5_webinst::is_elevated() {
+        if arg.installing.no_elevate {
+            bail!(tr("elevation-required", &[]));
+        }
+        // Only the install is elevated; the app starts with this process's rights.
+        eprintln!("{}", tr("elevation-requesting", &[]));
+        let mut install_args = Args(vec!["install".into(), "--for-app".into(), app.into(), "--yes".into()]);
+        install_args.option("--arch", arg.arch.map(|arch| arch.to_string()));
+        arg.network.args(&mut install_args);
+        arg.logging.args(&mut install_args);
+        arg.installing.args(&mut install_args);
+        let code = dotnet5_webinst::run_elevated(&install_args.0)?;
+        let installed = match arg.installing.exit_codes {
+            ExitCodes::Installer => matches!(code, 0 | ERROR_SUCCESS_REBOOT_REQUIRED),
+            ExitCodes::Stable => matches!(code, exit::OK | exit::ALREADY_INSTALLED | exit::REBOOT_REQUIRED),
+        };
+        if !installed {
+            std::process::exit(code);
+        }
+    } else if !plan.components.is_empty() {
+        cancel::install();
+        let mut report = Report::default();
+        let result = requests.iter().try_for_each(|request| smol::block_on(request.install(&mut report)));
+        arg.installing.finish(result)?;
+        if arg.installing.output == OutputFormat::Human {
+            for component in report.components.iter().filter(|component| !matches!(component.action, report::Action::Skipped)) {
+                eprintln!("{}", component);
+            }
+        } else {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+    }
+
+    let dll = app.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("dll"));
+    let mut command = match (&arg.install_dir, dll) {
+        (Some(dir), true) => std::process::Command::new(dir.join(if cfg!(windows) { "dotnet.exe" } else { "dotnet" })),
+        (None, true) => std::process::Command::new("dotnet"),
+        (_, false) => std::process::Command::new(app),
     };
+    if dll {
+        command.arg(app);
+    }
+    command.args(app_args);
+    if let Some(dir) = &arg.install_dir {
+        command.env("DOTNET_ROOT", dir);
+    }
+    let status = command.status().map_err(|e| anyhow!("cannot start {}: {}", app.display(), e))?;
+    std::process::exit(status.code().unwrap_or(1));
+}
+
+fn uninstall(arg: UninstallArg) -> Result<()> {
+    arg.logging.init()?;
+    arg.installing.apply();
 
-    path.exists()
+    let mut builder = InstallRequest::builder()
+        .runtime(arg.runtime)
+        .arch(arg.arch)
+        .version(&arg.version)
+        .installer_ui(arg.ui);
+    if let Some(dir) = &arg.install_dir {
+        builder = builder.install_dir(dir);
+    }
+    let request = builder.build()?;
+    if arg.install_dir.is_none() {
+        ensure_elevated(arg.installing.no_elevate)?;
+    }
+    cancel::install();
+
+    let mut report = Report::default();
+    let result = smol::block_on(request.uninstall(&mut report));
+    print_report(arg.installing.output, &mut report, &result)?;
+    arg.installing.finish(result)
+}
+
+fn update(arg: UpdateArg) -> Result<()> {
+    arg.logging.init()?;
+    arg.network.apply()?;
+    arg.installing.apply();
+
+    let arches = if arg.arches.is_empty() { Architecture::installable() } else { arg.arches.clone() };
+    let updates = smol::block_on(dotnet5_webinst::available_updates(&arches, arg.security_only));
+    if arg.installing.output == OutputFormat::Human {
+        for update in &updates {
+            eprintln!("{}", update);
+        }
+    }
+    let requests = updates
+        .iter()
+        .map(|update| {
+            let request = InstallRequest::builder()
+                .runtime(update.runtime)
+                .arch(update.arch)
+                .version(&update.available.to_string())
+                .skip_vcredist(true)
+                .quiet(arg.quiet)
+                .installer_ui(arg.ui)
+                .build()?;
+            Ok(request)
+        })
+        .collect();
+    let options = RunOptions { plan_only: arg.dry_run, yes: arg.yes, sbom: arg.sbom.as_deref(), ..RunOptions::default() };
+    run(requests, &arg.installing, options)
 }
 
-fn download_url(arch: Architecture, runtime: Runtime, version: Version, product_version: &str) -> String {
-    let arch = match arch {
-        Architecture::X86 => "x86",
-        Architecture::X64 => "x64",
+/// How a [`run`] goes besides the [`Installing`] options.
+#[derive(Default)]
+struct RunOptions<'a> {
+    /// Only plan, without installing.
+    plan_only: bool,
+    /// Go ahead without asking to confirm the plan.
+    yes: bool,
+    /// Run as an ExePackage of a WiX Burn bundle.
+    bootstrapper: bool,
+    /// Lockfile to record what was installed in.
+    record: Option<&'a Path>,
+    /// Where to write an SBOM of what was installed.
+    sbom: Option<&'a Path>,
+}
+
+/// Plans and installs `requests`, elevating if needed, records what was
+/// installed in the lockfile at `record` and an SBOM of it at `sbom` if
+/// given, then reports the outcome
+/// and exits with the installer's code if it failed or needs a reboot, or
+/// with the stable code of the outcome if `--exit-codes` says so.
+///
+/// As a `bootstrapper`, messages go to the log, nobody watching the console,
+/// and the exit code is one a WiX Burn bundle understands.
+fn run(requests: Result<Vec<InstallRequest>>, installing: &Installing, options: RunOptions) -> Result<()> {
+    let RunOptions { plan_only, yes, bootstrapper, record, sbom } = options;
+    let Installing { output, exit_codes, no_elevate, .. } = *installing;
+    let say = |message: &dyn std::fmt::Display| {
+        if bootstrapper {
+            log::info!("{}", message);
+        } else {
+            eprintln!("{}", message);
+        }
     };
+    let mut report = Report::default();
+    let start = Instant::now();
+    let (mut plan_time, mut install_time) = (None, Duration::ZERO);
+    let result = requests.and_then(|requests| {
+        let mut plan = Plan::default();
+        smol::block_on(InstallRequest::plan_all(&requests, &mut plan))?;
+        plan_time = Some(start.elapsed());
+        if output == OutputFormat::Human {
+            for component in &plan.components {
+                say(&tr("plan", &[("plan", component)]));
+            }
+            say(&tr("plan", &[("plan", &plan)]));
+        }
+        let needs_elevation = plan.components.iter().any(|component| component.machine_wide);
+        let nothing_to_do = plan.components.is_empty();
+        report.plan = Some(plan);
 
-    match runtime {
-        Runtime::Dotnet => format!(
-            "{}/Runtime/{}/dotnet-runtime-{}-win-{}.exe",
-            BASE_URL, version, product_version, arch
-        ),
-        Runtime::AspCore => format!(
-            "{}/aspnetcore/Runtime/{}/aspnetcore-runtime-{}-win-{}.exe",
-            BASE_URL, version, product_version, arch
-        ),
-        Runtime::WindowsDesktop => {
-            if version.major >= 5 {
-                format!(
-                    "{}/WindowsDesktop/{}/windowsdesktop-runtime-{}-win-{}.exe",
-                    BASE_URL, version, product_version, arch
-                )
+        if plan_only {
+            return Ok(());
+        }
+        // The elevated copy runs in its own console, so its output isn't seen here.
+        if needs_elevation {
+            ensure_elevated(no_elevate)?;
+        }
+        // Asked after elevating, since the elevated copy shows the plan in its own console.
+        let interactive = std::io::stdin().is_terminal() && std::io::stderr().is_terminal();
+        if !yes && !nothing_to_do && output == OutputFormat::Human && !bootstrapper && interactive && !confirm()? {
+            bail!(DotnetRedistError::Cancelled(anyhow!("the plan was not confirmed")));
+        }
+        cancel::install();
+        let installing = Instant::now();
+        let installed = requests.iter().try_for_each(|request| smol::block_on(request.install(&mut report)));
+        install_time = installing.elapsed();
+        installed?;
+        if let Some(path) = record {
+            record_installed(path, &report)?;
+        }
+        match sbom {
+            Some(path) => dotnet5_webinst::write_sbom(&report, path),
+            None => Ok(()),
+        }
+    });
+
+    // Parent installers expect the failing installer's own code, and 3010
+    // when the machine must reboot before the runtime can be used.
+    let cancelled = result
+        .as_ref()
+        .err()
+        .is_some_and(|e| matches!(e.downcast_ref(), Some(DotnetRedistError::Cancelled(_))));
+    let exit_code = match &result {
+        Err(_) if cancelled => cancel::EXIT_CODE,
+        Err(_) => report
+            .components
+            .iter()
+            .find(|component| matches!(component.action, report::Action::Failed))
+            .and_then(|component| component.exit_code)
+            .unwrap_or(1),
+        Ok(()) if report.reboot_required => ERROR_SUCCESS_REBOOT_REQUIRED,
+        Ok(()) => 0,
+    };
+    let exit_code = match exit_codes {
+        _ if bootstrapper => burn_exit_code(exit_code, cancelled),
+        ExitCodes::Stable => exit::code(result.as_ref().err(), &report),
+        ExitCodes::Installer => exit_code,
+    };
+    if let Err(e) = &result {
+        report.status = if cancelled { report::Status::Cancelled } else { report::Status::Failed };
+        report.error = Some(format!("{:#}", e));
+    }
+    if !plan_only {
+        let plan_time = plan_time.unwrap_or_else(|| start.elapsed());
+        report.summary = Some(report::Summary::new(&report, plan_time, install_time, start.elapsed()));
+    }
+    ipc::report(&report);
+    smol::block_on(dotnet5_webinst::telemetry::send(&report, result.as_ref().err()));
+    dotnet5_webinst::timing::write_report();
+
+    if output == OutputFormat::Human {
+        for component in &report.components {
+            say(component);
+        }
+        if let Some(summary) = report.summary.as_ref().filter(|_| !report.components.is_empty()) {
+            say(&tr("summary", &[("summary", summary)]));
+        }
+        if report.reboot_required {
+            say(&tr("reboot-notice", &[]));
+        }
+        // On stdout, for the caller to evaluate, unless that carries the messages for a parent process.
+        for command in report.environment.iter().filter(|_| !ipc::owns_stdout()) {
+            println!("{}", command);
+        }
+        if cancelled {
+            say(&tr("cancelled", &[]));
+        } else if let Err(e) = &result {
+            if bootstrapper {
+                log::error!("{:?}", e);
             } else {
-                format!(
-                    "{}/Runtime/{}/windowsdesktop-runtime-{}-win-{}.exe",
-                    BASE_URL, version, product_version, arch
-                )
+                eprintln!("{}", tr("error", &[("error", &format!("{:?}", e))]));
             }
         }
+    } else {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    }
+
+    if exit_code != 0 {
+        std::process::exit(exit_code);
+    }
+    Ok(())
+}
+
+/// The exit code of a run as a Burn bundle understands it: success, a reboot
+/// required or started, or a Windows Installer failure code, 1603 for any
+/// failure that has none.
+fn burn_exit_code(code: i32, cancelled: bool) -> i32 {
+    match code {
+        _ if cancelled => ERROR_INSTALL_USEREXIT,
+        0 | ERROR_SUCCESS_REBOOT_REQUIRED | ERROR_SUCCESS_REBOOT_INITIATED => code,
+        _ if BURN_FAILURE_CODES.contains(&code) => code,
+        _ => ERROR_INSTALL_FAILURE,
     }
 }
 
-async fn find_product_version(runtime: Runtime, version: &Version) -> Result<String> {
-    let url = match runtime {
-        Runtime::Dotnet | Runtime::WindowsDesktop => {
-            format!("{}/Runtime/{}/productVersion.txt", CDN_URL, version)
+fn doctor(arg: DoctorArg) -> Result<()> {
+    arg.logging.init()?;
+    arg.network.apply()?;
+
+    if !arg.firewall {
+        println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+        for fact in dotnet5_webinst::diagnose() {
+            println!("{}", fact);
         }
-        Runtime::AspCore => format!("{}/aspnetcore/Runtime{}", BASE_URL, version),
-    };
+        println!();
+    }
+
+    let endpoints = dotnet5_webinst::firewall::endpoints()?;
+    let mut blocked = 0;
+    for endpoint in &endpoints {
+        let address = format!("{}:{}", endpoint.host, endpoint.port);
+        match smol::block_on(dotnet5_webinst::firewall::check(endpoint)) {
+            Ok(()) => println!("{}", tr("doctor-connection-ok", &[("endpoint", &address)])),
+            Err(e) => {
+                blocked += 1;
+                println!("{}", tr("doctor-connection-failed", &[("endpoint", &address), ("error", &format!("{:#}", e))]));
+            }
+        }
+    }
+    println!();
+
+    println!("{}", tr("doctor-allow", &[]));
+    for endpoint in &endpoints {
+        println!("{}", endpoint);
+    }
+
+    if blocked > 0 {
+        bail!(tr("doctor-blocked", &[("blocked", &blocked), ("total", &endpoints.len())]));
+    }
+    Ok(())
+}
+
+fn compat(arg: CompatArg) -> Result<()> {
+    arg.logging.init()?;
 
-    let mut response = http::get(&url).await?;
-    if response.status() == StatusCode::Ok {
-        Ok(response
-            .body_string()
-            .await
-            .map_err(Error::msg)?
-            .trim()
-            .to_string())
+    let compatibility = smol::block_on(dotnet5_webinst::check_compatibility(&arg.app, arg.arch))?;
+    if arg.output == OutputFormat::Human {
+        for check in &compatibility.checks {
+            println!("{}", check);
+        }
     } else {
-        Ok(version.to_string())
+        println!("{}", serde_json::to_string_pretty(&compatibility)?);
+    }
+
+    let failed = compatibility.checks.iter().filter(|check| !check.ok).count();
+    if failed > 0 {
+        bail!(tr("compat-failed", &[
+            ("app", &arg.app.display()),
+            ("failed", &failed),
+            ("total", &compatibility.checks.len()),
+        ]));
     }
+    if arg.output == OutputFormat::Human {
+        println!("\n{}", tr("compat-ok", &[("app", &arg.app.display())]));
+    }
+    Ok(())
 }
 
-async fn find_best_version(runtime: Runtime, version: DotnetVersion) -> Result<Version> {
-    if let DotnetVersion {
-        major,
-        minor: Some(minor),
-        patch: Some(patch),
-    } = version
-    {
-        return Ok(Version::new(major, minor, patch));
+fn ensure(mut arg: EnsureArg) -> Result<()> {
+    if let Some(dir) = &arg.install.crash_dir {
+        dotnet5_webinst::crash::install(dir.clone());
+    }
+    arg.install.logging.init()?;
+    arg.install.network.apply()?;
+    arg.install.latest_patch = true;
+    if let Some(timeout) = arg.install.installer_timeout {
+        dotnet5_webinst::set_installer_timeout(timeout);
+    }
+    arg.install.installing.apply();
+    dotnet5_webinst::set_machine_wide_only(arg.install.machine_wide_only);
+    if let Some(dir) = &arg.install.temp_dir {
+        dotnet5_webinst::set_temp_dir(dir)?;
     }
 
-    let url = match runtime {
-        Runtime::Dotnet | Runtime::WindowsDesktop => format!("{}/Runtime", BASE_URL),
-        Runtime::AspCore => format!("{}/aspnetcore/Runtime", BASE_URL),
-    };
+    // There is nobody to answer a UAC prompt between checks.
+    if !dotnet5_webinst::is_elevated() {
+        bail!(tr("ensure-elevation-required", &[]));
+    }
 
-    let minor = if let Some(minor) = version.minor {
-        minor
-    } else {
-        find_newest_minor(&url, version.major).await?
+    loop {
+        let mut report = Report::default();
+        let result = arg
+            .install
+            .requests()
+            .and_then(|requests| requests.iter().try_for_each(|request| Ok(smol::block_on(request.install(&mut report))?)));
+
+        for component in &report.components {
+            if !matches!(component.action, report::Action::Skipped) {
+                log::info!("{}", component);
+            }
+        }
+        let result = match result {
+            Ok(()) if arg.install.record => record_installed(&arg.install.lockfile, &report),
+            result => result,
+        };
+        let result = match (result, &arg.install.sbom) {
+            (Ok(()), Some(path)) => dotnet5_webinst::write_sbom(&report, path),
+            (result, _) => result,
+        };
+        match &result {
+            Ok(()) if report.reboot_required => log::warn!("{}", tr("ensure-reboot", &[])),
+            Ok(()) => log::info!("{}", tr("ensure-up-to-date", &[])),
+            Err(_) if arg.once => {}
+            Err(e) => log::error!("{:#}", e),
+        }
+        dotnet5_webinst::timing::write_report();
+
+        if arg.once && arg.install.installing.exit_codes == ExitCodes::Stable {
+            if let Err(e) = &result {
+                log::error!("{:#}", e);
+            }
+            std::process::exit(exit::code(result.as_ref().err(), &report));
+        }
+        if arg.once {
+            return result;
+        }
+        log::info!("{}", tr("ensure-waiting", &[("interval", &format!("{:?}", arg.interval))]));
+        std::thread::sleep(arg.interval);
+    }
+}
+
+/// Pins what `report` installed in the lockfile at `path`, next to what it pins already.
+fn record_installed(path: &Path, report: &Report) -> Result<()> {
+    let mut lockfile = if path.exists() { Lockfile::load(path)? } else { Lockfile::default() };
+    let count = lockfile.record(report);
+    lockfile.save(path)?;
+    log::info!("{}", tr("lock-recorded", &[("count", &count), ("path", &path.display())]));
+    Ok(())
+}
+
+/// Parses an interval such as `90`, `90s`, `30m`, `12h` or `1d`.
+fn parse_interval(s: &str) -> Result<Duration> {
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => s.split_at(index),
+        None => (s, "s"),
     };
+    let secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => bail!(tr("interval-unit", &[("unit", &format!("{:?}", unit))])),
+    };
+    let interval = Duration::from_secs(number.parse::<u64>()? * secs);
+    if interval.is_zero() {
+        bail!(tr("interval-zero", &[]));
+    }
+    Ok(interval)
+}
 
-    let full_url = format!("{}/{}.{}/latest.version", url, version.major, minor);
-    let version_text = http::get(&full_url)
-        .await?
-        .body_string()
-        .await
-        .map_err(Error::msg)?;
+fn lock(arg: LockArg) -> Result<()> {
+    arg.logging.init()?;
+    arg.network.apply()?;
 
-    if let Some(version_text) = version_text.lines().last() {
-        Ok(Version::from_str(version_text)?)
+    let mut lockfile = if arg.lockfile.exists() {
+        Lockfile::load(&arg.lockfile)?
     } else {
-        Err(anyhow!(
-            "version file did not contain expected version text"
-        ))
+        Lockfile::default()
+    };
+    for builder in arg.requirement.builders()? {
+        let request = builder.build()?;
+        for entry in smol::block_on(request.lock())? {
+            eprintln!("{}", tr("lock-locked", &[
+                ("runtime", &entry.runtime),
+                ("arch", &entry.arch),
+                ("version", &entry.version),
+                ("resolved", &entry.resolved_version),
+            ]));
+            lockfile.insert(entry);
+        }
     }
+    lockfile.save(&arg.lockfile)
 }
 
-async fn find_newest_minor(url: &str, major_version: u64) -> Result<u64> {
-    for minor in 0.. {
-        let full_url = format!("{}/{}.{}/latest.version", url, major_version, minor);
-        let response = http::get(&full_url).await?;
-        if StatusCode::NotFound == response.status() {
-            if minor > 0 {
-                return Ok(minor - 1);
-            } else {
-                return Err(anyhow!("No available versions found"));
+fn resolve(arg: ResolveArg) -> Result<()> {
+    arg.logging.init()?;
+    arg.network.apply()?;
+
+    let mut mismatches = 0;
+    for builder in arg.requirement.builders()? {
+        let request = builder.build()?;
+        for entry in smol::block_on(request.lock())? {
+            println!("{} {} {} {}", entry.runtime, entry.arch, entry.resolved_version, entry.url);
+            if let Some(pattern) = &arg.assert {
+                if !matches_pattern(&entry.resolved_version, pattern) {
+                    eprintln!("{}", tr("resolve-outside", &[
+                        ("runtime", &entry.runtime),
+                        ("arch", &entry.arch),
+                        ("version", &entry.version),
+                        ("resolved", &entry.resolved_version),
+                        ("pattern", pattern),
+                    ]));
+                    mismatches += 1;
+                }
             }
         }
     }
 
-    unreachable!();
+    if mismatches > 0 {
+        bail!(tr("resolve-mismatches", &[("mismatches", &mismatches)]));
+    }
+    Ok(())
+}
+
+/// Matches a version against a dotted pattern in which `*` stands for any
+/// component, or for all remaining components when it comes last.
+fn matches_pattern(version: &str, pattern: &str) -> bool {
+    let mut parts = version.split('.');
+    let mut wanted = pattern.split('.').peekable();
+    while let Some(want) = wanted.next() {
+        if want == "*" && wanted.peek().is_none() {
+            return true;
+        }
+        match parts.next() {
+            Some(part) if want == "*" || want == part => {}
+            _ => return false,
+        }
+    }
+    parts.next().is_none()
 }
 
-fn get_root_install(arch: Architecture) -> &'static Path {
-    match (arch, is_64bit_os()) {
-        (Architecture::X64, true) | (Architecture::X86, false) => Path::new("C:\\Program Files\\dotnet"),
-        (Architecture::X86, true) => Path::new("C:\\Program Files (x86)\\dotnet"),
-        _ => unreachable!()
+fn verify(arg: VerifyArg) -> Result<()> {
+    arg.logging.init()?;
+    arg.network.apply()?;
+
+    match smol::block_on(dotnet5_webinst::identify_installer(&arg.file))? {
+        Some(entry) => {
+            println!("{} {} {}", entry.name, entry.version, entry.url);
+            Ok(())
+        }
+        None => Err(DotnetRedistError::VerificationFailed(anyhow!(
+            "{} is not an installer or archive in the release metadata",
+            arg.file.display()
+        ))
+        .into()),
     }
 }
 
-fn is_64bit_os() -> bool {
-    std::env::var_os("PROCESSOR_ARCHITEW6432").is_some() || std::env::consts::ARCH == "x86_64"
+fn update_catalog(network: Network) -> Result<()> {
+    network.apply()?;
+
+    let count = smol::block_on(dotnet5_webinst::update_catalog())?;
+    eprintln!("{}", tr("catalog-updated", &[("count", &count)]));
+    Ok(())
+}
+
+fn generate_docker(arg: GenerateDockerArg) -> Result<()> {
+    arg.logging.init()?;
+    arg.network.apply()?;
+
+    let mut builders = arg.requirement.builders()?;
+    if builders.len() != 1 {
+        bail!(tr("docker-single", &[]));
+    }
+    let request = builders.remove(0).build()?;
+    print!("{}", smol::block_on(request.dockerfile(arg.container))?);
+    Ok(())
 }
 
-fn is_syswow64() -> bool {
-    std::env::var_os("PROCESSOR_ARCHITEW6432").is_some()
+fn bundle(arg: BundleArg) -> Result<()> {
+    arg.logging.init()?;
+    arg.network.apply()?;
+
+    let mut bundle = Bundle::open(&arg.dir)?;
+    for version in &arg.versions {
+        let version = smol::block_on(dotnet5_webinst::resolve_channel(version))?;
+        for &runtime in &arg.runtimes {
+            for &arch in &arg.arches {
+                let mut builder = InstallRequest::builder()
+                    .version(&version)
+                    .runtime(runtime)
+                    .arch(arch)
+                    .quiet(arg.quiet);
+                if let Some(url) = &arg.vcredist_url {
+                    builder = builder.vcredist_url(url);
+                }
+                let request = builder.build()?;
+                smol::block_on(request.bundle_into(&mut bundle))?;
+            }
+        }
+    }
+
+    Ok(())
 }
@@ -0,0 +1,127 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Result, anyhow};
+use http_types::{Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{DotnetRedistError, cache, http, verify::hex};
+
+/// A metadata response kept on disk, with what the server needs to tell
+/// whether it has changed since.
+#[derive(Serialize, Deserialize)]
+struct Cached {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    body: String,
+}
+
+/// How long a fetched file is reused within the process before it is
+/// revalidated, long enough for one run's resolution.
+const SHARED_FOR: Duration = Duration::from_secs(60);
+
+/// A file fetched by this process and when.
+type Fetched = Option<(Instant, Option<String>)>;
+
+/// The files this process fetched, each behind a lock so that requests
+/// resolving at the same time wait for one fetch instead of each making it.
+static FETCHED: OnceLock<Mutex<HashMap<String, Arc<smol::lock::Mutex<Fetched>>>>> = OnceLock::new();
+
+/// Fetches the text at `url`, or `None` if the server has no such file.
+///
+/// A file is fetched once for all requests resolving within [`SHARED_FOR`].
+/// Responses are cached on disk and revalidated with a conditional request,
+/// so unchanged metadata costs a `304 Not Modified`. When the server cannot
+/// be reached the cached copy is used as it is.
+pub(crate) async fn get_text(url: &str) -> Result<Option<String>> {
+    let slot = FETCHED
+        .get_or_init(Mutex::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .entry(url.to_string())
+        .or_default()
+        .clone();
+    let mut fetched = slot.lock().await;
+    if let Some((at, body)) = fetched.as_ref().filter(|(at, _)| at.elapsed() < SHARED_FOR) {
+        log::debug!("{} was fetched {:?} ago", url, at.elapsed());
+        return Ok(body.clone());
+    }
+    let body = fetch(url).await?;
+    *fetched = Some((Instant::now(), body.clone()));
+    Ok(body)
+}
+
+/// Fetches the text at `url` through the disk cache.
+async fn fetch(url: &str) -> Result<Option<String>> {
+    let path = path(url);
+    let cached = path.as_deref().and_then(load).filter(|cached| cached.url == url);
+
+    let etag = cached.as_ref().and_then(|cached| cached.etag.as_deref());
+    let last_modified = cached.as_ref().and_then(|cached| cached.last_modified.as_deref());
+    let mut response = match (http::get_if_modified(url, etag, last_modified).await, cached) {
+        (Ok(response), cached) if response.status() == StatusCode::NotModified && cached.is_some() => {
+            log::debug!("{} is unchanged", url);
+            return Ok(cached.map(|cached| cached.body));
+        }
+        (Ok(response), _) => response,
+        (Err(e), Some(cached)) => {
+            log::warn!("{:#}; using the copy cached earlier", e);
+            return Ok(Some(cached.body));
+        }
+        (Err(e), None) => return Err(e),
+    };
+
+    if response.status().is_client_error() {
+        return Ok(None);
+    }
+    if response.status() != StatusCode::Ok {
+        return Err(DotnetRedistError::Network(anyhow!("could not fetch {}: {}", url, response.status())).into());
+    }
+
+    let body = http::text(&mut response).await?;
+    if let Some(path) = path {
+        let cached = Cached {
+            url: url.to_string(),
+            etag: header(&response, "ETag"),
+            last_modified: header(&response, "Last-Modified"),
+            body,
+        };
+        if let Err(e) = store(&path, &cached) {
+            log::debug!("could not cache {}: {}", url, e);
+        }
+        return Ok(Some(cached.body));
+    }
+    Ok(Some(body))
+}
+
+/// Where the response from `url` is cached, next to the installers.
+fn path(url: &str) -> Option<PathBuf> {
+    let key = hex(&Sha256::digest(url.as_bytes()));
+    Some(cache::dir()?.join("metadata").join(format!("{}.json", &key[..32])))
+}
+
+fn load(path: &Path) -> Option<Cached> {
+    serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+fn store(path: &Path, cached: &Cached) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    // Write under a temporary name so other runs never read a partial file.
+    let partial = path.with_extension("partial");
+    fs::write(&partial, serde_json::to_string(cached)?)?;
+    fs::rename(&partial, path)
+}
+
+fn header(response: &Response, name: &str) -> Option<String> {
+    response.header(name).map(|values| values.last().as_str().to_string())
+}
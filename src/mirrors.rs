@@ -0,0 +1,97 @@
+use std::sync::OnceLock;
+
+use crate::{BASE_URL, CDN_URL};
+
+/// The newer download host, with the same layout as the others. Release
+/// metadata links installers there.
+const BUILDS_URL: &str = "https://builds.dotnet.microsoft.com/dotnet";
+
+/// The feed of daily builds, with the layout of the download server but a
+/// `latest.version` for each channel's newest build.
+const DAILY_URL: &str = "https://ci.dot.net/public";
+
+/// The NuGet flat container that dotnet global tool packages are looked up in.
+const NUGET_URL: &str = "https://api.nuget.org/v3-flatcontainer";
+
+static MIRRORS: OnceLock<Vec<String>> = OnceLock::new();
+static FEED: OnceLock<String> = OnceLock::new();
+static DAILY_FEED: OnceLock<String> = OnceLock::new();
+static NUGET_FEED: OnceLock<String> = OnceLock::new();
+
+/// Replaces the official endpoints with `feed`, an internal mirror with the
+/// layout of `https://dotnetcli.azureedge.net/dotnet`, for networks that
+/// block them. Installers linked from other hosts are still fetched there.
+pub fn set_feed(feed: String) {
+    let _ = FEED.set(feed.trim_end_matches('/').to_string());
+}
+
+/// Whether a feed replaces the official endpoints.
+pub(crate) fn has_feed() -> bool {
+    FEED.get().is_some()
+}
+
+/// The endpoints serving the dotnet download server's layout before any
+/// mirrors: the feed if there is one, else the CDN and the primary endpoint.
+pub(crate) fn endpoints() -> Vec<&'static str> {
+    match FEED.get() {
+        Some(feed) => vec![feed.as_str()],
+        None => vec![CDN_URL, BASE_URL],
+    }
+}
+
+/// Replaces the daily builds feed with `feed`, a mirror of `https://ci.dot.net/public`.
+pub fn set_daily_feed(feed: String) {
+    let _ = DAILY_FEED.set(feed.trim_end_matches('/').to_string());
+}
+
+/// The feed daily builds are resolved and downloaded from.
+pub(crate) fn daily_endpoint() -> &'static str {
+    DAILY_FEED.get().map_or(DAILY_URL, String::as_str)
+}
+
+/// Replaces nuget.org with `feed`, a NuGet v3 flat container such as
+/// `https://api.nuget.org/v3-flatcontainer`, for global tool packages.
+pub fn set_nuget_feed(feed: String) {
+    let _ = NUGET_FEED.set(feed.trim_end_matches('/').to_string());
+}
+
+/// The flat container global tool packages are looked up in.
+pub(crate) fn nuget_endpoint() -> &'static str {
+    NUGET_FEED.get().map_or(NUGET_URL, String::as_str)
+}
+
+/// Adds mirrors of the dotnet download server, tried after the official endpoints.
+///
+/// Each mirror is the equivalent of `https://dotnetcli.azureedge.net/dotnet`.
+pub fn set_mirrors(mirrors: Vec<String>) {
+    let mirrors = mirrors
+        .into_iter()
+        .map(|mirror| mirror.trim_end_matches('/').to_string())
+        .collect();
+    let _ = MIRRORS.set(mirrors);
+}
+
+/// The configured mirrors.
+pub(crate) fn list() -> &'static [String] {
+    MIRRORS.get().map(Vec::as_slice).unwrap_or_default()
+}
+
+/// Lists the URLs to try for `url`: the [`endpoints`], then any mirrors.
+/// URLs outside the dotnet download server are left alone, except that a
+/// feed also stands in for the newer download host.
+pub(crate) fn candidates(url: &str) -> Vec<String> {
+    let official: &[&str] = match FEED.get() {
+        Some(_) => &[CDN_URL, BASE_URL, BUILDS_URL],
+        None => &[CDN_URL, BASE_URL],
+    };
+    let path = match official.iter().find_map(|endpoint| url.strip_prefix(endpoint)) {
+        Some(path) => path,
+        None => return vec![url.to_string()],
+    };
+
+    endpoints()
+        .into_iter()
+        .chain(MIRRORS.get().into_iter().flatten().map(String::as_str))
+        .map(|endpoint| format!("{}{}", endpoint, path))
+        .collect()
+}
@@ -0,0 +1,34 @@
+use semver::Version;
+
+use crate::{
+    registry::{self, View},
+    releases::Artifact,
+};
+
+/// The offline installer, which covers both x86 and x64.
+const URL: &str = "https://download.visualstudio.microsoft.com/download/pr/2d6bb6b2-226a-4baa-bdec-798822606ff1/8494001c276a4b96804cde7829c04d7f/ndp48-x86-x64-allos-enu.exe";
+const FULL_KEY: &str = "SOFTWARE\\Microsoft\\NET Framework Setup\\NDP\\v4\\Full";
+/// The lowest `Release` value of 4.8, as on Windows 10 May 2019 Update;
+/// other Windows versions record higher ones.
+const RELEASE_48: u32 = 528040;
+
+pub(crate) fn version() -> Version {
+    Version::new(4, 8, 0)
+}
+
+/// Whether .NET Framework 4.8 or a later 4.x is installed.
+pub(crate) fn is_installed() -> bool {
+    let release = registry::read_dword(FULL_KEY, "Release", View::Registry64);
+    log::debug!(".NET Framework 4 release: {:?}", release);
+    release.is_some_and(|release| release >= RELEASE_48)
+}
+
+/// The offline installer. Microsoft publishes no hash for it, so only its
+/// signature can be checked.
+pub(crate) fn artifact() -> Artifact {
+    Artifact {
+        version: version(),
+        url: URL.to_string(),
+        hash: None,
+    }
+}
@@ -0,0 +1,74 @@
+use anyhow::{Result, anyhow, bail};
+
+use crate::{
+    DotnetRedistError,
+    platform::{is_windows_server, os_description, windows_build},
+};
+
+/// The oldest Windows a range of .NET releases supports, from the supported
+/// OS lists Microsoft publishes with each release.
+struct WindowsSupport {
+    /// The first major release the row applies to, until the next row's.
+    major: u64,
+    client: (u32, &'static str),
+    server: (u32, &'static str),
+}
+
+const WINDOWS_SUPPORT: &[WindowsSupport] = &[
+    WindowsSupport {
+        major: 0,
+        client: (7601, "Windows 7 SP1"),
+        server: (7601, "Windows Server 2008 R2 SP1"),
+    },
+    WindowsSupport {
+        major: 5,
+        client: (7601, "Windows 7 SP1"),
+        server: (9200, "Windows Server 2012"),
+    },
+    WindowsSupport {
+        major: 7,
+        client: (9600, "Windows 8.1"),
+        server: (9200, "Windows Server 2012"),
+    },
+    WindowsSupport {
+        major: 8,
+        client: (14393, "Windows 10 1607"),
+        server: (9200, "Windows Server 2012"),
+    },
+];
+
+/// The oldest Windows build .NET `major` supports on this edition of
+/// Windows, and its name.
+pub(crate) fn minimum_windows(major: u64) -> (u32, &'static str) {
+    let support = WINDOWS_SUPPORT
+        .iter()
+        .rev()
+        .find(|support| support.major <= major)
+        .unwrap_or(&WINDOWS_SUPPORT[0]);
+    if is_windows_server() {
+        support.server
+    } else {
+        support.client
+    }
+}
+
+/// Fails if this Windows is older than .NET `major` supports, which its
+/// installer would only report with an opaque exit code.
+pub(crate) fn check(major: u64) -> Result<()> {
+    // Only Windows has a build to compare, and one that cannot be read is
+    // left to the installer.
+    let build = match windows_build() {
+        Some(build) => build,
+        None => return Ok(()),
+    };
+    let (minimum, name) = minimum_windows(major);
+    if build < minimum {
+        bail!(DotnetRedistError::UnsupportedPlatform(anyhow!(
+            ".NET {} needs {} or later, but this is {}",
+            major,
+            name,
+            os_description()
+        )));
+    }
+    Ok(())
+}
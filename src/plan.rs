@@ -0,0 +1,116 @@
+use std::fmt::Display;
+
+use serde::Serialize;
+
+use crate::Runtime;
+
+/// What a run is expected to download and install, worked out before it starts.
+#[derive(Default, Serialize)]
+pub struct Plan {
+    pub components: Vec<PlannedComponent>,
+    /// Bytes to download, counting only components whose size is known.
+    pub download_bytes: u64,
+    pub installed_bytes: u64,
+    pub duration_secs: u64,
+}
+
+/// A missing component and rough estimates of what installing it costs.
+#[derive(Serialize)]
+pub struct PlannedComponent {
+    pub name: String,
+    pub arch: String,
+    pub version: Option<String>,
+    /// The URL of the installer, or its path if it is local.
+    pub source: Option<String>,
+    /// `None` when the server doesn't report a size; zero for local installers.
+    pub download_bytes: Option<u64>,
+    pub installed_bytes: Option<u64>,
+    /// Time the installer itself takes to run, excluding the download.
+    pub duration_secs: u64,
+    /// Whether it is installed for the whole machine, which needs
+    /// administrator rights, rather than into a directory of its own.
+    pub machine_wide: bool,
+}
+
+/// A component detection found missing, named as in the plan and report.
+#[derive(Serialize, PartialEq, Eq)]
+pub struct Missing {
+    pub name: String,
+    pub arch: String,
+    pub version: Option<String>,
+}
+
+impl Plan {
+    pub(crate) fn push(&mut self, component: PlannedComponent) {
+        self.download_bytes += component.download_bytes.unwrap_or(0);
+        self.installed_bytes += component.installed_bytes.unwrap_or(0);
+        self.duration_secs += component.duration_secs;
+        self.components.push(component);
+    }
+}
+
+/// Installers are compressed; these are typical ratios of installed size to
+/// installer size and typical silent install times on an ordinary machine.
+pub(crate) fn estimate(runtime: Option<Runtime>, package_bytes: Option<u64>) -> (Option<u64>, u64) {
+    let (ratio, secs) = match runtime {
+        None => (2.0, 20),
+        Some(Runtime::Dotnet) | Some(Runtime::AspCore) => (2.5, 30),
+        Some(Runtime::WindowsDesktop) => (2.5, 45),
+        Some(Runtime::HostingBundle) => (2.0, 60),
+        Some(Runtime::Sdk) => (3.0, 120),
+        Some(Runtime::NetFx48) => (3.0, 300),
+    };
+    (package_bytes.map(|bytes| (bytes as f64 * ratio) as u64), secs)
+}
+
+impl Display for PlannedComponent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.name, self.arch)?;
+        if let Some(version) = &self.version {
+            write!(f, " {}", version)?;
+        }
+        match self.download_bytes {
+            Some(0) => f.write_str(": local installer")?,
+            Some(bytes) => write!(f, ": {} download", megabytes(bytes))?,
+            None => f.write_str(": unknown download size")?,
+        }
+        match &self.source {
+            Some(path) if self.download_bytes == Some(0) => write!(f, " {}", path)?,
+            Some(url) => write!(f, " from {}", url)?,
+            None => {}
+        }
+        if let Some(bytes) = self.installed_bytes {
+            write!(f, ", about {} installed", megabytes(bytes))?;
+        }
+        write!(f, ", about {}s to install", self.duration_secs)
+    }
+}
+
+impl Display for Missing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.name, self.arch)?;
+        if let Some(version) = &self.version {
+            write!(f, " {}", version)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Plan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.components.is_empty() {
+            return f.write_str("nothing to install");
+        }
+        write!(
+            f,
+            "{} to download, about {} installed, about {}s to install",
+            megabytes(self.download_bytes),
+            megabytes(self.installed_bytes),
+            self.duration_secs
+        )
+    }
+}
+
+fn megabytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / 1_000_000.0)
+}
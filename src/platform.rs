@@ -0,0 +1,173 @@
+use std::sync::OnceLock;
+
+use crate::registry::{self, View};
+
+const CURRENT_VERSION_KEY: &str = "SOFTWARE\\Microsoft\\Windows NT\\CurrentVersion";
+
+/// PE machine types, as `IsWow64Process2` reports them.
+const IMAGE_FILE_MACHINE_UNKNOWN: u16 = 0;
+pub(crate) const IMAGE_FILE_MACHINE_I386: u16 = 0x014c;
+pub(crate) const IMAGE_FILE_MACHINE_AMD64: u16 = 0x8664;
+pub(crate) const IMAGE_FILE_MACHINE_ARM64: u16 = 0xaa64;
+
+/// The machine this process is built for and the one the OS runs on.
+#[derive(Copy, Clone)]
+struct Machines {
+    /// The machine WOW64 emulates for this process, or unknown if it doesn't
+    /// run under WOW64. x64 emulation on ARM64 is not WOW64.
+    process: u16,
+    native: u16,
+}
+
+fn machines() -> Machines {
+    static MACHINES: OnceLock<Machines> = OnceLock::new();
+    *MACHINES.get_or_init(query)
+}
+
+/// Whether the OS is 64-bit, even when this process is 32-bit.
+pub(crate) fn is_64bit_os() -> bool {
+    matches!(machines().native, IMAGE_FILE_MACHINE_AMD64 | IMAGE_FILE_MACHINE_ARM64)
+}
+
+/// Whether the machine is ARM64, even when this process runs emulated.
+pub(crate) fn is_arm64_host() -> bool {
+    machines().native == IMAGE_FILE_MACHINE_ARM64
+}
+
+/// Whether this is a 32-bit process on 64-bit Windows, which sees `SysWOW64`
+/// as its system directory.
+pub(crate) fn is_wow64() -> bool {
+    machines().process != IMAGE_FILE_MACHINE_UNKNOWN
+}
+
+/// The Windows build number, such as 19045.
+pub(crate) fn windows_build() -> Option<u32> {
+    registry::read_string(CURRENT_VERSION_KEY, "CurrentBuildNumber", View::Registry64)?.parse().ok()
+}
+
+/// Whether this is a server edition of Windows, which .NET supports from
+/// different builds than client editions.
+pub(crate) fn is_windows_server() -> bool {
+    installation_type().is_some_and(|kind| kind.starts_with("Server") || kind == "Nano Server")
+}
+
+/// Whether this is Server Core or Nano Server, which lack the Desktop
+/// Experience that Windows Forms and WPF need.
+pub(crate) fn is_server_core() -> bool {
+    installation_type().is_some_and(|kind| kind == "Server Core" || kind == "Nano Server")
+}
+
+/// Client, Server, Server Core or Nano Server.
+fn installation_type() -> Option<String> {
+    registry::read_string(CURRENT_VERSION_KEY, "InstallationType", View::Registry64)
+}
+
+/// The name and version of the OS, for diagnostics.
+pub(crate) fn os_description() -> String {
+    if cfg!(windows) {
+        let product = registry::read_string(CURRENT_VERSION_KEY, "ProductName", View::Registry64);
+        let release = registry::read_string(CURRENT_VERSION_KEY, "DisplayVersion", View::Registry64)
+            .or_else(|| registry::read_string(CURRENT_VERSION_KEY, "ReleaseId", View::Registry64));
+        let mut description = product.unwrap_or_else(|| "Windows".to_string());
+        if let Some(release) = release {
+            description.push_str(&format!(" {}", release));
+        }
+        if let Some(build) = windows_build() {
+            description.push_str(&format!(" (build {})", build));
+        }
+        description
+    } else if cfg!(target_os = "macos") {
+        let version = std::process::Command::new("sw_vers").arg("-productVersion").output();
+        match version {
+            Ok(output) => format!("macOS {}", String::from_utf8_lossy(&output.stdout).trim()),
+            Err(_) => "macOS".to_string(),
+        }
+    } else {
+        std::fs::read_to_string("/etc/os-release")
+            .ok()
+            .and_then(|release| {
+                release
+                    .lines()
+                    .find_map(|line| line.strip_prefix("PRETTY_NAME="))
+                    .map(|name| name.trim_matches('"').to_string())
+            })
+            .unwrap_or_else(|| std::env::consts::OS.to_string())
+    }
+}
+
+#[cfg(windows)]
+fn query() -> Machines {
+    use std::{mem, ptr::null};
+    use winapi::{
+        shared::minwindef::{BOOL, USHORT},
+        um::{
+            libloaderapi::{GetModuleHandleW, GetProcAddress},
+            processthreadsapi::GetCurrentProcess,
+            sysinfoapi::{GetNativeSystemInfo, SYSTEM_INFO},
+            winnt::{HANDLE, PROCESSOR_ARCHITECTURE_AMD64, PROCESSOR_ARCHITECTURE_ARM64, PROCESSOR_ARCHITECTURE_INTEL},
+            wow64apiset::IsWow64Process,
+        },
+    };
+
+    type IsWow64Process2 = unsafe extern "system" fn(HANDLE, *mut USHORT, *mut USHORT) -> BOOL;
+
+    unsafe {
+        // Only Windows 10 1511 and later have it, so it is looked up rather than linked.
+        let kernel32: Vec<u16> = "kernel32.dll\0".encode_utf16().collect();
+        let module = GetModuleHandleW(kernel32.as_ptr());
+        let function = if module.is_null() {
+            null()
+        } else {
+            GetProcAddress(module, b"IsWow64Process2\0".as_ptr().cast()) as *const ()
+        };
+        if !function.is_null() {
+            let is_wow64_process2: IsWow64Process2 = mem::transmute(function);
+            let (mut process, mut native) = (0, 0);
+            if is_wow64_process2(GetCurrentProcess(), &mut process, &mut native) != 0 {
+                return Machines { process, native };
+            }
+        }
+
+        // Older Windows doesn't emulate x64 on ARM64, so the native system
+        // info is accurate there.
+        let mut info: SYSTEM_INFO = mem::zeroed();
+        GetNativeSystemInfo(&mut info);
+        let native = match info.u.s().wProcessorArchitecture {
+            PROCESSOR_ARCHITECTURE_AMD64 => IMAGE_FILE_MACHINE_AMD64,
+            PROCESSOR_ARCHITECTURE_ARM64 => IMAGE_FILE_MACHINE_ARM64,
+            PROCESSOR_ARCHITECTURE_INTEL => IMAGE_FILE_MACHINE_I386,
+            _ => IMAGE_FILE_MACHINE_UNKNOWN,
+        };
+        let mut wow64 = 0;
+        let process = if IsWow64Process(GetCurrentProcess(), &mut wow64) != 0 && wow64 != 0 {
+            IMAGE_FILE_MACHINE_I386
+        } else {
+            IMAGE_FILE_MACHINE_UNKNOWN
+        };
+        Machines { process, native }
+    }
+}
+
+#[cfg(not(windows))]
+fn query() -> Machines {
+    let native = match std::env::consts::ARCH {
+        // Rosetta reports x64 to translated processes, but not this.
+        "x86_64" if cfg!(target_os = "macos") && rosetta() => IMAGE_FILE_MACHINE_ARM64,
+        "x86_64" => IMAGE_FILE_MACHINE_AMD64,
+        "aarch64" => IMAGE_FILE_MACHINE_ARM64,
+        "x86" => IMAGE_FILE_MACHINE_I386,
+        _ => IMAGE_FILE_MACHINE_UNKNOWN,
+    };
+    Machines {
+        process: IMAGE_FILE_MACHINE_UNKNOWN,
+        native,
+    }
+}
+
+#[cfg(not(windows))]
+fn rosetta() -> bool {
+    std::process::Command::new("sysctl")
+        .args(["-n", "hw.optional.arm64"])
+        .output()
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).trim() == "1")
+}
@@ -0,0 +1,148 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result, bail};
+use semver::Version;
+use serde::Deserialize;
+
+use crate::{
+    detect::file_version,
+    registry::{self, View},
+};
+
+/// A custom prerequisite described by a TOML file in the plugin directory.
+///
+/// ```toml
+/// name = "divvun-spellers"
+///
+/// [[detect]]
+/// type = "registry"
+/// key = "SOFTWARE\\Divvun\\Spellers"
+/// value = "Version"
+/// min_version = "1.2"
+///
+/// [install]
+/// url = "https://example.com/spellers.exe"
+/// sha512 = "..."
+/// args = ["/S"]
+/// success_codes = [0, 3010]
+/// ```
+#[derive(Deserialize)]
+pub struct Plugin {
+    pub name: String,
+    /// All rules must match for the component to count as installed.
+    pub detect: Vec<Rule>,
+    pub install: Install,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Rule {
+    /// A string value under `HKEY_LOCAL_MACHINE` exists, optionally holding at least `min_version`.
+    Registry {
+        key: String,
+        value: String,
+        min_version: Option<String>,
+    },
+    /// A file exists, optionally with a file version of at least `min_version`.
+    File {
+        path: String,
+        min_version: Option<String>,
+    },
+}
+
+#[derive(Deserialize)]
+pub struct Install {
+    pub url: String,
+    pub sha512: Option<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default = "default_success_codes")]
+    pub success_codes: Vec<i32>,
+}
+
+fn default_success_codes() -> Vec<i32> {
+    vec![0]
+}
+
+/// Loads every `*.toml` plugin in `dir`, in file name order.
+pub fn load_dir(dir: &Path) -> Result<Vec<Plugin>> {
+    let mut paths = fs::read_dir(dir)
+        .with_context(|| format!("cannot read plugin directory {}", dir.display()))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    paths
+        .iter()
+        .map(|path| {
+            let contents = fs::read_to_string(path)?;
+            let plugin: Plugin =
+                toml::from_str(&contents).with_context(|| format!("invalid plugin {}", path.display()))?;
+            if plugin.detect.is_empty() {
+                bail!("plugin {} has no detection rules", path.display());
+            }
+            Ok(plugin)
+        })
+        .collect()
+}
+
+impl Plugin {
+    pub(crate) fn is_installed(&self) -> Result<bool> {
+        for rule in &self.detect {
+            if !rule.matches()? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl Rule {
+    fn matches(&self) -> Result<bool> {
+        let (found, min_version) = match self {
+            Rule::Registry {
+                key,
+                value,
+                min_version,
+            } => {
+                let found = registry::read_string(key, value, View::Registry64);
+                (found.map(|found| parse_version(&found)), min_version)
+            }
+            Rule::File { path, min_version } => {
+                let path = Path::new(path);
+                if !path.exists() {
+                    return Ok(false);
+                }
+                (Some(file_version(path)), min_version)
+            }
+        };
+
+        Ok(match (found, min_version) {
+            (None, _) => false,
+            (Some(_), None) => true,
+            (Some(found), Some(min_version)) => {
+                let min_version = parse_version(min_version)
+                    .with_context(|| format!("invalid min_version {}", min_version))?;
+                found.is_some_and(|found| found >= min_version)
+            }
+        })
+    }
+}
+
+/// Parses a dotted version of one to four numeric parts, ignoring the fourth.
+fn parse_version(s: &str) -> Option<Version> {
+    let parts = s
+        .trim()
+        .split('.')
+        .map(|part| part.parse().ok())
+        .collect::<Option<Vec<u64>>>()?;
+
+    match *parts.as_slice() {
+        [major] => Some(Version::new(major, 0, 0)),
+        [major, minor] => Some(Version::new(major, minor, 0)),
+        [major, minor, patch] | [major, minor, patch, _] => Some(Version::new(major, minor, patch)),
+        _ => None,
+    }
+}
@@ -0,0 +1,228 @@
+use std::{
+    collections::HashMap,
+    pin::Pin,
+    sync::{
+        Arc, Mutex, MutexGuard, OnceLock, PoisonError,
+        atomic::{AtomicBool, Ordering},
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use async_rustls::client::TlsStream;
+use http_types::{Body, Response};
+use smol::{
+    io::{self, BufReader},
+    net::TcpStream,
+    prelude::*,
+};
+
+use crate::schannel::SchannelStream;
+
+/// How long a connection may sit idle before it is closed instead of
+/// reused, well within the minute or two servers keep them open for.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+/// Idle connections kept for each server.
+const MAX_IDLE: usize = 4;
+
+/// The server a connection goes to: its scheme, host and port.
+pub(crate) type Key = (String, String, u16);
+
+/// An established connection to a server, with TLS or without.
+pub(crate) enum Connection {
+    Plain(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
+    Schannel(Box<SchannelStream>),
+}
+
+impl AsyncRead for Connection {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Connection::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            Connection::Tls(stream) => Pin::new(stream).poll_read(cx, buf),
+            Connection::Schannel(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Connection {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Connection::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            Connection::Tls(stream) => Pin::new(stream).poll_write(cx, buf),
+            Connection::Schannel(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Connection::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            Connection::Tls(stream) => Pin::new(stream).poll_flush(cx),
+            Connection::Schannel(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Connection::Plain(stream) => Pin::new(stream).poll_close(cx),
+            Connection::Tls(stream) => Pin::new(stream).poll_close(cx),
+            Connection::Schannel(stream) => Pin::new(stream).poll_close(cx),
+        }
+    }
+}
+
+/// The idle connections to each server, with when they went idle.
+type Idle = HashMap<Key, Vec<(Connection, Instant)>>;
+
+fn idle() -> MutexGuard<'static, Idle> {
+    static IDLE: OnceLock<Mutex<Idle>> = OnceLock::new();
+    IDLE.get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+/// An idle connection to the server `key` names, if one was kept.
+pub(crate) fn take(key: &Key) -> Option<Connection> {
+    let mut idle = idle();
+    let connections = idle.get_mut(key)?;
+    while let Some((connection, since)) = connections.pop() {
+        if since.elapsed() < IDLE_TIMEOUT {
+            return Some(connection);
+        }
+    }
+    None
+}
+
+fn put(key: Key, connection: Connection) {
+    let mut idle = idle();
+    let connections = idle.entry(key).or_default();
+    connections.retain(|(_, since)| since.elapsed() < IDLE_TIMEOUT);
+    if connections.len() < MAX_IDLE {
+        connections.push((connection, Instant::now()));
+    }
+}
+
+/// A connection lent to a single request, which goes back to the pool when
+/// dropped if the response to it was read to the end.
+pub(crate) struct Lease {
+    connection: Option<Connection>,
+    key: Key,
+    reusable: Arc<AtomicBool>,
+    started: bool,
+}
+
+impl Lease {
+    pub(crate) fn new(connection: Connection, key: Key) -> Self {
+        Lease {
+            connection: Some(connection),
+            key,
+            reusable: Arc::new(AtomicBool::new(false)),
+            started: false,
+        }
+    }
+
+    /// Set once the connection may be reused, by [`reuse_after`].
+    pub(crate) fn reusable(&self) -> Arc<AtomicBool> {
+        self.reusable.clone()
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        if self.reusable.load(Ordering::SeqCst) {
+            if let Some(connection) = self.connection.take() {
+                put(self.key.clone(), connection);
+            }
+        }
+    }
+}
+
+impl AsyncRead for Lease {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        let connection = match self.connection.as_mut() {
+            Some(connection) => connection,
+            None => return Poll::Ready(Err(io::ErrorKind::NotConnected.into())),
+        };
+        match Pin::new(connection).poll_read(cx, buf) {
+            // async-h1 panics on a response that ends before it starts, which
+            // is what a server closing the connection while it was idle gives.
+            Poll::Ready(Ok(0)) if !self.started && !buf.is_empty() => Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::ConnectionReset,
+                "connection closed before the response",
+            ))),
+            Poll::Ready(Ok(n)) => {
+                self.started = true;
+                Poll::Ready(Ok(n))
+            }
+            poll => poll,
+        }
+    }
+}
+
+impl AsyncWrite for Lease {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match self.connection.as_mut() {
+            Some(connection) => Pin::new(connection).poll_write(cx, buf),
+            None => Poll::Ready(Err(io::ErrorKind::NotConnected.into())),
+        }
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.connection.as_mut() {
+            Some(connection) => Pin::new(connection).poll_flush(cx),
+            None => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.connection.as_mut() {
+            Some(connection) => Pin::new(connection).poll_close(cx),
+            None => Poll::Ready(Ok(())),
+        }
+    }
+}
+
+/// Marks the connection `response` came over as `reusable` once its body
+/// has been read to the end, unless the server is closing it or the body
+/// runs to the end of the connection rather than a Content-Length. The
+/// response to a `head` request has no body, whatever length it gives.
+pub(crate) fn reuse_after(response: &mut Response, head: bool, reusable: Arc<AtomicBool>) {
+    let closes = response
+        .header("Connection")
+        .is_some_and(|value| value.last().as_str().eq_ignore_ascii_case("close"));
+    let len = match response.header("Content-Length").and_then(|value| value.last().as_str().parse().ok()) {
+        Some(len) if !closes => len,
+        _ => return,
+    };
+
+    if len == 0 || head {
+        reusable.store(true, Ordering::SeqCst);
+        return;
+    }
+    let body = response.take_body();
+    let watched = EndOfBody { body, len, read: 0, reusable };
+    response.set_body(Body::from_reader(BufReader::new(watched), Some(len)));
+}
+
+/// A response body that tells its connection when all of it has been read.
+struct EndOfBody {
+    body: Body,
+    len: usize,
+    read: usize,
+    reusable: Arc<AtomicBool>,
+}
+
+impl AsyncRead for EndOfBody {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.body).poll_read(cx, buf);
+        if let Poll::Ready(Ok(n)) = poll {
+            self.read += n;
+            // A body cut short ends early with a read of 0 instead, and
+            // leaves the connection unusable.
+            if self.read == self.len {
+                self.reusable.store(true, Ordering::SeqCst);
+            }
+        }
+        poll
+    }
+}
@@ -0,0 +1,100 @@
+use std::fmt::Display;
+
+use anyhow::{Context, Error, Result, bail};
+use http_types::Url;
+
+use crate::http;
+
+/// The network layer a connectivity check failed at.
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum Layer {
+    Dns,
+    Tcp,
+    Proxy,
+    ProxyAuth,
+    Tls,
+}
+
+impl Display for Layer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Layer::Dns => f.write_str("DNS resolution"),
+            Layer::Tcp => f.write_str("TCP connect"),
+            Layer::Proxy => f.write_str("proxy tunnel"),
+            Layer::ProxyAuth => f.write_str("proxy authentication"),
+            Layer::Tls => f.write_str("TLS handshake"),
+        }
+    }
+}
+
+/// Checks that every host in `urls` can be reached, layer by layer, and
+/// reports which layer fails for the ones that cannot.
+pub async fn run(urls: &[&str]) -> Result<()> {
+    let mut hosts = Vec::new();
+    for url in urls {
+        let url = Url::parse(url)?;
+        let host = http::host_name(&url).context("cannot parse host")?.to_string();
+        let port = url.port_or_known_default().context("cannot guess port")?;
+        if !hosts.iter().any(|(h, p, _, _)| *h == host && *p == port) {
+            hosts.push((host, port, http::proxy_for(&url), url.scheme() == "https"));
+        }
+    }
+
+    let mut failures = 0;
+    for (host, port, proxy, tls) in &hosts {
+        if let Some(proxy) = proxy {
+            // Only the address, as the URL may carry credentials.
+            let (proxy_host, proxy_port) = (http::host_name(proxy).unwrap_or_default(), proxy.port_or_known_default());
+            log::info!("preflight: {}:{} via proxy {}:{}", host, port, proxy_host, proxy_port.unwrap_or(80));
+        }
+
+        match check_host(host, *port, proxy.as_ref(), *tls).await {
+            Ok(()) => log::info!("preflight: {}:{} ok", host, port),
+            Err((layer, e)) => {
+                failures += 1;
+                log::error!("preflight: {}:{} failed at {}: {}", host, port, layer, e);
+            }
+        }
+    }
+
+    if failures > 0 {
+        bail!(
+            "connectivity preflight failed for {} of {} hosts",
+            failures,
+            hosts.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// Connects to `host:port`, through `proxy` if given, and shakes hands
+/// over TLS if `tls` is set.
+pub(crate) async fn check_host(host: &str, port: u16, proxy: Option<&Url>, tls: bool) -> Result<(), (Layer, Error)> {
+    let addrs = match proxy {
+        Some(proxy) => http::resolve_proxy(proxy).await,
+        None => http::resolve(host, port).await,
+    }
+    .map_err(|e| (Layer::Dns, e))?;
+    let mut stream = http::connect(&addrs)
+        .await
+        .map_err(|e| (Layer::Tcp, e.into()))?;
+
+    if let Some(proxy) = proxy {
+        http::tunnel(&mut stream, proxy, host, port).await.map_err(|e| {
+            if e.is::<http::ProxyAuthRequired>() {
+                (Layer::ProxyAuth, e)
+            } else {
+                (Layer::Proxy, e)
+            }
+        })?;
+    }
+
+    if tls {
+        http::tls_connect(host, stream)
+            .await
+            .map_err(|e| (Layer::Tls, e))?;
+    }
+
+    Ok(())
+}
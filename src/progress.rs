@@ -0,0 +1,148 @@
+use std::{
+    io::{IsTerminal, Write},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+const BAR_WIDTH: usize = 30;
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What is happening during an install, for frontends to show progress by.
+///
+/// Downloads are told apart by URL, since the VC++ redistributable and the
+/// runtimes may download at the same time and ahead of their `Installing`.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// Working out which release the requested version of `component` resolves to.
+    ResolvingVersion { component: String },
+    /// Started or resumed downloading `url`, of `total` bytes if the server said.
+    DownloadStarted { url: String, total: Option<u64> },
+    /// `bytes` of `url` have been downloaded so far.
+    DownloadProgress { url: String, bytes: u64 },
+    /// Started installing `component`, downloading its installer unless that is done already.
+    Installing { component: String },
+    /// `component` is installed.
+    Done { component: String },
+}
+
+/// Receives the events of an install as they happen.
+pub type Listener = Arc<dyn Fn(&Event) + Send + Sync>;
+
+/// Where a download reports progress: a bar on stderr, a listener, both or neither.
+#[derive(Clone, Copy)]
+pub(crate) struct Sink<'a> {
+    pub(crate) bar: bool,
+    pub(crate) listener: Option<&'a Listener>,
+}
+
+/// A terminal progress bar for a single download, drawn on stderr, which
+/// also tells the listener how the download goes.
+pub(crate) struct Progress {
+    enabled: bool,
+    url: String,
+    listener: Option<(Listener, String)>,
+    done: u64,
+    resumed_from: u64,
+    total: Option<u64>,
+    start: Instant,
+    drawn: Option<Instant>,
+}
+
+impl Progress {
+    /// Starts tracking the download of `url` that already has `done` of `total` bytes.
+    ///
+    /// Nothing is drawn unless the sink has the bar and stderr is a terminal.
+    pub(crate) fn new(sink: Sink, url: &str, done: u64, total: Option<u64>) -> Self {
+        let started = Event::DownloadStarted { url: url.to_string(), total };
+        crate::logging::record(serde_json::to_value(&started).unwrap_or_default());
+        let listener = sink.listener.map(|listener| (listener.clone(), url.to_string()));
+        if let Some((listener, _)) = &listener {
+            listener(&started);
+        }
+        Progress {
+            enabled: sink.bar && std::io::stderr().is_terminal(),
+            url: url.to_string(),
+            listener,
+            done,
+            resumed_from: done,
+            total,
+            start: Instant::now(),
+            drawn: None,
+        }
+    }
+
+    pub(crate) fn advance(&mut self, bytes: u64) {
+        self.done += bytes;
+        if let Some((listener, url)) = &self.listener {
+            listener(&Event::DownloadProgress { url: url.clone(), bytes: self.done });
+        }
+        if self.drawn.is_none_or(|drawn| drawn.elapsed() >= REDRAW_INTERVAL) {
+            self.draw();
+        }
+    }
+
+    /// Draws the final state and moves to a new line.
+    /// Takes back bytes that were counted but have to be fetched again.
+    pub(crate) fn retreat(&mut self, bytes: u64) {
+        self.done = self.done.saturating_sub(bytes);
+    }
+
+    pub(crate) fn finish(&mut self) {
+        crate::logging::record(serde_json::json!({
+            "event": "download_ended",
+            "url": self.url,
+            "bytes": self.done,
+            "total": self.total,
+            "duration_ms": self.start.elapsed().as_millis() as u64,
+        }));
+        if self.enabled && self.drawn.is_some() {
+            self.draw();
+            eprintln!();
+        }
+    }
+
+    fn draw(&mut self) {
+        if !self.enabled {
+            return;
+        }
+        self.drawn = Some(Instant::now());
+
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let rate = if elapsed > 0.0 {
+            (self.done - self.resumed_from) as f64 / elapsed
+        } else {
+            0.0
+        };
+
+        let mut line = String::new();
+        match self.total {
+            Some(total) if total > 0 => {
+                let filled = (self.done.min(total) as f64 / total as f64 * BAR_WIDTH as f64) as usize;
+                line.push_str(&format!(
+                    "[{}{}] {} / {}",
+                    "#".repeat(filled),
+                    " ".repeat(BAR_WIDTH - filled),
+                    megabytes(self.done),
+                    megabytes(total)
+                ));
+                line.push_str(&format!("  {}/s", megabytes(rate as u64)));
+                if rate > 0.0 {
+                    let eta = (total.saturating_sub(self.done) as f64 / rate) as u64;
+                    line.push_str(&format!("  ETA {}:{:02}", eta / 60, eta % 60));
+                }
+            }
+            _ => line.push_str(&format!("{}  {}/s", megabytes(self.done), megabytes(rate as u64))),
+        }
+
+        let mut stderr = std::io::stderr();
+        let _ = write!(stderr, "\r{:<78}", line);
+        let _ = stderr.flush();
+    }
+}
+
+fn megabytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+}
@@ -0,0 +1,107 @@
+use std::fmt::Display;
+
+use anyhow::{Result, anyhow};
+use semver::{Identifier, Version};
+use semver_parser::{Compat, Op, RangeSet};
+
+/// A semver range such as `>=5.0.3, <6`, `~5.0` or `5.0.*`, in Cargo's
+/// syntax: comma separated comparators that must all hold, and `||` between
+/// alternatives.
+#[derive(Clone)]
+pub struct VersionRange {
+    text: String,
+    alternatives: Vec<Vec<(Op, Version)>>,
+}
+
+impl VersionRange {
+    pub fn parse(s: &str) -> Result<Self> {
+        let set = RangeSet::parse(s, Compat::Cargo).map_err(|e| anyhow!("invalid version range: {}", e))?;
+        let alternatives = set
+            .ranges
+            .into_iter()
+            .map(|range| {
+                range
+                    .comparator_set
+                    .into_iter()
+                    .map(|comparator| {
+                        let pre = comparator.pre.into_iter().map(identifier).collect();
+                        let version = Version { pre, ..Version::new(comparator.major, comparator.minor, comparator.patch) };
+                        (comparator.op, version)
+                    })
+                    .collect()
+            })
+            .collect();
+        Ok(VersionRange {
+            text: s.trim().to_string(),
+            alternatives,
+        })
+    }
+
+    /// Whether `version` is in the range. As in Cargo and npm, a prerelease
+    /// only is when a comparator names a prerelease of the same version, or
+    /// when `include_prerelease` is set.
+    pub fn matches(&self, version: &Version, include_prerelease: bool) -> bool {
+        self.alternatives.iter().any(|comparators| {
+            let prerelease_allowed = include_prerelease
+                || version.pre.is_empty()
+                || comparators.iter().any(|(_, bound)| !bound.pre.is_empty() && same_release(bound, version));
+            prerelease_allowed && comparators.iter().all(|(op, bound)| holds(op, version, bound))
+        })
+    }
+
+    /// The lowest version the range can match, or 0.0.0 if it has no lower bound.
+    pub fn lowest(&self) -> Version {
+        self.alternatives
+            .iter()
+            .map(|comparators| {
+                comparators
+                    .iter()
+                    .filter(|(op, _)| matches!(op, Op::Gt | Op::Gte | Op::Eq))
+                    .map(|(_, bound)| bound.clone())
+                    .max()
+                    .unwrap_or_else(|| Version::new(0, 0, 0))
+            })
+            .min()
+            .unwrap_or_else(|| Version::new(0, 0, 0))
+    }
+
+    /// Whether the `major.minor` channel can hold releases in the range.
+    pub(crate) fn accepts_channel(&self, major: u64, minor: u64) -> bool {
+        let first = Version::new(major, minor, 0);
+        self.alternatives.iter().any(|comparators| {
+            comparators.iter().all(|(op, bound)| match op {
+                Op::Gt | Op::Gte => (bound.major, bound.minor) <= (major, minor),
+                Op::Eq => (bound.major, bound.minor) == (major, minor),
+                Op::Lt => first < *bound,
+                Op::Lte => first <= *bound,
+            })
+        })
+    }
+}
+
+impl Display for VersionRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+fn holds(op: &Op, version: &Version, bound: &Version) -> bool {
+    match op {
+        Op::Lt => version < bound,
+        Op::Lte => version <= bound,
+        Op::Gt => version > bound,
+        Op::Gte => version >= bound,
+        Op::Eq => version == bound,
+    }
+}
+
+fn same_release(a: &Version, b: &Version) -> bool {
+    (a.major, a.minor, a.patch) == (b.major, b.minor, b.patch)
+}
+
+fn identifier(identifier: semver_parser::Identifier) -> Identifier {
+    match identifier {
+        semver_parser::Identifier::Numeric(n) => Identifier::Numeric(n),
+        semver_parser::Identifier::AlphaNumeric(s) => Identifier::AlphaNumeric(s),
+    }
+}
@@ -0,0 +1,51 @@
+use std::process::Command;
+
+use crate::registry::{self, View};
+
+/// Puts a Remote Desktop Session Host into install mode for as long as it
+/// lives, so per-user settings written by installers are shadowed for every
+/// user instead of only the one running the install.
+pub(crate) struct InstallMode(());
+
+impl InstallMode {
+    /// Switches to install mode if this is a session host, warning instead of
+    /// failing if that doesn't work.
+    pub(crate) fn enter() -> Option<InstallMode> {
+        if !is_session_host() {
+            return None;
+        }
+
+        log::debug!("switching the Remote Desktop session host to install mode");
+        if change_user("/install") {
+            Some(InstallMode(()))
+        } else {
+            log::warn!("could not switch the Remote Desktop session host to install mode");
+            None
+        }
+    }
+}
+
+impl Drop for InstallMode {
+    fn drop(&mut self) {
+        if !change_user("/execute") {
+            log::warn!("could not switch the Remote Desktop session host back to execute mode; run `change user /execute`");
+        }
+    }
+}
+
+/// Whether Remote Desktop Services runs in application server mode, which
+/// is the case on session hosts but not for plain remote administration.
+fn is_session_host() -> bool {
+    registry::read_dword(
+        "SYSTEM\\CurrentControlSet\\Control\\Terminal Server",
+        "TSAppCompat",
+        View::Registry64,
+    ) == Some(1)
+}
+
+fn change_user(mode: &str) -> bool {
+    Command::new("change.exe")
+        .args(["user", mode])
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
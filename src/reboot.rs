@@ -0,0 +1,63 @@
+use anyhow::{Result, bail};
+use clap::arg_enum;
+
+use crate::registry::{self, View};
+
+arg_enum! {
+    /// What to do when Windows is waiting for a reboot before installing.
+    #[derive(Copy, Clone, PartialEq, Eq)]
+    pub enum PendingReboot {
+        Warn,
+        Abort,
+        Ignore,
+    }
+}
+
+/// Lists the signs that Windows has work queued for the next reboot.
+fn indicators() -> Vec<&'static str> {
+    let mut found = Vec::new();
+
+    // An empty REG_MULTI_SZ is one or two UTF-16 terminators.
+    let renames = registry::value_size(
+        "SYSTEM\\CurrentControlSet\\Control\\Session Manager",
+        "PendingFileRenameOperations",
+        View::Registry64,
+    );
+    if renames.is_some_and(|size| size > 4) {
+        found.push("pending file rename operations");
+    }
+    if registry::key_exists(
+        "SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Component Based Servicing\\RebootPending",
+        View::Registry64,
+    ) {
+        found.push("component servicing reboot pending");
+    }
+    if registry::key_exists(
+        "SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\WindowsUpdate\\Auto Update\\RebootRequired",
+        View::Registry64,
+    ) {
+        found.push("Windows Update reboot required");
+    }
+
+    found
+}
+
+/// Warns about or refuses to continue with a pending reboot, since
+/// installers often fail or leave a broken runtime behind until it happens.
+pub(crate) fn check(policy: PendingReboot) -> Result<()> {
+    if policy == PendingReboot::Ignore {
+        return Ok(());
+    }
+
+    let found = indicators();
+    if found.is_empty() {
+        return Ok(());
+    }
+
+    let found = found.join(", ");
+    if policy == PendingReboot::Abort {
+        bail!("a reboot is pending ({}); reboot before installing", found);
+    }
+    log::warn!("a reboot is pending ({}); installers may fail until it happens", found);
+    Ok(())
+}
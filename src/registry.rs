@@ -0,0 +1,374 @@
+/// Which registry view of `HKEY_LOCAL_MACHINE` to use on 64-bit Windows.
+#[derive(Copy, Clone)]
+pub(crate) enum View {
+    /// The 32-bit view (`WOW6432Node`).
+    Registry32,
+    /// The 64-bit view.
+    Registry64,
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::{
+        ffi::OsStr,
+        io,
+        os::windows::ffi::OsStrExt,
+        ptr::null_mut,
+    };
+
+    use winapi::{
+        shared::{minwindef::HKEY, winerror::ERROR_SUCCESS},
+        um::{
+            winnt::{
+                KEY_READ, KEY_SET_VALUE, KEY_WOW64_32KEY, KEY_WOW64_64KEY, REG_DWORD, REG_EXPAND_SZ, REG_OPTION_NON_VOLATILE,
+                REG_SZ,
+            },
+            winreg::{
+                HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE, RRF_NOEXPAND, RRF_RT_ANY, RRF_RT_REG_DWORD, RRF_RT_REG_EXPAND_SZ, RRF_RT_REG_SZ,
+                RRF_SUBKEY_WOW6432KEY, RRF_SUBKEY_WOW6464KEY,
+                RegCloseKey, RegCreateKeyExW, RegDeleteValueW, RegEnumKeyExW, RegEnumValueW, RegGetValueW, RegOpenKeyExW,
+                RegSetValueExW,
+            },
+        },
+    };
+
+    use super::View;
+
+    fn wide(s: &str) -> Vec<u16> {
+        OsStr::new(s).encode_wide().chain(Some(0)).collect()
+    }
+
+    pub(crate) fn read_string(key: &str, name: &str, view: View) -> Option<String> {
+        let flags = RRF_RT_REG_SZ
+            | match view {
+                View::Registry32 => RRF_SUBKEY_WOW6432KEY,
+                View::Registry64 => RRF_SUBKEY_WOW6464KEY,
+            };
+        get_string(HKEY_LOCAL_MACHINE, key, name, flags)
+    }
+
+    /// Reads a variable of the user's environment, or with `machine` of the
+    /// machine's, as stored, leaving references such as `%USERPROFILE%` unexpanded.
+    pub(crate) fn read_environment(name: &str, machine: bool) -> Option<String> {
+        let (hive, key) = environment_key(machine);
+        get_string(hive, key, name, RRF_RT_REG_SZ | RRF_RT_REG_EXPAND_SZ | RRF_NOEXPAND)
+    }
+
+    /// Sets a variable of the user's or the machine's environment, expandable
+    /// as Windows stores `Path`.
+    pub(crate) fn write_environment(name: &str, value: &str, machine: bool) -> io::Result<()> {
+        let (hive, key) = environment_key(machine);
+        let value = wide(value);
+        set_value(hive, key, name, REG_EXPAND_SZ, value.as_ptr().cast(), (value.len() * 2) as u32, View::Registry64)
+    }
+
+    fn environment_key(machine: bool) -> (HKEY, &'static str) {
+        if machine {
+            (HKEY_LOCAL_MACHINE, "SYSTEM\\CurrentControlSet\\Control\\Session Manager\\Environment")
+        } else {
+            (HKEY_CURRENT_USER, "Environment")
+        }
+    }
+
+    fn get_string(hive: HKEY, key: &str, name: &str, flags: u32) -> Option<String> {
+        let (key, name) = (wide(key), wide(name));
+
+        let mut size = 0;
+        let status = unsafe {
+            RegGetValueW(hive, key.as_ptr(), name.as_ptr(), flags, null_mut(), null_mut(), &mut size)
+        };
+        if status as u32 != ERROR_SUCCESS {
+            return None;
+        }
+
+        let mut buf = vec![0u16; (size as usize).div_ceil(2)];
+        let status = unsafe {
+            RegGetValueW(
+                hive,
+                key.as_ptr(),
+                name.as_ptr(),
+                flags,
+                null_mut(),
+                buf.as_mut_ptr().cast(),
+                &mut size,
+            )
+        };
+        if status as u32 != ERROR_SUCCESS {
+            return None;
+        }
+
+        let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+        Some(String::from_utf16_lossy(&buf[..len]))
+    }
+
+    pub(crate) fn read_dword(key: &str, name: &str, view: View) -> Option<u32> {
+        let flags = RRF_RT_REG_DWORD
+            | match view {
+                View::Registry32 => RRF_SUBKEY_WOW6432KEY,
+                View::Registry64 => RRF_SUBKEY_WOW6464KEY,
+            };
+        let (key, name) = (wide(key), wide(name));
+
+        let mut value = 0u32;
+        let mut size = std::mem::size_of::<u32>() as u32;
+        let status = unsafe {
+            RegGetValueW(
+                HKEY_LOCAL_MACHINE,
+                key.as_ptr(),
+                name.as_ptr(),
+                flags,
+                null_mut(),
+                (&mut value as *mut u32).cast(),
+                &mut size,
+            )
+        };
+        if status as u32 != ERROR_SUCCESS {
+            return None;
+        }
+
+        Some(value)
+    }
+
+    pub(crate) fn key_exists(key: &str, view: View) -> bool {
+        let access = KEY_READ
+            | match view {
+                View::Registry32 => KEY_WOW64_32KEY,
+                View::Registry64 => KEY_WOW64_64KEY,
+            };
+        let key = wide(key);
+
+        let mut hkey: HKEY = null_mut();
+        let status = unsafe { RegOpenKeyExW(HKEY_LOCAL_MACHINE, key.as_ptr(), 0, access, &mut hkey) };
+        if status as u32 != ERROR_SUCCESS {
+            return false;
+        }
+
+        unsafe { RegCloseKey(hkey) };
+        true
+    }
+
+    pub(crate) fn value_names(key: &str, view: View) -> Vec<String> {
+        let access = KEY_READ
+            | match view {
+                View::Registry32 => KEY_WOW64_32KEY,
+                View::Registry64 => KEY_WOW64_64KEY,
+            };
+        let key = wide(key);
+
+        let mut hkey: HKEY = null_mut();
+        let status = unsafe { RegOpenKeyExW(HKEY_LOCAL_MACHINE, key.as_ptr(), 0, access, &mut hkey) };
+        if status as u32 != ERROR_SUCCESS {
+            return Vec::new();
+        }
+
+        let mut names = Vec::new();
+        // Value names are at most 16383 characters.
+        let mut buf = vec![0u16; 16384];
+        for index in 0.. {
+            let mut len = buf.len() as u32;
+            let status = unsafe {
+                RegEnumValueW(hkey, index, buf.as_mut_ptr(), &mut len, null_mut(), null_mut(), null_mut(), null_mut())
+            };
+            if status as u32 != ERROR_SUCCESS {
+                break;
+            }
+            names.push(String::from_utf16_lossy(&buf[..len as usize]));
+        }
+
+        unsafe { RegCloseKey(hkey) };
+        names
+    }
+
+    pub(crate) fn subkey_names(key: &str, view: View) -> Vec<String> {
+        let access = KEY_READ
+            | match view {
+                View::Registry32 => KEY_WOW64_32KEY,
+                View::Registry64 => KEY_WOW64_64KEY,
+            };
+        let key = wide(key);
+
+        let mut hkey: HKEY = null_mut();
+        let status = unsafe { RegOpenKeyExW(HKEY_LOCAL_MACHINE, key.as_ptr(), 0, access, &mut hkey) };
+        if status as u32 != ERROR_SUCCESS {
+            return Vec::new();
+        }
+
+        let mut names = Vec::new();
+        // Key names are at most 255 characters.
+        let mut buf = vec![0u16; 256];
+        for index in 0.. {
+            let mut len = buf.len() as u32;
+            let status = unsafe {
+                RegEnumKeyExW(hkey, index, buf.as_mut_ptr(), &mut len, null_mut(), null_mut(), null_mut(), null_mut())
+            };
+            if status as u32 != ERROR_SUCCESS {
+                break;
+            }
+            names.push(String::from_utf16_lossy(&buf[..len as usize]));
+        }
+
+        unsafe { RegCloseKey(hkey) };
+        names
+    }
+
+    pub(crate) fn value_size(key: &str, name: &str, view: View) -> Option<u32> {
+        let flags = RRF_RT_ANY
+            | match view {
+                View::Registry32 => RRF_SUBKEY_WOW6432KEY,
+                View::Registry64 => RRF_SUBKEY_WOW6464KEY,
+            };
+        let (key, name) = (wide(key), wide(name));
+
+        let mut size = 0;
+        let status = unsafe {
+            RegGetValueW(HKEY_LOCAL_MACHINE, key.as_ptr(), name.as_ptr(), flags, null_mut(), null_mut(), &mut size)
+        };
+        if status as u32 != ERROR_SUCCESS {
+            return None;
+        }
+
+        Some(size)
+    }
+
+    pub(crate) fn write_string(key: &str, name: &str, value: &str, view: View) -> io::Result<()> {
+        let value = wide(value);
+        set_value(HKEY_LOCAL_MACHINE, key, name, REG_SZ, value.as_ptr().cast(), (value.len() * 2) as u32, view)
+    }
+
+    pub(crate) fn write_dword(key: &str, name: &str, value: u32, view: View) -> io::Result<()> {
+        set_value(HKEY_LOCAL_MACHINE, key, name, REG_DWORD, (&value as *const u32).cast(), 4, view)
+    }
+
+    /// Sets a string value under `HKEY_CURRENT_USER`, which needs no elevation.
+    pub(crate) fn write_user_string(key: &str, name: &str, value: &str) -> io::Result<()> {
+        let value = wide(value);
+        set_value(HKEY_CURRENT_USER, key, name, REG_SZ, value.as_ptr().cast(), (value.len() * 2) as u32, View::Registry64)
+    }
+
+    /// Creates `key` if needed and sets `name` to the `size` bytes at `data`.
+    fn set_value(hive: HKEY, key: &str, name: &str, kind: u32, data: *const u8, size: u32, view: View) -> io::Result<()> {
+        let access = KEY_SET_VALUE
+            | match view {
+                View::Registry32 => KEY_WOW64_32KEY,
+                View::Registry64 => KEY_WOW64_64KEY,
+            };
+        let (key, name) = (wide(key), wide(name));
+
+        let mut hkey: HKEY = null_mut();
+        let status = unsafe {
+            RegCreateKeyExW(
+                hive,
+                key.as_ptr(),
+                0,
+                null_mut(),
+                REG_OPTION_NON_VOLATILE,
+                access,
+                null_mut(),
+                &mut hkey,
+                null_mut(),
+            )
+        };
+        if status as u32 != ERROR_SUCCESS {
+            return Err(io::Error::from_raw_os_error(status));
+        }
+
+        let status = unsafe {
+            RegSetValueExW(hkey, name.as_ptr(), 0, kind, data, size)
+        };
+        unsafe { RegCloseKey(hkey) };
+        if status as u32 != ERROR_SUCCESS {
+            return Err(io::Error::from_raw_os_error(status));
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn delete_value(key: &str, name: &str, view: View) -> io::Result<()> {
+        let access = KEY_SET_VALUE
+            | match view {
+                View::Registry32 => KEY_WOW64_32KEY,
+                View::Registry64 => KEY_WOW64_64KEY,
+            };
+        let (key, name) = (wide(key), wide(name));
+
+        let mut hkey: HKEY = null_mut();
+        let status = unsafe { RegOpenKeyExW(HKEY_LOCAL_MACHINE, key.as_ptr(), 0, access, &mut hkey) };
+        if status as u32 != ERROR_SUCCESS {
+            return Err(io::Error::from_raw_os_error(status));
+        }
+
+        let status = unsafe { RegDeleteValueW(hkey, name.as_ptr()) };
+        unsafe { RegCloseKey(hkey) };
+        if status as u32 != ERROR_SUCCESS {
+            return Err(io::Error::from_raw_os_error(status));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+pub(crate) use windows::{
+    delete_value, key_exists, read_dword, read_environment, read_string, subkey_names, value_names, value_size,
+    write_dword, write_environment, write_string, write_user_string,
+};
+
+#[cfg(not(windows))]
+pub(crate) fn read_string(_key: &str, _name: &str, _view: View) -> Option<String> {
+    None
+}
+
+#[cfg(not(windows))]
+pub(crate) fn read_environment(_name: &str, _machine: bool) -> Option<String> {
+    None
+}
+
+#[cfg(not(windows))]
+pub(crate) fn write_environment(_name: &str, _value: &str, _machine: bool) -> std::io::Result<()> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "the registry is only available on Windows"))
+}
+
+#[cfg(not(windows))]
+pub(crate) fn read_dword(_key: &str, _name: &str, _view: View) -> Option<u32> {
+    None
+}
+
+#[cfg(not(windows))]
+pub(crate) fn key_exists(_key: &str, _view: View) -> bool {
+    false
+}
+
+#[cfg(not(windows))]
+pub(crate) fn value_names(_key: &str, _view: View) -> Vec<String> {
+    Vec::new()
+}
+
+#[cfg(not(windows))]
+pub(crate) fn subkey_names(_key: &str, _view: View) -> Vec<String> {
+    Vec::new()
+}
+
+#[cfg(not(windows))]
+pub(crate) fn value_size(_key: &str, _name: &str, _view: View) -> Option<u32> {
+    None
+}
+
+#[cfg(not(windows))]
+pub(crate) fn write_string(_key: &str, _name: &str, _value: &str, _view: View) -> std::io::Result<()> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "the registry is only available on Windows"))
+}
+
+#[cfg(not(windows))]
+pub(crate) fn write_dword(_key: &str, _name: &str, _value: u32, _view: View) -> std::io::Result<()> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "the registry is only available on Windows"))
+}
+
+#[cfg(not(windows))]
+pub(crate) fn write_user_string(_key: &str, _name: &str, _value: &str) -> std::io::Result<()> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "the registry is only available on Windows"))
+}
+
+#[cfg(not(windows))]
+pub(crate) fn delete_value(_key: &str, _name: &str, _view: View) -> std::io::Result<()> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "the registry is only available on Windows"))
+}
@@ -0,0 +1,396 @@
+use anyhow::{Context, Result, anyhow, bail};
+use semver::Version;
+use serde::{Deserialize, de::DeserializeOwned};
+
+use crate::{
+    Architecture, BASE_URL, DotnetRedistError, DotnetVersion, RollForward, Runtime, approved,
+    catalog::{self, Entry},
+    metadata,
+};
+
+#[derive(Deserialize)]
+struct Index {
+    #[serde(rename = "releases-index")]
+    releases_index: Vec<Channel>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Channel {
+    channel_version: String,
+    release_type: Option<String>,
+    support_phase: Option<String>,
+    #[serde(rename = "releases.json")]
+    releases_json: String,
+}
+
+#[derive(Deserialize)]
+struct ChannelReleases {
+    releases: Vec<Release>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Release {
+    #[serde(default)]
+    security: bool,
+    #[serde(default)]
+    cve_list: Vec<Cve>,
+    runtime: Option<Product>,
+    #[serde(default)]
+    sdks: Vec<Product>,
+    aspnetcore_runtime: Option<Product>,
+    windowsdesktop: Option<Product>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Cve {
+    cve_id: String,
+}
+
+#[derive(Deserialize)]
+struct Product {
+    version: String,
+    #[serde(default)]
+    files: Vec<ReleaseFile>,
+}
+
+#[derive(Deserialize)]
+struct ReleaseFile {
+    name: String,
+    url: String,
+    hash: String,
+}
+
+/// The form a runtime is distributed in.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Package {
+    /// The silent-capable installer.
+    Exe,
+    /// The archive of the install layout, for xcopy-style deployment.
+    Zip,
+    /// The macOS installer package.
+    Pkg,
+    /// The Linux archive of the install layout.
+    TarGz,
+}
+
+impl Package {
+    /// The archive of the install layout on this platform, for installs
+    /// into a directory of their own.
+    pub fn archive() -> Package {
+        if cfg!(windows) {
+            Package::Zip
+        } else {
+            Package::TarGz
+        }
+    }
+
+    /// The form runtimes are installed from on this platform.
+    pub fn installer() -> Package {
+        if cfg!(windows) {
+            Package::Exe
+        } else if cfg!(target_os = "macos") {
+            Package::Pkg
+        } else {
+            Package::TarGz
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Package::Exe => "exe",
+            Package::Zip => "zip",
+            Package::Pkg => "pkg",
+            Package::TarGz => "tar.gz",
+        }
+    }
+
+    /// The operating system part of the runtime identifier the package is for.
+    pub fn os(self) -> &'static str {
+        match self {
+            Package::Exe | Package::Zip => "win",
+            Package::Pkg => "osx",
+            Package::TarGz if cfg!(target_os = "macos") => "osx",
+            Package::TarGz => "linux",
+        }
+    }
+}
+
+/// An installer resolved from the official release metadata.
+pub struct Artifact {
+    pub version: Version,
+    pub url: String,
+    pub hash: Option<String>,
+}
+
+/// Resolves the release `version` rolls forward to from the official
+/// `releases-index.json` and per-channel `releases.json` metadata.
+pub async fn resolve(
+    runtime: Runtime,
+    arch: Architecture,
+    version: &DotnetVersion,
+    package: Package,
+    include_prerelease: bool,
+    roll_forward: RollForward,
+) -> Result<Artifact> {
+    let index: Index = get_json(&format!("{}/release-metadata/releases-index.json", BASE_URL)).await?;
+
+    let mut channels = index
+        .releases_index
+        .into_iter()
+        .filter_map(|channel| Some((parse_channel(&channel.channel_version)?, channel)))
+        .filter(|((major, minor), _)| version.accepts_channel(*major, *minor, roll_forward))
+        .collect::<Vec<_>>();
+    // Minor and Major roll forward to the lowest channel with a matching
+    // release, the other policies to the newest.
+    channels.sort_by_key(|(channel_version, _)| *channel_version);
+    if !matches!(roll_forward, RollForward::Minor | RollForward::Major) {
+        channels.reverse();
+    }
+    if channels.is_empty() {
+        bail!(DotnetRedistError::VersionNotFound(anyhow!("no release channel found for version {}", version)));
+    }
+
+    let file_name = file_name(runtime, arch, package);
+
+    for (_, channel) in &channels {
+        let releases: ChannelReleases = get_json(&channel.releases_json).await?;
+        let candidates = releases.releases.iter().flat_map(|release| {
+            products(runtime, release).into_iter().filter_map(|product| {
+                let file = product.files.iter().find(|file| file.name == file_name)?;
+                Some((Version::parse(&product.version).ok()?, file))
+            })
+        })
+        .filter(|(version, _)| approved::allows(version));
+
+        if let Some((product_version, file)) = version.select(candidates, include_prerelease, roll_forward) {
+            return Ok(Artifact {
+                version: product_version,
+                url: file.url.clone(),
+                hash: Some(file.hash.clone()),
+            });
+        }
+    }
+
+    Err(DotnetRedistError::VersionNotFound(anyhow!("no {} found for version {}", file_name, version)).into())
+}
+
+/// Replaces the support channel keywords `lts`, `sts` and `current` with the
+/// `major.minor` of the newest channel of that kind still in support, and
+/// `latest` with that of `lts`. Anything else is returned unchanged.
+pub async fn resolve_channel(version: &str) -> Result<String> {
+    let keyword = version.to_lowercase();
+    if !["lts", "sts", "current", "latest"].contains(&keyword.as_str()) {
+        return Ok(version.to_string());
+    }
+    let release_type = if keyword == "latest" { "lts" } else { keyword.as_str() };
+
+    let channels = match get_json::<Index>(&format!("{}/release-metadata/releases-index.json", BASE_URL)).await {
+        Ok(index) => index
+            .releases_index
+            .into_iter()
+            .map(|channel| (channel.channel_version, channel.release_type, channel.support_phase))
+            .collect::<Vec<_>>(),
+        Err(e) if !catalog::channels().is_empty() => {
+            log::warn!("{:#}; resolving {} with the channels known when this tool was built", e, keyword);
+            catalog::channels()
+                .iter()
+                .map(|channel| (channel.channel.clone(), channel.release_type.clone(), channel.support_phase.clone()))
+                .collect()
+        }
+        Err(e) => return Err(e),
+    };
+    let channel = channels
+        .iter()
+        .filter(|(_, channel_type, support_phase)| {
+            matches!(support_phase.as_deref(), Some("active") | Some("maintenance"))
+                && (keyword == "current" || channel_type.as_deref() == Some(release_type))
+        })
+        .filter_map(|(channel_version, _, _)| parse_channel(channel_version))
+        .max()
+        .with_context(|| format!("no supported {} release channel found", keyword))?;
+
+    let version = format!("{}.{}", channel.0, channel.1);
+    log::info!("{} resolves to {}", keyword, version);
+    Ok(version)
+}
+
+/// Lists the released SDK versions of the `major.minor` channel, newest first.
+pub(crate) async fn sdk_versions(major: u64, minor: u64) -> Result<Vec<Version>> {
+    let index: Index = get_json(&format!("{}/release-metadata/releases-index.json", BASE_URL)).await?;
+    let channel = index
+        .releases_index
+        .iter()
+        .find(|channel| parse_channel(&channel.channel_version) == Some((major, minor)))
+        .with_context(|| format!("no release channel found for version {}.{}", major, minor))?;
+    let releases: ChannelReleases = get_json(&channel.releases_json).await?;
+
+    let mut versions = releases
+        .releases
+        .iter()
+        .flat_map(|release| &release.sdks)
+        .filter_map(|sdk| Version::parse(&sdk.version).ok())
+        .filter(|version| version.pre.is_empty())
+        .collect::<Vec<_>>();
+    versions.sort_by(|a, b| b.cmp(a));
+    versions.dedup();
+    Ok(versions)
+}
+
+/// The link the release metadata has for the installer of exactly `version`,
+/// if its channel lists that release.
+pub(crate) async fn file_url(runtime: Runtime, arch: Architecture, version: &Version, package: Package) -> Result<Option<String>> {
+    let index: Index = get_json(&format!("{}/release-metadata/releases-index.json", BASE_URL)).await?;
+    let channel = match index
+        .releases_index
+        .iter()
+        .find(|channel| parse_channel(&channel.channel_version) == Some((version.major, version.minor)))
+    {
+        Some(channel) => channel,
+        None => return Ok(None),
+    };
+    let releases: ChannelReleases = get_json(&channel.releases_json).await?;
+
+    let file_name = file_name(runtime, arch, package);
+    Ok(releases
+        .releases
+        .iter()
+        .flat_map(|release| products(runtime, release))
+        .filter(|product| Version::parse(&product.version).ok().as_ref() == Some(version))
+        .flat_map(|product| &product.files)
+        .find(|file| file.name == file_name)
+        .map(|file| file.url.clone()))
+}
+
+/// The security advisories a release is affected by: those fixed by the
+/// later security releases of its channel.
+pub(crate) struct Vulnerability {
+    pub(crate) cves: Vec<String>,
+    /// The newest release of the channel, which has every fix.
+    pub(crate) patched: Version,
+}
+
+/// The advisories `version` of `runtime` is affected by, if any.
+pub(crate) async fn vulnerability(runtime: Runtime, version: &Version) -> Result<Option<Vulnerability>> {
+    let index: Index = get_json(&format!("{}/release-metadata/releases-index.json", BASE_URL)).await?;
+    let channel = match index
+        .releases_index
+        .iter()
+        .find(|channel| parse_channel(&channel.channel_version) == Some((version.major, version.minor)))
+    {
+        Some(channel) => channel,
+        None => return Ok(None),
+    };
+    let releases: ChannelReleases = get_json(&channel.releases_json).await?;
+
+    let mut cves = Vec::new();
+    let mut patched: Option<Version> = None;
+    for release in &releases.releases {
+        let later = products(runtime, release)
+            .iter()
+            .filter_map(|product| Version::parse(&product.version).ok())
+            .filter(|later| later > version && later.pre.is_empty())
+            .max();
+        let later = match later {
+            Some(later) => later,
+            None => continue,
+        };
+        if release.security {
+            cves.extend(release.cve_list.iter().map(|cve| cve.cve_id.clone()));
+        }
+        if patched.as_ref().is_none_or(|patched| later > *patched) {
+            patched = Some(later);
+        }
+    }
+    cves.sort();
+    cves.dedup();
+    Ok(patched.filter(|_| !cves.is_empty()).map(|patched| Vulnerability { cves, patched }))
+}
+
+/// Lists the Windows installers and archives of every release in every channel.
+pub(crate) async fn windows_files() -> Result<Vec<Entry>> {
+    let index: Index = get_json(&format!("{}/release-metadata/releases-index.json", BASE_URL)).await?;
+
+    let hosting_bundle = file_name(Runtime::HostingBundle, Architecture::X64, Package::Exe);
+    let mut entries = Vec::new();
+    for channel in &index.releases_index {
+        let releases: ChannelReleases = get_json(&channel.releases_json).await?;
+        for release in &releases.releases {
+            let products = release
+                .runtime
+                .iter()
+                .chain(&release.aspnetcore_runtime)
+                .chain(&release.windowsdesktop)
+                .chain(&release.sdks);
+            for product in products {
+                for file in &product.files {
+                    let windows = file.name.contains("-win-x") || file.name.contains("-win-arm64") || file.name == hosting_bundle;
+                    if windows && (file.name.ends_with(".exe") || file.name.ends_with(".zip")) {
+                        entries.push(Entry {
+                            name: file.name.clone(),
+                            version: product.version.clone(),
+                            url: file.url.clone(),
+                            hash: Some(file.hash.clone()),
+                            local: true,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+fn products(runtime: Runtime, release: &Release) -> Vec<&Product> {
+    match runtime {
+        Runtime::Dotnet => release.runtime.iter().collect(),
+        Runtime::AspCore | Runtime::HostingBundle => release.aspnetcore_runtime.iter().collect(),
+        Runtime::WindowsDesktop => release.windowsdesktop.iter().collect(),
+        Runtime::Sdk => release.sdks.iter().collect(),
+        Runtime::NetFx48 => Vec::new(),
+    }
+}
+
+/// The name release metadata lists the installer or archive under.
+pub(crate) fn file_name(runtime: Runtime, arch: Architecture, package: Package) -> String {
+    // The hosting bundle carries every architecture.
+    if runtime == Runtime::HostingBundle {
+        return format!("{}-{}.{}", file_prefix(runtime), package.os(), package.extension());
+    }
+    format!(
+        "{}-{}-{}.{}",
+        file_prefix(runtime),
+        package.os(),
+        arch.to_string().to_lowercase(),
+        package.extension()
+    )
+}
+
+pub(crate) fn file_prefix(runtime: Runtime) -> &'static str {
+    match runtime {
+        Runtime::Dotnet => "dotnet-runtime",
+        Runtime::AspCore => "aspnetcore-runtime",
+        Runtime::WindowsDesktop => "windowsdesktop-runtime",
+        Runtime::Sdk => "dotnet-sdk",
+        Runtime::NetFx48 => "ndp48",
+        Runtime::HostingBundle => "dotnet-hosting",
+    }
+}
+
+fn parse_channel(channel_version: &str) -> Option<(u64, u64)> {
+    let mut parts = channel_version.split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+async fn get_json<T: DeserializeOwned>(url: &str) -> Result<T> {
+    let body = match metadata::get_text(url).await? {
+        Some(body) => body,
+        None => return Err(DotnetRedistError::Network(anyhow!("could not fetch {}: no such file", url)).into()),
+    };
+    serde_json::from_str(&body).with_context(|| format!("invalid release metadata at {}", url))
+}
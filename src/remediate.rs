@@ -0,0 +1,67 @@
+use std::{fmt::Display, io, time::Duration};
+
+use anyhow::Error;
+
+use crate::{
+    http,
+    installer::{self, ERROR_INSTALL_ALREADY_RUNNING},
+};
+
+/// Win32 error: the file is being used by another process.
+const ERROR_SHARING_VIOLATION: i32 = 32;
+
+/// A failure class that has a known fix-and-retry strategy.
+#[derive(Copy, Clone)]
+pub enum Failure {
+    MsiBusy,
+    FileLocked,
+    Network,
+}
+
+impl Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Failure::MsiBusy => f.write_str("another installation is in progress"),
+            Failure::FileLocked => f.write_str("the installer is locked by another process"),
+            Failure::Network => f.write_str("transient network failure"),
+        }
+    }
+}
+
+impl Failure {
+    /// Classifies an error, returning `None` if it is not remediable.
+    pub fn classify(e: &Error) -> Option<Failure> {
+        for cause in e.chain() {
+            if let Some(installer::Failed(ERROR_INSTALL_ALREADY_RUNNING)) = cause.downcast_ref() {
+                return Some(Failure::MsiBusy);
+            }
+
+            if let Some(e) = cause.downcast_ref::<io::Error>() {
+                if e.raw_os_error() == Some(ERROR_SHARING_VIOLATION) {
+                    return Some(Failure::FileLocked);
+                }
+            }
+        }
+
+        if http::is_transient(e) {
+            return Some(Failure::Network);
+        }
+
+        None
+    }
+
+    /// Applies the fix for this failure before the next attempt.
+    pub async fn remediate(self, attempt: u32) {
+        // Each fix amounts to giving the conflicting party time to finish:
+        // msiexec to complete its transaction, the virus scanner to release
+        // the freshly written installer, or the network to recover.
+        let wait = match self {
+            Failure::MsiBusy => Duration::from_secs(60),
+            Failure::FileLocked => Duration::from_secs(10),
+            Failure::Network => Duration::from_secs(5),
+        } * attempt;
+
+        log::warn!("remediation: {}, retrying in {}s", self, wait.as_secs());
+        smol::Timer::after(wait).await;
+    }
+}
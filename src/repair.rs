@@ -0,0 +1,87 @@
+use std::path::PathBuf;
+
+use semver::Version;
+
+use crate::{
+    Architecture, Runtime,
+    registry::{self, View},
+};
+
+const UNINSTALL_KEY: &str = "SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Uninstall";
+
+/// A runtime installer bundle Windows lists as installed.
+pub(crate) struct Registered {
+    /// Its name in Programs and Features, such as `Microsoft .NET Runtime - 8.0.11 (x64)`.
+    pub(crate) name: String,
+    pub(crate) version: Version,
+    /// The copy Burn kept in its package cache, if it is still there.
+    pub(crate) cached: Option<PathBuf>,
+}
+
+/// The installed bundles of `runtime` for `arch`, newest first.
+pub(crate) fn registered(runtime: Runtime, arch: Architecture) -> Vec<Registered> {
+    // Burn registers bundles in the 32-bit view whatever they install.
+    let mut bundles: Vec<_> = registry::subkey_names(UNINSTALL_KEY, View::Registry32)
+        .into_iter()
+        .filter_map(|subkey| {
+            let key = format!("{}\\{}", UNINSTALL_KEY, subkey);
+            // The packages a bundle chains have entries of their own, without a cache path.
+            let cache_path = registry::read_string(&key, "BundleCachePath", View::Registry32)?;
+            let name = registry::read_string(&key, "DisplayName", View::Registry32)?;
+            let version = bundle_version(runtime, arch, &name)?;
+            let cached = Some(PathBuf::from(cache_path)).filter(|path| path.is_file());
+            Some(Registered { name, version, cached })
+        })
+        .collect();
+    bundles.sort_by(|a, b| b.version.cmp(&a.version));
+    bundles
+}
+
+/// The versions of `runtime` for `arch` that Windows Installer lists as
+/// installed packages, which Microsoft Update services on their own, apart
+/// from the bundles that first installed them.
+pub(crate) fn msi_versions(runtime: Runtime, arch: Architecture) -> Vec<Version> {
+    // The packages are registered in the view of the architecture they install.
+    let view = match arch {
+        Architecture::X86 => View::Registry32,
+        Architecture::X64 | Architecture::Arm64 => View::Registry64,
+    };
+    // The hosting bundle's runtime is the ASP.NET Core shared framework package.
+    let runtime = match runtime {
+        Runtime::HostingBundle => Runtime::AspCore,
+        runtime => runtime,
+    };
+    registry::subkey_names(UNINSTALL_KEY, view)
+        .into_iter()
+        .filter_map(|subkey| {
+            let key = format!("{}\\{}", UNINSTALL_KEY, subkey);
+            if registry::read_dword(&key, "WindowsInstaller", view) != Some(1) {
+                return None;
+            }
+            bundle_version(runtime, arch, &registry::read_string(&key, "DisplayName", view)?)
+        })
+        .collect()
+}
+
+/// The version a bundle's or package's display name gives, if it is one of `runtime` for `arch`.
+fn bundle_version(runtime: Runtime, arch: Architecture, name: &str) -> Option<Version> {
+    let prefixes: &[&str] = match runtime {
+        Runtime::Dotnet => &["Microsoft .NET Runtime - ", "Microsoft .NET Core Runtime - "],
+        Runtime::AspCore => &["Microsoft ASP.NET Core "],
+        Runtime::WindowsDesktop => &["Microsoft Windows Desktop Runtime - "],
+        Runtime::HostingBundle => &["Microsoft .NET ", "Microsoft .NET Core "],
+        Runtime::Sdk => &["Microsoft .NET SDK ", "Microsoft .NET Core SDK "],
+        Runtime::NetFx48 => return None,
+    };
+    let rest = prefixes.iter().find_map(|prefix| name.strip_prefix(prefix))?;
+    let version = Version::parse(rest.split_whitespace().next()?).ok()?;
+
+    // The hosting bundle installs both architectures, so its name has none.
+    let of_arch = name.ends_with(&format!("({})", arch.to_string().to_lowercase()));
+    let matches = match runtime {
+        Runtime::HostingBundle => rest.ends_with(" - Windows Server Hosting"),
+        Runtime::AspCore => of_arch && rest.contains(" - Shared Framework"),
+        _ => of_arch,
+    };
+    Some(version).filter(|_| matches)
+}
@@ -0,0 +1,176 @@
+use std::{fmt::Display, time::Duration};
+
+use serde::Serialize;
+
+use crate::{Architecture, Plan, installer};
+
+/// Structured summary of a run, emitted with `--output json`.
+#[derive(Default, Serialize)]
+pub struct Report {
+    pub plan: Option<Plan>,
+    pub components: Vec<Component>,
+    pub conflicts: Vec<String>,
+    pub status: Status,
+    pub error: Option<String>,
+    /// Whether an installer asked for a reboot to finish.
+    pub reboot_required: bool,
+    /// Shell commands that point apps at runtimes installed to a directory of
+    /// their own, when asked to print them instead of setting the environment.
+    pub environment: Vec<String>,
+    pub summary: Option<Summary>,
+}
+
+/// Totals of a run, for tracking how long provisioning takes.
+#[derive(Default, Serialize)]
+pub struct Summary {
+    /// The components installed, repaired or uninstalled, with their versions.
+    pub installed: Vec<String>,
+    pub download_bytes: u64,
+    /// Time spent downloading, added up over the downloads.
+    pub download_ms: u64,
+    /// Average download speed in bytes per second, if anything was downloaded.
+    pub download_bytes_per_sec: Option<u64>,
+    /// Wall-clock time spent resolving versions and working out what is missing.
+    pub plan_ms: u64,
+    /// Wall-clock time spent installing, less the time spent downloading.
+    pub install_ms: u64,
+    pub total_ms: u64,
+}
+
+impl Summary {
+    /// Sums up the components of `report`, given how long planning and
+    /// installing took and the whole run.
+    pub fn new(report: &Report, plan: Duration, install: Duration, total: Duration) -> Self {
+        let mut summary = Summary {
+            plan_ms: plan.as_millis() as u64,
+            total_ms: total.as_millis() as u64,
+            ..Summary::default()
+        };
+        for component in &report.components {
+            if matches!(component.action, Action::Installed | Action::Repaired | Action::Uninstalled) {
+                let version = component.resolved_version.as_ref().or(component.requested_version.as_ref());
+                let name = format!("{} {}", component.name, component.arch);
+                summary.installed.push(version.map_or_else(|| name.clone(), |version| format!("{} {}", name, version)));
+            }
+            summary.download_bytes += component.download_bytes.unwrap_or(0);
+            summary.download_ms += component.download_ms.unwrap_or(0);
+        }
+        if summary.download_bytes > 0 && summary.download_ms > 0 {
+            summary.download_bytes_per_sec = Some(summary.download_bytes * 1000 / summary.download_ms);
+        }
+        summary.install_ms = (install.as_millis() as u64).saturating_sub(summary.download_ms);
+        summary
+    }
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} installed", self.installed.len())?;
+        if self.download_bytes > 0 {
+            write!(f, ", {:.1} MB downloaded", self.download_bytes as f64 / 1_000_000.0)?;
+        }
+        if let Some(speed) = self.download_bytes_per_sec {
+            write!(f, " at {:.1} MB/s", speed as f64 / 1_000_000.0)?;
+        }
+        write!(
+            f,
+            "; planning {:.1}s, downloading {:.1}s, installing {:.1}s, {:.1}s in all",
+            self.plan_ms as f64 / 1000.0,
+            self.download_ms as f64 / 1000.0,
+            self.install_ms as f64 / 1000.0,
+            self.total_ms as f64 / 1000.0
+        )
+    }
+}
+
+#[derive(Serialize)]
+pub struct Component {
+    pub name: String,
+    pub arch: String,
+    pub requested_version: Option<String>,
+    pub detected: bool,
+    pub resolved_version: Option<String>,
+    pub url: Option<String>,
+    pub hash: Option<String>,
+    pub action: Action,
+    pub exit_code: Option<i32>,
+    /// Time from starting to install the component to finishing, including
+    /// downloading its installer unless that was done ahead.
+    pub duration_ms: Option<u64>,
+    /// Bytes downloaded for the component; zero if the cache had its installer.
+    pub download_bytes: Option<u64>,
+    pub download_ms: Option<u64>,
+}
+
+impl Component {
+    /// The entry for the `name` component on `arch`, not yet detected or
+    /// acted on; callers set the fields they know.
+    pub fn new(name: impl Into<String>, arch: Architecture) -> Self {
+        Component {
+            name: name.into(),
+            arch: arch.to_string(),
+            requested_version: None,
+            detected: false,
+            resolved_version: None,
+            url: None,
+            hash: None,
+            action: Action::Skipped,
+            exit_code: None,
+            duration_ms: None,
+            download_bytes: None,
+            download_ms: None,
+        }
+    }
+}
+
+impl Display for Component {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.name, self.arch)?;
+        if let Some(version) = self.resolved_version.as_ref().or(self.requested_version.as_ref()) {
+            write!(f, " {}", version)?;
+        }
+        write!(f, ": {}", self.action)?;
+        match self.exit_code.map(|code| (code, installer::describe(code))) {
+            Some((0, _)) | None => {}
+            Some((code, Some(meaning))) => write!(f, " (exit code {}: {})", code, meaning)?,
+            Some((code, None)) => write!(f, " (exit code {})", code)?,
+        }
+        if let Some(duration_ms) = self.duration_ms {
+            write!(f, " in {:.1}s", duration_ms as f64 / 1000.0)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Skipped,
+    Installed,
+    Repaired,
+    Uninstalled,
+    Failed,
+    Cancelled,
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Action::Skipped => f.write_str("skipped"),
+            Action::Installed => f.write_str("installed"),
+            Action::Repaired => f.write_str("repaired"),
+            Action::Uninstalled => f.write_str("uninstalled"),
+            Action::Failed => f.write_str("failed"),
+            Action::Cancelled => f.write_str("cancelled"),
+        }
+    }
+}
+
+#[derive(Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    #[default]
+    Ok,
+    Failed,
+    Cancelled,
+}
@@ -0,0 +1,155 @@
+use std::{fmt::Display, fs, path::Path, str::FromStr};
+
+use anyhow::{Context, Result, anyhow, bail};
+use ring::signature::{ED25519, UnparsedPublicKey};
+use serde::{Deserialize, Deserializer, de::Error};
+use sha2::{Digest, Sha256};
+
+use crate::{Architecture, DotnetRedistError, InstallRequest, InstallRequestBuilder, RollForward, Runtime, http, verify::hex};
+
+/// The components an application depends on, installed in the order listed.
+///
+/// ```toml
+/// [[components]]
+/// runtime = "windowsdesktop"
+/// version = "8.0"
+/// arch = "x64"
+///
+/// [[components]]
+/// runtime = "aspcore"
+/// version = "8"
+/// arch = "x86"
+/// ```
+///
+/// The same structure is accepted as JSON when the file name ends in `.json`.
+#[derive(Deserialize)]
+pub struct Requirements {
+    pub components: Vec<Requirement>,
+}
+
+/// How a manifest downloaded from a URL is checked before it is trusted.
+pub enum ManifestCheck {
+    /// The base64 Ed25519 public key that the manifest's detached signature,
+    /// in base64 at its URL with `.sig` appended, must verify with. The
+    /// manifest can then change without changing the check.
+    Signature(String),
+    /// The SHA-256 hash, in hex, the manifest must have.
+    Sha256(String),
+}
+
+/// One required component.
+#[derive(Deserialize)]
+pub struct Requirement {
+    /// `major[.minor[.patch]]`; any release with that prefix satisfies it.
+    /// `lts`, `sts` and `current` stand for the newest supported channel of that kind.
+    pub version: String,
+    #[serde(default, deserialize_with = "parse_optional")]
+    pub runtime: Option<Runtime>,
+    #[serde(deserialize_with = "parse")]
+    pub arch: Architecture,
+    pub global_tool: Option<String>,
+    /// How far past `version` an installed or downloaded release may be.
+    #[serde(default, deserialize_with = "parse_optional")]
+    pub roll_forward: Option<RollForward>,
+}
+
+impl Requirements {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents =
+            fs::read_to_string(path).with_context(|| format!("cannot read manifest {}", path.display()))?;
+        let json = path.extension().is_some_and(|ext| ext == "json");
+        Self::parse(&contents, json, &path.display())
+    }
+
+    /// Downloads the manifest at `url`, which a central team can then change
+    /// for every machine using it, and verifies it with `check`.
+    pub async fn fetch(url: &str, check: &ManifestCheck) -> Result<Self> {
+        let contents = fetch_text(url).await?;
+        match check {
+            ManifestCheck::Sha256(expected) => {
+                let actual = hex(&Sha256::digest(contents.as_bytes()));
+                if !actual.eq_ignore_ascii_case(expected.trim()) {
+                    bail!(DotnetRedistError::VerificationFailed(anyhow!(
+                        "SHA256 mismatch for manifest {}: expected {}, got {}",
+                        url,
+                        expected.trim(),
+                        actual
+                    )));
+                }
+            }
+            ManifestCheck::Signature(key) => {
+                let key = base64::decode(key.trim()).context("the manifest key is not base64")?;
+                let signature_url = format!("{}.sig", url);
+                let signature = base64::decode(fetch_text(&signature_url).await?.trim())
+                    .with_context(|| format!("{} is not base64", signature_url))?;
+                UnparsedPublicKey::new(&ED25519, &key).verify(contents.as_bytes(), &signature).map_err(|_| {
+                    DotnetRedistError::VerificationFailed(anyhow!("manifest {} is not signed with the manifest key", url))
+                })?;
+            }
+        }
+
+        let path = url.split(['?', '#']).next().unwrap_or(url);
+        Self::parse(&contents, path.ends_with(".json"), &url)
+    }
+
+    pub(crate) fn parse(contents: &str, json: bool, name: &dyn Display) -> Result<Self> {
+        let requirements: Requirements = if json {
+            serde_json::from_str(contents).with_context(|| format!("invalid manifest {}", name))?
+        } else {
+            toml::from_str(contents).with_context(|| format!("invalid manifest {}", name))?
+        };
+
+        if requirements.components.is_empty() {
+            bail!("manifest {} lists no components", name);
+        }
+        Ok(requirements)
+    }
+}
+
+async fn fetch_text(url: &str) -> Result<String> {
+    let mut response = http::get(url).await?;
+    if !response.status().is_success() {
+        bail!(DotnetRedistError::Network(anyhow!("could not download {}: {}", url, response.status())));
+    }
+    http::text(&mut response).await
+}
+
+impl Requirement {
+    /// Starts an install request for this component.
+    pub fn builder(&self) -> InstallRequestBuilder {
+        let mut builder = InstallRequest::builder()
+            .version(&self.version)
+            .arch(self.arch);
+
+        if let Some(runtime) = self.runtime {
+            builder = builder.runtime(runtime);
+        }
+        if let Some(package_id) = &self.global_tool {
+            builder = builder.global_tool(package_id);
+        }
+        if let Some(roll_forward) = self.roll_forward {
+            builder = builder.roll_forward(roll_forward);
+        }
+
+        builder
+    }
+}
+
+fn parse<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    let s = String::deserialize(deserializer)?;
+    s.parse().map_err(D::Error::custom)
+}
+
+fn parse_optional<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    parse(deserializer).map(Some)
+}
@@ -0,0 +1,392 @@
+use std::str::FromStr;
+
+use anyhow::{Context, Error, Result, anyhow, bail};
+use http_types::StatusCode;
+use semver::Version;
+
+use crate::{
+    Architecture, BASE_URL, DotnetRedistError, DotnetVersion, RollForward, Runtime, approved, catalog, http, metadata,
+    mirrors, netfx,
+    releases::{self, Artifact, Package, file_prefix},
+};
+
+/// The directory of the download server that holds `runtime`'s releases of
+/// `major`. The Windows Desktop runtime moved out of the .NET runtime's
+/// directory into its own in .NET 5, for its releases and channel files alike.
+fn server_dir(runtime: Runtime, major: u64) -> &'static str {
+    match runtime {
+        Runtime::Dotnet => "Runtime",
+        Runtime::WindowsDesktop if major >= 5 => "WindowsDesktop",
+        Runtime::WindowsDesktop => "Runtime",
+        Runtime::AspCore | Runtime::HostingBundle => "aspnetcore/Runtime",
+        Runtime::Sdk => "Sdk",
+        Runtime::NetFx48 => unreachable!(".NET Framework is not on the dotnet download server"),
+    }
+}
+
+/// The URL of an installer on the download server at `base`.
+fn download_url(base: &str, arch: Architecture, runtime: Runtime, version: Version, product_version: &str, package: Package) -> String {
+    if runtime == Runtime::NetFx48 {
+        return netfx::artifact().url;
+    }
+    let dir = server_dir(runtime, version.major);
+    let (os, ext) = (package.os(), package.extension());
+
+    // The hosting bundle carries every architecture.
+    if runtime == Runtime::HostingBundle {
+        return format!("{}/{}/{}/{}-{}-{}.{}", base, dir, version, file_prefix(runtime), product_version, os, ext);
+    }
+    let arch = match arch {
+        Architecture::X86 => "x86",
+        Architecture::X64 => "x64",
+        Architecture::Arm64 => "arm64",
+    };
+    format!(
+        "{}/{}/{}/{}-{}-{}-{}.{}",
+        base,
+        dir,
+        version,
+        file_prefix(runtime),
+        product_version,
+        os,
+        arch,
+        ext
+    )
+}
+
+/// Resolves the installer for a requirement from the catalog built into the
+/// binary, then the official release metadata, falling back to probing the
+/// download server's layout, and to the newest releases known when the
+/// binary was built if the layout's version files are unreachable too.
+pub(crate) async fn resolve_installer(
+    runtime: Runtime,
+    arch: Architecture,
+    version: &DotnetVersion,
+    package: Package,
+    include_prerelease: bool,
+    roll_forward: RollForward,
+) -> Result<Artifact> {
+    // The Framework isn't on the dotnet download server; there is one installer.
+    if runtime == Runtime::NetFx48 {
+        let artifact = netfx::artifact();
+        if !version.is_satisfied_by(&artifact.version, include_prerelease, roll_forward) {
+            bail!(DotnetRedistError::VersionNotFound(anyhow!(
+                ".NET Framework {} does not satisfy version {}",
+                artifact.version,
+                version
+            )));
+        }
+        if !approved::allows(&artifact.version) {
+            bail!(DotnetRedistError::VersionNotFound(anyhow!("no approved version satisfies {}", version)));
+        }
+        return Ok(artifact);
+    }
+
+    if let Some(artifact) = catalog::find(runtime, arch, version, package, include_prerelease, roll_forward) {
+        return Ok(artifact);
+    }
+
+    match releases::resolve(runtime, arch, version, package, include_prerelease, roll_forward).await {
+        Ok(artifact) => Ok(artifact),
+        Err(e) => {
+            log::warn!("{:#}, probing the download server instead", e);
+            let version = match approved::list() {
+                Some(approved) => newest_approved(approved, version, include_prerelease, roll_forward)?,
+                None => match find_best_version(runtime, version, include_prerelease, roll_forward).await {
+                    Ok(version) => version,
+                    Err(e) => match catalog::latest(runtime, version, include_prerelease, roll_forward) {
+                        Some(built_in) => {
+                            log::warn!(
+                                "{:#}; falling back to {}, the newest release known when this tool was built",
+                                e,
+                                built_in
+                            );
+                            built_in
+                        }
+                        None => return Err(e),
+                    },
+                },
+            };
+            let url = find_download_url(runtime, arch, &version, None, package).await?;
+            Ok(Artifact { version, url, hash: None })
+        }
+    }
+}
+
+/// The installer in the download server's `blob_version` directory, named
+/// by `product_version` if given and else by the one looked up as usual, for
+/// releases whose metadata is inconsistent. There is no hash to check it by.
+pub(crate) async fn resolve_blob(
+    runtime: Runtime,
+    arch: Architecture,
+    blob_version: &Version,
+    product_version: Option<&str>,
+    package: Package,
+) -> Result<Artifact> {
+    let url = find_download_url(runtime, arch, blob_version, product_version, package).await?;
+    Ok(Artifact { version: blob_version.clone(), url, hash: None })
+}
+
+/// Resolves the installer of the newest daily build of the channel `version`
+/// names from the daily builds feed, or of the build it names in full.
+///
+/// A daily build counts as a build of the release it precedes when deciding
+/// whether it satisfies the version. The feed publishes hashes next to its
+/// files rather than in release metadata.
+pub(crate) async fn resolve_daily(
+    runtime: Runtime,
+    arch: Architecture,
+    version: &DotnetVersion,
+    package: Package,
+    roll_forward: RollForward,
+) -> Result<Artifact> {
+    let base = mirrors::daily_endpoint();
+    let dir = format!("{}/{}", base, server_dir(runtime, version.major));
+
+    let build = match version {
+        DotnetVersion { major, minor: Some(minor), patch: Some(patch), pre, range: None } if !pre.is_empty() => {
+            Version { pre: pre.clone(), ..Version::new(*major, *minor, *patch) }
+        }
+        _ => {
+            let minor = match version.minor {
+                Some(minor) if roll_forward != RollForward::LatestMinor => minor,
+                _ => find_newest_minor(&dir, version.major).await?,
+            };
+            let url = format!("{}/{}.{}/latest.version", dir, version.major, minor);
+            let text = metadata::get_text(&url)
+                .await?
+                .ok_or_else(|| DotnetRedistError::VersionNotFound(anyhow!("{} does not exist", url)))?;
+            let build = parse_build_version(text.lines().last().unwrap_or_default())
+                .with_context(|| format!("{} does not name a build", url))?;
+            let release = Version { pre: Vec::new(), build: Vec::new(), ..build.clone() };
+            if !version.is_satisfied_by(&release, true, roll_forward) {
+                bail!(DotnetRedistError::VersionNotFound(anyhow!(
+                    "the newest {}.{} daily build {} does not satisfy {}",
+                    version.major, minor, build, version
+                )));
+            }
+            build
+        }
+    };
+
+    let product_version = match metadata::get_text(&format!("{}/{}/productVersion.txt", dir, build)).await {
+        Ok(Some(text)) if !text.trim().is_empty() => text.trim().to_string(),
+        result => {
+            if let Err(e) = result {
+                log::debug!("{:#}", e);
+            }
+            build.to_string()
+        }
+    };
+    let url = download_url(base, arch, runtime, build.clone(), &product_version, package);
+    if !http::exists(&url).await? {
+        bail!(DotnetRedistError::VersionNotFound(anyhow!("the daily build {} has no installer at {}", build, url)));
+    }
+    let hash = match metadata::get_text(&format!("{}.sha512", url)).await {
+        Ok(text) => text.and_then(|text| text.split_whitespace().next().map(str::to_lowercase)),
+        Err(e) => {
+            log::debug!("{:#}", e);
+            None
+        }
+    };
+    Ok(Artifact { version: build, url, hash })
+}
+
+/// Parses a build version such as `10.0.0-preview.4.25206.1`, dropping its
+/// build metadata and the leading zeros semver forbids in build numbers.
+fn parse_build_version(text: &str) -> Result<Version> {
+    let text = text.trim();
+    let version = text.split('+').next().unwrap_or_default();
+    let version = version
+        .split('.')
+        .map(|part| match part.trim_start_matches('0') {
+            _ if !part.bytes().all(|b| b.is_ascii_digit()) => part,
+            "" => "0",
+            trimmed => trimmed,
+        })
+        .collect::<Vec<_>>()
+        .join(".");
+    Version::parse(&version).with_context(|| format!("invalid build version {}", text))
+}
+
+/// The approved version `version` rolls forward to. The download server
+/// has no listing to check it against, so it is tried as is.
+fn newest_approved(
+    approved: &[Version],
+    version: &DotnetVersion,
+    include_prerelease: bool,
+    roll_forward: RollForward,
+) -> Result<Version> {
+    version
+        .select(approved.iter().map(|approved| (approved.clone(), ())), include_prerelease, roll_forward)
+        .map(|(approved, ())| approved)
+        .ok_or_else(|| DotnetRedistError::VersionNotFound(anyhow!("no approved version satisfies {}", version)).into())
+}
+
+/// The URL of the installer of `version`. Its file name has the product
+/// version, which for some releases differs from the version the directory
+/// is named by, so unless `product_version` is given that is read from
+/// `productVersion.txt`, then looked up in the release metadata, and only
+/// then taken to be the version itself.
+async fn find_download_url(
+    runtime: Runtime,
+    arch: Architecture,
+    version: &Version,
+    product_version: Option<&str>,
+    package: Package,
+) -> Result<String> {
+    let product_version = match product_version {
+        Some(product_version) => Some(product_version.to_string()),
+        None => find_product_version(runtime, version).await,
+    };
+    let url = match product_version {
+        Some(product_version) => download_url(BASE_URL, arch, runtime, version.clone(), &product_version, package),
+        None => match releases::file_url(runtime, arch, version, package).await {
+            Ok(Some(url)) => url,
+            result => {
+                if let Err(e) = result {
+                    log::debug!("{:#}", e);
+                }
+                log::debug!("no product version found for {} {}, assuming it is the same", runtime, version);
+                download_url(BASE_URL, arch, runtime, version.clone(), &version.to_string(), package)
+            }
+        },
+    };
+
+    if !http::exists(&url).await? {
+        bail!(DotnetRedistError::VersionNotFound(anyhow!(
+            "{} {} has no installer at {}",
+            runtime,
+            version,
+            url
+        )));
+    }
+    Ok(url)
+}
+
+/// Reads the product version of `version` from `productVersion.txt`, asking
+/// each endpoint and mirror in turn, since they do not all have the file.
+async fn find_product_version(runtime: Runtime, version: &Version) -> Option<String> {
+    let hosts = mirrors::endpoints().into_iter().chain(mirrors::list().iter().map(String::as_str));
+    for host in hosts {
+        let url = format!("{}/{}/{}/productVersion.txt", host, server_dir(runtime, version.major), version);
+        let mut response = match http::get_from_host(&url).await {
+            Ok(response) if response.status() == StatusCode::Ok => response,
+            Ok(response) => {
+                log::debug!("{} returned {}", url, response.status());
+                continue;
+            }
+            Err(e) => {
+                log::debug!("{}: {:#}", url, e);
+                continue;
+            }
+        };
+        match http::text(&mut response).await {
+            Ok(text) if !text.trim().is_empty() => return Some(text.trim().to_string()),
+            Ok(_) => log::debug!("{} is empty", url),
+            Err(e) => log::debug!("{}: {:#}", url, e),
+        }
+    }
+    None
+}
+
+/// Finds the release `version` rolls forward to from the `latest.version`
+/// files, which only name the newest patch of each channel.
+async fn find_best_version(
+    runtime: Runtime,
+    version: &DotnetVersion,
+    include_prerelease: bool,
+    roll_forward: RollForward,
+) -> Result<Version> {
+    if let (
+        DotnetVersion {
+            major,
+            minor: Some(minor),
+            patch: Some(patch),
+            pre,
+            range: None,
+        },
+        RollForward::Disable,
+    ) = (version, roll_forward)
+    {
+        return Ok(Version {
+            pre: pre.clone(),
+            ..Version::new(*major, *minor, *patch)
+        });
+    }
+
+    if runtime == Runtime::NetFx48 {
+        return Ok(netfx::version());
+    }
+    let url = format!("{}/{}", BASE_URL, server_dir(runtime, version.major));
+
+    let minor = match version.minor {
+        Some(minor) if roll_forward != RollForward::LatestMinor => minor,
+        _ => find_newest_minor(&url, version.major).await?,
+    };
+
+    let full_url = format!("{}/{}.{}/latest.version", url, version.major, minor);
+    let version_text = metadata::get_text(&full_url)
+        .await?
+        .ok_or_else(|| DotnetRedistError::VersionNotFound(anyhow!("{} does not exist", full_url)))?;
+
+    if let Some(version_text) = version_text.lines().last() {
+        // Preview channels publish their newest preview as the latest version.
+        let latest = Version::from_str(version_text.trim())?;
+        if !latest.pre.is_empty() && !include_prerelease {
+            bail!(DotnetRedistError::VersionNotFound(anyhow!(
+                "the newest {}.{} release is the prerelease {}; pass --include-prerelease to install it",
+                version.major, minor, latest
+            )));
+        }
+        if !version.is_satisfied_by(&latest, include_prerelease, roll_forward) {
+            bail!(DotnetRedistError::VersionNotFound(anyhow!(
+                "the newest {}.{} release {} does not satisfy {}",
+                version.major, minor, latest, version
+            )));
+        }
+        Ok(latest)
+    } else {
+        Err(anyhow!(
+            "version file did not contain expected version text"
+        ))
+    }
+}
+
+/// How many minor versions are probed at once, which the default
+/// connection limit per host lets run side by side.
+const MINOR_PROBES: u64 = 4;
+
+/// Finds the newest minor version of `major_version` with a `latest.version`
+/// file, asking only whether each exists. Minors are released in order, so
+/// the first missing one ends the search.
+async fn find_newest_minor(url: &str, major_version: u64) -> Result<u64> {
+    let executor = smol::LocalExecutor::new();
+    for first in (0..).step_by(MINOR_PROBES as usize) {
+        let probes = (first..first + MINOR_PROBES)
+            .map(|minor| {
+                let full_url = format!("{}/{}.{}/latest.version", url, major_version, minor);
+                executor.spawn(async move { http::exists(&full_url).await })
+            })
+            .collect::<Vec<_>>();
+        let found = executor
+            .run(async {
+                let mut found = Vec::new();
+                for probe in probes {
+                    found.push(probe.await?);
+                }
+                Ok::<_, Error>(found)
+            })
+            .await?;
+
+        match found.iter().position(|&exists| !exists) {
+            Some(0) if first == 0 => {
+                return Err(DotnetRedistError::VersionNotFound(anyhow!("No available versions found")).into());
+            }
+            Some(missing) => return Ok(first + missing as u64 - 1),
+            None => {}
+        }
+    }
+
+    unreachable!();
+}
@@ -0,0 +1,51 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+use serde_json::{Value, json};
+
+use crate::report::{Action, Report};
+
+/// Writes a CycloneDX 1.5 JSON document to `path` listing the components
+/// `report` installed or repaired, with their versions and, where known,
+/// the SHA-512 hash and download URL of their installers.
+pub fn write(report: &Report, path: &Path) -> Result<()> {
+    let components = report
+        .components
+        .iter()
+        .filter(|component| matches!(component.action, Action::Installed | Action::Repaired))
+        .map(|component| {
+            let version = component.resolved_version.as_ref().or(component.requested_version.as_ref());
+            let mut entry = json!({
+                "type": "framework",
+                "bom-ref": format!("{}-{}-{}", component.name, component.arch.to_lowercase(), version.map_or("", |v| v)),
+                "name": component.name,
+                "version": version,
+                "properties": [{ "name": "arch", "value": component.arch.to_lowercase() }],
+            });
+            if let Some(hash) = &component.hash {
+                entry["hashes"] = json!([{ "alg": "SHA-512", "content": hash.to_lowercase() }]);
+            }
+            if let Some(url) = &component.url {
+                entry["externalReferences"] = json!([{ "type": "distribution", "url": url }]);
+            }
+            entry
+        })
+        .collect::<Vec<Value>>();
+
+    let document = json!({
+        "bomFormat": "CycloneDX",
+        "specVersion": "1.5",
+        "version": 1,
+        "metadata": {
+            "tools": {
+                "components": [{
+                    "type": "application",
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                }],
+            },
+        },
+        "components": components,
+    });
+    fs::write(path, serde_json::to_string_pretty(&document)?).with_context(|| format!("cannot write {}", path.display()))
+}
@@ -0,0 +1,448 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use anyhow::Result;
+use smol::{net::TcpStream, prelude::*};
+
+/// A TLS connection through Windows' own TLS stack, which follows the
+/// machine's trust store, the protocols and ciphers group policy allows,
+/// and FIPS mode.
+#[cfg(windows)]
+pub(crate) struct SchannelStream {
+    tcp: TcpStream,
+    credentials: winapi::shared::sspi::CredHandle,
+    context: Option<winapi::shared::sspi::CtxtHandle>,
+    /// The server's name, NUL-terminated, which its certificate has to match.
+    target: Vec<u16>,
+    sizes: winapi::shared::sspi::SecPkgContext_StreamSizes,
+    /// Bytes received but not decrypted yet, the start of an incomplete record.
+    received: Vec<u8>,
+    /// Bytes decrypted but not read yet.
+    plain: Vec<u8>,
+    /// Records encrypted but not sent yet.
+    outgoing: Vec<u8>,
+    closed: bool,
+}
+
+/// Performs a TLS handshake for `host` over an established TCP stream with
+/// Schannel, which checks the server's certificate itself unless `insecure`.
+#[cfg(windows)]
+pub(crate) async fn connect(host: &str, tcp: TcpStream, insecure: bool) -> Result<SchannelStream> {
+    use std::{mem, ptr::null_mut};
+    use winapi::{
+        shared::{
+            sspi::{AcquireCredentialsHandleW, QueryContextAttributesW, SECPKG_ATTR_STREAM_SIZES, SECPKG_CRED_OUTBOUND},
+            winerror::{SEC_E_INCOMPLETE_MESSAGE, SEC_E_OK, SEC_I_CONTINUE_NEEDED, SEC_I_INCOMPLETE_CREDENTIALS},
+        },
+        um::schannel::{
+            SCH_CRED_AUTO_CRED_VALIDATION, SCH_CRED_MANUAL_CRED_VALIDATION, SCH_CRED_NO_SERVERNAME_CHECK, SCHANNEL_CRED,
+            SCHANNEL_CRED_VERSION, UNISP_NAME,
+        },
+    };
+
+    // No protocols or algorithms of our own, so that the system's settings apply.
+    let mut cred: SCHANNEL_CRED = unsafe { mem::zeroed() };
+    cred.dwVersion = SCHANNEL_CRED_VERSION;
+    cred.dwFlags = if insecure {
+        SCH_CRED_MANUAL_CRED_VALIDATION | SCH_CRED_NO_SERVERNAME_CHECK
+    } else {
+        SCH_CRED_AUTO_CRED_VALIDATION
+    };
+    let mut package = wide(UNISP_NAME);
+    let mut credentials = unsafe { mem::zeroed() };
+    let status = unsafe {
+        AcquireCredentialsHandleW(
+            null_mut(),
+            package.as_mut_ptr(),
+            SECPKG_CRED_OUTBOUND,
+            null_mut(),
+            &mut cred as *mut _ as _,
+            None,
+            null_mut(),
+            &mut credentials,
+            null_mut(),
+        )
+    };
+    if status < 0 {
+        anyhow::bail!("cannot set up Schannel: error {:#x}", status);
+    }
+
+    let mut stream = SchannelStream {
+        tcp,
+        credentials,
+        context: None,
+        target: wide(host),
+        sizes: unsafe { mem::zeroed() },
+        received: Vec::new(),
+        plain: Vec::new(),
+        outgoing: Vec::new(),
+        closed: false,
+    };
+    let mut read_more = false;
+    loop {
+        if read_more {
+            let mut chunk = [0; 16 * 1024];
+            let read = stream.tcp.read(&mut chunk).await?;
+            if read == 0 {
+                anyhow::bail!("{} closed the connection during the TLS handshake", host);
+            }
+            stream.received.extend_from_slice(&chunk[..read]);
+        }
+
+        let (status, token) = stream.step();
+        if !token.is_empty() {
+            stream.tcp.write_all(&token).await?;
+        }
+        match status {
+            SEC_E_OK => break,
+            SEC_I_CONTINUE_NEEDED => read_more = stream.received.is_empty(),
+            SEC_E_INCOMPLETE_MESSAGE => read_more = true,
+            SEC_I_INCOMPLETE_CREDENTIALS => {
+                anyhow::bail!("{} asks for a client certificate, which is not supported", host)
+            }
+            status => anyhow::bail!("TLS handshake with {} failed: Schannel error {:#x}", host, status),
+        }
+    }
+
+    let status = unsafe {
+        QueryContextAttributesW(
+            stream.context.as_mut().unwrap(),
+            SECPKG_ATTR_STREAM_SIZES,
+            &mut stream.sizes as *mut _ as _,
+        )
+    };
+    if status != SEC_E_OK {
+        anyhow::bail!("cannot get the TLS record sizes from Schannel: error {:#x}", status);
+    }
+    Ok(stream)
+}
+
+#[cfg(windows)]
+impl SchannelStream {
+    /// Feeds the handshake messages received so far to Schannel, keeping
+    /// what it did not consume, and returns its status and what to send.
+    fn step(&mut self) -> (i32, Vec<u8>) {
+        use std::{mem, ptr::null_mut};
+        use winapi::shared::{
+            sspi::{
+                FreeContextBuffer, ISC_REQ_ALLOCATE_MEMORY, ISC_REQ_CONFIDENTIALITY, ISC_REQ_EXTENDED_ERROR,
+                ISC_REQ_REPLAY_DETECT, ISC_REQ_SEQUENCE_DETECT, ISC_REQ_STREAM, InitializeSecurityContextW,
+                SECBUFFER_ALERT, SECBUFFER_EMPTY, SECBUFFER_EXTRA, SECBUFFER_TOKEN, SECBUFFER_VERSION, SecBuffer,
+                SecBufferDesc,
+            },
+            winerror::SEC_E_INCOMPLETE_MESSAGE,
+        };
+
+        let mut input = [
+            buffer(SECBUFFER_TOKEN, &mut self.received),
+            SecBuffer { cbBuffer: 0, BufferType: SECBUFFER_EMPTY, pvBuffer: null_mut() },
+        ];
+        let mut input_desc = SecBufferDesc { ulVersion: SECBUFFER_VERSION, cBuffers: 2, pBuffers: input.as_mut_ptr() };
+        let mut output = [
+            SecBuffer { cbBuffer: 0, BufferType: SECBUFFER_TOKEN, pvBuffer: null_mut() },
+            SecBuffer { cbBuffer: 0, BufferType: SECBUFFER_ALERT, pvBuffer: null_mut() },
+        ];
+        let mut output_desc =
+            SecBufferDesc { ulVersion: SECBUFFER_VERSION, cBuffers: 2, pBuffers: output.as_mut_ptr() };
+
+        let started = self.context.is_some();
+        let context = self.context.get_or_insert_with(|| unsafe { mem::zeroed() });
+        let mut attributes = 0;
+        let status = unsafe {
+            InitializeSecurityContextW(
+                &mut self.credentials,
+                if started { context as *mut _ } else { null_mut() },
+                self.target.as_mut_ptr(),
+                ISC_REQ_SEQUENCE_DETECT
+                    | ISC_REQ_REPLAY_DETECT
+                    | ISC_REQ_CONFIDENTIALITY
+                    | ISC_REQ_ALLOCATE_MEMORY
+                    | ISC_REQ_STREAM
+                    | ISC_REQ_EXTENDED_ERROR,
+                0,
+                0,
+                if started { &mut input_desc } else { null_mut() },
+                0,
+                context,
+                &mut output_desc,
+                &mut attributes,
+                null_mut(),
+            )
+        };
+        if !started && status < 0 {
+            self.context = None;
+        }
+
+        let mut token = Vec::new();
+        for buffer in &output {
+            if buffer.pvBuffer.is_null() {
+                continue;
+            }
+            if buffer.BufferType == SECBUFFER_TOKEN {
+                token.extend_from_slice(unsafe { contents(buffer) });
+            }
+            unsafe { FreeContextBuffer(buffer.pvBuffer) };
+        }
+        if status != SEC_E_INCOMPLETE_MESSAGE {
+            let extra = if input[1].BufferType == SECBUFFER_EXTRA { input[1].cbBuffer as usize } else { 0 };
+            self.received.drain(..self.received.len() - extra);
+        }
+        (status, token)
+    }
+
+    /// Decrypts the next record received, if it is all there; returns whether it was.
+    fn decrypt(&mut self) -> io::Result<bool> {
+        use std::ptr::null_mut;
+        use winapi::shared::{
+            sspi::{
+                DecryptMessage, SECBUFFER_DATA, SECBUFFER_EMPTY, SECBUFFER_EXTRA, SECBUFFER_VERSION, SecBuffer,
+                SecBufferDesc,
+            },
+            winerror::{SEC_E_INCOMPLETE_MESSAGE, SEC_E_OK, SEC_I_CONTEXT_EXPIRED, SEC_I_RENEGOTIATE},
+        };
+
+        let empty = SecBuffer { cbBuffer: 0, BufferType: SECBUFFER_EMPTY, pvBuffer: null_mut() };
+        let mut buffers = [
+            buffer(SECBUFFER_DATA, &mut self.received),
+            empty,
+            empty,
+            empty,
+        ];
+        let mut desc = SecBufferDesc { ulVersion: SECBUFFER_VERSION, cBuffers: 4, pBuffers: buffers.as_mut_ptr() };
+        let status = unsafe { DecryptMessage(self.context.as_mut().unwrap(), &mut desc, 0, null_mut()) };
+        match status {
+            SEC_E_INCOMPLETE_MESSAGE => return Ok(false),
+            SEC_I_CONTEXT_EXPIRED => {
+                // The server sent close_notify.
+                self.closed = true;
+                self.received.clear();
+                return Ok(true);
+            }
+            SEC_E_OK | SEC_I_RENEGOTIATE => {}
+            status => return Err(schannel_error("cannot decrypt a TLS record", status)),
+        }
+
+        let mut extra = 0;
+        for buffer in &buffers {
+            match buffer.BufferType {
+                // Decrypted in place, within `received`.
+                SECBUFFER_DATA => self.plain.extend_from_slice(unsafe { contents(buffer) }),
+                SECBUFFER_EXTRA => extra = buffer.cbBuffer as usize,
+                _ => {}
+            }
+        }
+        self.received.drain(..self.received.len() - extra);
+
+        // TLS 1.3 servers send session tickets and key updates after the
+        // handshake, which go back through it.
+        if status == SEC_I_RENEGOTIATE {
+            let (status, token) = self.step();
+            if status < 0 {
+                return Err(schannel_error("cannot process a post-handshake TLS message", status));
+            }
+            self.outgoing.extend_from_slice(&token);
+        }
+        Ok(true)
+    }
+
+    /// Encrypts as much of `data` as fits in one record, queueing it to be sent.
+    fn encrypt(&mut self, data: &[u8]) -> io::Result<usize> {
+        use std::ptr::null_mut;
+        use winapi::shared::{
+            sspi::{
+                EncryptMessage, SECBUFFER_DATA, SECBUFFER_EMPTY, SECBUFFER_STREAM_HEADER, SECBUFFER_STREAM_TRAILER,
+                SECBUFFER_VERSION, SecBuffer, SecBufferDesc,
+            },
+            winerror::SEC_E_OK,
+        };
+
+        let len = data.len().min(self.sizes.cbMaximumMessage as usize);
+        let (header, trailer) = (self.sizes.cbHeader as usize, self.sizes.cbTrailer as usize);
+        let mut record = vec![0; header + len + trailer];
+        record[header..header + len].copy_from_slice(&data[..len]);
+        let base = record.as_mut_ptr();
+        let mut buffers = [
+            SecBuffer { cbBuffer: header as u32, BufferType: SECBUFFER_STREAM_HEADER, pvBuffer: base as _ },
+            SecBuffer { cbBuffer: len as u32, BufferType: SECBUFFER_DATA, pvBuffer: unsafe { base.add(header) } as _ },
+            SecBuffer {
+                cbBuffer: trailer as u32,
+                BufferType: SECBUFFER_STREAM_TRAILER,
+                pvBuffer: unsafe { base.add(header + len) } as _,
+            },
+            SecBuffer { cbBuffer: 0, BufferType: SECBUFFER_EMPTY, pvBuffer: null_mut() },
+        ];
+        let mut desc = SecBufferDesc { ulVersion: SECBUFFER_VERSION, cBuffers: 4, pBuffers: buffers.as_mut_ptr() };
+        let status = unsafe { EncryptMessage(self.context.as_mut().unwrap(), 0, &mut desc, 0) };
+        if status != SEC_E_OK {
+            return Err(schannel_error("cannot encrypt a TLS record", status));
+        }
+
+        // The trailer may come out shorter than the most it can take.
+        record.truncate(buffers[..3].iter().map(|buffer| buffer.cbBuffer as usize).sum());
+        self.outgoing.extend_from_slice(&record);
+        Ok(len)
+    }
+
+    /// Sends the queued records.
+    fn poll_send(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.outgoing.is_empty() {
+            match Pin::new(&mut self.tcp).poll_write(cx, &self.outgoing) {
+                Poll::Ready(Ok(0)) => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                Poll::Ready(Ok(written)) => {
+                    self.outgoing.drain(..written);
+                }
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(windows)]
+impl AsyncRead for SchannelStream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        // Answers to post-handshake messages go out as soon as they can.
+        if let Poll::Ready(Err(e)) = this.poll_send(cx) {
+            return Poll::Ready(Err(e));
+        }
+        loop {
+            if !this.plain.is_empty() {
+                let len = buf.len().min(this.plain.len());
+                buf[..len].copy_from_slice(&this.plain[..len]);
+                this.plain.drain(..len);
+                return Poll::Ready(Ok(len));
+            }
+            if this.closed {
+                return Poll::Ready(Ok(0));
+            }
+            if !this.received.is_empty() {
+                match this.decrypt() {
+                    Ok(true) => continue,
+                    Ok(false) => {}
+                    Err(e) => return Poll::Ready(Err(e)),
+                }
+            }
+
+            let mut chunk = [0; 16 * 1024];
+            match Pin::new(&mut this.tcp).poll_read(cx, &mut chunk) {
+                Poll::Ready(Ok(0)) if this.received.is_empty() => this.closed = true,
+                Poll::Ready(Ok(0)) => {
+                    let e = io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed mid-record");
+                    return Poll::Ready(Err(e));
+                }
+                Poll::Ready(Ok(read)) => this.received.extend_from_slice(&chunk[..read]),
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+impl AsyncWrite for SchannelStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        match this.poll_send(cx) {
+            Poll::Ready(Ok(())) => {}
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+            Poll::Pending => return Poll::Pending,
+        }
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let written = this.encrypt(buf)?;
+        // Start sending it; what doesn't go out now goes with the next write or flush.
+        if let Poll::Ready(Err(e)) = this.poll_send(cx) {
+            return Poll::Ready(Err(e));
+        }
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        match this.poll_send(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut this.tcp).poll_flush(cx),
+            poll => poll,
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        match this.poll_send(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut this.tcp).poll_close(cx),
+            poll => poll,
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Drop for SchannelStream {
+    fn drop(&mut self) {
+        use winapi::shared::sspi::{DeleteSecurityContext, FreeCredentialsHandle};
+
+        unsafe {
+            if let Some(context) = &mut self.context {
+                DeleteSecurityContext(context);
+            }
+            FreeCredentialsHandle(&mut self.credentials);
+        }
+    }
+}
+
+/// A buffer for Schannel to read `data` from or write it in place.
+#[cfg(windows)]
+fn buffer(kind: u32, data: &mut [u8]) -> winapi::shared::sspi::SecBuffer {
+    winapi::shared::sspi::SecBuffer { cbBuffer: data.len() as u32, BufferType: kind, pvBuffer: data.as_mut_ptr() as _ }
+}
+
+/// The bytes Schannel put in `buffer`.
+#[cfg(windows)]
+unsafe fn contents(buffer: &winapi::shared::sspi::SecBuffer) -> &[u8] {
+    std::slice::from_raw_parts(buffer.pvBuffer as *const u8, buffer.cbBuffer as usize)
+}
+
+#[cfg(windows)]
+fn schannel_error(what: &str, status: i32) -> io::Error {
+    io::Error::other(format!("{}: Schannel error {:#x}", what, status))
+}
+
+#[cfg(windows)]
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(Some(0)).collect()
+}
+
+/// Schannel is only on Windows, so there are no such connections elsewhere.
+#[cfg(not(windows))]
+pub(crate) enum SchannelStream {}
+
+#[cfg(not(windows))]
+pub(crate) async fn connect(_host: &str, _tcp: TcpStream, _insecure: bool) -> Result<SchannelStream> {
+    anyhow::bail!("Schannel is only available on Windows")
+}
+
+#[cfg(not(windows))]
+impl AsyncRead for SchannelStream {
+    fn poll_read(self: Pin<&mut Self>, _cx: &mut Context<'_>, _buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        match *self.get_mut() {}
+    }
+}
+
+#[cfg(not(windows))]
+impl AsyncWrite for SchannelStream {
+    fn poll_write(self: Pin<&mut Self>, _cx: &mut Context<'_>, _buf: &[u8]) -> Poll<io::Result<usize>> {
+        match *self.get_mut() {}
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match *self.get_mut() {}
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match *self.get_mut() {}
+    }
+}
@@ -0,0 +1,33 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+use serde_json::json;
+
+use crate::{Architecture, Requirements, detect};
+
+/// Records the runtimes and SDKs of `arches` in the machine-wide install,
+/// pinned at their exact versions, so that another machine can be given the
+/// same ones with [`load`]. It is a requirements manifest in JSON, which
+/// `install --manifest` reads as well.
+pub fn export(arches: &[Architecture]) -> Result<String> {
+    let components = arches
+        .iter()
+        .flat_map(|&arch| detect::installed(arch))
+        .filter(|installed| installed.root == detect::get_root_install(installed.arch))
+        .map(|installed| {
+            json!({
+                "runtime": installed.runtime.to_string().to_lowercase(),
+                "version": installed.version.to_string(),
+                "arch": installed.arch.to_string().to_lowercase(),
+                "roll_forward": "disable",
+            })
+        })
+        .collect::<Vec<_>>();
+    Ok(serde_json::to_string_pretty(&json!({ "components": components }))?)
+}
+
+/// Reads a snapshot [`export`] wrote, whatever the file is called.
+pub fn load(path: &Path) -> Result<Requirements> {
+    let contents = fs::read_to_string(path).with_context(|| format!("cannot read snapshot {}", path.display()))?;
+    Requirements::parse(&contents, true, &path.display())
+}
@@ -0,0 +1,106 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, anyhow};
+
+use crate::DotnetRedistError;
+
+/// Fails if any volume lacks the room that `needs`, pairs of a directory and
+/// the bytes that will be written under it, add up to on that volume.
+pub(crate) fn check(needs: &[(PathBuf, u64)]) -> Result<()> {
+    let mut volumes: Vec<(PathBuf, u64, Vec<&Path>)> = Vec::new();
+    for (dir, bytes) in needs.iter().filter(|(_, bytes)| *bytes > 0) {
+        let existing = existing_ancestor(dir);
+        let volume = volume(&existing).unwrap_or(existing);
+        match volumes.iter_mut().find(|(other, _, _)| *other == volume) {
+            Some((_, total, dirs)) => {
+                *total += bytes;
+                dirs.push(dir);
+            }
+            None => volumes.push((volume, *bytes, vec![dir])),
+        }
+    }
+
+    for (volume, needed, dirs) in volumes {
+        let free = match free_space(&volume) {
+            Some(free) => free,
+            None => {
+                log::debug!("cannot tell the free space on {}", volume.display());
+                continue;
+            }
+        };
+        log::debug!("{} needs about {} bytes and has {} free", volume.display(), needed, free);
+        if needed > free {
+            let dirs = dirs.iter().map(|dir| dir.display().to_string()).collect::<Vec<_>>();
+            return Err(DotnetRedistError::InsufficientDiskSpace(anyhow!(
+                "not enough disk space on {} for {}: about {} needed, {} free; \
+                 free up space or pass --skip-disk-check to override",
+                volume.display(),
+                dirs.join(" and "),
+                megabytes(needed),
+                megabytes(free)
+            ))
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// The nearest directory that exists at or above `path`.
+pub(crate) fn existing_ancestor(path: &Path) -> PathBuf {
+    path.ancestors()
+        .find(|ancestor| ancestor.exists())
+        .unwrap_or(path)
+        .to_path_buf()
+}
+
+fn megabytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / 1_000_000.0)
+}
+
+#[cfg(windows)]
+pub(crate) fn free_space(path: &Path) -> Option<u64> {
+    use std::{os::windows::ffi::OsStrExt, ptr::null_mut};
+    use winapi::um::{fileapi::GetDiskFreeSpaceExW, winnt::ULARGE_INTEGER};
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut available: ULARGE_INTEGER = unsafe { std::mem::zeroed() };
+    match unsafe { GetDiskFreeSpaceExW(wide.as_ptr(), &mut available, null_mut(), null_mut()) } {
+        0 => None,
+        _ => Some(unsafe { *available.QuadPart() }),
+    }
+}
+
+/// The POSIX `df` output is the portable way to ask without libc bindings.
+#[cfg(not(windows))]
+pub(crate) fn free_space(path: &Path) -> Option<u64> {
+    let kilobytes: u64 = df(path)?.get(3)?.parse().ok()?;
+    Some(kilobytes * 1024)
+}
+
+/// The root of the volume `path` is on, such as `C:\` or a mount point.
+#[cfg(windows)]
+fn volume(path: &Path) -> Option<PathBuf> {
+    use std::{ffi::OsString, os::windows::ffi::{OsStrExt, OsStringExt}};
+    use winapi::um::fileapi::GetVolumePathNameW;
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut buf = [0u16; 261];
+    if unsafe { GetVolumePathNameW(wide.as_ptr(), buf.as_mut_ptr(), buf.len() as u32) } == 0 {
+        return None;
+    }
+    let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+    Some(OsString::from_wide(&buf[..len]).into())
+}
+
+#[cfg(not(windows))]
+fn volume(path: &Path) -> Option<PathBuf> {
+    Some(df(path)?.get(5)?.into())
+}
+
+/// The fields of the line `df -Pk` prints for `path`.
+#[cfg(not(windows))]
+fn df(path: &Path) -> Option<Vec<String>> {
+    let output = std::process::Command::new("df").arg("-Pk").arg(path).output().ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Some(stdout.lines().nth(1)?.split_whitespace().map(str::to_string).collect())
+}
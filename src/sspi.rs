@@ -0,0 +1,150 @@
+use anyhow::Result;
+
+/// A client's side of a Negotiate or NTLM handshake with the credentials of
+/// the logged-on user, through SSPI.
+#[cfg(windows)]
+pub(crate) struct Handshake {
+    credentials: winapi::shared::sspi::CredHandle,
+    context: Option<winapi::shared::sspi::CtxtHandle>,
+    target: Vec<u16>,
+}
+
+#[cfg(windows)]
+impl Handshake {
+    /// Starts a handshake using the security `package`, `Negotiate` or
+    /// `NTLM`, with the service principal `target`, such as `HTTP/proxy.corp`.
+    pub(crate) fn new(package: &str, target: &str) -> Result<Self> {
+        use std::{mem, ptr::null_mut};
+        use winapi::shared::sspi::{AcquireCredentialsHandleW, SECPKG_CRED_OUTBOUND};
+
+        let mut package = wide(package);
+        unsafe {
+            let mut credentials = mem::zeroed();
+            let status = AcquireCredentialsHandleW(
+                null_mut(),
+                package.as_mut_ptr(),
+                SECPKG_CRED_OUTBOUND,
+                null_mut(),
+                null_mut(),
+                None,
+                null_mut(),
+                &mut credentials,
+                null_mut(),
+            );
+            if status < 0 {
+                anyhow::bail!("cannot get the credentials of the logged-on user: SSPI error {:#x}", status);
+            }
+            Ok(Handshake {
+                credentials,
+                context: None,
+                target: wide(target),
+            })
+        }
+    }
+
+    /// The next token to send, given the server's last `challenge`, which is
+    /// `None` for the first.
+    pub(crate) fn step(&mut self, challenge: Option<&[u8]>) -> Result<Vec<u8>> {
+        use std::{mem, ptr::null_mut, slice};
+        use winapi::{
+            shared::sspi::{
+                FreeContextBuffer, ISC_REQ_ALLOCATE_MEMORY, ISC_REQ_CONNECTION, InitializeSecurityContextW,
+                SECBUFFER_TOKEN, SECBUFFER_VERSION, SECURITY_NATIVE_DREP, SecBuffer, SecBufferDesc,
+            },
+            shared::winerror::{SEC_I_COMPLETE_AND_CONTINUE, SEC_I_COMPLETE_NEEDED},
+        };
+
+        let mut input = challenge.unwrap_or_default().to_vec();
+        let mut input_buffer = SecBuffer {
+            cbBuffer: input.len() as u32,
+            BufferType: SECBUFFER_TOKEN,
+            pvBuffer: input.as_mut_ptr() as _,
+        };
+        let mut input_desc = SecBufferDesc {
+            ulVersion: SECBUFFER_VERSION,
+            cBuffers: 1,
+            pBuffers: &mut input_buffer,
+        };
+        let mut output_buffer = SecBuffer {
+            cbBuffer: 0,
+            BufferType: SECBUFFER_TOKEN,
+            pvBuffer: null_mut(),
+        };
+        let mut output_desc = SecBufferDesc {
+            ulVersion: SECBUFFER_VERSION,
+            cBuffers: 1,
+            pBuffers: &mut output_buffer,
+        };
+
+        unsafe {
+            let mut context = self.context.unwrap_or_else(|| mem::zeroed());
+            let mut attributes = 0;
+            let status = InitializeSecurityContextW(
+                &mut self.credentials,
+                match &mut self.context {
+                    Some(context) => context as *mut _,
+                    None => null_mut(),
+                },
+                self.target.as_mut_ptr(),
+                ISC_REQ_ALLOCATE_MEMORY | ISC_REQ_CONNECTION,
+                0,
+                SECURITY_NATIVE_DREP,
+                if challenge.is_some() { &mut input_desc } else { null_mut() },
+                0,
+                &mut context,
+                &mut output_desc,
+                &mut attributes,
+                null_mut(),
+            );
+            if status < 0 {
+                anyhow::bail!("SSPI could not answer the proxy's challenge: error {:#x}", status);
+            }
+            self.context = Some(context);
+            if status == SEC_I_COMPLETE_NEEDED || status == SEC_I_COMPLETE_AND_CONTINUE {
+                log::debug!("SSPI asked to complete the token, which HTTP authentication does not need");
+            }
+
+            if output_buffer.pvBuffer.is_null() {
+                return Ok(Vec::new());
+            }
+            let token = slice::from_raw_parts(output_buffer.pvBuffer as *const u8, output_buffer.cbBuffer as usize);
+            let token = token.to_vec();
+            FreeContextBuffer(output_buffer.pvBuffer);
+            Ok(token)
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Handshake {
+    fn drop(&mut self) {
+        use winapi::shared::sspi::{DeleteSecurityContext, FreeCredentialsHandle};
+
+        unsafe {
+            if let Some(context) = &mut self.context {
+                DeleteSecurityContext(context);
+            }
+            FreeCredentialsHandle(&mut self.credentials);
+        }
+    }
+}
+
+#[cfg(windows)]
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(Some(0)).collect()
+}
+
+/// Only Windows can authenticate as the logged-on user.
+#[cfg(not(windows))]
+pub(crate) struct Handshake;
+
+#[cfg(not(windows))]
+impl Handshake {
+    pub(crate) fn new(_package: &str, _target: &str) -> Result<Self> {
+        anyhow::bail!("authenticating as the logged-on user is only supported on Windows")
+    }
+
+    pub(crate) fn step(&mut self, _challenge: Option<&[u8]>) -> Result<Vec<u8>> {
+        unreachable!()
+    }
+}
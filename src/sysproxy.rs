@@ -0,0 +1,186 @@
+use http_types::Url;
+
+/// The proxy the Windows settings route `url` through, if any: the user's
+/// Internet Options, including a PAC script or WPAD, and otherwise the
+/// machine-wide WinHTTP proxy. Group policy pushes its proxy into both.
+#[cfg(windows)]
+pub(crate) fn for_url(url: &Url) -> Option<Url> {
+    use std::{
+        collections::HashMap,
+        sync::{Mutex, OnceLock, PoisonError},
+    };
+
+    // Running a PAC script or finding one is slow, and its answer rarely
+    // differs between paths on the same host.
+    static CACHE: OnceLock<Mutex<HashMap<String, Option<Url>>>> = OnceLock::new();
+    let key = format!("{}://{}", url.scheme(), url.host_str()?);
+    let mut cache = CACHE.get_or_init(Default::default).lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(proxy) = cache.get(&key) {
+        return proxy.clone();
+    }
+
+    let proxy = windows::lookup(url);
+    match &proxy {
+        Some(proxy) => log::debug!("the system routes {} through {}", key, proxy),
+        None => log::debug!("the system routes {} directly", key),
+    }
+    cache.insert(key, proxy.clone());
+    proxy
+}
+
+#[cfg(not(windows))]
+pub(crate) fn for_url(_url: &Url) -> Option<Url> {
+    None
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::{mem, ptr::null_mut};
+
+    use http_types::Url;
+    use winapi::{
+        shared::ntdef::LPWSTR,
+        um::{winbase::GlobalFree, winhttp::*},
+    };
+
+    fn wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain(Some(0)).collect()
+    }
+
+    /// Takes ownership of a string WinHTTP allocated, freeing it.
+    unsafe fn take(s: LPWSTR) -> Option<String> {
+        if s.is_null() {
+            return None;
+        }
+        let len = (0..).take_while(|&i| *s.add(i) != 0).count();
+        let value = String::from_utf16_lossy(std::slice::from_raw_parts(s, len));
+        GlobalFree(s as _);
+        Some(value).filter(|value| !value.is_empty())
+    }
+
+    pub(super) fn lookup(url: &Url) -> Option<Url> {
+        let mut ie: WINHTTP_CURRENT_USER_IE_PROXY_CONFIG = unsafe { mem::zeroed() };
+        if unsafe { WinHttpGetIEProxyConfigForCurrentUser(&mut ie) } != 0 {
+            let auto_detect = ie.fAutoDetect != 0;
+            let (pac, proxy, bypass) =
+                unsafe { (take(ie.lpszAutoConfigUrl), take(ie.lpszProxy), take(ie.lpszProxyBypass)) };
+            if pac.is_some() || auto_detect {
+                match auto_proxy(url, pac.as_deref()) {
+                    Some(answer) => return answer,
+                    None => log::debug!("no proxy script answered for {}", url),
+                }
+            }
+            if let Some(proxy) = proxy {
+                return choose(url, &proxy, bypass.as_deref());
+            }
+        }
+
+        let mut info: WINHTTP_PROXY_INFO = unsafe { mem::zeroed() };
+        if unsafe { WinHttpGetDefaultProxyConfiguration(&mut info) } == 0 {
+            return None;
+        }
+        let named = info.dwAccessType == WINHTTP_ACCESS_TYPE_NAMED_PROXY;
+        let (proxy, bypass) = unsafe { (take(info.lpszProxy), take(info.lpszProxyBypass)) };
+        match proxy {
+            Some(proxy) if named => choose(url, &proxy, bypass.as_deref()),
+            _ => None,
+        }
+    }
+
+    /// Asks the PAC script at `pac`, or the one WPAD finds, for the proxy of
+    /// `url`. `None` if there is no script or it fails; `Some(None)` if it
+    /// says to connect directly.
+    fn auto_proxy(url: &Url, pac: Option<&str>) -> Option<Option<Url>> {
+        let agent = wide(env!("CARGO_PKG_NAME"));
+        let pac = pac.map(wide);
+        let target = wide(url.as_str());
+        unsafe {
+            let session = WinHttpOpen(agent.as_ptr(), WINHTTP_ACCESS_TYPE_NO_PROXY, null_mut(), null_mut(), 0);
+            if session.is_null() {
+                return None;
+            }
+
+            let mut options: WINHTTP_AUTOPROXY_OPTIONS = mem::zeroed();
+            match &pac {
+                Some(pac) => {
+                    options.dwFlags = WINHTTP_AUTOPROXY_CONFIG_URL;
+                    options.lpszAutoConfigUrl = pac.as_ptr();
+                }
+                None => {
+                    options.dwFlags = WINHTTP_AUTOPROXY_AUTO_DETECT;
+                    options.dwAutoDetectFlags = WINHTTP_AUTO_DETECT_TYPE_DHCP | WINHTTP_AUTO_DETECT_TYPE_DNS_A;
+                }
+            }
+            options.fAutoLogonIfChallenged = 1;
+
+            let mut info: WINHTTP_PROXY_INFO = mem::zeroed();
+            let ok = WinHttpGetProxyForUrl(session, target.as_ptr(), &mut options, &mut info) != 0;
+            WinHttpCloseHandle(session);
+            if !ok {
+                return None;
+            }
+
+            let named = info.dwAccessType == WINHTTP_ACCESS_TYPE_NAMED_PROXY;
+            let (proxy, bypass) = (take(info.lpszProxy), take(info.lpszProxyBypass));
+            Some(match proxy {
+                Some(proxy) if named => choose(url, &proxy, bypass.as_deref()),
+                _ => None,
+            })
+        }
+    }
+
+    /// Picks the proxy for `url` from a WinINET proxy list such as
+    /// `host:port` or `http=host:port;https=host:port`, unless the bypass
+    /// list such as `<local>;*.corp.example;10.*` exempts its host.
+    fn choose(url: &Url, proxies: &str, bypass: Option<&str>) -> Option<Url> {
+        let host = url.host_str()?;
+        let bypassed = bypass.into_iter().flat_map(entries).any(|entry| match entry {
+            "<local>" => !host.contains('.'),
+            pattern => matches(pattern, host),
+        });
+        if bypassed {
+            return None;
+        }
+
+        let scheme = url.scheme();
+        let proxy = entries(proxies)
+            .find_map(|entry| match entry.split_once('=') {
+                Some((for_scheme, proxy)) if for_scheme.eq_ignore_ascii_case(scheme) => Some(proxy),
+                _ => None,
+            })
+            .or_else(|| entries(proxies).find(|entry| !entry.contains('=')))?;
+
+        if proxy.contains("://") {
+            Url::parse(proxy).ok()
+        } else {
+            Url::parse(&format!("http://{}", proxy)).ok()
+        }
+    }
+
+    fn entries(list: &str) -> impl Iterator<Item = &str> {
+        list.split(|c: char| c == ';' || c.is_whitespace()).filter(|entry| !entry.is_empty())
+    }
+
+    /// Matches `host` against a bypass pattern in which `*` stands for any run of characters.
+    fn matches(pattern: &str, host: &str) -> bool {
+        let pattern = pattern.to_lowercase();
+        let host = host.to_lowercase();
+        let mut parts = pattern.split('*');
+        let first = parts.next().unwrap_or_default();
+        let mut rest = match host.strip_prefix(first) {
+            Some(rest) => rest,
+            None => return false,
+        };
+        let parts = parts.collect::<Vec<_>>();
+        for (i, part) in parts.iter().enumerate() {
+            if i == parts.len() - 1 {
+                return rest.ends_with(part);
+            }
+            match rest.find(part) {
+                Some(index) => rest = &rest[index + part.len()..],
+                None => return false,
+            }
+        }
+        rest.is_empty()
+    }
+}
@@ -0,0 +1,82 @@
+use std::sync::OnceLock;
+
+use serde::Serialize;
+
+use crate::{
+    DotnetRedistError, exit, http,
+    report::{Action, Report, Status},
+};
+
+static URL: OnceLock<String> = OnceLock::new();
+
+/// Sends the anonymous [`send`] record of each run to `url`. Nothing is sent unless this is set.
+pub fn set_url(url: String) {
+    let _ = URL.set(url);
+}
+
+/// What a run sends: no paths, URLs, host names or error messages, only
+/// what was asked for and how it went.
+#[derive(Serialize)]
+struct Record<'a> {
+    tool_version: &'static str,
+    os: &'static str,
+    components: Vec<Requested<'a>>,
+    status: &'a Status,
+    /// The class of the failure, if the run failed, as in the stable exit codes.
+    error_class: Option<&'static str>,
+    exit_code: i32,
+    duration_ms: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct Requested<'a> {
+    name: &'a str,
+    arch: &'a str,
+    requested_version: Option<&'a str>,
+    resolved_version: Option<&'a str>,
+    action: &'a Action,
+}
+
+/// POSTs an anonymous JSON record of the run that reported `report` and
+/// failed with `error`, if it did, to the URL set with [`set_url`].
+/// Failing to send it is logged and otherwise ignored.
+pub async fn send(report: &Report, error: Option<&anyhow::Error>) {
+    let url = match URL.get() {
+        Some(url) => url,
+        None => return,
+    };
+    let record = Record {
+        tool_version: env!("CARGO_PKG_VERSION"),
+        os: std::env::consts::OS,
+        components: report
+            .components
+            .iter()
+            .map(|component| Requested {
+                name: &component.name,
+                arch: &component.arch,
+                requested_version: component.requested_version.as_deref(),
+                resolved_version: component.resolved_version.as_deref(),
+                action: &component.action,
+            })
+            .collect(),
+        status: &report.status,
+        error_class: error.map(|e| {
+            e.chain().find_map(|cause| cause.downcast_ref::<DotnetRedistError>()).map_or("other", DotnetRedistError::class)
+        }),
+        exit_code: exit::code(error, report),
+        duration_ms: report.summary.as_ref().map(|summary| summary.total_ms),
+    };
+
+    let body = match serde_json::to_vec(&record) {
+        Ok(body) => body,
+        Err(e) => {
+            log::debug!("cannot serialize the run's record: {}", e);
+            return;
+        }
+    };
+    match http::post_json(url, body).await {
+        Ok(response) if response.status().is_success() => log::debug!("sent the run's record to {}", url),
+        Ok(response) => log::warn!("{} refused the run's record: {}", url, response.status()),
+        Err(e) => log::warn!("cannot send the run's record to {}: {:#}", url, e),
+    }
+}
@@ -0,0 +1,132 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{
+        Once, OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Result, bail};
+use tempfile::TempDir;
+
+use crate::cache;
+
+static DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// How long a temporary directory or partial file may go untouched before
+/// it is taken for the leftover of a run that crashed. No run takes a day.
+const STALE_AFTER: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Downloads installers into `dir`, and has installers unpack themselves
+/// there, instead of into the system's temporary directory, which locked
+/// down machines may keep tiny or forbid running programs from.
+///
+/// Fails unless `dir` can be created, written to and run programs from.
+pub fn set_dir(dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir).with_context(|| format!("cannot create temporary directory {}", dir.display()))?;
+    let dir = dir.canonicalize().with_context(|| format!("cannot use temporary directory {}", dir.display()))?;
+    check_executable(&dir).with_context(|| format!("cannot run programs from {}", dir.display()))?;
+    let _ = DIR.set(dir);
+    Ok(())
+}
+
+/// The directory temporary files go in.
+pub(crate) fn dir() -> PathBuf {
+    DIR.get().cloned().unwrap_or_else(std::env::temp_dir)
+}
+
+/// The overriding directory, to pass on to installers, if there is one.
+pub(crate) fn overridden() -> Option<&'static Path> {
+    DIR.get().map(PathBuf::as_path)
+}
+
+/// A new directory of this run's own in the temporary directory, removed when dropped.
+/// The first one a run creates has the leftovers of crashed runs swept up first.
+pub(crate) fn create() -> io::Result<TempDir> {
+    static SWEPT: Once = Once::new();
+    SWEPT.call_once(sweep);
+    tempfile::Builder::new().prefix("dotnet5-webinst").tempdir_in(dir())
+}
+
+/// The name `path` is written under until it is complete, unique to this
+/// process and call so that runs sharing a directory never write to the same file.
+pub(crate) fn partial(path: &Path) -> PathBuf {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}-{}.partial", std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed)));
+    path.with_file_name(name)
+}
+
+/// Removes the temporary directories and partial downloads that crashed runs
+/// left behind, going by how long they have gone untouched.
+fn sweep() {
+    let stale = |path: &Path| {
+        fs::symlink_metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_some_and(|age| age > STALE_AFTER)
+    };
+
+    for entry in fs::read_dir(dir()).into_iter().flatten().flatten() {
+        let path = entry.path();
+        let ours = entry.file_name().to_string_lossy().starts_with("dotnet5-webinst");
+        if ours && path.is_dir() && stale(&path) {
+            match fs::remove_dir_all(&path) {
+                Ok(()) => log::info!("removed {} left behind by an earlier run", path.display()),
+                Err(e) => log::debug!("cannot remove {}: {}", path.display(), e),
+            }
+        }
+    }
+
+    let cache = match cache::dir() {
+        Some(cache) => cache,
+        None => return,
+    };
+    for entry in fs::read_dir(cache).into_iter().flatten().flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if (name.ends_with(".partial") || name.ends_with(".chunks-partial")) && stale(&path) {
+            match fs::remove_file(&path) {
+                Ok(()) => log::info!("removed {} left behind by an earlier run", path.display()),
+                Err(e) => log::debug!("cannot remove {}: {}", path.display(), e),
+            }
+        }
+    }
+}
+
+/// Runs a copy of a small system program from `dir`, which fails where
+/// AppLocker or a `noexec` mount would stop the installers.
+fn check_executable(dir: &Path) -> Result<()> {
+    let probe = tempfile::Builder::new().prefix("dotnet5-webinst-probe").tempdir_in(dir)?;
+
+    #[cfg(windows)]
+    let program = {
+        let system = std::env::var_os("SystemRoot").context("SystemRoot is not set")?;
+        let program = probe.path().join("whoami.exe");
+        fs::copy(Path::new(&system).join("System32\\whoami.exe"), &program)?;
+        program
+    };
+    #[cfg(not(windows))]
+    let program = {
+        use std::os::unix::fs::PermissionsExt;
+
+        let program = probe.path().join("probe.sh");
+        fs::write(&program, "#!/bin/sh\nexit 0\n")?;
+        fs::set_permissions(&program, fs::Permissions::from_mode(0o755))?;
+        program
+    };
+
+    let status = Command::new(&program)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+    if !status.success() {
+        bail!("a test program exited with {}", status);
+    }
+    Ok(())
+}
@@ -0,0 +1,155 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+    time::Instant,
+};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::logging;
+
+static START: OnceLock<Instant> = OnceLock::new();
+static SPANS: Mutex<Vec<Recorded>> = Mutex::new(Vec::new());
+static REPORT: OnceLock<PathBuf> = OnceLock::new();
+
+/// A phase of provisioning whose spans are timed: looking up a host,
+/// connecting to it, the TLS handshake, resolving a version, downloading
+/// and running an installer or extracting an archive.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Phase {
+    Dns,
+    Connect,
+    Tls,
+    Resolve,
+    Download,
+    Install,
+}
+
+impl Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Phase::Dns => "dns",
+            Phase::Connect => "connect",
+            Phase::Tls => "tls",
+            Phase::Resolve => "resolve",
+            Phase::Download => "download",
+            Phase::Install => "install",
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct Recorded {
+    phase: Phase,
+    name: String,
+    /// Since the first span of the run started.
+    start_ms: u64,
+    duration_ms: u64,
+    bytes: Option<u64>,
+}
+
+/// A span of a phase being timed, recorded when dropped.
+pub(crate) struct Span {
+    phase: Phase,
+    name: String,
+    start: Instant,
+    bytes: Option<u64>,
+}
+
+/// Starts timing a span of `phase` called `name`, such as the host or URL it is about.
+pub(crate) fn span(phase: Phase, name: impl Into<String>) -> Span {
+    START.get_or_init(Instant::now);
+    Span { phase, name: name.into(), start: Instant::now(), bytes: None }
+}
+
+impl Span {
+    /// Records that the span moved `bytes`, for the throughput of downloads.
+    pub(crate) fn bytes(&mut self, bytes: u64) {
+        self.bytes = Some(bytes);
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let duration = self.start.elapsed();
+        log::debug!("{} {} took {:.3}s", self.phase, self.name, duration.as_secs_f64());
+        logging::record(serde_json::json!({
+            "event": "span",
+            "phase": self.phase,
+            "name": self.name,
+            "duration_ms": duration.as_millis() as u64,
+            "bytes": self.bytes,
+        }));
+        // Kept for the report only, so that embedders never asking for one do not collect spans forever.
+        if REPORT.get().is_none() {
+            return;
+        }
+        let start = START.get().map_or(0, |start| self.start.saturating_duration_since(*start).as_millis() as u64);
+        SPANS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(Recorded {
+            phase: self.phase,
+            name: std::mem::take(&mut self.name),
+            start_ms: start,
+            duration_ms: duration.as_millis() as u64,
+            bytes: self.bytes,
+        });
+    }
+}
+
+/// The spans of a phase added up.
+#[derive(Default, Serialize)]
+struct Total {
+    count: usize,
+    duration_ms: u64,
+    bytes: u64,
+    /// Bytes per second over the spans that moved any.
+    bytes_per_sec: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    totals: BTreeMap<Phase, Total>,
+    spans: &'a [Recorded],
+}
+
+/// Writes a JSON report of the spans timed in the run, and their totals
+/// by phase, to `path` when the run finishes. Only spans ending after this
+/// is called are in it.
+pub fn set_report(path: PathBuf) {
+    let _ = REPORT.set(path);
+}
+
+/// Writes the report [`set_report`] asked for, if any, with the spans timed
+/// so far. Failing to write it is logged and otherwise ignored.
+pub fn write_report() {
+    if let Some(path) = REPORT.get() {
+        if let Err(e) = write(path) {
+            log::warn!("cannot write the timing report: {:#}", e);
+        }
+    }
+}
+
+fn write(path: &Path) -> Result<()> {
+    let spans = SPANS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut totals: BTreeMap<Phase, Total> = BTreeMap::new();
+    let mut moving_ms: BTreeMap<Phase, u64> = BTreeMap::new();
+    for span in spans.iter() {
+        let total = totals.entry(span.phase).or_default();
+        total.count += 1;
+        total.duration_ms += span.duration_ms;
+        if let Some(bytes) = span.bytes.filter(|&bytes| bytes > 0) {
+            total.bytes += bytes;
+            *moving_ms.entry(span.phase).or_default() += span.duration_ms;
+        }
+    }
+    for (phase, total) in totals.iter_mut() {
+        total.bytes_per_sec = moving_ms.get(phase).filter(|&&ms| ms > 0).map(|ms| total.bytes * 1000 / ms);
+    }
+
+    let report = Report { totals, spans: &spans };
+    fs::write(path, serde_json::to_string_pretty(&report)?).with_context(|| format!("cannot write {}", path.display()))
+}
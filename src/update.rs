@@ -0,0 +1,94 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use semver::Version;
+
+use crate::{
+    Architecture, DotnetVersion, RollForward, Runtime,
+    detect::{get_root_install, versions_in},
+    hosting,
+    releases::{self, Package},
+    resolve::resolve_installer,
+};
+
+/// A machine-wide runtime with a newer patch of its `major.minor` version out.
+pub struct Update {
+    /// The runtime that installs the patch: the hosting bundle for ASP.NET
+    /// Core where IIS has its module, so that the module is kept up too.
+    pub runtime: Runtime,
+    pub arch: Architecture,
+    /// The newest patch installed.
+    pub installed: Version,
+    pub available: Version,
+}
+
+impl Display for Update {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} -> {}",
+            self.runtime.to_string().to_lowercase(),
+            self.arch,
+            self.installed,
+            self.available
+        )
+    }
+}
+
+/// Finds the patches newer than the machine-wide runtimes of `arches`, for
+/// each `major.minor` version of them installed. Previews are left alone,
+/// and versions whose newest patch cannot be resolved are skipped with a warning.
+/// With `security_only`, so are versions no newer security release was made for.
+pub async fn available(arches: &[Architecture], security_only: bool) -> Vec<Update> {
+    let mut updates = Vec::new();
+    for &arch in arches {
+        let root = get_root_install(arch);
+        for runtime in [Runtime::Dotnet, Runtime::AspCore, Runtime::WindowsDesktop] {
+            let mut newest: BTreeMap<(u64, u64), Version> = BTreeMap::new();
+            for version in versions_in(root, runtime).into_iter().filter(|version| version.pre.is_empty()) {
+                let entry = newest.entry((version.major, version.minor)).or_insert_with(|| version.clone());
+                if version > *entry {
+                    *entry = version;
+                }
+            }
+
+            let runtime = match runtime {
+                Runtime::AspCore if hosting::is_module_installed() => Runtime::HostingBundle,
+                runtime => runtime,
+            };
+            for ((major, minor), installed) in newest {
+                if security_only && !has_security_release(runtime, &installed).await {
+                    log::info!("{} {} {} has no newer security release; keeping it", runtime, arch, installed);
+                    continue;
+                }
+                let channel = DotnetVersion { major, minor: Some(minor), patch: None, pre: Vec::new(), range: None };
+                let artifact =
+                    match resolve_installer(runtime, arch, &channel, Package::installer(), false, RollForward::Disable).await {
+                        Ok(artifact) => artifact,
+                        Err(e) => {
+                            log::warn!("cannot check {} {} {} for a newer patch: {:#}", runtime, arch, channel, e);
+                            continue;
+                        }
+                    };
+                // The hosting bundle updates both architectures at once.
+                let duplicate = updates.iter().any(|update: &Update| {
+                    update.runtime == Runtime::HostingBundle && update.runtime == runtime && update.available == artifact.version
+                });
+                if artifact.version > installed && !duplicate {
+                    updates.push(Update { runtime, arch, installed, available: artifact.version });
+                }
+            }
+        }
+    }
+    updates
+}
+
+/// Whether a release of `version`'s channel newer than it fixes security advisories.
+async fn has_security_release(runtime: Runtime, version: &Version) -> bool {
+    match releases::vulnerability(runtime, version).await {
+        Ok(vulnerability) => vulnerability.is_some(),
+        Err(e) => {
+            log::warn!("cannot check {} {} for security releases: {:#}", runtime, version, e);
+            false
+        }
+    }
+}
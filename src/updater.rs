@@ -0,0 +1,91 @@
+use std::{path::Path, time::Duration};
+
+use anyhow::{Result, bail};
+
+/// Name of the scheduled task, in the root folder of the Task Scheduler.
+pub const TASK_NAME: &str = concat!(env!("CARGO_PKG_NAME"), " updater");
+
+/// Registers a scheduled task running this executable as SYSTEM every
+/// `interval`, as `ensure --once` with the requirements manifest at
+/// `manifest`, so its runtimes are kept on their newest patch unattended.
+/// An updater registered before is replaced.
+///
+/// `update` would patch every machine-wide runtime and takes no manifest;
+/// `ensure` keeps to the runtimes the manifest lists.
+#[cfg(windows)]
+pub fn install(manifest: &Path, interval: Duration, log_file: Option<&Path>) -> Result<()> {
+    use anyhow::Context;
+
+    use crate::{Requirements, elevation::quote};
+
+    // The task runs from System32, so relative paths would land there.
+    let manifest = manifest.canonicalize().with_context(|| format!("cannot find {}", manifest.display()))?;
+    Requirements::load(&manifest)?;
+    let exe = std::env::current_exe()?;
+
+    let mut command = vec![
+        quote(&exe.to_string_lossy()),
+        "ensure".to_string(),
+        "--once".to_string(),
+        "--manifest".to_string(),
+        quote(&manifest.to_string_lossy()),
+    ];
+    if let Some(log_file) = log_file {
+        let log_file = std::env::current_dir()?.join(log_file);
+        command.extend(["--log-file".to_string(), quote(&log_file.to_string_lossy())]);
+    }
+
+    let (schedule, modifier) = schedule(interval)?;
+    let command = command.join(" ");
+    let modifier = modifier.to_string();
+    schtasks(&[
+        "/Create", "/F", "/TN", TASK_NAME, "/TR", &command, "/SC", schedule, "/MO", &modifier, "/RU", "SYSTEM", "/RL", "HIGHEST",
+    ])
+}
+
+#[cfg(not(windows))]
+pub fn install(_manifest: &Path, interval: Duration, _log_file: Option<&Path>) -> Result<()> {
+    schedule(interval)?;
+    bail!("scheduled tasks are only supported on Windows")
+}
+
+/// Deletes the scheduled task [`install`] registered. Returns whether there was one.
+#[cfg(windows)]
+pub fn remove() -> Result<bool> {
+    if schtasks(&["/Query", "/TN", TASK_NAME]).is_err() {
+        return Ok(false);
+    }
+    schtasks(&["/Delete", "/F", "/TN", TASK_NAME])?;
+    Ok(true)
+}
+
+#[cfg(not(windows))]
+pub fn remove() -> Result<bool> {
+    bail!("scheduled tasks are only supported on Windows")
+}
+
+/// The schtasks schedule type and modifier running a task every `interval`,
+/// which must be a whole number of minutes, hours below a day, or days.
+fn schedule(interval: Duration) -> Result<(&'static str, u64)> {
+    let minutes = interval.as_secs() / 60;
+    if !interval.as_secs().is_multiple_of(60) || minutes == 0 {
+        bail!("the updater interval must be a whole number of minutes");
+    }
+    Ok(match minutes {
+        m if m.is_multiple_of(24 * 60) && m / (24 * 60) <= 365 => ("DAILY", m / (24 * 60)),
+        m if m.is_multiple_of(60) && m < 24 * 60 => ("HOURLY", m / 60),
+        m if m < 24 * 60 => ("MINUTE", m),
+        _ => bail!("the updater interval must be less than a day or a whole number of days up to 365"),
+    })
+}
+
+#[cfg(windows)]
+fn schtasks(args: &[&str]) -> Result<()> {
+    use anyhow::Context;
+
+    let output = std::process::Command::new("schtasks.exe").args(args).output().context("cannot run schtasks")?;
+    if !output.status.success() {
+        bail!("schtasks {} failed: {}", args[0], String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    Architecture,
+    environment::{broadcast_change, variable},
+    platform::{is_64bit_os, is_arm64_host},
+    registry,
+};
+
+/// The default location of per-user installs, as dotnet-install.ps1 and
+/// dotnet-install.sh use it. 32-bit runtimes on 64-bit Windows get a
+/// directory of their own so both architectures can be installed.
+pub(crate) fn root(arch: Architecture) -> Result<PathBuf> {
+    let root = if cfg!(windows) {
+        PathBuf::from(std::env::var_os("LOCALAPPDATA").context("LOCALAPPDATA is not set")?).join("Microsoft\\dotnet")
+    } else {
+        PathBuf::from(std::env::var_os("HOME").context("HOME is not set")?).join(".dotnet")
+    };
+
+    Ok(match arch {
+        Architecture::X86 if cfg!(windows) && is_64bit_os() => root.join("x86"),
+        // As machine-wide, x64 runtimes go next to the native ARM64 ones.
+        Architecture::X64 if is_arm64_host() => root.join("x64"),
+        _ => root,
+    })
+}
+
+/// Points the user's environment at `root`, so apps and later detection
+/// find the runtimes installed there.
+pub(crate) fn register(arch: Architecture, root: &Path) -> Result<()> {
+    let name = variable(arch);
+    if std::env::var_os(name).is_some_and(|value| Path::new(&value) == root) {
+        return Ok(());
+    }
+
+    if cfg!(windows) {
+        registry::write_user_string("Environment", name, &root.display().to_string())
+            .with_context(|| format!("could not set {} for the user", name))?;
+        broadcast_change();
+        log::info!("set {} to {} for the user; apps started from now on use it", name, root.display());
+    } else {
+        log::info!("set {}={} in your shell profile for apps to use it", name, root.display());
+    }
+    std::env::set_var(name, root);
+    Ok(())
+}
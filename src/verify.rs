@@ -0,0 +1,268 @@
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow, bail};
+use sha2::{Digest, Sha512};
+use smol::{fs::File, prelude::*};
+
+use crate::{DotnetRedistError, catalog::{self, Entry}, releases};
+
+/// Checks that the file at `path` has the given hex-encoded SHA512 hash.
+pub async fn sha512(path: &Path, expected: &str) -> Result<()> {
+    compare(path, expected, &sha512_of(path).await?)
+}
+
+/// The hex-encoded SHA512 hash of the file at `path`.
+async fn sha512_of(path: &Path) -> Result<String> {
+    let mut file = File::open(path).await?;
+    let mut hasher = Sha512::new();
+    let mut buf = vec![0; 64 * 1024];
+
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+
+    Ok(hex(&hasher.finalize()))
+}
+
+/// The official Windows installer or archive the file at `path` is, found
+/// by its SHA512 hash in the catalog or else in the release metadata.
+pub async fn identify(path: &Path) -> Result<Option<Entry>> {
+    let hash = sha512_of(path).await.with_context(|| format!("cannot read {}", path.display()))?;
+    let matches = |entry: &Entry| entry.hash.as_deref().is_some_and(|official| official.eq_ignore_ascii_case(&hash));
+    if let Some(entry) = catalog::entries().iter().find(|entry| matches(entry)) {
+        return Ok(Some(entry.clone()));
+    }
+    let entries = releases::windows_files().await.context("cannot look the hash up in the release metadata")?;
+    Ok(entries.into_iter().find(matches))
+}
+
+/// A SHA512 computed over a download as it is written, so the file doesn't
+/// have to be read again to verify it.
+pub(crate) struct StreamingHash {
+    /// `None` once the file holds bytes that were not hashed.
+    hasher: Option<Sha512>,
+    len: u64,
+}
+
+impl StreamingHash {
+    pub(crate) fn new() -> Self {
+        StreamingHash {
+            hasher: Some(Sha512::new()),
+            len: 0,
+        }
+    }
+
+    /// Continues with a transfer that appends to the first `offset` bytes.
+    pub(crate) fn resume_at(&mut self, offset: u64) {
+        if offset == 0 {
+            *self = StreamingHash::new();
+        } else if offset != self.len {
+            self.hasher = None;
+        }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        if let Some(hasher) = &mut self.hasher {
+            hasher.update(data);
+        }
+        self.len += data.len() as u64;
+    }
+
+    /// Checks the downloaded file at `path` against the hash, reading it
+    /// again only if part of it was not hashed on the way in.
+    pub(crate) async fn verify(self, path: &Path, expected: &str) -> Result<()> {
+        match self.hasher {
+            Some(hasher) => compare(path, expected, &hex(&hasher.finalize())),
+            None => sha512(path, expected).await,
+        }
+    }
+}
+
+fn compare(path: &Path, expected: &str, actual: &str) -> Result<()> {
+    if !actual.eq_ignore_ascii_case(expected.trim()) {
+        bail!(DotnetRedistError::VerificationFailed(anyhow!(
+            "SHA512 mismatch for {}: expected {}, got {}",
+            path.display(),
+            expected,
+            actual
+        )));
+    }
+
+    Ok(())
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Checks that the file at `path` carries a valid Authenticode signature
+/// from one of `publishers`.
+#[cfg(windows)]
+pub fn authenticode(path: &Path, publishers: &[String]) -> Result<()> {
+    let publisher = windows::signer(path).map_err(DotnetRedistError::VerificationFailed)?;
+    if !publishers.iter().any(|p| p.eq_ignore_ascii_case(&publisher)) {
+        bail!(DotnetRedistError::VerificationFailed(anyhow!(
+            "{} is signed by \"{}\", which is not a trusted publisher",
+            path.display(),
+            publisher
+        )));
+    }
+
+    Ok(())
+}
+
+/// Authenticode is a Windows-only concept, so there is nothing to check elsewhere.
+#[cfg(not(windows))]
+pub fn authenticode(_path: &Path, _publishers: &[String]) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::{mem, os::windows::ffi::OsStrExt, path::Path, ptr};
+
+    use anyhow::{Result, bail};
+    use winapi::{
+        shared::minwindef::DWORD,
+        um::{
+            handleapi::INVALID_HANDLE_VALUE,
+            softpub::WINTRUST_ACTION_GENERIC_VERIFY_V2,
+            wincrypt::*,
+            wintrust::*,
+        },
+    };
+
+    /// Verifies the file's signature chain and returns the signer's display name.
+    pub fn signer(path: &Path) -> Result<String> {
+        let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+
+        let status = unsafe { verify_trust(&wide) };
+        if status != 0 {
+            bail!(
+                "{} does not have a valid Authenticode signature (WinVerifyTrust 0x{:08x})",
+                path.display(),
+                status
+            );
+        }
+
+        match unsafe { signer_name(&wide) } {
+            Some(name) => Ok(name),
+            None => bail!("could not read the signer of {}", path.display()),
+        }
+    }
+
+    unsafe fn verify_trust(wide: &[u16]) -> i32 {
+        let mut file_info: WINTRUST_FILE_INFO = mem::zeroed();
+        file_info.cbStruct = mem::size_of::<WINTRUST_FILE_INFO>() as DWORD;
+        file_info.pcwszFilePath = wide.as_ptr();
+
+        let mut data: WINTRUST_DATA = mem::zeroed();
+        data.cbStruct = mem::size_of::<WINTRUST_DATA>() as DWORD;
+        data.dwUIChoice = WTD_UI_NONE;
+        data.fdwRevocationChecks = WTD_REVOKE_NONE;
+        data.dwUnionChoice = WTD_CHOICE_FILE;
+        *data.u.pFile_mut() = &mut file_info;
+        data.dwStateAction = WTD_STATEACTION_VERIFY;
+
+        let mut action = WINTRUST_ACTION_GENERIC_VERIFY_V2;
+        let status = WinVerifyTrust(
+            INVALID_HANDLE_VALUE as _,
+            &mut action,
+            &mut data as *mut _ as _,
+        );
+
+        data.dwStateAction = WTD_STATEACTION_CLOSE;
+        WinVerifyTrust(INVALID_HANDLE_VALUE as _, &mut action, &mut data as *mut _ as _);
+
+        status
+    }
+
+    unsafe fn signer_name(wide: &[u16]) -> Option<String> {
+        let mut encoding = 0;
+        let mut content_type = 0;
+        let mut format_type = 0;
+        let mut store: HCERTSTORE = ptr::null_mut();
+        let mut msg: HCRYPTMSG = ptr::null_mut();
+
+        let ok = CryptQueryObject(
+            CERT_QUERY_OBJECT_FILE,
+            wide.as_ptr() as _,
+            CERT_QUERY_CONTENT_FLAG_PKCS7_SIGNED_EMBED,
+            CERT_QUERY_FORMAT_FLAG_BINARY,
+            0,
+            &mut encoding,
+            &mut content_type,
+            &mut format_type,
+            &mut store,
+            &mut msg,
+            ptr::null_mut(),
+        );
+        if ok == 0 {
+            return None;
+        }
+
+        let name = find_signer_name(store, msg);
+
+        CryptMsgClose(msg);
+        CertCloseStore(store, 0);
+
+        name
+    }
+
+    unsafe fn find_signer_name(store: HCERTSTORE, msg: HCRYPTMSG) -> Option<String> {
+        let mut size = 0;
+        if CryptMsgGetParam(msg, CMSG_SIGNER_INFO_PARAM, 0, ptr::null_mut(), &mut size) == 0 {
+            return None;
+        }
+
+        // Back the signer info with u64s to keep it suitably aligned.
+        let mut buf = vec![0u64; (size as usize).div_ceil(8)];
+        if CryptMsgGetParam(msg, CMSG_SIGNER_INFO_PARAM, 0, buf.as_mut_ptr() as _, &mut size) == 0 {
+            return None;
+        }
+        let signer_info = &*(buf.as_ptr() as *const CMSG_SIGNER_INFO);
+
+        let mut cert_info: CERT_INFO = mem::zeroed();
+        cert_info.Issuer = signer_info.Issuer;
+        cert_info.SerialNumber = signer_info.SerialNumber;
+
+        let cert = CertFindCertificateInStore(
+            store,
+            X509_ASN_ENCODING | PKCS_7_ASN_ENCODING,
+            0,
+            CERT_FIND_SUBJECT_CERT,
+            &cert_info as *const _ as _,
+            ptr::null(),
+        );
+        if cert.is_null() {
+            return None;
+        }
+
+        let len = CertGetNameStringW(
+            cert,
+            CERT_NAME_SIMPLE_DISPLAY_TYPE,
+            0,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            0,
+        );
+        let mut name = vec![0u16; len as usize];
+        CertGetNameStringW(
+            cert,
+            CERT_NAME_SIMPLE_DISPLAY_TYPE,
+            0,
+            ptr::null_mut(),
+            name.as_mut_ptr(),
+            len,
+        );
+        CertFreeCertificateContext(cert);
+
+        // Drop the trailing NUL.
+        name.pop();
+        Some(String::from_utf16_lossy(&name))
+    }
+}
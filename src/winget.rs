@@ -0,0 +1,50 @@
+use std::{path::Path, process::Command};
+
+use anyhow::Result;
+use semver::Version;
+
+use crate::{Architecture, Runtime, installer};
+
+/// The winget package of the `major.minor` release line of `runtime`:
+/// one per major version from .NET 5 on, and per `major_minor` before.
+pub(crate) fn package_id(runtime: Runtime, major: u64, minor: u64) -> Option<String> {
+    let name = match runtime {
+        Runtime::Dotnet => "Runtime",
+        Runtime::AspCore => "AspNetCore",
+        Runtime::WindowsDesktop => "DesktopRuntime",
+        Runtime::HostingBundle => "HostingBundle",
+        Runtime::Sdk => "SDK",
+        Runtime::NetFx48 => return None,
+    };
+    let line = if major >= 5 { major.to_string() } else { format!("{}_{}", major, minor) };
+    Some(format!("Microsoft.DotNet.{}.{}", name, line))
+}
+
+/// Whether winget is installed and runs for this user; it isn't on Server
+/// editions and for accounts without an app execution alias, such as SYSTEM.
+pub(crate) fn is_available() -> bool {
+    cfg!(windows) && Command::new("winget").arg("--version").output().is_ok_and(|output| output.status.success())
+}
+
+/// Installs `version`, or else the newest release, of the winget package
+/// `id` for `arch`, silently and accepting its agreements.
+pub(crate) fn install(id: &str, arch: Architecture, version: Option<&Version>) -> Result<Option<i32>> {
+    let arch = arch.to_string().to_lowercase();
+    let mut args = vec![
+        "install",
+        "--id",
+        id,
+        "--exact",
+        "--architecture",
+        &arch,
+        "--silent",
+        "--disable-interactivity",
+        "--accept-package-agreements",
+        "--accept-source-agreements",
+    ];
+    let version = version.map(Version::to_string);
+    if let Some(version) = &version {
+        args.extend(["--version", version]);
+    }
+    installer::run_with(Path::new("winget"), &args, &[0])
+}
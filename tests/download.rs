@@ -0,0 +1,234 @@
+//! Installing runtimes from the fixture feed: downloading, verifying and
+//! extracting their archives, and what is recorded about the run.
+
+mod fixture;
+
+use std::{fs::File, thread, time::Duration};
+
+use dotnet5_webinst::{DotnetRedistError, Report, exit, load_snapshot, report::Action, telemetry, timing, write_sbom};
+
+#[test]
+fn install_extracts_the_verified_archive() {
+    let (dir, report) = fixture::install("8.0");
+    assert!(matches!(report.components[0].action, Action::Installed));
+    assert_eq!(report.components[0].resolved_version.as_deref(), Some("8.0.11"));
+    assert!(report.components[0].download_bytes.is_some());
+    assert!(dir.path().join("shared/Microsoft.NETCore.App/8.0.11").is_dir());
+    assert!(fixture::feed(&[]).requested("GET", &fixture::archive("8.0.11")));
+}
+
+#[test]
+fn download_not_matching_its_hash_is_rejected() {
+    fixture::feed(&[]);
+    let dir = tempfile::tempdir().unwrap();
+    let request = fixture::request("8.0.10", dir.path()).build().unwrap();
+
+    let mut report = Report::default();
+    match smol::block_on(request.install(&mut report)) {
+        Err(DotnetRedistError::VerificationFailed(_)) => {}
+        result => panic!("expected VerificationFailed, got {:?}", result.err()),
+    }
+    assert!(!dir.path().join("shared/Microsoft.NETCore.App/8.0.10").exists());
+}
+
+#[test]
+fn download_over_the_size_cap_fails_before_it_starts() {
+    fixture::feed(&[]);
+    let dir = tempfile::tempdir().unwrap();
+    let request = fixture::request("8.0", dir.path()).max_download_size(1).build().unwrap();
+
+    let mut report = Report::default();
+    let error = smol::block_on(request.install(&mut report)).unwrap_err();
+    assert!(format!("{:#}", error).contains("--max-download-size"), "{:#}", error);
+    assert!(report.components.is_empty());
+    assert!(!dir.path().join("shared").exists());
+}
+
+#[test]
+fn force_reinstalls_a_present_version() {
+    let (dir, _) = fixture::install("8.0");
+
+    let mut report = Report::default();
+    let request = fixture::request("8.0", dir.path()).build().unwrap();
+    smol::block_on(request.install(&mut report)).unwrap();
+    assert!(matches!(report.components[0].action, Action::Skipped));
+    assert_eq!(exit::code(None, &report), exit::ALREADY_INSTALLED);
+
+    let mut report = Report::default();
+    let request = fixture::request("8.0", dir.path()).force(true).build().unwrap();
+    smol::block_on(request.install(&mut report)).unwrap();
+    assert!(report.components[0].detected);
+    assert!(matches!(report.components[0].action, Action::Installed));
+}
+
+#[test]
+fn repair_refuses_a_private_install() {
+    let dir = tempfile::tempdir().unwrap();
+    match fixture::request("8.0", dir.path()).repair(true).build() {
+        Err(DotnetRedistError::InvalidRequest(_)) => {}
+        result => panic!("expected InvalidRequest, got {:?}", result.err()),
+    }
+}
+
+#[test]
+fn vulnerable_version_is_refused_on_request() {
+    fixture::feed(&[]);
+    let dir = tempfile::tempdir().unwrap();
+    let request = fixture::request("8.0.10", dir.path()).fail_on_vulnerable(true).build().unwrap();
+
+    match smol::block_on(request.install(&mut Report::default())) {
+        Err(DotnetRedistError::Vulnerable(_)) => {}
+        result => panic!("expected Vulnerable, got {:?}", result.err()),
+    }
+    assert!(!dir.path().join("shared/Microsoft.NETCore.App/8.0.10").exists());
+}
+
+#[test]
+fn uninstall_removes_the_matching_versions_from_the_install_dir() {
+    let (dir, _) = fixture::install("8.0");
+
+    let mut report = Report::default();
+    let request = fixture::request("8.0", dir.path()).build().unwrap();
+    smol::block_on(request.uninstall(&mut report)).unwrap();
+    assert_eq!(report.components.len(), 1);
+    assert!(matches!(report.components[0].action, Action::Uninstalled));
+    assert!(!dir.path().join("shared/Microsoft.NETCore.App/8.0.11").exists());
+}
+
+#[test]
+fn security_release_replaces_the_installed_patch() {
+    let (dir, _) = fixture::install("8.0");
+    // Pass the install off as the previous patch, which 8.0.11 fixes advisories of.
+    let shared = dir.path().join("shared/Microsoft.NETCore.App");
+    std::fs::rename(shared.join("8.0.11"), shared.join("8.0.10")).unwrap();
+
+    let mut report = Report::default();
+    let request = fixture::request("8.0", dir.path()).latest_patch(true).security_only(true).build().unwrap();
+    smol::block_on(request.install(&mut report)).unwrap();
+    assert!(matches!(report.components[0].action, Action::Installed));
+    assert!(shared.join("8.0.11").is_dir());
+}
+
+#[test]
+fn given_blob_and_product_version_name_the_installer() {
+    let fixture = fixture::feed(&[]);
+    let dir = tempfile::tempdir().unwrap();
+    let request = fixture::request("8.0", dir.path())
+        .blob_version("8.0.11")
+        .product_version("8.0.11")
+        .no_verify(true)
+        .build()
+        .unwrap();
+
+    let mut report = Report::default();
+    smol::block_on(request.install(&mut report)).unwrap();
+    assert_eq!(report.components[0].resolved_version.as_deref(), Some("8.0.11"));
+    assert!(dir.path().join("shared/Microsoft.NETCore.App/8.0.11").is_dir());
+    assert!(!fixture.requested("GET", "/Runtime/8.0.11/productVersion.txt"));
+
+    let request = fixture::request("8.0", dir.path()).blob_version("8.0.11").product_version("8.0.99").force(true).build().unwrap();
+    match smol::block_on(request.install(&mut Report::default())) {
+        Err(DotnetRedistError::VersionNotFound(_)) => {}
+        result => panic!("expected VersionNotFound, got {:?}", result.err()),
+    }
+}
+
+#[test]
+fn product_version_needs_a_full_version() {
+    let dir = tempfile::tempdir().unwrap();
+    match fixture::request("8.0", dir.path()).product_version("8.0.11").build() {
+        Err(DotnetRedistError::InvalidRequest(_)) => {}
+        result => panic!("expected InvalidRequest, got {:?}", result.err()),
+    }
+}
+
+#[test]
+fn snapshot_installs_its_pinned_versions() {
+    fixture::feed(&[]);
+    let dir = tempfile::tempdir().unwrap();
+    let snapshot = dir.path().join("machine");
+    std::fs::write(
+        &snapshot,
+        r#"{"components": [{"runtime": "dotnet", "version": "8.0.11", "arch": "x64", "roll_forward": "disable"}]}"#,
+    )
+    .unwrap();
+
+    let requirements = load_snapshot(&snapshot).unwrap();
+    assert_eq!(requirements.components.len(), 1);
+    let install_dir = dir.path().join("dotnet");
+    for component in &requirements.components {
+        let request = component.builder().install_dir(&install_dir).skip_vcredist(true).quiet(true).build().unwrap();
+        smol::block_on(request.install(&mut Report::default())).unwrap();
+    }
+    assert!(install_dir.join("shared/Microsoft.NETCore.App/8.0.11").is_dir());
+}
+
+#[test]
+fn install_waits_for_the_run_holding_the_machine_lock() {
+    fixture::feed(&[]);
+    let dir = tempfile::tempdir().unwrap();
+    let request = fixture::request("8.0", dir.path()).build().unwrap();
+
+    let lock = File::create(fixture::instance_lock()).unwrap();
+    lock.lock().unwrap();
+    let installing = thread::spawn(move || smol::block_on(request.install(&mut Report::default())));
+    thread::sleep(Duration::from_millis(500));
+    assert!(!dir.path().join("shared").exists());
+
+    lock.unlock().unwrap();
+    installing.join().unwrap().unwrap();
+    assert!(dir.path().join("shared/Microsoft.NETCore.App/8.0.11").is_dir());
+}
+
+#[test]
+fn sbom_lists_the_installed_runtime_with_its_hash_and_url() {
+    let (dir, report) = fixture::install("8.0");
+
+    let path = dir.path().join("sbom.json");
+    write_sbom(&report, &path).unwrap();
+    let sbom: serde_json::Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+    assert_eq!(sbom["bomFormat"], "CycloneDX");
+    let component = &sbom["components"][0];
+    assert_eq!(component["name"], "dotnet");
+    assert_eq!(component["version"], "8.0.11");
+    assert_eq!(component["hashes"][0]["alg"], "SHA-512");
+    assert_eq!(component["hashes"][0]["content"].as_str().unwrap().len(), 128);
+    let url = component["externalReferences"][0]["url"].as_str().unwrap();
+    assert!(url.ends_with(&fixture::archive("8.0.11")), "{}", url);
+}
+
+#[test]
+fn run_record_is_posted_to_the_report_url() {
+    let (_dir, report) = fixture::install("8.0");
+    let fixture = fixture::feed(&[]);
+    smol::block_on(telemetry::send(&report, None));
+    assert!(!fixture.requested("POST", "/runs"));
+
+    telemetry::set_url(format!("{}/runs", fixture.url));
+    smol::block_on(telemetry::send(&report, None));
+    assert!(fixture.requested("POST", "/runs"));
+}
+
+#[test]
+fn timing_report_has_the_download_and_its_throughput() {
+    fixture::feed(&[]);
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("timing.json");
+    timing::set_report(path.clone());
+    // Past the download cache, which other tests may have filled already.
+    let request = fixture::request("8.0", dir.path()).no_cache(true).build().unwrap();
+    smol::block_on(request.install(&mut Report::default())).unwrap();
+
+    // The report also holds the spans of tests that ran alongside since it was set.
+    timing::write_report();
+    let timing: serde_json::Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+    let spans = timing["spans"].as_array().unwrap();
+    let downloaded = spans.iter().any(|span| {
+        span["phase"] == "download"
+            && span["name"].as_str().unwrap().ends_with(&fixture::archive("8.0.11"))
+            && span["bytes"].as_u64().unwrap() > 0
+    });
+    assert!(downloaded, "no download span with its bytes");
+    assert!(timing["totals"]["download"]["count"].as_u64().unwrap() >= 1);
+    assert!(spans.iter().any(|span| span["phase"] == "resolve"));
+}
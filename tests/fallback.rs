@@ -0,0 +1,33 @@
+//! Resolving versions from the download server's layout when the release
+//! metadata cannot be fetched.
+
+mod fixture;
+
+use dotnet5_webinst::RollForward;
+
+#[test]
+fn channel_resolves_from_latest_version_without_metadata() {
+    let fixture = fixture::feed(&["release-metadata/"]);
+    let dir = tempfile::tempdir().unwrap();
+    let request = fixture::request("8.0", dir.path()).build().unwrap();
+
+    let entry = smol::block_on(request.lock()).unwrap().remove(0);
+    assert_eq!(entry.resolved_version, "8.0.11");
+    assert!(entry.url.ends_with(&fixture::archive("8.0.11")), "{}", entry.url);
+    assert_eq!(entry.hash, None);
+    assert!(fixture.requested("GET", "/Runtime/8.0/latest.version"));
+    assert!(fixture.requested("GET", "/Runtime/8.0.11/productVersion.txt"));
+    assert!(fixture.requested("HEAD", &fixture::archive("8.0.11")));
+}
+
+#[test]
+fn newest_minor_is_probed_without_fetching_bodies() {
+    let fixture = fixture::feed(&["release-metadata/"]);
+    let dir = tempfile::tempdir().unwrap();
+    let request = fixture::request("8", dir.path()).roll_forward(RollForward::LatestMinor).build().unwrap();
+
+    let entry = smol::block_on(request.lock()).unwrap().remove(0);
+    assert_eq!(entry.resolved_version, "8.0.11");
+    assert!(fixture.requested("HEAD", "/Runtime/8.1/latest.version"));
+    assert!(!fixture.requested("GET", "/Runtime/8.1/latest.version"));
+}
@@ -0,0 +1,202 @@
+//! A local stand-in for the dotnet download server and the daily builds
+//! feed, serving the recorded metadata and dummy payloads in
+//! `tests/fixtures/dotnet` and `tests/fixtures/public`, the manifests in
+//! `tests/fixtures/fleet` and the global tool packages in
+//! `tests/fixtures/nuget`, so that resolving, falling back and downloading
+//! can be tested without the network.
+//!
+//! The installer's settings are set once per process and shared by every
+//! test in a binary: the feed and NuGet feed [`feed`] points at this
+//! server, what [`isolate`] sets, the download cache, and the timing report
+//! and telemetry URL once a test in `download.rs` sets them. So tests
+//! install into temporary directories of their own, take turns through
+//! [`instance_lock`], skip the cache with `no_cache` where they count
+//! downloads, and only assert on the records they made themselves. A
+//! scenario that needs other settings, such as a broken feed, mirrors, a
+//! temporary directory or the log format, gets a test file, and so a
+//! binary, of its own.
+
+// Each test binary only uses some of the helpers.
+#![allow(dead_code)]
+
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+    thread,
+    time::Duration,
+};
+
+use dotnet5_webinst::{
+    Architecture, InstallRequest, InstallRequestBuilder, Report, RetryPolicy, Runtime, set_daily_feed, set_feed, set_instance_lock,
+    set_nuget_feed, set_refresh_catalog, set_retry_policy, set_timeout,
+};
+use tempfile::TempDir;
+
+/// A running fixture server.
+pub struct Fixture {
+    /// The equivalent of `https://dotnetcli.azureedge.net/dotnet`.
+    pub url: String,
+    /// Paths under it answered with `503 Service Unavailable`; an empty one breaks everything.
+    broken: &'static [&'static str],
+    requests: Mutex<Vec<String>>,
+}
+
+impl Fixture {
+    /// The requests served so far, as `METHOD /path`.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Whether `method` was sent for a path under the server's root ending in `suffix`.
+    pub fn requested(&self, method: &str, suffix: &str) -> bool {
+        self.requests()
+            .iter()
+            .any(|request| request.starts_with(&format!("{} ", method)) && request.ends_with(suffix))
+    }
+
+    fn handle(&self, mut stream: TcpStream) -> io::Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+                break;
+            }
+        }
+
+        let mut parts = request_line.split_whitespace();
+        let (method, path) = (parts.next().unwrap_or_default(), parts.next().unwrap_or_default());
+        self.requests.lock().unwrap().push(format!("{} {}", method, path));
+
+        let (root, file) = match ["public", "fleet", "nuget"]
+            .iter()
+            .find_map(|dir| Some((root().with_file_name(dir), path.strip_prefix(&format!("/{}/", dir))?)))
+        {
+            Some((dir, file)) => (dir, Some(file)),
+            None => (root(), path.strip_prefix("/dotnet/")),
+        };
+        let (status, body) = match file {
+            Some(file) if self.broken.iter().any(|broken| file.starts_with(broken)) => {
+                ("503 Service Unavailable", Vec::new())
+            }
+            Some(file) if !file.contains("..") => match fs::read(root.join(file)) {
+                Ok(body) => ("200 OK", body),
+                Err(_) => ("404 Not Found", Vec::new()),
+            },
+            _ => ("404 Not Found", Vec::new()),
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            status,
+            body.len()
+        )?;
+        if method != "HEAD" {
+            stream.write_all(&body)?;
+        }
+        stream.flush()
+    }
+}
+
+/// The recorded copy of the download server's layout.
+fn root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join("dotnet")
+}
+
+/// Starts a fixture server on a free local port, answering the paths
+/// starting with any of `broken` with `503 Service Unavailable`.
+pub fn serve(broken: &'static [&'static str]) -> &'static Fixture {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("cannot listen on a local port");
+    let fixture: &'static Fixture = Box::leak(Box::new(Fixture {
+        url: format!("http://{}/dotnet", listener.local_addr().unwrap()),
+        broken,
+        requests: Mutex::default(),
+    }));
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            thread::spawn(move || fixture.handle(stream));
+        }
+    });
+    fixture
+}
+
+/// Keeps the installer off the machine's caches, catalogs and instance
+/// lock, and makes it give up on failures at once instead of retrying them.
+/// Returns the directory standing in for the machine's data directory.
+pub fn isolate() -> &'static Path {
+    static DATA: OnceLock<PathBuf> = OnceLock::new();
+    DATA.get_or_init(|| {
+        let data = tempfile::tempdir().expect("cannot create a temporary directory").into_path();
+        std::env::set_var("LOCALAPPDATA", &data);
+        set_instance_lock(data.join("instance.lock"));
+        set_refresh_catalog(true);
+        set_retry_policy(RetryPolicy { attempts: 1, backoff: Duration::from_millis(10) });
+        set_timeout(Duration::from_secs(10));
+        data
+    })
+}
+
+/// The lock file runs in the test binary take turns installing with.
+pub fn instance_lock() -> PathBuf {
+    isolate().join("instance.lock")
+}
+
+/// Starts the fixture server once per test binary and makes it the feed
+/// replacing the official endpoints and nuget.org.
+pub fn feed(broken: &'static [&'static str]) -> &'static Fixture {
+    static FEED: OnceLock<&'static Fixture> = OnceLock::new();
+    FEED.get_or_init(|| {
+        isolate();
+        let fixture = serve(broken);
+        set_feed(fixture.url.clone());
+        set_nuget_feed(fixture.url.replace("/dotnet", "/nuget"));
+        fixture
+    })
+}
+
+/// Starts the fixture server as [`feed`] does, and makes its recorded copy
+/// of the daily builds feed the daily feed too.
+pub fn daily_feed() -> &'static Fixture {
+    let fixture = feed(&[]);
+    set_daily_feed(fixture.url.replace("/dotnet", "/public"));
+    fixture
+}
+
+/// The path of the archive of the x64 .NET runtime `version` for this platform.
+pub fn archive(version: &str) -> String {
+    let (os, extension) = if cfg!(windows) {
+        ("win", "zip")
+    } else if cfg!(target_os = "macos") {
+        ("osx", "tar.gz")
+    } else {
+        ("linux", "tar.gz")
+    };
+    format!("/Runtime/{0}/dotnet-runtime-{0}-{1}-x64.{2}", version, os, extension)
+}
+
+/// A request for the x64 .NET runtime of `version`, installed into a
+/// directory of its own so that the archives, which every platform has, are used.
+pub fn request(version: &str, dir: &Path) -> InstallRequestBuilder {
+    InstallRequest::builder()
+        .arch(Architecture::X64)
+        .runtime(Runtime::Dotnet)
+        .version(version)
+        .install_dir(dir)
+        .skip_vcredist(true)
+        .quiet(true)
+}
+
+/// Installs the x64 .NET runtime `version` from the fixture feed into a new
+/// temporary directory, which is returned with the run's report.
+pub fn install(version: &str) -> (TempDir, Report) {
+    feed(&[]);
+    let dir = tempfile::tempdir().expect("cannot create a temporary directory");
+    let mut report = Report::default();
+    let request = request(version, dir.path()).build().expect("invalid request");
+    smol::block_on(request.install(&mut report)).expect("install failed");
+    (dir, report)
+}
@@ -0,0 +1,25 @@
+//! Writing the log file as JSON lines.
+
+mod fixture;
+
+use dotnet5_webinst::logging;
+
+#[test]
+fn json_log_records_the_steps_of_an_install() {
+    let dir = tempfile::tempdir().unwrap();
+    let log_file = dir.path().join("install.log");
+    logging::init(false, Some(&log_file), logging::Format::Json).unwrap();
+    fixture::install("8.0");
+
+    let lines: Vec<serde_json::Value> = std::fs::read_to_string(&log_file)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let resolved = lines.iter().find(|line| line["event"] == "resolved").unwrap();
+    assert_eq!(resolved["version"], "8.0.11");
+    assert!(resolved["timestamp"].is_number());
+    assert!(lines.iter().any(|line| line["event"] == "download_ended"));
+    assert!(lines.iter().any(|line| line["event"] == "done"));
+    assert!(lines.iter().any(|line| line["message"].is_string()));
+}
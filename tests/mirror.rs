@@ -0,0 +1,35 @@
+//! Falling back to a mirror when the feed is down.
+
+mod fixture;
+
+use std::sync::OnceLock;
+
+use dotnet5_webinst::{Report, set_feed, set_mirrors};
+
+use fixture::Fixture;
+
+/// A feed failing every request, and a mirror serving the fixture.
+fn servers() -> (&'static Fixture, &'static Fixture) {
+    static SERVERS: OnceLock<(&'static Fixture, &'static Fixture)> = OnceLock::new();
+    *SERVERS.get_or_init(|| {
+        fixture::isolate();
+        let (feed, mirror) = (fixture::serve(&[""]), fixture::serve(&[]));
+        set_feed(feed.url.clone());
+        set_mirrors(vec![mirror.url.clone()]);
+        (feed, mirror)
+    })
+}
+
+#[test]
+fn install_falls_back_to_the_mirror() {
+    let (feed, mirror) = servers();
+    let dir = tempfile::tempdir().unwrap();
+    let request = fixture::request("8.0", dir.path()).build().unwrap();
+
+    let mut report = Report::default();
+    smol::block_on(request.install(&mut report)).unwrap();
+    assert!(dir.path().join("shared/Microsoft.NETCore.App/8.0.11").is_dir());
+    assert!(feed.requested("GET", "/release-metadata/releases-index.json"));
+    assert!(mirror.requested("GET", "/release-metadata/releases-index.json"));
+    assert!(mirror.requested("GET", &fixture::archive("8.0.11")));
+}
@@ -0,0 +1,130 @@
+//! Resolving what to install: versions against the release metadata,
+//! global tools' SDKs from their packages, requirements manifests from a
+//! URL, and installers by their hashes.
+
+mod fixture;
+
+use std::path::Path;
+
+use dotnet5_webinst::{
+    Architecture, DotnetRedistError, InstallRequest, LockEntry, ManifestCheck, Plan, Quality, Requirements,
+    global_tool_sdk_channel, identify_installer, resolve_channel,
+};
+
+/// The public half of the key `fleet/runtimes.toml.sig` was made with.
+const MANIFEST_KEY: &str = "dENUIJ6OXh/1CAHY3/VLT4MCiaBkGBJ4mhyct6EtR/Y=";
+const MANIFEST_SHA256: &str = "ce41ab49dc26ca37a3b65b43eeb3288ddf60849741a2f235e99384ddacdedb1d";
+
+fn manifest_url() -> String {
+    fixture::feed(&[]).url.replace("/dotnet", "/fleet/runtimes.toml")
+}
+
+fn lock(version: &str) -> Result<LockEntry, DotnetRedistError> {
+    fixture::feed(&[]);
+    let dir = tempfile::tempdir().unwrap();
+    let request = fixture::request(version, dir.path()).build()?;
+    Ok(smol::block_on(request.lock())?.remove(0))
+}
+
+#[test]
+fn channel_keywords_resolve_from_the_release_index() {
+    fixture::feed(&[]);
+    assert_eq!(smol::block_on(resolve_channel("lts")).unwrap(), "8.0");
+    assert_eq!(smol::block_on(resolve_channel("latest")).unwrap(), "8.0");
+    assert_eq!(smol::block_on(resolve_channel("sts")).unwrap(), "9.0");
+}
+
+#[test]
+fn global_tool_needs_the_sdk_of_its_newest_framework() {
+    let fixture = fixture::feed(&[]);
+    assert_eq!(smol::block_on(global_tool_sdk_channel("Divvun.Tool")).unwrap(), "8.0");
+    // The prerelease listed after it is passed over.
+    assert!(fixture.requested("GET", "/nuget/divvun.tool/1.0.0/divvun.tool.1.0.0.nupkg"));
+    assert!(smol::block_on(global_tool_sdk_channel("missing.tool")).is_err());
+}
+
+#[test]
+fn channel_resolves_to_its_newest_release() {
+    let entry = lock("8.0").unwrap();
+    assert_eq!(entry.resolved_version, "8.0.11");
+    assert!(entry.url.ends_with(&fixture::archive("8.0.11")), "{}", entry.url);
+    assert!(entry.hash.is_some());
+}
+
+#[test]
+fn exact_version_resolves_to_itself() {
+    let entry = lock("8.0.10").unwrap();
+    assert_eq!(entry.resolved_version, "8.0.10");
+    assert!(entry.url.ends_with(&fixture::archive("8.0.10")), "{}", entry.url);
+}
+
+#[test]
+fn unreleased_version_is_not_found() {
+    match lock("8.0.99") {
+        Err(DotnetRedistError::VersionNotFound(_)) => {}
+        result => panic!("expected VersionNotFound, got {:?}", result.map(|entry| entry.resolved_version)),
+    }
+}
+
+#[test]
+fn requests_planned_together_keep_their_order() {
+    fixture::feed(&[]);
+    let dir = tempfile::tempdir().unwrap();
+    let requests = ["8.0", "8.0.10"]
+        .iter()
+        .map(|version| fixture::request(version, &dir.path().join(version)).build().unwrap())
+        .collect::<Vec<_>>();
+
+    let mut plan = Plan::default();
+    smol::block_on(InstallRequest::plan_all(&requests, &mut plan)).unwrap();
+    let versions = plan.components.iter().map(|component| component.version.as_deref()).collect::<Vec<_>>();
+    assert_eq!(versions, [Some("8.0.11"), Some("8.0.10")]);
+}
+
+#[test]
+fn daily_quality_resolves_the_newest_build_from_the_daily_feed() {
+    fixture::daily_feed();
+    let dir = tempfile::tempdir().unwrap();
+    let request = fixture::request("10.0", dir.path()).quality(Quality::Daily).build().unwrap();
+    let entry = smol::block_on(request.lock()).unwrap().remove(0);
+    assert_eq!(entry.resolved_version, "10.0.0-preview.4.25206.1");
+    assert!(entry.url.contains("/public/"), "{}", entry.url);
+    assert!(entry.url.ends_with(&fixture::archive("10.0.0-preview.4.25206.1")), "{}", entry.url);
+    assert!(entry.hash.is_some());
+}
+
+#[test]
+fn signed_manifest_is_fetched_and_verified() {
+    let check = ManifestCheck::Signature(MANIFEST_KEY.into());
+    let requirements = smol::block_on(Requirements::fetch(&manifest_url(), &check)).unwrap();
+    assert_eq!(requirements.components.len(), 1);
+    assert_eq!(requirements.components[0].version, "8.0");
+    assert!(requirements.components[0].arch == Architecture::X64);
+
+    smol::block_on(Requirements::fetch(&manifest_url(), &ManifestCheck::Sha256(MANIFEST_SHA256.into()))).unwrap();
+}
+
+#[test]
+fn manifest_not_matching_its_check_is_refused() {
+    let other_key = "11qYAYKxCrfVS/7TyWQHOg7hcvPapiMlrwIaaPcHURo=";
+    match smol::block_on(Requirements::fetch(&manifest_url(), &ManifestCheck::Signature(other_key.into()))) {
+        Err(e) => assert!(matches!(e.downcast_ref(), Some(DotnetRedistError::VerificationFailed(_))), "{:#}", e),
+        Ok(_) => panic!("a manifest signed with another key was accepted"),
+    }
+    let wrong_hash = MANIFEST_SHA256.replace('c', "d");
+    assert!(smol::block_on(Requirements::fetch(&manifest_url(), &ManifestCheck::Sha256(wrong_hash))).is_err());
+}
+
+#[test]
+fn published_archive_is_identified_and_others_are_not() {
+    fixture::feed(&[]);
+    let archive = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/dotnet/Runtime/8.0.11/dotnet-runtime-8.0.11-win-x64.zip");
+    let entry = smol::block_on(identify_installer(&archive)).unwrap().expect("the published archive was not identified");
+    assert_eq!(entry.name, "dotnet-runtime-win-x64.zip");
+    assert_eq!(entry.version, "8.0.11");
+
+    let dir = tempfile::tempdir().unwrap();
+    let other = dir.path().join("dotnet-runtime-8.0.11-win-x64.zip");
+    std::fs::write(&other, b"not the published archive").unwrap();
+    assert!(smol::block_on(identify_installer(&other)).unwrap().is_none());
+}
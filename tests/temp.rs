@@ -0,0 +1,43 @@
+//! Sweeping up what crashed runs left in the temporary and cache directories.
+
+mod fixture;
+
+use std::{
+    fs::{self, File},
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use dotnet5_webinst::set_temp_dir;
+
+/// Makes `path` look untouched for two days.
+fn age(path: &Path) {
+    let two_days_ago = SystemTime::now() - Duration::from_secs(2 * 24 * 60 * 60);
+    File::open(path).unwrap().set_modified(two_days_ago).unwrap();
+}
+
+#[test]
+fn the_first_download_sweeps_up_after_crashed_runs() {
+    fixture::feed(&[]);
+    let temp = tempfile::tempdir().unwrap();
+    set_temp_dir(temp.path()).unwrap();
+    let cache = Path::new(&std::env::var_os("LOCALAPPDATA").unwrap()).join("dotnet5-redist/cache");
+    fs::create_dir_all(&cache).unwrap();
+
+    let orphan = temp.path().join("dotnet5-webinstCrashed");
+    fs::create_dir(&orphan).unwrap();
+    fs::write(orphan.join("installer.exe"), b"MZ").unwrap();
+    age(&orphan);
+    let running = temp.path().join("dotnet5-webinstRunning");
+    fs::create_dir(&running).unwrap();
+    let partial = cache.join("0123-dotnet-runtime-8.0.11-win-x64.zip.42-0.partial");
+    fs::write(&partial, b"PK").unwrap();
+    age(&partial);
+
+    fixture::install("8.0");
+    assert!(!orphan.exists());
+    assert!(running.exists());
+    assert!(!partial.exists());
+    let leftovers = fs::read_dir(&cache).unwrap().flatten().filter(|entry| entry.path().extension() == Some("partial".as_ref()));
+    assert_eq!(leftovers.count(), 0);
+}